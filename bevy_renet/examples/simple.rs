@@ -1,7 +1,7 @@
 use bevy::{prelude::*, render::mesh::PlaneMeshBuilder};
 use bevy_renet::netcode::{
     ClientAuthentication, NetcodeClientPlugin, NetcodeClientTransport, NetcodeServerPlugin, NetcodeServerTransport, NetcodeTransportError,
-    ServerAuthentication, ServerConfig,
+    ServerAuthentication, ServerConfig, NETCODE_MAX_PENDING_CLIENTS, NETCODE_SEND_RATE,
 };
 use bevy_renet::renet::{ClientId, ConnectionConfig, DefaultChannel, RenetClient, RenetServer, ServerEvent};
 use bevy_renet::{client_connected, RenetClientPlugin, RenetServerPlugin};
@@ -67,6 +67,8 @@ fn new_renet_server() -> (RenetServer, NetcodeServerTransport) {
         protocol_id: PROTOCOL_ID,
         public_addresses: vec![public_addr],
         authentication: ServerAuthentication::Unsecure,
+        keep_alive_interval: NETCODE_SEND_RATE,
+        max_pending_clients: NETCODE_MAX_PENDING_CLIENTS,
     };
 
     let transport = NetcodeServerTransport::new(server_config, socket).unwrap();
@@ -165,6 +167,10 @@ fn server_update_system(
                 let message = bincode::serialize(&ServerMessages::PlayerDisconnected { id: *client_id }).unwrap();
                 server.broadcast_message(DefaultChannel::ReliableOrdered, message);
             }
+            ServerEvent::ConnectionDenied { addr, reason } => {
+                println!("Connection from {} denied: {}", addr, reason);
+            }
+            _ => {}
         }
     }
 
@@ -0,0 +1,74 @@
+use bevy_app::prelude::*;
+use bevy_diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy_ecs::prelude::*;
+use renet::{RenetClient, RenetServer};
+
+use crate::RenetSend;
+
+/// Plugin that registers the network stats as [`Diagnostics`], so they are shown by the `LogDiagnosticsPlugin`
+/// and other diagnostics tools.
+///
+/// The stats are measured from the [`RenetClient`] resource, or from the [`RenetServer`] resource when there's no client,
+/// with the round-trip time and packet loss averaged across all clients, and the bandwidth summed.
+pub struct RenetDiagnosticsPlugin;
+
+impl RenetDiagnosticsPlugin {
+    /// Round-trip time in milliseconds.
+    pub const RTT: DiagnosticPath = DiagnosticPath::const_new("renet/rtt");
+    /// Percentage of the packets sent that were lost.
+    pub const PACKET_LOSS: DiagnosticPath = DiagnosticPath::const_new("renet/packet_loss");
+    pub const SENT_KBPS: DiagnosticPath = DiagnosticPath::const_new("renet/sent_kbps");
+    pub const RECEIVED_KBPS: DiagnosticPath = DiagnosticPath::const_new("renet/received_kbps");
+    /// Number of clients connected to the server, only measured in the server.
+    pub const CONNECTED_CLIENTS: DiagnosticPath = DiagnosticPath::const_new("renet/connected_clients");
+
+    pub fn diagnostic_system(mut diagnostics: Diagnostics, client: Option<Res<RenetClient>>, server: Option<Res<RenetServer>>) {
+        if let Some(client) = client {
+            if client.is_disconnected() {
+                return;
+            }
+
+            let network_info = client.network_info();
+            diagnostics.add_measurement(&Self::RTT, || network_info.rtt * 1000.);
+            diagnostics.add_measurement(&Self::PACKET_LOSS, || network_info.packet_loss * 100.);
+            diagnostics.add_measurement(&Self::SENT_KBPS, || network_info.bytes_sent_per_second * 8. / 1000.);
+            diagnostics.add_measurement(&Self::RECEIVED_KBPS, || network_info.bytes_received_per_second * 8. / 1000.);
+        } else if let Some(server) = server {
+            let connected_clients = server.connected_clients();
+            diagnostics.add_measurement(&Self::CONNECTED_CLIENTS, || connected_clients as f64);
+
+            let mut rtt = 0.;
+            let mut packet_loss = 0.;
+            let mut bytes_sent_per_second = 0.;
+            let mut bytes_received_per_second = 0.;
+            for client_id in server.clients_id_iter() {
+                let Ok(network_info) = server.network_info(client_id) else {
+                    continue;
+                };
+                rtt += network_info.rtt;
+                packet_loss += network_info.packet_loss;
+                bytes_sent_per_second += network_info.bytes_sent_per_second;
+                bytes_received_per_second += network_info.bytes_received_per_second;
+            }
+
+            diagnostics.add_measurement(&Self::SENT_KBPS, || bytes_sent_per_second * 8. / 1000.);
+            diagnostics.add_measurement(&Self::RECEIVED_KBPS, || bytes_received_per_second * 8. / 1000.);
+            if connected_clients > 0 {
+                diagnostics.add_measurement(&Self::RTT, || rtt * 1000. / connected_clients as f64);
+                diagnostics.add_measurement(&Self::PACKET_LOSS, || packet_loss * 100. / connected_clients as f64);
+            }
+        }
+    }
+}
+
+impl Plugin for RenetDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(Diagnostic::new(Self::RTT).with_suffix("ms"))
+            .register_diagnostic(Diagnostic::new(Self::PACKET_LOSS).with_suffix("%"))
+            .register_diagnostic(Diagnostic::new(Self::SENT_KBPS).with_suffix("kbps"))
+            .register_diagnostic(Diagnostic::new(Self::RECEIVED_KBPS).with_suffix("kbps"))
+            .register_diagnostic(Diagnostic::new(Self::CONNECTED_CLIENTS));
+
+        app.add_systems(PostUpdate, Self::diagnostic_system.after(RenetSend));
+    }
+}
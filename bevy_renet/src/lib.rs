@@ -1,10 +1,12 @@
 pub use renet;
 
-use bevy_app::prelude::*;
+use std::time::{Duration, Instant};
+
+use bevy_app::{prelude::*, ScheduleRunnerPlugin};
 use bevy_ecs::prelude::*;
 use bevy_time::prelude::*;
 
-use renet::{RenetClient, RenetServer, ServerEvent};
+use renet::{ClientId, RenetClient, RenetServer, ServerEvent};
 
 #[cfg(feature = "netcode")]
 pub mod netcode;
@@ -12,6 +14,15 @@ pub mod netcode;
 #[cfg(feature = "steam")]
 pub mod steam;
 
+#[cfg(feature = "replication")]
+pub mod replication;
+
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+
+#[cfg(feature = "visualizer")]
+pub mod visualizer;
+
 /// This system set is where all transports receive messages
 ///
 /// If you want to ensure data has arrived in the [`RenetClient`] or [`RenetServer`], then schedule your
@@ -34,6 +45,58 @@ pub struct RenetServerPlugin;
 
 pub struct RenetClientPlugin;
 
+/// Plugin that connects a local [`RenetClient`] resource to the [`RenetServer`] resource in the same app.
+///
+/// Use it in listen servers, where the host player is also a client. Create the client with
+/// [`RenetServer::new_local_client`], and insert it together with [`RenetLocalClient`].
+/// The host can then use the [`RenetClient`] resource like any other client.
+pub struct RenetLocalClientPlugin;
+
+/// Plugin for dedicated servers without a window, runs the app loop at a fixed network tick.
+///
+/// Adds the [`ScheduleRunnerPlugin`] running each update after the tick duration, so it should be used
+/// with `MinimalPlugins` with its [`ScheduleRunnerPlugin`] disabled. Each update increments the [`NetworkTick`]
+/// resource, and a [`NetworkTickOverrun`] event is sent when an update takes longer than the tick duration.
+///
+/// # Usage
+/// ```ignore
+/// App::new()
+///     .add_plugins(MinimalPlugins.build().disable::<ScheduleRunnerPlugin>())
+///     .add_plugins(RenetServerPlugin)
+///     .add_plugins(RenetHeadlessServerPlugin::from_tick_rate(30));
+/// ```
+pub struct RenetHeadlessServerPlugin {
+    pub tick_duration: Duration,
+}
+
+/// Number of the current update, incremented at the start of each update by the [`RenetHeadlessServerPlugin`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Resource)]
+pub struct NetworkTick(pub u64);
+
+/// Event sent when an update took longer than the tick duration of the [`RenetHeadlessServerPlugin`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Event)]
+pub struct NetworkTickOverrun {
+    pub tick: NetworkTick,
+    /// Time spent in the update.
+    pub duration: Duration,
+    /// Tick duration configured in the plugin.
+    pub budget: Duration,
+}
+
+#[derive(Debug, Resource)]
+struct NetworkTickBudget {
+    budget: Duration,
+    start: Instant,
+}
+
+/// The client id of the local [`RenetClient`] resource, created with [`RenetServer::new_local_client`].
+///
+/// This resource is removed when the local client disconnects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource)]
+pub struct RenetLocalClient {
+    pub client_id: ClientId,
+}
+
 impl Plugin for RenetServerPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<Events<ServerEvent>>();
@@ -72,6 +135,104 @@ impl RenetClientPlugin {
     }
 }
 
+impl RenetHeadlessServerPlugin {
+    /// Creates the plugin running the given number of ticks per second, panics if it is zero.
+    pub fn from_tick_rate(ticks_per_second: u32) -> Self {
+        Self {
+            tick_duration: Duration::from_secs(1) / ticks_per_second,
+        }
+    }
+}
+
+impl Plugin for RenetHeadlessServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(ScheduleRunnerPlugin::run_loop(self.tick_duration));
+        app.init_resource::<NetworkTick>();
+        app.add_event::<NetworkTickOverrun>();
+        app.insert_resource(NetworkTickBudget {
+            budget: self.tick_duration,
+            start: Instant::now(),
+        });
+        app.add_systems(First, Self::start_tick_system);
+        app.add_systems(Last, Self::check_tick_budget_system);
+    }
+}
+
+impl RenetHeadlessServerPlugin {
+    fn start_tick_system(mut tick: ResMut<NetworkTick>, mut budget: ResMut<NetworkTickBudget>) {
+        tick.0 += 1;
+        budget.start = Instant::now();
+    }
+
+    fn check_tick_budget_system(tick: Res<NetworkTick>, budget: Res<NetworkTickBudget>, mut overruns: EventWriter<NetworkTickOverrun>) {
+        let duration = budget.start.elapsed();
+        if duration > budget.budget {
+            overruns.send(NetworkTickOverrun {
+                tick: *tick,
+                duration,
+                budget: budget.budget,
+            });
+        }
+    }
+}
+
+impl Plugin for RenetLocalClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PreUpdate,
+            Self::process_local_client
+                .in_set(RenetReceive)
+                .run_if(resource_exists::<RenetLocalClient>)
+                .run_if(resource_exists::<RenetServer>)
+                .run_if(resource_exists::<RenetClient>)
+                .after(RenetServerPlugin::update_system)
+                .after(RenetClientPlugin::update_system)
+                .before(RenetServerPlugin::emit_server_events_system),
+        );
+
+        app.add_systems(
+            PostUpdate,
+            Self::process_local_client
+                .in_set(RenetSend)
+                .run_if(resource_exists::<RenetLocalClient>)
+                .run_if(resource_exists::<RenetServer>)
+                .run_if(resource_exists::<RenetClient>),
+        );
+
+        app.add_systems(
+            Last,
+            Self::disconnect_on_exit
+                .run_if(resource_exists::<RenetLocalClient>)
+                .run_if(resource_exists::<RenetServer>)
+                .run_if(resource_exists::<RenetClient>),
+        );
+    }
+}
+
+impl RenetLocalClientPlugin {
+    pub fn process_local_client(
+        mut commands: Commands,
+        local_client: Res<RenetLocalClient>,
+        mut server: ResMut<RenetServer>,
+        mut client: ResMut<RenetClient>,
+    ) {
+        if server.process_local_client(local_client.client_id, &mut client).is_err() {
+            commands.remove_resource::<RenetLocalClient>();
+        }
+    }
+
+    pub fn disconnect_on_exit(
+        exit: EventReader<AppExit>,
+        local_client: Res<RenetLocalClient>,
+        mut server: ResMut<RenetServer>,
+        mut client: ResMut<RenetClient>,
+    ) {
+        if !exit.is_empty() {
+            server.disconnect_local_client(local_client.client_id, &mut client);
+        }
+    }
+}
+
 pub fn client_connected(client: Option<Res<RenetClient>>) -> bool {
     match client {
         Some(client) => client.is_connected(),
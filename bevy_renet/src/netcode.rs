@@ -14,6 +14,7 @@ pub struct NetcodeClientPlugin;
 impl Plugin for NetcodeServerPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<NetcodeTransportError>();
+        app.add_event::<NetcodeClientConnected>();
 
         app.add_systems(
             PreUpdate,
@@ -48,8 +49,13 @@ impl NetcodeServerPlugin {
         mut server: ResMut<RenetServer>,
         time: Res<Time>,
         mut transport_errors: EventWriter<NetcodeTransportError>,
+        mut clients_connected: EventWriter<NetcodeClientConnected>,
     ) {
-        if let Err(e) = transport.update(time.delta(), &mut server) {
+        // The packets paced in the previous frame are sent before the new ones
+        transport.send_paced_packets();
+        let result = transport.update(time.delta(), &mut server);
+        clients_connected.send_batch(transport.clients_connected_last_update().iter().cloned());
+        if let Err(e) = result {
             transport_errors.send(e);
         }
     }
@@ -101,6 +107,10 @@ impl NetcodeClientPlugin {
         time: Res<Time>,
         mut transport_errors: EventWriter<NetcodeTransportError>,
     ) {
+        // The packets paced in the previous frame are sent before the new ones
+        if let Err(e) = transport.send_paced_packets() {
+            transport_errors.send(e);
+        }
         if let Err(e) = transport.update(time.delta(), &mut client) {
             transport_errors.send(e);
         }
@@ -0,0 +1,226 @@
+use std::{any::type_name, collections::HashMap};
+
+use bevy_app::prelude::*;
+use bevy_ecs::{prelude::*, world::EntityWorldMut};
+use renet::{RenetClient, RenetServer, ServerEvent};
+pub use renet_replication::*;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{RenetReceive, RenetSend};
+
+/// Plugin that replicates the entities with the [`Replicated`] component to the clients.
+///
+/// Only the components registered with [`AppReplicationExt::replicate`] are replicated.
+/// The messages are sent in the channel, that must be reliable and ordered.
+pub struct ReplicationServerPlugin {
+    pub channel_id: u8,
+}
+
+/// Plugin that applies the entities replicated from the server.
+///
+/// The replicated entities are spawned with the [`Replicated`] component, and can be found from the
+/// server entity with the [`ServerEntityMap`] resource.
+pub struct ReplicationClientPlugin {
+    pub channel_id: u8,
+}
+
+/// Marks the entity to be replicated in the server, and the entities replicated from the server in the client.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct Replicated;
+
+/// Maps the server entities to the entities spawned in the client.
+#[derive(Debug, Default, Resource)]
+pub struct ServerEntityMap {
+    entities: HashMap<NetworkEntity, Entity>,
+}
+
+impl ServerEntityMap {
+    /// Returns the client entity replicated from the server entity.
+    pub fn get(&self, server_entity: Entity) -> Option<Entity> {
+        self.entities.get(&server_entity.to_bits()).copied()
+    }
+}
+
+struct ReplicatedComponentFns {
+    name: &'static str,
+    insert: fn(&mut EntityWorldMut, &[u8]) -> bincode::Result<()>,
+    remove: fn(&mut EntityWorldMut),
+}
+
+#[derive(Default, Resource)]
+struct ReplicationRegistry {
+    components: Vec<ReplicatedComponentFns>,
+}
+
+/// Registers the components to be replicated.
+pub trait AppReplicationExt {
+    /// Replicates the component, serialized with bincode.
+    ///
+    /// Should be called in the server and in the client, the components must be registered in the same order.
+    fn replicate<C: Component + Serialize + DeserializeOwned>(&mut self) -> &mut Self;
+}
+
+impl AppReplicationExt for App {
+    fn replicate<C: Component + Serialize + DeserializeOwned>(&mut self) -> &mut Self {
+        let mut registry = self.world_mut().get_resource_or_init::<ReplicationRegistry>();
+        let kind = ComponentKind::try_from(registry.components.len()).expect("too many replicated components");
+        registry.components.push(ReplicatedComponentFns {
+            name: type_name::<C>(),
+            insert: |entity, data| {
+                entity.insert(bincode::deserialize::<C>(data)?);
+                Ok(())
+            },
+            remove: |entity| {
+                entity.remove::<C>();
+            },
+        });
+
+        let replicate_system = move |query: Query<(Entity, Ref<C>, Ref<Replicated>)>,
+                                     mut removed: RemovedComponents<C>,
+                                     mut replication: ResMut<ReplicationServer>| {
+            for entity in removed.read() {
+                replication.remove(entity.to_bits(), kind);
+            }
+
+            for (entity, component, replicated) in query.iter() {
+                if !component.is_changed() && !replicated.is_added() {
+                    continue;
+                }
+                match bincode::serialize(&*component) {
+                    Ok(data) => replication.insert(entity.to_bits(), kind, data),
+                    Err(e) => log::error!("Failed to serialize replicated component {}: {e}", type_name::<C>()),
+                }
+            }
+        };
+
+        self.add_systems(
+            PostUpdate,
+            replicate_system
+                .run_if(resource_exists::<ReplicationServer>)
+                .after(ReplicationServerPlugin::spawn_system)
+                .before(ReplicationServerPlugin::send_system),
+        )
+    }
+}
+
+impl Plugin for ReplicationServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplicationRegistry>();
+        app.insert_resource(ReplicationServer::new(self.channel_id));
+
+        app.add_systems(
+            PostUpdate,
+            (Self::spawn_system, Self::despawn_system, Self::send_system)
+                .chain()
+                .before(RenetSend)
+                .run_if(resource_exists::<ReplicationServer>)
+                .run_if(resource_exists::<RenetServer>),
+        );
+    }
+}
+
+impl ReplicationServerPlugin {
+    pub fn spawn_system(query: Query<Entity, Added<Replicated>>, mut replication: ResMut<ReplicationServer>) {
+        for entity in query.iter() {
+            replication.spawn(entity.to_bits());
+        }
+    }
+
+    pub fn despawn_system(mut removed: RemovedComponents<Replicated>, mut replication: ResMut<ReplicationServer>) {
+        for entity in removed.read() {
+            replication.despawn(entity.to_bits());
+        }
+    }
+
+    pub fn send_system(
+        mut server_events: EventReader<ServerEvent>,
+        mut replication: ResMut<ReplicationServer>,
+        mut server: ResMut<RenetServer>,
+    ) {
+        for event in server_events.read() {
+            if let ServerEvent::ClientDisconnected { client_id, .. } = event {
+                replication.remove_client(*client_id);
+            }
+        }
+
+        replication.send(&mut server);
+    }
+}
+
+impl Plugin for ReplicationClientPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplicationRegistry>();
+        app.init_resource::<ServerEntityMap>();
+        app.insert_resource(ReplicationClient::new(self.channel_id));
+
+        app.add_systems(
+            PreUpdate,
+            Self::apply_replication_system
+                .after(RenetReceive)
+                .run_if(resource_exists::<ReplicationClient>),
+        );
+    }
+}
+
+impl ReplicationClientPlugin {
+    /// Receives the replication messages and applies them to the world,
+    /// all the replicated entities are despawned when disconnected.
+    pub fn apply_replication_system(world: &mut World) {
+        world.resource_scope(|world, mut replication: Mut<ReplicationClient>| {
+            match world.get_resource_mut::<RenetClient>() {
+                Some(mut client) if !client.is_disconnected() => replication.receive(&mut client),
+                _ => replication.clear(),
+            }
+
+            world.resource_scope(|world, registry: Mut<ReplicationRegistry>| {
+                world.resource_scope(|world, mut entity_map: Mut<ServerEntityMap>| {
+                    while let Some(event) = replication.get_event() {
+                        apply_event(world, &registry, &mut entity_map, event);
+                    }
+                });
+            });
+        });
+    }
+}
+
+fn apply_event(world: &mut World, registry: &ReplicationRegistry, entity_map: &mut ServerEntityMap, event: ReplicationEvent) {
+    match event {
+        ReplicationEvent::Spawn(server_entity) => {
+            let entity = world.spawn(Replicated).id();
+            entity_map.entities.insert(server_entity, entity);
+        }
+        ReplicationEvent::Despawn(server_entity) => {
+            if let Some(entity) = entity_map.entities.remove(&server_entity) {
+                world.despawn(entity);
+            }
+        }
+        ReplicationEvent::Insert {
+            entity: server_entity,
+            component,
+            data,
+        } => {
+            let Some(fns) = registry.components.get(component as usize) else {
+                log::error!("Received unregistered replicated component {component}");
+                return;
+            };
+            let Some(mut entity) = entity_map.entities.get(&server_entity).and_then(|e| world.get_entity_mut(*e).ok()) else {
+                return;
+            };
+            if let Err(e) = (fns.insert)(&mut entity, &data) {
+                log::error!("Failed to deserialize replicated component {}: {e}", fns.name);
+            }
+        }
+        ReplicationEvent::Remove {
+            entity: server_entity,
+            component,
+        } => {
+            let Some(fns) = registry.components.get(component as usize) else {
+                log::error!("Received unregistered replicated component {component}");
+                return;
+            };
+            if let Some(mut entity) = entity_map.entities.get(&server_entity).and_then(|e| world.get_entity_mut(*e).ok()) {
+                (fns.remove)(&mut entity);
+            }
+        }
+    }
+}
@@ -11,6 +11,10 @@ pub struct SteamServerPlugin;
 
 pub struct SteamClientPlugin;
 
+/// Runs the callbacks of the [`SteamTransportRunner`] non-send resource before the transports are updated,
+/// so connection events are not missed or delayed a frame. Added by the server and client plugins.
+pub struct SteamCallbacksPlugin;
+
 #[derive(Debug, Event)]
 pub struct SteamTransportError(pub SteamError);
 
@@ -20,8 +24,28 @@ impl std::fmt::Display for SteamTransportError {
     }
 }
 
+impl Plugin for SteamCallbacksPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PreUpdate, Self::run_callbacks_system.before(RenetReceive));
+    }
+}
+
+impl SteamCallbacksPlugin {
+    pub fn run_callbacks_system(runner: Option<NonSend<SteamTransportRunner>>) {
+        if let Some(runner) = runner {
+            if let Err(e) = runner.run_callbacks() {
+                log::error!("Failed to run steam callbacks: {e}");
+            }
+        }
+    }
+}
+
 impl Plugin for SteamServerPlugin {
     fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<SteamCallbacksPlugin>() {
+            app.add_plugins(SteamCallbacksPlugin);
+        }
+
         app.add_systems(
             PreUpdate,
             Self::update_system
@@ -68,6 +92,10 @@ impl SteamServerPlugin {
 
 impl Plugin for SteamClientPlugin {
     fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<SteamCallbacksPlugin>() {
+            app.add_plugins(SteamCallbacksPlugin);
+        }
+
         app.add_event::<SteamTransportError>();
 
         app.add_systems(
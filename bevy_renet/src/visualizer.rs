@@ -0,0 +1,90 @@
+use bevy_app::prelude::*;
+use bevy_ecs::prelude::*;
+use bevy_egui::EguiContexts;
+use renet::{RenetClient, RenetServer, ServerEvent};
+
+pub use renet_visualizer::*;
+
+use crate::RenetReceive;
+
+/// Plugin that updates the [`RenetServerVisualizer`] and [`RenetClientVisualizer`] resources and draws their windows with `bevy_egui`,
+/// the `EguiPlugin` must be added to the app.
+///
+/// In the server, clients are added and removed from the visualizer with the [`ServerEvent`]s, with connected events in their timeline.
+/// `N` is the number of values kept in the graphs, the visualizer resources are accessed as `RenetServerVisualizer<N>`.
+///
+/// The windows are only drawn while [`RenetVisualizerWindow`] is visible.
+pub struct RenetVisualizerPlugin<const N: usize = 200>;
+
+/// Visibility of the visualizer windows drawn by the [`RenetVisualizerPlugin`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Resource)]
+pub struct RenetVisualizerWindow {
+    pub visible: bool,
+}
+
+impl Default for RenetVisualizerWindow {
+    fn default() -> Self {
+        Self { visible: true }
+    }
+}
+
+impl<const N: usize> Plugin for RenetVisualizerPlugin<N> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Events<ServerEvent>>();
+        app.init_resource::<RenetVisualizerWindow>();
+        app.init_resource::<RenetServerVisualizer<N>>();
+        app.init_resource::<RenetClientVisualizer<N>>();
+
+        app.add_systems(
+            PreUpdate,
+            (
+                Self::update_server_system.run_if(resource_exists::<RenetServer>),
+                Self::update_client_system.run_if(resource_exists::<RenetClient>),
+            )
+                .after(RenetReceive),
+        );
+        app.add_systems(
+            Update,
+            (
+                Self::show_server_window_system.run_if(resource_exists::<RenetServer>),
+                Self::show_client_window_system.run_if(resource_exists::<RenetClient>),
+            )
+                .run_if(|window: Res<RenetVisualizerWindow>| window.visible),
+        );
+    }
+}
+
+impl<const N: usize> RenetVisualizerPlugin<N> {
+    pub fn update_server_system(
+        mut visualizer: ResMut<RenetServerVisualizer<N>>,
+        mut server_events: EventReader<ServerEvent>,
+        server: Res<RenetServer>,
+    ) {
+        for event in server_events.read() {
+            match event {
+                ServerEvent::ClientConnected { client_id } => {
+                    visualizer.add_client(*client_id);
+                    visualizer.add_event(*client_id, TimelineEvent::Connected);
+                }
+                ServerEvent::ClientDisconnected { client_id, .. } => visualizer.remove_client(*client_id),
+                _ => {}
+            }
+        }
+
+        visualizer.update_from_server(&server);
+    }
+
+    pub fn update_client_system(mut visualizer: ResMut<RenetClientVisualizer<N>>, client: Res<RenetClient>) {
+        if client.is_connected() {
+            visualizer.add_network_info(client.network_info());
+        }
+    }
+
+    pub fn show_server_window_system(mut egui_contexts: EguiContexts, mut visualizer: ResMut<RenetServerVisualizer<N>>) {
+        visualizer.show_window(egui_contexts.ctx_mut());
+    }
+
+    pub fn show_client_window_system(mut egui_contexts: EguiContexts, visualizer: Res<RenetClientVisualizer<N>>) {
+        visualizer.show_window(egui_contexts.ctx_mut());
+    }
+}
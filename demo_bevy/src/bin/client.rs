@@ -82,8 +82,8 @@ fn add_netcode_network(app: &mut App) {
 
 #[cfg(feature = "steam")]
 fn add_steam_network(app: &mut App) {
-    use bevy_renet::steam::{SteamClientPlugin, SteamClientTransport, SteamTransportError};
-    use steamworks::{SingleClient, SteamId};
+    use bevy_renet::steam::{SteamClientPlugin, SteamClientTransport, SteamTransportError, SteamTransportRunner};
+    use steamworks::SteamId;
 
     let (steam_client, single) = steamworks::Client::init_app(480).unwrap();
 
@@ -94,7 +94,9 @@ fn add_steam_network(app: &mut App) {
     let server_steam_id = SteamId::from_raw(server_steam_id);
 
     let client = RenetClient::new(connection_config());
-    let transport = SteamClientTransport::new(&steam_client, &server_steam_id).unwrap();
+    let mut transport = SteamClientTransport::new(&steam_client, &server_steam_id).unwrap();
+    let runner = SteamTransportRunner::new(single);
+    transport.set_callback_handle(runner.handle());
 
     app.add_plugins(SteamClientPlugin);
     app.insert_resource(client);
@@ -103,12 +105,8 @@ fn add_steam_network(app: &mut App) {
 
     app.configure_sets(Update, Connected.run_if(client_connected));
 
-    app.insert_non_send_resource(single);
-    fn steam_callbacks(client: NonSend<SingleClient>) {
-        client.run_callbacks();
-    }
-
-    app.add_systems(PreUpdate, steam_callbacks);
+    // The callbacks are run by the SteamClientPlugin
+    app.insert_non_send_resource(runner);
 
     // If any error is found we just panic
     #[allow(clippy::never_loop)]
@@ -13,7 +13,7 @@ use demo_bevy::{
     setup_level, spawn_fireball, ClientChannel, NetworkedEntities, Player, PlayerCommand, PlayerInput, Projectile, ServerChannel,
     ServerMessages, Velocity,
 };
-use renet_visualizer::RenetServerVisualizer;
+use renet_visualizer::{RenetServerVisualizer, TimelineEvent};
 
 #[derive(Debug, Default, Resource)]
 pub struct ServerLobby {
@@ -32,7 +32,9 @@ struct BotId(u64);
 
 #[cfg(feature = "netcode")]
 fn add_netcode_network(app: &mut App) {
-    use bevy_renet::netcode::{NetcodeServerPlugin, NetcodeServerTransport, ServerAuthentication, ServerConfig};
+    use bevy_renet::netcode::{
+        NetcodeServerPlugin, NetcodeServerTransport, ServerAuthentication, ServerConfig, NETCODE_MAX_PENDING_CLIENTS, NETCODE_SEND_RATE,
+    };
     use demo_bevy::{connection_config, PROTOCOL_ID};
     use std::{net::UdpSocket, time::SystemTime};
 
@@ -49,6 +51,8 @@ fn add_netcode_network(app: &mut App) {
         protocol_id: PROTOCOL_ID,
         public_addresses: vec![public_addr],
         authentication: ServerAuthentication::Unsecure,
+        keep_alive_interval: NETCODE_SEND_RATE,
+        max_pending_clients: NETCODE_MAX_PENDING_CLIENTS,
     };
 
     let transport = NetcodeServerTransport::new(server_config, socket).unwrap();
@@ -58,9 +62,8 @@ fn add_netcode_network(app: &mut App) {
 
 #[cfg(feature = "steam")]
 fn add_steam_network(app: &mut App) {
-    use bevy_renet::steam::{AccessPermission, SteamServerConfig, SteamServerPlugin, SteamServerTransport};
+    use bevy_renet::steam::{AccessPermission, SteamServerConfig, SteamServerPlugin, SteamServerTransport, SteamTransportRunner};
     use demo_bevy::connection_config;
-    use steamworks::SingleClient;
 
     let (steam_client, single) = steamworks::Client::init_app(480).unwrap();
 
@@ -70,18 +73,15 @@ fn add_steam_network(app: &mut App) {
         max_clients: 10,
         access_permission: AccessPermission::Public,
     };
-    let transport = SteamServerTransport::new(&steam_client, steam_transport_config).unwrap();
+    let mut transport = SteamServerTransport::new(&steam_client, steam_transport_config).unwrap();
+    let runner = SteamTransportRunner::new(single);
+    transport.set_callback_handle(runner.handle());
 
     app.add_plugins(SteamServerPlugin);
     app.insert_resource(server);
     app.insert_non_send_resource(transport);
-    app.insert_non_send_resource(single);
-
-    fn steam_callbacks(client: NonSend<SingleClient>) {
-        client.run_callbacks();
-    }
-
-    app.add_systems(PreUpdate, steam_callbacks);
+    // The callbacks are run by the SteamServerPlugin
+    app.insert_non_send_resource(runner);
 }
 
 fn main() {
@@ -142,6 +142,7 @@ fn server_update_system(
             ServerEvent::ClientConnected { client_id } => {
                 println!("Player {} connected.", client_id);
                 visualizer.add_client(*client_id);
+                visualizer.add_event(*client_id, TimelineEvent::Connected);
 
                 // Initialize other players for this new client
                 for (entity, player, transform) in players.iter() {
@@ -189,6 +190,10 @@ fn server_update_system(
                 let message = bincode::serialize(&ServerMessages::PlayerRemove { id: *client_id }).unwrap();
                 server.broadcast_message(ServerChannel::ServerMessages, message);
             }
+            ServerEvent::ConnectionDenied { addr, reason } => {
+                println!("Connection from {} denied: {}", addr, reason);
+            }
+            _ => {}
         }
     }
 
@@ -238,7 +243,7 @@ fn update_projectiles_system(mut commands: Commands, mut projectiles: Query<(Ent
 }
 
 fn update_visulizer_system(mut egui_contexts: EguiContexts, mut visualizer: ResMut<RenetServerVisualizer<200>>, server: Res<RenetServer>) {
-    visualizer.update(&server);
+    visualizer.update_from_server(&server);
     visualizer.show_window(egui_contexts.ctx_mut());
 }
 
@@ -1,7 +1,7 @@
 use std::{f32::consts::PI, time::Duration};
 
 use bevy::prelude::*;
-use bevy_renet::renet::{ChannelConfig, ClientId, ConnectionConfig, SendType};
+use bevy_renet::renet::{ChannelConfig, ClientId, ConnectionConfig, ResendStrategy, SendType};
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "netcode")]
@@ -79,15 +79,19 @@ impl ClientChannel {
                 channel_id: Self::Input.into(),
                 max_memory_usage_bytes: 5 * 1024 * 1024,
                 send_type: SendType::ReliableOrdered {
-                    resend_time: Duration::ZERO,
+                    resend_time: ResendStrategy::Fixed(Duration::ZERO),
                 },
+                slice_size: None,
+                ordering: None,
             },
             ChannelConfig {
                 channel_id: Self::Command.into(),
                 max_memory_usage_bytes: 5 * 1024 * 1024,
                 send_type: SendType::ReliableOrdered {
-                    resend_time: Duration::ZERO,
+                    resend_time: ResendStrategy::Fixed(Duration::ZERO),
                 },
+                slice_size: None,
+                ordering: None,
             },
         ]
     }
@@ -109,13 +113,17 @@ impl ServerChannel {
                 channel_id: Self::NetworkedEntities.into(),
                 max_memory_usage_bytes: 10 * 1024 * 1024,
                 send_type: SendType::Unreliable,
+                slice_size: None,
+                ordering: None,
             },
             ChannelConfig {
                 channel_id: Self::ServerMessages.into(),
                 max_memory_usage_bytes: 10 * 1024 * 1024,
                 send_type: SendType::ReliableOrdered {
-                    resend_time: Duration::from_millis(200),
+                    resend_time: ResendStrategy::Fixed(Duration::from_millis(200)),
                 },
+                slice_size: None,
+                ordering: None,
             },
         ]
     }
@@ -126,6 +134,9 @@ pub fn connection_config() -> ConnectionConfig {
         available_bytes_per_tick: 1024 * 1024,
         client_channels_config: ClientChannel::channels_config(),
         server_channels_config: ServerChannel::channels_config(),
+        pacing_rate: None,
+        sent_packet_discard_time: Duration::from_secs(3),
+        ..Default::default()
     }
 }
 
@@ -9,6 +9,7 @@ use std::{collections::HashMap, time::Instant};
 
 use crate::{
     server::{ChatServer, SYSTEM_MESSAGE_CLIENT_ID},
+    transfer::{FileTransfers, ReceivedFile, FILE_CHANNEL_ID},
     ui::{draw_chat, draw_loader, draw_main_screen},
     Message, ServerMessages,
 };
@@ -19,6 +20,8 @@ pub struct UiState {
     pub server_addr: String,
     pub error: Option<String>,
     pub text_input: String,
+    pub file_path: String,
+    pub file_error: Option<String>,
     pub show_network_info: bool,
 }
 
@@ -30,6 +33,8 @@ pub enum AppState {
         usernames: HashMap<ClientId, String>,
         messages: Vec<Message>,
         visualizer: Box<RenetClientVisualizer<240>>,
+        transfers: Box<FileTransfers>,
+        files: Vec<ReceivedFile>,
     },
     HostChat {
         chat_server: Box<ChatServer>,
@@ -82,6 +87,8 @@ impl ChatApp {
                 usernames,
                 messages,
                 visualizer,
+                transfers,
+                files,
             } => {
                 client.update(duration);
                 if let Err(e) = transport.update(duration, client) {
@@ -117,6 +124,16 @@ impl ChatApp {
                         }
                     }
 
+                    while let Some(message) = client.receive_message(FILE_CHANNEL_ID) {
+                        if let Some(file) = transfers.receive(&message) {
+                            files.push(file);
+                        }
+                    }
+
+                    for message in transfers.update(client.channel_available_memory(FILE_CHANNEL_ID)) {
+                        client.send_message(FILE_CHANNEL_ID, message);
+                    }
+
                     if let Err(e) = transport.send_packets(client) {
                         error!("Error sending packets: {}", e);
                         self.state = AppState::MainScreen;
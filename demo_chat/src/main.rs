@@ -8,6 +8,7 @@ use std::collections::HashMap;
 
 mod client;
 mod server;
+mod transfer;
 mod ui;
 
 const PROTOCOL_ID: u64 = 27;
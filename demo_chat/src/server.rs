@@ -5,11 +5,14 @@ use std::{
     time::{Duration, SystemTime},
 };
 
-use renet::{ClientId, ConnectionConfig, DefaultChannel, RenetServer, ServerEvent};
-use renet_netcode::{NetcodeServerTransport, ServerAuthentication, ServerConfig};
+use renet::{ClientId, DefaultChannel, RateLimit, RateLimiter, RenetServer, ServerEvent};
+use renet_netcode::{NetcodeServerTransport, ServerAuthentication, ServerConfig, NETCODE_MAX_PENDING_CLIENTS, NETCODE_SEND_RATE};
 use renet_visualizer::RenetServerVisualizer;
 
-use crate::{ClientMessages, Message, ServerMessages, Username, PROTOCOL_ID};
+use crate::{
+    transfer::{self, FileTransfers, OutgoingFile, ReceivedFile, FILE_CHANNEL_ID},
+    ClientMessages, Message, ServerMessages, Username, PROTOCOL_ID,
+};
 use bincode::Options;
 use log::info;
 
@@ -22,6 +25,8 @@ pub struct ChatServer {
     pub usernames: HashMap<ClientId, String>,
     pub messages: Vec<Message>,
     pub visualizer: RenetServerVisualizer<240>,
+    pub transfers: HashMap<ClientId, FileTransfers>,
+    pub files: Vec<ReceivedFile>,
 }
 
 impl ChatServer {
@@ -34,11 +39,24 @@ impl ChatServer {
             protocol_id: PROTOCOL_ID,
             public_addresses: vec![socket.local_addr().unwrap()],
             authentication: ServerAuthentication::Unsecure,
+            keep_alive_interval: NETCODE_SEND_RATE,
+            max_pending_clients: NETCODE_MAX_PENDING_CLIENTS,
         };
 
         let transport = NetcodeServerTransport::new(server_config, socket).unwrap();
 
-        let server: RenetServer = RenetServer::new(ConnectionConfig::default());
+        let mut server: RenetServer = RenetServer::new(transfer::connection_config());
+
+        // Throttle chat spam, messages above the limit are dropped
+        let mut rate_limiter = RateLimiter::default();
+        rate_limiter.set_limit(
+            DefaultChannel::ReliableOrdered,
+            RateLimit {
+                messages_per_second: 2.0,
+                burst: 5,
+            },
+        );
+        server.set_rate_limiter(rate_limiter);
 
         let mut usernames = HashMap::new();
         usernames.insert(HOST_CLIENT_ID, host_username);
@@ -49,6 +67,8 @@ impl ChatServer {
             usernames,
             messages: vec![],
             visualizer: RenetServerVisualizer::default(),
+            transfers: HashMap::new(),
+            files: vec![],
         }
     }
 
@@ -56,13 +76,14 @@ impl ChatServer {
         self.server.update(duration);
         self.transport.update(duration, &mut self.server).unwrap();
 
-        self.visualizer.update(&self.server);
+        self.visualizer.update_from_server(&self.server);
 
         while let Some(event) = self.server.get_event() {
             match event {
                 ServerEvent::ClientConnected { client_id } => {
                     let user_data = self.transport.user_data(client_id).unwrap();
                     self.visualizer.add_client(client_id);
+                    self.transfers.insert(client_id, FileTransfers::default());
                     let username = Username::from_user_data(&user_data).0;
                     self.usernames.insert(client_id, username.clone());
                     let message = bincode::options()
@@ -77,12 +98,17 @@ impl ChatServer {
                 }
                 ServerEvent::ClientDisconnected { client_id, reason: _ } => {
                     self.visualizer.remove_client(client_id);
+                    self.transfers.remove(&client_id);
                     self.usernames.remove(&client_id);
                     let message = bincode::options()
                         .serialize(&ServerMessages::ClientDisconnected { client_id })
                         .unwrap();
                     self.server.broadcast_message(DefaultChannel::ReliableOrdered, message);
                 }
+                ServerEvent::ConnectionDenied { addr, reason } => {
+                    info!("Connection from {} denied: {}", addr, reason);
+                }
+                _ => {}
             }
         }
 
@@ -95,6 +121,24 @@ impl ChatServer {
                     }
                 }
             }
+
+            while let Some(message) = self.server.receive_message(client_id, FILE_CHANNEL_ID) {
+                let Some(transfers) = self.transfers.get_mut(&client_id) else {
+                    break;
+                };
+                if let Some(file) = transfers.receive(&message) {
+                    info!("Received file {} from client {}", file.name, client_id);
+                    self.send_file(client_id, &file.name, &file.data);
+                    self.files.push(file);
+                }
+            }
+        }
+
+        for (client_id, transfers) in self.transfers.iter_mut() {
+            let available_memory = self.server.channel_available_memory(*client_id, FILE_CHANNEL_ID);
+            for message in transfers.update(available_memory) {
+                self.server.send_message(*client_id, FILE_CHANNEL_ID, message);
+            }
         }
 
         self.transport.send_packets(&mut self.server);
@@ -108,4 +152,16 @@ impl ChatServer {
         let message = bincode::options().serialize(&ServerMessages::ClientMessage(message)).unwrap();
         self.server.broadcast_message(DefaultChannel::ReliableOrdered, message);
     }
+
+    /// Sends the file to all clients except the sender.
+    pub fn send_file(&mut self, sender: ClientId, name: &str, data: &[u8]) {
+        for (client_id, transfers) in self.transfers.iter_mut() {
+            if *client_id != sender {
+                transfers.send_file(OutgoingFile::new(sender, name.to_string(), data));
+            }
+        }
+
+        let text = format!("sent the file {} ({} bytes)", name, data.len());
+        self.receive_message(sender, text);
+    }
 }
@@ -0,0 +1,195 @@
+use std::collections::VecDeque;
+
+use bincode::Options;
+use log::warn;
+use renet::{Bytes, ChannelConfig, ClientId, ConnectionConfig, DefaultChannel, ResendStrategy, SendType};
+use serde::{Deserialize, Serialize};
+
+// Files are sent in their own channel, after the chat channels so chat messages have priority
+pub const FILE_CHANNEL_ID: u8 = 3;
+const FILE_CHANNEL_MEMORY: usize = 4 * 1024 * 1024;
+// Each chunk is a big message, sliced by renet into many packets
+const CHUNK_SIZE: usize = 64 * 1024;
+pub const MAX_FILE_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+enum FileMessage {
+    Start { sender: ClientId, name: String, size: u64 },
+    Chunk(Vec<u8>),
+}
+
+/// Default channels with the file channel added.
+pub fn connection_config() -> ConnectionConfig {
+    let mut channels_config = DefaultChannel::config();
+    channels_config.push(ChannelConfig {
+        channel_id: FILE_CHANNEL_ID,
+        max_memory_usage_bytes: FILE_CHANNEL_MEMORY,
+        send_type: SendType::ReliableOrdered {
+            resend_time: ResendStrategy::Fixed(std::time::Duration::from_millis(300)),
+        },
+        slice_size: None,
+        ordering: None,
+    });
+
+    ConnectionConfig {
+        server_channels_config: channels_config.clone(),
+        client_channels_config: channels_config,
+        ..Default::default()
+    }
+}
+
+pub struct OutgoingFile {
+    pub name: String,
+    messages: Vec<Bytes>,
+    next_message: usize,
+    queued_bytes: usize,
+    total_bytes: usize,
+}
+
+impl OutgoingFile {
+    pub fn new(sender: ClientId, name: String, data: &[u8]) -> Self {
+        let start = FileMessage::Start {
+            sender,
+            name: name.clone(),
+            size: data.len() as u64,
+        };
+        let messages: Vec<Bytes> = std::iter::once(start)
+            .chain(data.chunks(CHUNK_SIZE).map(|chunk| FileMessage::Chunk(chunk.to_vec())))
+            .map(|message| bincode::options().serialize(&message).unwrap().into())
+            .collect();
+        let total_bytes = messages.iter().map(|message| message.len()).sum();
+
+        Self {
+            name,
+            messages,
+            next_message: 0,
+            queued_bytes: 0,
+            total_bytes,
+        }
+    }
+
+    // Bytes sent that were not acked yet, the file channel only has messages of this file
+    fn in_flight(available_memory: usize) -> usize {
+        FILE_CHANNEL_MEMORY - available_memory
+    }
+
+    /// Ratio of the file acked by the receiver.
+    pub fn progress(&self, available_memory: usize) -> f32 {
+        let acked_bytes = self.queued_bytes.saturating_sub(Self::in_flight(available_memory));
+        acked_bytes as f32 / self.total_bytes as f32
+    }
+
+    fn is_complete(&self, available_memory: usize) -> bool {
+        self.next_message == self.messages.len() && Self::in_flight(available_memory) == 0
+    }
+
+    // Returns the messages that fit in the available memory of the channel
+    fn queue_messages(&mut self, mut available_memory: usize) -> Vec<Bytes> {
+        let mut messages = vec![];
+        while let Some(message) = self.messages.get(self.next_message) {
+            if message.len() > available_memory {
+                break;
+            }
+            available_memory -= message.len();
+            self.queued_bytes += message.len();
+            self.next_message += 1;
+            messages.push(message.clone());
+        }
+
+        messages
+    }
+}
+
+pub struct ReceivedFile {
+    pub sender: ClientId,
+    pub name: String,
+    pub data: Vec<u8>,
+    size: usize,
+}
+
+impl ReceivedFile {
+    pub fn progress(&self) -> f32 {
+        self.data.len() as f32 / self.size.max(1) as f32
+    }
+
+    fn is_complete(&self) -> bool {
+        self.data.len() == self.size
+    }
+
+    /// Saves the file in the working directory, only the file name sent is used.
+    pub fn save(&self) -> std::io::Result<()> {
+        let name = std::path::Path::new(&self.name)
+            .file_name()
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid file name"))?;
+        std::fs::write(name, &self.data)
+    }
+}
+
+/// File transfers with a connection, files are sent one at a time.
+#[derive(Default)]
+pub struct FileTransfers {
+    outgoing: VecDeque<OutgoingFile>,
+    incoming: Option<ReceivedFile>,
+}
+
+impl FileTransfers {
+    pub fn send_file(&mut self, file: OutgoingFile) {
+        self.outgoing.push_back(file);
+    }
+
+    /// Returns the messages to send in the file channel, with the available memory of the channel.
+    pub fn update(&mut self, available_memory: usize) -> Vec<Bytes> {
+        if self.outgoing.front().is_some_and(|file| file.is_complete(available_memory)) {
+            self.outgoing.pop_front();
+        }
+
+        match self.outgoing.front_mut() {
+            Some(file) => file.queue_messages(available_memory),
+            None => vec![],
+        }
+    }
+
+    /// Processes a message received in the file channel, returns the file once all of it is received.
+    pub fn receive(&mut self, message: &[u8]) -> Option<ReceivedFile> {
+        match bincode::options().deserialize::<FileMessage>(message) {
+            Ok(FileMessage::Start { sender, name, size }) => {
+                if size as usize > MAX_FILE_SIZE {
+                    warn!("Ignored file {name} with {size} bytes, above the maximum size");
+                    return None;
+                }
+                self.incoming = Some(ReceivedFile {
+                    sender,
+                    name,
+                    data: Vec::with_capacity(size as usize),
+                    size: size as usize,
+                });
+            }
+            Ok(FileMessage::Chunk(chunk)) => {
+                let incoming = self.incoming.as_mut()?;
+                if incoming.data.len() + chunk.len() > incoming.size {
+                    warn!("Discarded file {}, received more bytes than its size", incoming.name);
+                    self.incoming = None;
+                    return None;
+                }
+                incoming.data.extend_from_slice(&chunk);
+            }
+            Err(e) => {
+                warn!("Failed to deserialize file message: {e}");
+                return None;
+            }
+        }
+
+        if self.incoming.as_ref().is_some_and(|incoming| incoming.is_complete()) {
+            return self.incoming.take();
+        }
+        None
+    }
+
+    pub fn outgoing(&self) -> Option<&OutgoingFile> {
+        self.outgoing.front()
+    }
+
+    pub fn incoming(&self) -> Option<&ReceivedFile> {
+        self.incoming.as_ref()
+    }
+}
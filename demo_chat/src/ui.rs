@@ -3,7 +3,7 @@ use eframe::{
     egui::{self, lerp, Color32, Layout, Pos2, Ui, Vec2},
     epaint::{PathShape, PathStroke},
 };
-use renet::{ClientId, ConnectionConfig, DefaultChannel, RenetClient};
+use renet::{ClientId, DefaultChannel, RenetClient};
 use renet_netcode::{ClientAuthentication, NetcodeClientTransport};
 
 use std::{
@@ -15,6 +15,7 @@ use std::{
 use crate::{
     client::{AppState, UiState},
     server::{ChatServer, HOST_CLIENT_ID, SYSTEM_MESSAGE_CLIENT_ID},
+    transfer::{self, FileTransfers, OutgoingFile, ReceivedFile, FILE_CHANNEL_ID, MAX_FILE_SIZE},
 };
 use crate::{ClientMessages, Username, PROTOCOL_ID};
 
@@ -73,6 +74,90 @@ pub fn draw_host_commands(ui: &mut Ui, chat_server: &mut ChatServer) {
     });
 }
 
+fn draw_transfers(ui: &mut Ui, transfers: &FileTransfers, available_memory: usize, username: Option<&String>) {
+    if let Some(file) = transfers.outgoing() {
+        let text = match username {
+            Some(username) => format!("Sending {} to {}", file.name, username),
+            None => format!("Sending {}", file.name),
+        };
+        ui.add(egui::ProgressBar::new(file.progress(available_memory)).text(text));
+    }
+    if let Some(file) = transfers.incoming() {
+        let text = match username {
+            Some(username) => format!("Receiving {} from {}", file.name, username),
+            None => format!("Receiving {}", file.name),
+        };
+        ui.add(egui::ProgressBar::new(file.progress()).text(text));
+    }
+}
+
+fn draw_received_files(ui: &mut Ui, ui_state: &mut UiState, files: &[ReceivedFile], usernames: &HashMap<ClientId, String>) {
+    for file in files.iter() {
+        ui.horizontal(|ui| {
+            let sender = usernames.get(&file.sender).map_or("unknown", |username| username.as_str());
+            ui.label(format!("{} from {}", file.name, sender));
+            if ui.button("💾").on_hover_text("Save in the working directory").clicked() {
+                ui_state.file_error = file.save().err().map(|e| format!("Failed to save {}: {}", file.name, e));
+            }
+        });
+    }
+}
+
+pub fn draw_files(ui: &mut Ui, ui_state: &mut UiState, state: &AppState, usernames: &HashMap<ClientId, String>) {
+    ui.vertical_centered(|ui| {
+        ui.heading("Files");
+    });
+
+    ui.separator();
+
+    match state {
+        AppState::ClientChat {
+            client, transfers, files, ..
+        } => {
+            draw_transfers(ui, transfers, client.channel_available_memory(FILE_CHANNEL_ID), None);
+            draw_received_files(ui, ui_state, files, usernames);
+        }
+        AppState::HostChat { chat_server } => {
+            for (client_id, transfers) in chat_server.transfers.iter() {
+                let available_memory = chat_server.server.channel_available_memory(*client_id, FILE_CHANNEL_ID);
+                draw_transfers(ui, transfers, available_memory, usernames.get(client_id));
+            }
+            draw_received_files(ui, ui_state, &chat_server.files, usernames);
+        }
+        AppState::MainScreen => {}
+    }
+
+    if let Some(error) = &ui_state.file_error {
+        ui.colored_label(Color32::RED, error);
+    }
+}
+
+fn send_file(ui_state: &mut UiState, state: &mut AppState) {
+    let path = std::path::Path::new(&ui_state.file_path);
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            ui_state.file_error = Some(format!("Failed to read file: {}", e));
+            return;
+        }
+    };
+    if data.len() > MAX_FILE_SIZE {
+        ui_state.file_error = Some(format!("File is bigger than {} bytes", MAX_FILE_SIZE));
+        return;
+    }
+
+    let name = path
+        .file_name()
+        .map_or("file".to_string(), |name| name.to_string_lossy().to_string());
+    match state {
+        AppState::HostChat { chat_server } => chat_server.send_file(HOST_CLIENT_ID, &name, &data),
+        AppState::ClientChat { transport, transfers, .. } => transfers.send_file(OutgoingFile::new(transport.client_id(), name, &data)),
+        AppState::MainScreen => unreachable!(),
+    }
+    ui_state.file_error = None;
+    ui_state.file_path.clear();
+}
+
 pub fn draw_main_screen(ui_state: &mut UiState, state: &mut AppState, ctx: &egui::Context) {
     egui::CentralPanel::default().show(ctx, |ui| {
         egui::Area::new("buttons".into())
@@ -107,6 +192,8 @@ pub fn draw_main_screen(ui_state: &mut UiState, state: &mut AppState, ctx: &egui
                                             transport: Box::new(transport),
                                             messages: vec![],
                                             usernames: HashMap::new(),
+                                            transfers: Box::default(),
+                                            files: vec![],
                                         };
                                     }
                                 }
@@ -171,6 +258,8 @@ pub fn draw_chat(ui_state: &mut UiState, state: &mut AppState, usernames: HashMa
                 }
             });
 
+            draw_files(ui, ui_state, state, &usernames);
+
             let exit = ui.with_layout(Layout::bottom_up(eframe::emath::Align::Center).with_cross_justify(true), |ui| {
                 ui.button("Exit").clicked()
             });
@@ -224,6 +313,14 @@ pub fn draw_chat(ui_state: &mut UiState, state: &mut AppState, usernames: HashMa
             };
             ui_state.text_input.clear();
         }
+
+        ui.horizontal(|ui| {
+            ui.label("File:");
+            ui.text_edit_singleline(&mut ui_state.file_path);
+            if ui.button("Send File").clicked() && !ui_state.file_path.is_empty() {
+                send_file(ui_state, state);
+            }
+        });
     });
 
     egui::CentralPanel::default().show(ctx, |ui| {
@@ -252,7 +349,7 @@ pub fn draw_chat(ui_state: &mut UiState, state: &mut AppState, usernames: HashMa
 }
 
 fn create_renet_client(username: String, server_addr: SocketAddr) -> (RenetClient, NetcodeClientTransport) {
-    let connection_config = ConnectionConfig::default();
+    let connection_config = transfer::connection_config();
     let client = RenetClient::new(connection_config);
 
     let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
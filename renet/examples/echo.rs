@@ -8,7 +8,8 @@ use std::{
 
 use renet::{ClientId, ConnectionConfig, DefaultChannel, RenetClient, RenetServer, ServerEvent};
 use renet_netcode::{
-    ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication, ServerConfig, NETCODE_USER_DATA_BYTES,
+    ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication, ServerConfig, NETCODE_MAX_PENDING_CLIENTS,
+    NETCODE_SEND_RATE, NETCODE_USER_DATA_BYTES,
 };
 
 // Helper struct to pass an username in the user data
@@ -72,6 +73,8 @@ fn server(public_addr: SocketAddr) {
         protocol_id: PROTOCOL_ID,
         public_addresses: vec![public_addr],
         authentication: ServerAuthentication::Unsecure,
+        keep_alive_interval: NETCODE_SEND_RATE,
+        max_pending_clients: NETCODE_MAX_PENDING_CLIENTS,
     };
     let socket: UdpSocket = UdpSocket::bind(public_addr).unwrap();
 
@@ -114,6 +117,10 @@ fn server(public_addr: SocketAddr) {
                         );
                     }
                 }
+                ServerEvent::ConnectionDenied { addr, reason } => {
+                    println!("Connection from {} denied: {}", addr, reason);
+                }
+                _ => {}
             }
         }
 
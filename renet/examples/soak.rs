@@ -0,0 +1,248 @@
+//! Soak test for long-running stability, should be run before releases:
+//!
+//! ```sh
+//! cargo run --release -p renet --example soak -- [CLIENTS] [HOURS] [PACKET_LOSS]
+//! ```
+//!
+//! A headless server and the clients exchange reliable, sliced and unreliable messages through links
+//! with simulated latency and packet loss. The time is simulated, so hours of traffic run in minutes.
+//! The test panics if a reliable message is lost, duplicated or out of order, if the memory used by the
+//! connections keeps growing, or if the network stats drift from the simulated conditions.
+use std::{collections::VecDeque, time::Duration};
+
+use renet::{ClientId, ConnectionConfig, DefaultChannel, RenetClient, RenetServer};
+
+const TICK: Duration = Duration::from_millis(16);
+const LATENCY: Duration = Duration::from_millis(50);
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+// Memory usage is measured after the warmup, the usage after that can't grow above the tolerance
+const WARMUP: Duration = Duration::from_secs(5 * 60);
+const MEMORY_GROWTH_TOLERANCE: f64 = 2.0;
+const SLICED_MESSAGE_BYTES: usize = 5000;
+
+// Xorshift random number generator, so the runs are reproducible
+struct Random(u64);
+
+impl Random {
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// One direction of a connection, with latency and packet loss
+#[derive(Default)]
+struct Link {
+    packets: VecDeque<(Duration, Vec<u8>)>,
+}
+
+impl Link {
+    fn send(&mut self, packets: Vec<Vec<u8>>, current_time: Duration, packet_loss: f64, random: &mut Random) {
+        for packet in packets {
+            if random.next_f64() >= packet_loss {
+                self.packets.push_back((current_time + LATENCY, packet));
+            }
+        }
+    }
+
+    fn receive(&mut self, current_time: Duration) -> Vec<Vec<u8>> {
+        let mut packets = vec![];
+        while self.packets.front().is_some_and(|(deliver_at, _)| *deliver_at <= current_time) {
+            packets.push(self.packets.pop_front().unwrap().1);
+        }
+        packets
+    }
+}
+
+// Counters of the messages sent and received in one direction
+#[derive(Default)]
+struct MessageCounter {
+    next_reliable: u64,
+    next_sliced: u64,
+    unreliable_sent: u64,
+    expected_reliable: u64,
+    expected_sliced: u64,
+    unreliable_received: u64,
+}
+
+impl MessageCounter {
+    fn send(&mut self, send: &mut impl FnMut(DefaultChannel, Vec<u8>), tick: u64) {
+        send(DefaultChannel::ReliableOrdered, self.next_reliable.to_le_bytes().to_vec());
+        self.next_reliable += 1;
+
+        if tick.is_multiple_of(60) {
+            let mut message = vec![0; SLICED_MESSAGE_BYTES];
+            message[..8].copy_from_slice(&self.next_sliced.to_le_bytes());
+            send(DefaultChannel::ReliableOrdered, message);
+            self.next_sliced += 1;
+        }
+
+        send(DefaultChannel::Unreliable, self.unreliable_sent.to_le_bytes().to_vec());
+        self.unreliable_sent += 1;
+    }
+
+    fn receive(&mut self, receive: &mut impl FnMut(DefaultChannel) -> Option<Vec<u8>>) {
+        while let Some(message) = receive(DefaultChannel::ReliableOrdered) {
+            let id = u64::from_le_bytes(message[..8].try_into().unwrap());
+            if message.len() == SLICED_MESSAGE_BYTES {
+                assert_eq!(id, self.expected_sliced, "sliced message lost, duplicated or out of order");
+                self.expected_sliced += 1;
+            } else {
+                assert_eq!(id, self.expected_reliable, "reliable message lost, duplicated or out of order");
+                self.expected_reliable += 1;
+            }
+        }
+
+        while let Some(message) = receive(DefaultChannel::Unreliable) {
+            let id = u64::from_le_bytes(message[..8].try_into().unwrap());
+            assert!(id < self.unreliable_sent, "received unreliable message never sent");
+            self.unreliable_received += 1;
+        }
+    }
+}
+
+struct SoakClient {
+    client_id: ClientId,
+    client: RenetClient,
+    to_server: Link,
+    to_client: Link,
+    client_messages: MessageCounter,
+    server_messages: MessageCounter,
+}
+
+fn main() {
+    env_logger::init();
+    let args: Vec<String> = std::env::args().collect();
+    let num_clients: u64 = args.get(1).map_or(8, |arg| arg.parse().unwrap());
+    let hours: f64 = args.get(2).map_or(1.0, |arg| arg.parse().unwrap());
+    let packet_loss: f64 = args.get(3).map_or(0.05, |arg| arg.parse().unwrap());
+    let duration = Duration::from_secs_f64(hours * 3600.0);
+    println!("Soak test with {num_clients} clients for {hours} hours and {packet_loss} packet loss");
+
+    let mut random = Random(0x2545F4914F6CDD1D);
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut clients: Vec<SoakClient> = (0..num_clients)
+        .map(|client_id| {
+            server.add_connection(client_id);
+            let mut client = RenetClient::new(ConnectionConfig::default());
+            client.set_connected();
+            SoakClient {
+                client_id,
+                client,
+                to_server: Link::default(),
+                to_client: Link::default(),
+                client_messages: MessageCounter::default(),
+                server_messages: MessageCounter::default(),
+            }
+        })
+        .collect();
+
+    let mut current_time = Duration::ZERO;
+    let mut next_report = REPORT_INTERVAL;
+    let mut baseline_memory: Option<(usize, usize)> = None;
+    let mut tick: u64 = 0;
+    while current_time < duration {
+        current_time += TICK;
+        tick += 1;
+        server.update(TICK);
+
+        for soak_client in clients.iter_mut() {
+            let client_id = soak_client.client_id;
+            soak_client.client.update(TICK);
+
+            for packet in soak_client.to_server.receive(current_time) {
+                server.process_packet_from(&packet, client_id).unwrap();
+            }
+            for packet in soak_client.to_client.receive(current_time) {
+                soak_client.client.process_packet(&packet);
+            }
+
+            let client = &mut soak_client.client;
+            soak_client
+                .client_messages
+                .receive(&mut |channel| server.receive_message(client_id, channel).map(Vec::from));
+            soak_client
+                .server_messages
+                .receive(&mut |channel| client.receive_message(channel).map(Vec::from));
+
+            soak_client
+                .client_messages
+                .send(&mut |channel, message| client.send_message(channel, message), tick);
+            soak_client
+                .server_messages
+                .send(&mut |channel, message| server.send_message(client_id, channel, message), tick);
+
+            let packets = soak_client.client.get_packets_to_send();
+            soak_client.to_server.send(packets, current_time, packet_loss, &mut random);
+            let packets = server.get_packets_to_send(client_id).unwrap();
+            soak_client.to_client.send(packets, current_time, packet_loss, &mut random);
+        }
+
+        assert!(
+            clients.iter().all(|soak_client| soak_client.client.is_connected()),
+            "client disconnected: {:?}",
+            clients.iter().find_map(|soak_client| soak_client.client.disconnect_reason())
+        );
+
+        if current_time >= next_report {
+            next_report += REPORT_INTERVAL;
+            report(&server, &clients, current_time, packet_loss, &mut baseline_memory);
+        }
+    }
+
+    println!("Soak test finished without errors");
+}
+
+fn report(
+    server: &RenetServer,
+    clients: &[SoakClient],
+    current_time: Duration,
+    packet_loss: f64,
+    baseline_memory: &mut Option<(usize, usize)>,
+) {
+    let server_memory = server.memory_usage();
+    let client_memory = clients
+        .iter()
+        .map(|soak_client| soak_client.client.memory_usage())
+        .max()
+        .unwrap_or(0);
+    let rtt = clients.iter().map(|soak_client| soak_client.client.rtt()).fold(0.0, f64::max);
+    let measured_packet_loss = clients.iter().map(|soak_client| soak_client.client.packet_loss()).sum::<f64>() / clients.len() as f64;
+    let reliable_received: u64 = clients
+        .iter()
+        .map(|soak_client| soak_client.server_messages.expected_reliable)
+        .sum();
+    let unreliable_received: u64 = clients
+        .iter()
+        .map(|soak_client| soak_client.server_messages.unreliable_received)
+        .sum();
+    println!(
+        "[{:>6} min] server memory: {server_memory} bytes, client memory: {client_memory} bytes, rtt: {:.0}ms, packet loss: {:.3}, reliable received: {reliable_received}, unreliable received: {unreliable_received}",
+        current_time.as_secs() / 60,
+        rtt * 1000.0,
+        measured_packet_loss,
+    );
+
+    match baseline_memory {
+        None if current_time >= WARMUP => *baseline_memory = Some((server_memory, client_memory)),
+        Some((baseline_server, baseline_client)) => {
+            assert!(
+                server_memory as f64 <= *baseline_server as f64 * MEMORY_GROWTH_TOLERANCE,
+                "server memory grew from {baseline_server} to {server_memory} bytes"
+            );
+            assert!(
+                client_memory as f64 <= *baseline_client as f64 * MEMORY_GROWTH_TOLERANCE,
+                "client memory grew from {baseline_client} to {client_memory} bytes"
+            );
+        }
+        None => {}
+    }
+
+    assert!(rtt < (LATENCY * 10).as_secs_f64(), "rtt drifted to {rtt}");
+    assert!(
+        (measured_packet_loss - packet_loss).abs() < 0.1,
+        "measured packet loss {measured_packet_loss} drifted from the simulated {packet_loss}"
+    );
+}
@@ -1,9 +1,14 @@
+pub(crate) mod redundant;
 pub(crate) mod reliable;
 pub(crate) mod slice_constructor;
 pub(crate) mod unreliable;
 
 use std::time::Duration;
 
+use bytes::Bytes;
+
+use crate::packet::SLICE_SIZE;
+
 pub(crate) use slice_constructor::SliceConstructor;
 
 /// Delivery guarantee of a channel
@@ -13,20 +18,71 @@ pub enum SendType {
     Unreliable,
     /// Messages are guaranteed to be received and in the same order they were sent.
     ReliableOrdered {
-        resend_time: Duration,
+        resend_time: ResendStrategy,
     },
     /// Messages are guaranteed to be received but may be in an different order that they were sent.
     ReliableUnordered {
-        resend_time: Duration,
+        resend_time: ResendStrategy,
+    },
+    /// Messages are guaranteed to be received, but the order is only guaranteed between messages of the same stream.
+    /// A lost message only delays the next messages of its own stream, instead of all messages in the channel.
+    /// Useful for sending updates of many independent entities, using the entity as the stream.
+    /// Messages are sent with `send_message_on_stream`, `send_message` uses the stream 0.
+    ReliableOrderedStreams {
+        resend_time: ResendStrategy,
+    },
+    /// Messages can be lost, but each packet also repeats the last `redundancy` messages sent,
+    /// so a message is only lost if all the packets containing it are lost.
+    /// Older messages than the last one received are discarded, so messages are never received out of order.
+    /// Useful for sending player inputs every tick. Messages must not be bigger than the channel slice size.
+    UnreliableRedundant {
+        redundancy: usize,
     },
 }
 
+/// How long a reliable channel waits for a message to be acked before resending it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResendStrategy {
+    /// Always wait the same duration before resending.
+    Fixed(Duration),
+    /// Wait based on the measured round-trip time of the connection: `rtt * 1.25 + 4 * rtt variation`,
+    /// clamped between `min` and `max`. Uses `max` until the round-trip time is measured.
+    Adaptive { min: Duration, max: Duration },
+    /// Resend as soon as the receiver reports the packet as lost, instead of waiting for a timer.
+    /// This reduces the recovery time to about one round-trip time.
+    /// If the report is also lost, the message is resent after `resend_time`.
+    Nack { resend_time: Duration },
+}
+
+impl ResendStrategy {
+    pub(crate) fn resend_time(&self, rtt: f64, rtt_variation: f64) -> Duration {
+        match *self {
+            ResendStrategy::Fixed(resend_time) | ResendStrategy::Nack { resend_time } => resend_time,
+            ResendStrategy::Adaptive { min, max } => {
+                if rtt < f64::EPSILON {
+                    return max;
+                }
+
+                let resend_time = Duration::from_secs_f64(rtt * 1.25 + 4. * rtt_variation);
+                resend_time.clamp(min, max)
+            }
+        }
+    }
+}
+
+impl From<Duration> for ResendStrategy {
+    fn from(resend_time: Duration) -> Self {
+        ResendStrategy::Fixed(resend_time)
+    }
+}
+
 /// Configuration of a channel for a server or client
 /// Channels are unilateral and message based.
 #[derive(Debug, Clone)]
 pub struct ChannelConfig {
     /// Channel identifier, must be unique within its own list,
     /// but it can be repeated between the server and client lists.
+    /// The channel [`CONTROL_CHANNEL_ID`](crate::CONTROL_CHANNEL_ID) is reserved.
     pub channel_id: u8,
     /// Maximum number of bytes that the channel may hold without acknowledgement of messages before becoming full.
     /// Unreliable channels will drop new messages when this value is reached.
@@ -34,6 +90,87 @@ pub struct ChannelConfig {
     pub max_memory_usage_bytes: usize,
     /// Delivery guarantee of the channel.
     pub send_type: SendType,
+    /// Maximum size in bytes of the message slices and of the packets generated by the channel.
+    /// Can be reduced for networks with a smaller MTU, like some VPNs, to avoid IP fragmentation.
+    /// Must be the same for the sender and receiver, and can't be bigger than 1200 bytes.
+    /// Default: None, uses 1200 bytes.
+    pub slice_size: Option<usize>,
+    /// Limits how the channel waits for a missing message before delivering the next ones.
+    /// Only used by channels with [`SendType::ReliableOrdered`].
+    /// Default: None, waits indefinitely for the missing message.
+    pub ordering: Option<OrderingConfig>,
+}
+
+/// Configuration of the buffer of messages waiting for a missing message in an ordered channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderingConfig {
+    /// Maximum number of received messages that can wait for a missing message.
+    /// When reached, the channel errors with [`ChannelError::OrderingBufferFull`](crate::ChannelError::OrderingBufferFull),
+    /// unless `deliver_after` is set, in this case the missing message is skipped.
+    pub max_buffered_messages: usize,
+    /// If set, messages waiting longer than this for a missing message are delivered, skipping the missing message.
+    /// Skipped messages are discarded if they arrive later, so messages are still never delivered out of order.
+    /// Useful for semi-ordered data where old messages are no longer relevant.
+    pub deliver_after: Option<Duration>,
+}
+
+impl ChannelConfig {
+    pub(crate) fn slice_size(&self) -> usize {
+        let slice_size = self.slice_size.unwrap_or(SLICE_SIZE);
+        assert!(
+            slice_size > 0 && slice_size <= SLICE_SIZE,
+            "invalid slice size {slice_size} for channel {}, it must be between 1 and {SLICE_SIZE} bytes",
+            self.channel_id
+        );
+
+        slice_size
+    }
+}
+
+/// Statistics of the messages that were split into slices because they were bigger than the slice size.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SliceStats {
+    /// Number of messages sent that were split into slices.
+    pub sliced_messages_sent: u64,
+    /// Number of slices of the sliced messages sent, resent slices are not counted.
+    pub slices_sent: u64,
+    /// Number of sliced messages received with all its slices.
+    pub sliced_messages_received: u64,
+    /// Number of sliced messages discarded before all its slices were received.
+    /// Only happens in unreliable channels, when the missing slices are lost.
+    pub incomplete_messages_discarded: u64,
+}
+
+impl SliceStats {
+    /// Returns the average number of slices of the sliced messages sent.
+    pub fn average_slices_per_message(&self) -> f64 {
+        if self.sliced_messages_sent == 0 {
+            return 0.0;
+        }
+
+        self.slices_sent as f64 / self.sliced_messages_sent as f64
+    }
+}
+
+impl std::ops::AddAssign for SliceStats {
+    fn add_assign(&mut self, other: Self) {
+        self.sliced_messages_sent += other.sliced_messages_sent;
+        self.slices_sent += other.slices_sent;
+        self.sliced_messages_received += other.sliced_messages_received;
+        self.incomplete_messages_discarded += other.incomplete_messages_discarded;
+    }
+}
+
+/// A message received from a channel, with information about when and how it arrived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceivedMessage {
+    /// The content of the message.
+    pub payload: Bytes,
+    /// The connection time when the message was received.
+    pub receive_time: Duration,
+    /// The sequence of the packet that carried the message.
+    /// For sliced messages, this is the sequence of the packet that completed the message.
+    pub packet_sequence: u64,
 }
 
 /// Utility enumerator when using the default channels configuration.
@@ -61,20 +198,26 @@ impl DefaultChannel {
                 channel_id: 0,
                 max_memory_usage_bytes: 5 * 1024 * 1024,
                 send_type: SendType::Unreliable,
+                slice_size: None,
+                ordering: None,
             },
             ChannelConfig {
                 channel_id: 1,
                 max_memory_usage_bytes: 5 * 1024 * 1024,
                 send_type: SendType::ReliableUnordered {
-                    resend_time: Duration::from_millis(300),
+                    resend_time: ResendStrategy::Fixed(Duration::from_millis(300)),
                 },
+                slice_size: None,
+                ordering: None,
             },
             ChannelConfig {
                 channel_id: 2,
                 max_memory_usage_bytes: 5 * 1024 * 1024,
                 send_type: SendType::ReliableOrdered {
-                    resend_time: Duration::from_millis(300),
+                    resend_time: ResendStrategy::Fixed(Duration::from_millis(300)),
                 },
+                slice_size: None,
+                ordering: None,
             },
         ]
     }
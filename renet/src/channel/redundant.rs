@@ -0,0 +1,314 @@
+use std::{collections::VecDeque, time::Duration};
+
+use bytes::Bytes;
+
+use crate::{channel::ReceivedMessage, packet::Packet};
+
+#[derive(Debug)]
+pub struct SendChannelRedundant {
+    channel_id: u8,
+    redundancy: usize,
+    // Last messages sent followed by the messages not sent yet
+    messages: VecDeque<(u64, Bytes)>,
+    num_unsent_messages: usize,
+    next_message_id: u64,
+    slice_size: usize,
+    // Small messages are aggregated in packets up to this size
+    max_batch_size: usize,
+    max_memory_usage_bytes: usize,
+    memory_usage_bytes: usize,
+    // Time of the last update, messages sent are queued at this time
+    current_time: Duration,
+    // All messages not sent yet are sent together, so only the queue time of the first one is needed
+    first_unsent_queued_at: Option<Duration>,
+}
+
+#[derive(Debug)]
+pub struct ReceiveChannelRedundant {
+    channel_id: u8,
+    messages: VecDeque<ReceivedMessage>,
+    next_message_id: u64,
+    max_memory_usage_bytes: usize,
+    memory_usage_bytes: usize,
+}
+
+impl SendChannelRedundant {
+    pub fn new(channel_id: u8, redundancy: usize, max_memory_usage_bytes: usize, slice_size: usize) -> Self {
+        Self {
+            channel_id,
+            redundancy,
+            messages: VecDeque::new(),
+            num_unsent_messages: 0,
+            next_message_id: 0,
+            slice_size,
+            max_batch_size: slice_size,
+            max_memory_usage_bytes,
+            memory_usage_bytes: 0,
+            current_time: Duration::ZERO,
+            first_unsent_queued_at: None,
+        }
+    }
+
+    pub fn slice_size(&self) -> usize {
+        self.slice_size
+    }
+
+    /// Limits the bytes of small messages aggregated in a packet, it's never above the slice size.
+    pub fn set_max_batch_size(&mut self, max_batch_size: usize) {
+        self.max_batch_size = max_batch_size.min(self.slice_size);
+    }
+
+    /// Returns the size of the biggest message the channel can hold, messages are never sliced.
+    pub fn max_message_size(&self) -> usize {
+        self.slice_size.min(self.max_memory_usage_bytes)
+    }
+
+    pub fn can_send_message(&self, size_bytes: usize) -> bool {
+        size_bytes <= self.slice_size && size_bytes + self.memory_usage_bytes <= self.max_memory_usage_bytes
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage_bytes
+    }
+
+    pub fn available_memory(&self) -> usize {
+        self.max_memory_usage_bytes - self.memory_usage_bytes
+    }
+
+    /// Returns whether there are messages not sent, the last messages kept for redundancy are not counted.
+    pub fn has_pending_messages(&self) -> bool {
+        self.num_unsent_messages > 0
+    }
+
+    pub fn update(&mut self, current_time: Duration) {
+        self.current_time = current_time;
+    }
+
+    /// Returns the number of messages that were not sent yet.
+    pub fn queue_len(&self) -> usize {
+        self.num_unsent_messages
+    }
+
+    /// Returns the time since the oldest message not sent yet was queued.
+    pub fn oldest_unsent_age(&self) -> Option<Duration> {
+        Some(self.current_time - self.first_unsent_queued_at?)
+    }
+
+    pub fn get_packets_to_send(&mut self, packet_sequence: &mut u64, available_bytes: &mut u64) -> Vec<Packet> {
+        if self.num_unsent_messages == 0 {
+            return vec![];
+        }
+
+        // Select the new messages and the last messages already sent, newest first.
+        // Old messages are only repeated if there are bytes available.
+        let first_unsent_index = self.messages.len() - self.num_unsent_messages;
+        let first_index = first_unsent_index.saturating_sub(self.redundancy);
+        let mut selected_messages: Vec<(u64, Bytes)> = vec![];
+        for index in (first_index..self.messages.len()).rev() {
+            let (message_id, message) = &self.messages[index];
+            if *available_bytes < message.len() as u64 {
+                if index >= first_unsent_index {
+                    // Drop message, no available bytes to send
+                    continue;
+                }
+                break;
+            }
+
+            *available_bytes -= message.len() as u64;
+            selected_messages.push((*message_id, message.clone()));
+        }
+        selected_messages.reverse();
+
+        let mut packets: Vec<Packet> = vec![];
+        let mut small_messages: Vec<(u64, Bytes)> = vec![];
+        let mut small_messages_bytes = 0;
+        for (message_id, message) in selected_messages {
+            let serialized_size = message.len() + octets::varint_len(message.len() as u64) + octets::varint_len(message_id);
+            if !small_messages.is_empty() && small_messages_bytes + serialized_size > self.max_batch_size {
+                packets.push(Packet::SmallRedundant {
+                    sequence: *packet_sequence,
+                    channel_id: self.channel_id,
+                    messages: std::mem::take(&mut small_messages),
+                });
+                *packet_sequence += 1;
+                small_messages_bytes = 0;
+            }
+
+            small_messages_bytes += serialized_size;
+            small_messages.push((message_id, message));
+        }
+
+        if !small_messages.is_empty() {
+            packets.push(Packet::SmallRedundant {
+                sequence: *packet_sequence,
+                channel_id: self.channel_id,
+                messages: small_messages,
+            });
+            *packet_sequence += 1;
+        }
+
+        // Keep only the messages that can be repeated in the next packets
+        self.num_unsent_messages = 0;
+        self.first_unsent_queued_at = None;
+        while self.messages.len() > self.redundancy {
+            let (_, message) = self.messages.pop_front().unwrap();
+            self.memory_usage_bytes -= message.len();
+        }
+
+        packets
+    }
+
+    pub fn send_message(&mut self, message: Bytes) {
+        if message.len() > self.slice_size {
+            log::warn!(
+                "dropped redundant message sent in channel {} because it is bigger than {} bytes",
+                self.channel_id,
+                self.slice_size
+            );
+            return;
+        }
+
+        if self.memory_usage_bytes + message.len() > self.max_memory_usage_bytes {
+            log::warn!(
+                "dropped redundant message sent because channel {} is memory limited",
+                self.channel_id
+            );
+            return;
+        }
+
+        self.memory_usage_bytes += message.len();
+        self.messages.push_back((self.next_message_id, message));
+        self.next_message_id += 1;
+        self.num_unsent_messages += 1;
+        self.first_unsent_queued_at.get_or_insert(self.current_time);
+    }
+}
+
+impl ReceiveChannelRedundant {
+    pub fn new(channel_id: u8, max_memory_usage_bytes: usize) -> Self {
+        Self {
+            channel_id,
+            messages: VecDeque::new(),
+            next_message_id: 0,
+            max_memory_usage_bytes,
+            memory_usage_bytes: 0,
+        }
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage_bytes
+    }
+
+    pub fn process_message(&mut self, message: Bytes, message_id: u64, packet_sequence: u64, current_time: Duration) {
+        if message_id < self.next_message_id {
+            // Discard repeated or older message
+            return;
+        }
+
+        if self.memory_usage_bytes + message.len() > self.max_memory_usage_bytes {
+            log::warn!(
+                "dropped redundant message received because channel {} is memory limited",
+                self.channel_id
+            );
+            return;
+        }
+
+        self.next_message_id = message_id + 1;
+        self.memory_usage_bytes += message.len();
+        self.messages.push_back(ReceivedMessage {
+            payload: message,
+            receive_time: current_time,
+            packet_sequence,
+        });
+    }
+
+    pub fn receive_message(&mut self) -> Option<ReceivedMessage> {
+        let message = self.messages.pop_front()?;
+        self.memory_usage_bytes -= message.payload.len();
+        Some(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::SLICE_SIZE;
+
+    fn receive_packets(recv: &mut ReceiveChannelRedundant, packets: Vec<Packet>) {
+        for packet in packets {
+            let Packet::SmallRedundant { sequence, messages, .. } = packet else {
+                unreachable!();
+            };
+            for (message_id, message) in messages {
+                recv.process_message(message, message_id, sequence, Duration::ZERO);
+            }
+        }
+    }
+
+    #[test]
+    fn repeat_last_messages() {
+        let max_memory: usize = 10000;
+        let mut available_bytes = u64::MAX;
+        let mut sequence: u64 = 0;
+        let mut recv = ReceiveChannelRedundant::new(0, max_memory);
+        let mut send = SendChannelRedundant::new(0, 2, max_memory, SLICE_SIZE);
+
+        for i in 0..4u8 {
+            send.send_message(vec![i].into());
+            let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+            let Packet::SmallRedundant { messages, .. } = &packets[0] else {
+                unreachable!();
+            };
+            let expected_ids: Vec<u64> = (i.saturating_sub(2) as u64..=i as u64).collect();
+            assert_eq!(messages.iter().map(|(id, _)| *id).collect::<Vec<_>>(), expected_ids);
+
+            // Lose the packets with odd inputs
+            if i % 2 == 0 {
+                receive_packets(&mut recv, packets);
+            }
+        }
+
+        // Nothing new to send
+        assert!(send.get_packets_to_send(&mut sequence, &mut available_bytes).is_empty());
+
+        for i in 0..3u8 {
+            assert_eq!(recv.receive_message().unwrap().payload, vec![i]);
+        }
+        assert!(recv.receive_message().is_none());
+        assert_eq!(send.memory_usage_bytes, 2);
+    }
+
+    #[test]
+    fn discard_old_messages() {
+        let max_memory: usize = 10000;
+        let mut recv = ReceiveChannelRedundant::new(0, max_memory);
+
+        recv.process_message(vec![1].into(), 1, 0, Duration::ZERO);
+        recv.process_message(vec![0].into(), 0, 1, Duration::ZERO);
+        recv.process_message(vec![1].into(), 1, 1, Duration::ZERO);
+
+        assert_eq!(recv.receive_message().unwrap().payload, vec![1]);
+        assert!(recv.receive_message().is_none());
+    }
+
+    #[test]
+    fn repeat_only_with_available_bytes() {
+        let max_memory: usize = 10000;
+        let mut sequence: u64 = 0;
+        let mut send = SendChannelRedundant::new(0, 2, max_memory, SLICE_SIZE);
+
+        let mut available_bytes = u64::MAX;
+        send.send_message(vec![0; 10].into());
+        send.get_packets_to_send(&mut sequence, &mut available_bytes);
+
+        send.send_message(vec![1; 10].into());
+        available_bytes = 15;
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let Packet::SmallRedundant { messages, .. } = &packets[0] else {
+            unreachable!();
+        };
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0, 1);
+        assert_eq!(available_bytes, 5);
+    }
+}
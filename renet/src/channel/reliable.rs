@@ -1,29 +1,33 @@
 use std::{
-    collections::{btree_map, BTreeMap, BTreeSet, HashMap},
+    collections::{btree_map, BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     time::Duration,
 };
 
 use bytes::Bytes;
 
-use super::SliceConstructor;
+use super::{OrderingConfig, ReceivedMessage, ResendStrategy, SliceConstructor, SliceStats};
 use crate::{
     error::ChannelError,
-    packet::{Packet, Slice, SLICE_SIZE},
+    packet::{Packet, Slice},
 };
 
 #[derive(Debug)]
 enum UnackedMessage {
     Small {
         message: Bytes,
+        queued_at: Duration,
         last_sent: Option<Duration>,
+        nacked: bool,
     },
     Sliced {
         message: Bytes,
+        queued_at: Duration,
         num_slices: usize,
         num_acked_slices: usize,
         next_slice_to_send: usize,
         acked: Vec<bool>,
         last_sent: Vec<Option<Duration>>,
+        nacked: Vec<bool>,
     },
 }
 
@@ -32,9 +36,23 @@ pub struct SendChannelReliable {
     channel_id: u8,
     unacked_messages: BTreeMap<u64, UnackedMessage>,
     next_reliable_message_id: u64,
+    resend_strategy: ResendStrategy,
     resend_time: Duration,
+    slice_size: usize,
+    // Small messages are aggregated in packets up to this size
+    max_batch_size: usize,
     max_memory_usage_bytes: usize,
     memory_usage_bytes: usize,
+    // Total of messages and slices sent, including resends
+    num_sent: u64,
+    num_resent: u64,
+    slice_stats: SliceStats,
+    // Next message index of each stream, only for channels with streams
+    stream_indices: Option<HashMap<u64, u64>>,
+    // Priority of the messages sent with a priority above zero
+    priorities: HashMap<u64, u8>,
+    // Time of the last update, messages sent are queued at this time
+    current_time: Duration,
 }
 
 #[derive(Debug)]
@@ -44,49 +62,190 @@ enum ReliableOrder {
         most_recent_message_id: u64,
         received_messages: BTreeSet<u64>,
     },
+    Streams {
+        received_messages: BTreeSet<u64>,
+        next_stream_indices: HashMap<u64, u64>,
+        // Messages waiting for the previous messages of the same stream, keyed by (stream_id, stream_index)
+        pending_messages: BTreeMap<(u64, u64), (u64, ReceivedMessage)>,
+    },
 }
 
 #[derive(Debug)]
 pub struct ReceiveChannelReliable {
     slices: HashMap<u64, SliceConstructor>,
-    messages: BTreeMap<u64, Bytes>,
+    messages: BTreeMap<u64, ReceivedMessage>,
     oldest_pending_message_id: u64,
     reliable_order: ReliableOrder,
+    slice_size: usize,
     memory_usage_bytes: usize,
     max_memory_usage_bytes: usize,
+    slice_stats: SliceStats,
+    ordering: Option<OrderingConfig>,
+    dedup_window: Option<DedupWindow>,
+    // Messages received without their payload, they are skipped when received
+    discarded_messages: HashSet<u64>,
+}
+
+/// Ids of the last messages delivered by an unordered channel, messages with these ids are discarded.
+/// The ids are only valid for the session with the same peer, another peer starts again from the first message id.
+#[derive(Debug, Clone)]
+pub struct DedupWindow {
+    capacity: usize,
+    message_ids: VecDeque<u64>,
+    delivered: HashSet<u64>,
+}
+
+impl DedupWindow {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            message_ids: VecDeque::with_capacity(capacity),
+            delivered: HashSet::with_capacity(capacity),
+        }
+    }
+
+    /// Records the message as delivered, returns false if it was already delivered.
+    fn insert(&mut self, message_id: u64) -> bool {
+        if self.capacity == 0 {
+            return true;
+        }
+        if !self.delivered.insert(message_id) {
+            return false;
+        }
+
+        if self.message_ids.len() == self.capacity {
+            if let Some(oldest) = self.message_ids.pop_front() {
+                self.delivered.remove(&oldest);
+            }
+        }
+        self.message_ids.push_back(message_id);
+        true
+    }
 }
 
 impl UnackedMessage {
-    fn new_sliced(payload: Bytes) -> Self {
-        let num_slices = payload.len().div_ceil(SLICE_SIZE);
+    fn is_unsent(&self) -> bool {
+        match self {
+            UnackedMessage::Small { last_sent, .. } => last_sent.is_none(),
+            UnackedMessage::Sliced { last_sent, .. } => last_sent.iter().all(Option::is_none),
+        }
+    }
+
+    fn queued_at(&self) -> Duration {
+        match self {
+            UnackedMessage::Small { queued_at, .. } | UnackedMessage::Sliced { queued_at, .. } => *queued_at,
+        }
+    }
+
+    fn new_sliced(payload: Bytes, slice_size: usize, queued_at: Duration) -> Self {
+        let num_slices = payload.len().div_ceil(slice_size);
 
         Self::Sliced {
             message: payload,
+            queued_at,
             num_slices,
             num_acked_slices: 0,
             next_slice_to_send: 0,
             acked: vec![false; num_slices],
             last_sent: vec![None; num_slices],
+            nacked: vec![false; num_slices],
         }
     }
 }
 
 impl SendChannelReliable {
-    pub fn new(channel_id: u8, resend_time: Duration, max_memory_usage_bytes: usize) -> Self {
+    pub fn new(channel_id: u8, resend_strategy: ResendStrategy, max_memory_usage_bytes: usize, slice_size: usize) -> Self {
         Self {
             channel_id,
             unacked_messages: BTreeMap::new(),
             next_reliable_message_id: 0,
-            resend_time,
+            resend_strategy,
+            resend_time: resend_strategy.resend_time(0., 0.),
+            slice_size,
+            max_batch_size: slice_size,
             max_memory_usage_bytes,
             memory_usage_bytes: 0,
+            num_sent: 0,
+            num_resent: 0,
+            slice_stats: SliceStats::default(),
+            stream_indices: None,
+            priorities: HashMap::new(),
+            current_time: Duration::ZERO,
         }
     }
 
+    pub fn slice_size(&self) -> usize {
+        self.slice_size
+    }
+
+    /// Limits the bytes of small messages aggregated in a packet, it's never above the slice size.
+    pub fn set_max_batch_size(&mut self, max_batch_size: usize) {
+        self.max_batch_size = max_batch_size.min(self.slice_size);
+    }
+
+    /// Enables streams in the channel, messages are sent with a stream header.
+    pub fn with_streams(mut self) -> Self {
+        self.stream_indices = Some(HashMap::new());
+        self
+    }
+
+    pub fn has_streams(&self) -> bool {
+        self.stream_indices.is_some()
+    }
+
+    /// Updates the resend time with the connection round-trip time, when using an adaptive strategy.
+    pub fn update_resend_time(&mut self, rtt: f64, rtt_variation: f64) {
+        self.resend_time = self.resend_strategy.resend_time(rtt, rtt_variation);
+    }
+
+    pub fn slice_stats(&self) -> SliceStats {
+        self.slice_stats
+    }
+
+    /// Returns the total of messages and slices sent, and how many of those were resends.
+    pub fn send_counts(&self) -> (u64, u64) {
+        (self.num_sent, self.num_resent)
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage_bytes
+    }
+
+    /// Returns whether there are messages not sent or waiting for an ack.
+    pub fn has_pending_messages(&self) -> bool {
+        !self.unacked_messages.is_empty()
+    }
+
     pub fn available_memory(&self) -> usize {
         self.max_memory_usage_bytes - self.memory_usage_bytes
     }
 
+    pub fn update(&mut self, current_time: Duration) {
+        self.current_time = current_time;
+    }
+
+    /// Returns the number of messages that were not sent yet, messages waiting for an ack are not counted.
+    pub fn queue_len(&self) -> usize {
+        self.unacked_messages.values().filter(|message| message.is_unsent()).count()
+    }
+
+    /// Returns the time since the oldest message not sent yet was queued.
+    pub fn oldest_unsent_age(&self) -> Option<Duration> {
+        let queued_at = self
+            .unacked_messages
+            .values()
+            .filter(|message| message.is_unsent())
+            .map(UnackedMessage::queued_at)
+            .min()?;
+
+        Some(self.current_time - queued_at)
+    }
+
+    /// Returns the size of the biggest message the channel can hold.
+    pub fn max_message_size(&self) -> usize {
+        self.max_memory_usage_bytes
+    }
+
     pub fn can_send_message(&self, size_bytes: usize) -> bool {
         size_bytes + self.memory_usage_bytes <= self.max_memory_usage_bytes
     }
@@ -103,14 +262,19 @@ impl SendChannelReliable {
 
         'messages: for (&message_id, unacked_message) in self.unacked_messages.iter_mut() {
             match unacked_message {
-                UnackedMessage::Small { message, last_sent } => {
+                UnackedMessage::Small {
+                    message,
+                    last_sent,
+                    nacked,
+                    ..
+                } => {
                     if *available_bytes < message.len() as u64 {
                         // Skip message, no bytes available to send this message
                         continue;
                     }
 
                     if let Some(last_sent) = last_sent {
-                        if current_time - *last_sent < self.resend_time {
+                        if !*nacked && current_time - *last_sent < self.resend_time {
                             continue;
                         }
                     }
@@ -119,7 +283,7 @@ impl SendChannelReliable {
 
                     // Generate packet with small messages if you cannot fit
                     let serialized_size = message.len() + octets::varint_len(message.len() as u64) + octets::varint_len(message_id);
-                    if small_messages_bytes + serialized_size > SLICE_SIZE {
+                    if !small_messages.is_empty() && small_messages_bytes + serialized_size > self.max_batch_size {
                         packets.push(Packet::SmallReliable {
                             sequence: *packet_sequence,
                             channel_id: self.channel_id,
@@ -131,7 +295,12 @@ impl SendChannelReliable {
 
                     small_messages_bytes += serialized_size;
                     small_messages.push((message_id, message.clone()));
+                    self.num_sent += 1;
+                    if last_sent.is_some() {
+                        self.num_resent += 1;
+                    }
                     *last_sent = Some(current_time);
+                    *nacked = false;
 
                     continue;
                 }
@@ -140,12 +309,13 @@ impl SendChannelReliable {
                     num_slices,
                     acked,
                     last_sent,
+                    nacked,
                     next_slice_to_send,
                     ..
                 } => {
                     let start_index = *next_slice_to_send;
                     for i in 0..*num_slices {
-                        if *available_bytes < SLICE_SIZE as u64 {
+                        if *available_bytes < self.slice_size as u64 {
                             // Skip message, no bytes available to send a slice
                             continue 'messages;
                         }
@@ -156,13 +326,13 @@ impl SendChannelReliable {
                         }
 
                         if let Some(last_sent) = last_sent[i] {
-                            if current_time - last_sent < self.resend_time {
+                            if !nacked[i] && current_time - last_sent < self.resend_time {
                                 continue;
                             }
                         }
 
-                        let start = i * SLICE_SIZE;
-                        let end = if i == *num_slices - 1 { message.len() } else { (i + 1) * SLICE_SIZE };
+                        let start = i * self.slice_size;
+                        let end = if i == *num_slices - 1 { message.len() } else { (i + 1) * self.slice_size };
 
                         let payload = message.slice(start..end);
                         *available_bytes -= payload.len() as u64;
@@ -181,7 +351,12 @@ impl SendChannelReliable {
                         });
 
                         *packet_sequence += 1;
+                        self.num_sent += 1;
+                        if last_sent[i].is_some() {
+                            self.num_resent += 1;
+                        }
                         last_sent[i] = Some(current_time);
+                        nacked[i] = false;
                         *next_slice_to_send = i + 1 % *num_slices;
                     }
                 }
@@ -202,15 +377,89 @@ impl SendChannelReliable {
     }
 
     pub fn send_message(&mut self, message: Bytes) -> Result<(), ChannelError> {
+        if self.has_streams() {
+            return self.send_message_on_stream(0, message);
+        }
+
+        self.push_message(message)
+    }
+
+    /// Sends the message before the messages not sent yet with a lower priority.
+    /// Messages that were already sent keep their order, since their ids are known by the receiver.
+    pub fn send_message_with_priority(&mut self, message: Bytes, priority: u8) -> Result<(), ChannelError> {
+        self.send_message(message)?;
+        if priority == 0 {
+            return Ok(());
+        }
+
+        // Swap the message with the previous ones not sent yet that have a lower priority
+        let mut message_id = self.next_reliable_message_id - 1;
+        self.priorities.insert(message_id, priority);
+        while message_id > 0 {
+            let previous_id = message_id - 1;
+            let previous_priority = self.priorities.get(&previous_id).copied().unwrap_or(0);
+            let previous_unsent = self.unacked_messages.get(&previous_id).is_some_and(UnackedMessage::is_unsent);
+            if !previous_unsent || previous_priority >= priority {
+                break;
+            }
+
+            let previous = self.unacked_messages.remove(&previous_id).unwrap();
+            let current = self.unacked_messages.remove(&message_id).unwrap();
+            self.unacked_messages.insert(previous_id, current);
+            self.unacked_messages.insert(message_id, previous);
+
+            self.priorities.insert(previous_id, priority);
+            if previous_priority > 0 {
+                self.priorities.insert(message_id, previous_priority);
+            } else {
+                self.priorities.remove(&message_id);
+            }
+            message_id = previous_id;
+        }
+
+        Ok(())
+    }
+
+    pub fn send_message_on_stream(&mut self, stream_id: u64, message: Bytes) -> Result<(), ChannelError> {
+        let stream_indices = self.stream_indices.as_ref().expect("channel should have streams");
+        let stream_index = stream_indices.get(&stream_id).copied().unwrap_or(0);
+
+        let header_len = octets::varint_len(stream_id) + octets::varint_len(stream_index);
+        let mut buffer = vec![0; header_len + message.len()];
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        b.put_varint(stream_id).unwrap();
+        b.put_varint(stream_index).unwrap();
+        b.put_bytes(&message).unwrap();
+
+        self.push_message(buffer.into())?;
+        if let Some(stream_indices) = &mut self.stream_indices {
+            stream_indices.insert(stream_id, stream_index + 1);
+        }
+
+        Ok(())
+    }
+
+    fn push_message(&mut self, message: Bytes) -> Result<(), ChannelError> {
         if self.memory_usage_bytes + message.len() > self.max_memory_usage_bytes {
-            return Err(ChannelError::ReliableChannelMaxMemoryReached);
+            return Err(ChannelError::ReliableChannelMaxMemoryReached {
+                message_size: message.len(),
+                memory_usage_bytes: self.memory_usage_bytes,
+                max_memory_usage_bytes: self.max_memory_usage_bytes,
+            });
         }
 
         self.memory_usage_bytes += message.len();
-        let unacked_message = if message.len() > SLICE_SIZE {
-            UnackedMessage::new_sliced(message)
+        let unacked_message = if message.len() > self.slice_size {
+            self.slice_stats.sliced_messages_sent += 1;
+            self.slice_stats.slices_sent += message.len().div_ceil(self.slice_size) as u64;
+            UnackedMessage::new_sliced(message, self.slice_size, self.current_time)
         } else {
-            UnackedMessage::Small { message, last_sent: None }
+            UnackedMessage::Small {
+                message,
+                queued_at: self.current_time,
+                last_sent: None,
+                nacked: false,
+            }
         };
 
         self.unacked_messages.insert(self.next_reliable_message_id, unacked_message);
@@ -226,6 +475,31 @@ impl SendChannelReliable {
                 unreachable!("called ack on small message but found sliced");
             };
             self.memory_usage_bytes -= payload.len();
+            self.priorities.remove(&message_id);
+        }
+    }
+
+    /// Marks the message to be resent in the next packets, if the channel uses [`ResendStrategy::Nack`].
+    pub fn process_message_nack(&mut self, message_id: u64) {
+        if !matches!(self.resend_strategy, ResendStrategy::Nack { .. }) {
+            return;
+        }
+
+        if let Some(UnackedMessage::Small { nacked, .. }) = self.unacked_messages.get_mut(&message_id) {
+            *nacked = true;
+        }
+    }
+
+    /// Marks the slice to be resent in the next packets, if the channel uses [`ResendStrategy::Nack`].
+    pub fn process_slice_message_nack(&mut self, message_id: u64, slice_index: usize) {
+        if !matches!(self.resend_strategy, ResendStrategy::Nack { .. }) {
+            return;
+        }
+
+        if let Some(UnackedMessage::Sliced { acked, nacked, .. }) = self.unacked_messages.get_mut(&message_id) {
+            if !acked[slice_index] {
+                nacked[slice_index] = true;
+            }
         }
     }
 
@@ -255,12 +529,13 @@ impl SendChannelReliable {
         if *num_acked_slices == *num_slices {
             self.memory_usage_bytes -= message.len();
             self.unacked_messages.remove(&message_id);
+            self.priorities.remove(&message_id);
         }
     }
 }
 
 impl ReceiveChannelReliable {
-    pub fn new(max_memory_usage_bytes: usize, ordered: bool) -> Self {
+    pub fn new(max_memory_usage_bytes: usize, ordered: bool, slice_size: usize) -> Self {
         let reliable_order = match ordered {
             true => ReliableOrder::Ordered,
             false => ReliableOrder::Unordered {
@@ -273,26 +548,87 @@ impl ReceiveChannelReliable {
             messages: BTreeMap::new(),
             oldest_pending_message_id: 0,
             reliable_order,
+            slice_size,
             memory_usage_bytes: 0,
             max_memory_usage_bytes,
+            slice_stats: SliceStats::default(),
+            ordering: None,
+            dedup_window: None,
+            discarded_messages: HashSet::new(),
         }
     }
 
-    pub fn process_message(&mut self, message: Bytes, message_id: u64) -> Result<(), ChannelError> {
+    /// Limits the messages waiting for a missing message, only used in ordered channels.
+    pub fn with_ordering(mut self, ordering: OrderingConfig) -> Self {
+        self.ordering = Some(ordering);
+        self
+    }
+
+    /// Enables streams in the channel, messages are only ordered within the same stream.
+    pub fn with_streams(mut self) -> Self {
+        self.reliable_order = ReliableOrder::Streams {
+            received_messages: BTreeSet::new(),
+            next_stream_indices: HashMap::new(),
+            pending_messages: BTreeMap::new(),
+        };
+        self
+    }
+
+    /// Returns whether the channel is reliable unordered, the only kind of channel that uses a dedup window.
+    pub fn is_unordered(&self) -> bool {
+        matches!(self.reliable_order, ReliableOrder::Unordered { .. })
+    }
+
+    /// Sets the window of the delivered message ids, only used in unordered channels.
+    pub fn set_dedup_window(&mut self, dedup_window: Option<DedupWindow>) {
+        self.dedup_window = dedup_window;
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage_bytes
+    }
+
+    pub fn slice_stats(&self) -> SliceStats {
+        self.slice_stats
+    }
+
+    pub fn process_message(
+        &mut self,
+        message: Bytes,
+        message_id: u64,
+        packet_sequence: u64,
+        current_time: Duration,
+    ) -> Result<(), ChannelError> {
         if message_id < self.oldest_pending_message_id {
             // Discard old message already received
             return Ok(());
         }
 
+        if matches!(self.reliable_order, ReliableOrder::Ordered) && !self.messages.contains_key(&message_id) {
+            self.check_ordering_buffer(message_id)?;
+            if message_id < self.oldest_pending_message_id {
+                // Discard message skipped to make room in the ordering buffer
+                return Ok(());
+            }
+        }
+
         match &mut self.reliable_order {
             ReliableOrder::Ordered => {
                 if let btree_map::Entry::Vacant(entry) = self.messages.entry(message_id) {
                     if self.memory_usage_bytes + message.len() > self.max_memory_usage_bytes {
-                        return Err(ChannelError::ReliableChannelMaxMemoryReached);
+                        return Err(ChannelError::ReliableChannelMaxMemoryReached {
+                            message_size: message.len(),
+                            memory_usage_bytes: self.memory_usage_bytes,
+                            max_memory_usage_bytes: self.max_memory_usage_bytes,
+                        });
                     }
                     self.memory_usage_bytes += message.len();
 
-                    entry.insert(message);
+                    entry.insert(ReceivedMessage {
+                        payload: message,
+                        receive_time: current_time,
+                        packet_sequence,
+                    });
                 }
             }
             ReliableOrder::Unordered {
@@ -305,12 +641,71 @@ impl ReceiveChannelReliable {
 
                 if !received_messages.contains(&message_id) {
                     if self.memory_usage_bytes + message.len() > self.max_memory_usage_bytes {
-                        return Err(ChannelError::ReliableChannelMaxMemoryReached);
+                        return Err(ChannelError::ReliableChannelMaxMemoryReached {
+                            message_size: message.len(),
+                            memory_usage_bytes: self.memory_usage_bytes,
+                            max_memory_usage_bytes: self.max_memory_usage_bytes,
+                        });
                     }
                     self.memory_usage_bytes += message.len();
 
                     received_messages.insert(message_id);
+                    self.messages.insert(
+                        message_id,
+                        ReceivedMessage {
+                            payload: message,
+                            receive_time: current_time,
+                            packet_sequence,
+                        },
+                    );
+                }
+            }
+            ReliableOrder::Streams {
+                received_messages,
+                next_stream_indices,
+                pending_messages,
+            } => {
+                if received_messages.contains(&message_id) {
+                    return Ok(());
+                }
+
+                let Some((stream_id, stream_index, payload)) = decode_stream_message(&message) else {
+                    return Err(ChannelError::InvalidStreamMessage { message_id });
+                };
+
+                let next_stream_index = next_stream_indices.get(&stream_id).copied().unwrap_or(0);
+                if stream_index < next_stream_index || pending_messages.contains_key(&(stream_id, stream_index)) {
+                    // Each stream index can only be used by one message
+                    return Err(ChannelError::InvalidStreamMessage { message_id });
+                }
+
+                if self.memory_usage_bytes + payload.len() > self.max_memory_usage_bytes {
+                    return Err(ChannelError::ReliableChannelMaxMemoryReached {
+                        message_size: payload.len(),
+                        memory_usage_bytes: self.memory_usage_bytes,
+                        max_memory_usage_bytes: self.max_memory_usage_bytes,
+                    });
+                }
+                self.memory_usage_bytes += payload.len();
+
+                received_messages.insert(message_id);
+                while received_messages.remove(&self.oldest_pending_message_id) {
+                    self.oldest_pending_message_id += 1;
+                }
+
+                let received_message = ReceivedMessage {
+                    payload,
+                    receive_time: current_time,
+                    packet_sequence,
+                };
+                pending_messages.insert((stream_id, stream_index), (message_id, received_message));
+
+                // Release the messages of the stream that are no longer waiting for previous ones.
+                // Messages of the same stream have increasing ids, so they are received in order.
+                let next_stream_index = next_stream_indices.entry(stream_id).or_default();
+                while let Some((message_id, message)) = pending_messages.remove(&(stream_id, *next_stream_index)) {
                     self.messages.insert(message_id, message);
+                    *next_stream_index += 1;
                 }
             }
         }
@@ -318,16 +713,69 @@ impl ReceiveChannelReliable {
         Ok(())
     }
 
-    pub fn process_slice(&mut self, slice: Slice) -> Result<(), ChannelError> {
+    /// Processes the message id without keeping the payload, so the channel does not wait for it,
+    /// used for messages that were acked but must not be delivered.
+    pub fn discard_message(
+        &mut self,
+        message: Bytes,
+        message_id: u64,
+        packet_sequence: u64,
+        current_time: Duration,
+    ) -> Result<(), ChannelError> {
+        // Streams keep the message header, the stream index is still used
+        let message = match self.reliable_order {
+            ReliableOrder::Streams { .. } => {
+                let Some((_, _, payload)) = decode_stream_message(&message) else {
+                    return Err(ChannelError::InvalidStreamMessage { message_id });
+                };
+                message.slice(..message.len() - payload.len())
+            }
+            _ => Bytes::new(),
+        };
+
+        let was_received = self.is_message_received(message_id);
+        self.process_message(message, message_id, packet_sequence, current_time)?;
+        if !was_received && self.is_message_received(message_id) {
+            self.discarded_messages.insert(message_id);
+        }
+
+        Ok(())
+    }
+
+    pub fn process_slice(&mut self, slice: Slice, packet_sequence: u64, current_time: Duration) -> Result<(), ChannelError> {
+        let message_id = slice.message_id;
+        if let Some(message) = self.process_slice_payload(slice)? {
+            self.process_message(message, message_id, packet_sequence, current_time)?;
+        }
+
+        Ok(())
+    }
+
+    /// Like [`ReceiveChannelReliable::discard_message`], for sliced messages.
+    pub fn discard_slice(&mut self, slice: Slice, packet_sequence: u64, current_time: Duration) -> Result<(), ChannelError> {
+        let message_id = slice.message_id;
+        if let Some(message) = self.process_slice_payload(slice)? {
+            self.discard_message(message, message_id, packet_sequence, current_time)?;
+        }
+
+        Ok(())
+    }
+
+    // Returns the message when all its slices were received
+    fn process_slice_payload(&mut self, slice: Slice) -> Result<Option<Bytes>, ChannelError> {
         if self.messages.contains_key(&slice.message_id) || slice.message_id < self.oldest_pending_message_id {
             // Message already assembled
-            return Ok(());
+            return Ok(None);
         }
 
         if !self.slices.contains_key(&slice.message_id) {
-            let message_len = slice.num_slices * SLICE_SIZE;
+            let message_len = slice.num_slices * self.slice_size;
             if self.memory_usage_bytes + message_len > self.max_memory_usage_bytes {
-                return Err(ChannelError::ReliableChannelMaxMemoryReached);
+                return Err(ChannelError::ReliableChannelMaxMemoryReached {
+                    message_size: message_len,
+                    memory_usage_bytes: self.memory_usage_bytes,
+                    max_memory_usage_bytes: self.max_memory_usage_bytes,
+                });
             }
             self.memory_usage_bytes += message_len;
         }
@@ -335,28 +783,124 @@ impl ReceiveChannelReliable {
         let slice_constructor = self
             .slices
             .entry(slice.message_id)
-            .or_insert_with(|| SliceConstructor::new(slice.message_id, slice.num_slices));
+            .or_insert_with(|| SliceConstructor::new(slice.message_id, slice.num_slices, self.slice_size));
 
         if let Some(message) = slice_constructor.process_slice(slice.slice_index, &slice.payload)? {
             // Memory usage is re-added with the exactly message size
-            self.memory_usage_bytes -= slice.num_slices * SLICE_SIZE;
-            self.process_message(message, slice.message_id)?;
+            self.memory_usage_bytes -= slice.num_slices * self.slice_size;
+            self.slice_stats.sliced_messages_received += 1;
             self.slices.remove(&slice.message_id);
+            return Ok(Some(message));
+        }
+
+        Ok(None)
+    }
+
+    fn is_message_received(&self, message_id: u64) -> bool {
+        if message_id < self.oldest_pending_message_id {
+            return true;
         }
 
+        match &self.reliable_order {
+            ReliableOrder::Ordered => self.messages.contains_key(&message_id),
+            ReliableOrder::Unordered { received_messages, .. } | ReliableOrder::Streams { received_messages, .. } => {
+                received_messages.contains(&message_id)
+            }
+        }
+    }
+
+    /// Returns the id of the missing message that the received messages are waiting for.
+    /// Only ordered channels can be blocked.
+    pub fn blocked_on_message_id(&self) -> Option<u64> {
+        let is_blocked = matches!(self.reliable_order, ReliableOrder::Ordered)
+            && !self.messages.is_empty()
+            && !self.messages.contains_key(&self.oldest_pending_message_id);
+
+        is_blocked.then_some(self.oldest_pending_message_id)
+    }
+
+    /// Delivers the messages that waited longer than `deliver_after` for a missing message.
+    pub fn update(&mut self, current_time: Duration) {
+        let Some(deliver_after) = self.ordering.and_then(|ordering| ordering.deliver_after) else {
+            return;
+        };
+
+        if self.blocked_on_message_id().is_none() {
+            return;
+        }
+
+        if let Some((_, message)) = self.messages.first_key_value() {
+            if current_time - message.receive_time >= deliver_after {
+                self.skip_missing_messages();
+            }
+        }
+    }
+
+    fn check_ordering_buffer(&mut self, message_id: u64) -> Result<(), ChannelError> {
+        let Some(ordering) = self.ordering else {
+            return Ok(());
+        };
+
+        let Some(missing_message_id) = self.blocked_on_message_id() else {
+            return Ok(());
+        };
+
+        if message_id == missing_message_id || self.messages.len() < ordering.max_buffered_messages {
+            return Ok(());
+        }
+
+        if ordering.deliver_after.is_none() {
+            return Err(ChannelError::OrderingBufferFull {
+                missing_message_id,
+                max_buffered_messages: ordering.max_buffered_messages,
+            });
+        }
+
+        self.skip_missing_messages();
         Ok(())
     }
 
-    pub fn receive_message(&mut self) -> Option<Bytes> {
+    // Skips the missing messages until the first message received.
+    fn skip_missing_messages(&mut self) {
+        let Some(&message_id) = self.messages.keys().next() else {
+            return;
+        };
+
+        log::debug!(
+            "Skipped missing messages {}..{} in ordered channel",
+            self.oldest_pending_message_id,
+            message_id
+        );
+        self.oldest_pending_message_id = message_id;
+        self.discarded_messages.retain(|&discarded_id| discarded_id >= message_id);
+
+        // Discard the incomplete sliced messages that were skipped
+        let slice_size = self.slice_size;
+        let mut discarded_bytes = 0;
+        self.slices.retain(|&slice_message_id, slice| {
+            if slice_message_id < message_id {
+                discarded_bytes += slice.num_slices * slice_size;
+                return false;
+            }
+            true
+        });
+        self.memory_usage_bytes -= discarded_bytes;
+    }
+
+    pub fn receive_message(&mut self) -> Option<ReceivedMessage> {
         match &mut self.reliable_order {
-            ReliableOrder::Ordered => {
-                let message = self.messages.remove(&self.oldest_pending_message_id)?;
+            ReliableOrder::Ordered => loop {
+                let message_id = self.oldest_pending_message_id;
+                let message = self.messages.remove(&message_id)?;
 
                 self.oldest_pending_message_id += 1;
-                self.memory_usage_bytes -= message.len();
-                Some(message)
-            }
-            ReliableOrder::Unordered { received_messages, .. } => {
+                self.memory_usage_bytes -= message.payload.len();
+                if self.discarded_messages.remove(&message_id) {
+                    continue;
+                }
+                return Some(message);
+            },
+            ReliableOrder::Unordered { received_messages, .. } => loop {
                 let (message_id, message) = self.messages.pop_first()?;
 
                 if self.oldest_pending_message_id == message_id {
@@ -368,18 +912,45 @@ impl ReceiveChannelReliable {
                     }
                 }
 
-                self.memory_usage_bytes -= message.len();
-                Some(message)
-            }
+                self.memory_usage_bytes -= message.payload.len();
+                if self.discarded_messages.remove(&message_id) {
+                    continue;
+                }
+                if let Some(dedup_window) = self.dedup_window.as_mut() {
+                    if !dedup_window.insert(message_id) {
+                        log::debug!("Discarded duplicated message {message_id} in unordered channel");
+                        continue;
+                    }
+                }
+                return Some(message);
+            },
+            ReliableOrder::Streams { .. } => loop {
+                let (message_id, message) = self.messages.pop_first()?;
+
+                self.memory_usage_bytes -= message.payload.len();
+                if self.discarded_messages.remove(&message_id) {
+                    continue;
+                }
+                return Some(message);
+            },
         }
     }
 }
 
+fn decode_stream_message(message: &Bytes) -> Option<(u64, u64, Bytes)> {
+    let mut b = octets::Octets::with_slice(message);
+    let stream_id = b.get_varint().ok()?;
+    let stream_index = b.get_varint().ok()?;
+
+    Some((stream_id, stream_index, message.slice(b.off()..)))
+}
+
 #[cfg(test)]
 mod tests {
     use octets::OctetsMut;
 
     use super::*;
+    use crate::packet::SLICE_SIZE;
 
     #[test]
     fn small_packet() {
@@ -388,8 +959,8 @@ mod tests {
         let mut sequence: u64 = 0;
         let mut current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
-        let mut recv = ReceiveChannelReliable::new(max_memory, true);
-        let mut send = SendChannelReliable::new(0, resend_time, max_memory);
+        let mut recv = ReceiveChannelReliable::new(max_memory, true, SLICE_SIZE);
+        let mut send = SendChannelReliable::new(0, ResendStrategy::Fixed(resend_time), max_memory, SLICE_SIZE);
 
         let message1 = vec![1, 2, 3];
         let message2 = vec![3, 4, 5];
@@ -407,16 +978,16 @@ mod tests {
             else {
                 unreachable!();
             };
-            for (message, message_id) in messages {
-                recv.process_message(message_id, message).unwrap();
+            for (message_id, message) in messages {
+                recv.process_message(message, message_id, 0, current_time).unwrap();
             }
         }
 
         let new_message1 = recv.receive_message().unwrap();
         let new_message2 = recv.receive_message().unwrap();
 
-        assert_eq!(message1, new_message1);
-        assert_eq!(message2, new_message2);
+        assert_eq!(message1, new_message1.payload);
+        assert_eq!(message2, new_message2.payload);
 
         // Should not resend anything
         let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
@@ -426,6 +997,7 @@ mod tests {
         // Should resend now
         let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
         assert_eq!(packets.len(), 1);
+        assert_eq!(send.send_counts(), (4, 2));
 
         // Should not resend after ack
         current_time += resend_time;
@@ -436,6 +1008,43 @@ mod tests {
         assert!(packets.is_empty());
     }
 
+    #[test]
+    fn message_priority() {
+        let max_memory: usize = 10000;
+        let mut sequence: u64 = 0;
+        let current_time: Duration = Duration::ZERO;
+        let resend_time = Duration::from_millis(100);
+        let mut recv = ReceiveChannelReliable::new(max_memory, true, SLICE_SIZE);
+        let mut send = SendChannelReliable::new(0, ResendStrategy::Fixed(resend_time), max_memory, SLICE_SIZE);
+
+        // Messages already sent keep their order
+        let mut available_bytes = u64::MAX;
+        send.send_message(vec![0; 3].into()).unwrap();
+        let mut packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+
+        send.send_message(vec![1; 3].into()).unwrap();
+        send.send_message(vec![2; 3].into()).unwrap();
+        send.send_message_with_priority(vec![3; 3].into(), 2).unwrap();
+        send.send_message_with_priority(vec![4; 3].into(), 1).unwrap();
+
+        // Only bytes for two messages
+        available_bytes = 6;
+        packets.extend(send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time));
+        for packet in packets {
+            let Packet::SmallReliable { messages, .. } = packet else {
+                unreachable!();
+            };
+            for (message_id, message) in messages {
+                recv.process_message(message, message_id, 0, current_time).unwrap();
+            }
+        }
+
+        assert_eq!(recv.receive_message().unwrap().payload, vec![0; 3]);
+        assert_eq!(recv.receive_message().unwrap().payload, vec![3; 3]);
+        assert_eq!(recv.receive_message().unwrap().payload, vec![4; 3]);
+        assert!(recv.receive_message().is_none());
+    }
+
     #[test]
     fn small_packet_unordered() {
         let max_memory: usize = 10000;
@@ -443,8 +1052,8 @@ mod tests {
         let mut sequence: u64 = 0;
         let mut current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
-        let mut recv = ReceiveChannelReliable::new(max_memory, false);
-        let mut send = SendChannelReliable::new(0, resend_time, max_memory);
+        let mut recv = ReceiveChannelReliable::new(max_memory, false, SLICE_SIZE);
+        let mut send = SendChannelReliable::new(0, ResendStrategy::Fixed(resend_time), max_memory, SLICE_SIZE);
 
         let message1 = vec![1, 2, 3];
         let message2 = vec![3, 4, 5];
@@ -463,21 +1072,21 @@ mod tests {
         assert_eq!(messages.len(), 3);
 
         // Process and receive out of order
-        recv.process_message(messages[2].1.clone(), messages[2].0).unwrap();
+        recv.process_message(messages[2].1.clone(), messages[2].0, 0, current_time).unwrap();
         let new_message3 = recv.receive_message().unwrap();
 
-        recv.process_message(messages[1].1.clone(), messages[1].0).unwrap();
+        recv.process_message(messages[1].1.clone(), messages[1].0, 0, current_time).unwrap();
         let new_message2 = recv.receive_message().unwrap();
 
-        recv.process_message(messages[0].1.clone(), messages[0].0).unwrap();
+        recv.process_message(messages[0].1.clone(), messages[0].0, 0, current_time).unwrap();
         let new_message1 = recv.receive_message().unwrap();
 
-        assert_eq!(message1, new_message1);
-        assert_eq!(message2, new_message2);
-        assert_eq!(message3, new_message3);
+        assert_eq!(message1, new_message1.payload);
+        assert_eq!(message2, new_message2.payload);
+        assert_eq!(message3, new_message3.payload);
 
         match &recv.reliable_order {
-            ReliableOrder::Ordered => unreachable!(),
+            ReliableOrder::Ordered | ReliableOrder::Streams { .. } => unreachable!(),
             ReliableOrder::Unordered {
                 most_recent_message_id,
                 received_messages,
@@ -506,6 +1115,152 @@ mod tests {
         assert!(packets.is_empty());
     }
 
+    #[test]
+    fn streams() {
+        let max_memory: usize = 10000;
+        let mut available_bytes = u64::MAX;
+        let mut sequence: u64 = 0;
+        let current_time = Duration::ZERO;
+        let resend_time = Duration::from_millis(100);
+        let mut recv = ReceiveChannelReliable::new(max_memory, false, SLICE_SIZE).with_streams();
+        let mut send = SendChannelReliable::new(0, ResendStrategy::Fixed(resend_time), max_memory, SLICE_SIZE).with_streams();
+
+        send.send_message_on_stream(1, vec![1, 0].into()).unwrap();
+        send.send_message_on_stream(2, vec![2, 0].into()).unwrap();
+        send.send_message_on_stream(1, vec![1, 1].into()).unwrap();
+        send.send_message_on_stream(2, vec![2, 1].into()).unwrap();
+
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let Packet::SmallReliable { messages, .. } = &packets[0] else {
+            unreachable!();
+        };
+        assert_eq!(messages.len(), 4);
+
+        // The first message of stream 1 is lost, only stream 2 is delivered
+        for (message_id, message) in messages.iter().skip(1) {
+            recv.process_message(message.clone(), *message_id, 0, current_time).unwrap();
+        }
+        assert_eq!(recv.receive_message().unwrap().payload, vec![2, 0]);
+        assert_eq!(recv.receive_message().unwrap().payload, vec![2, 1]);
+        assert!(recv.receive_message().is_none());
+
+        // Repeated messages are discarded
+        recv.process_message(messages[3].1.clone(), messages[3].0, 0, current_time).unwrap();
+        assert!(recv.receive_message().is_none());
+
+        // The lost message releases the waiting messages of its stream, in order
+        recv.process_message(messages[0].1.clone(), messages[0].0, 0, current_time).unwrap();
+        assert_eq!(recv.receive_message().unwrap().payload, vec![1, 0]);
+        assert_eq!(recv.receive_message().unwrap().payload, vec![1, 1]);
+        assert!(recv.receive_message().is_none());
+
+        assert_eq!(recv.oldest_pending_message_id, 4);
+        assert_eq!(recv.memory_usage_bytes, 0);
+    }
+
+    #[test]
+    fn discard_message() {
+        let current_time = Duration::ZERO;
+        for ordered in [true, false] {
+            let mut recv = ReceiveChannelReliable::new(10000, ordered, SLICE_SIZE);
+            recv.discard_message(vec![0].into(), 0, 0, current_time).unwrap();
+            recv.process_message(vec![1].into(), 1, 0, current_time).unwrap();
+            // Repeated discarded messages are still discarded
+            recv.discard_message(vec![0].into(), 0, 0, current_time).unwrap();
+
+            assert_eq!(recv.receive_message().unwrap().payload, vec![1]);
+            assert!(recv.receive_message().is_none());
+            assert!(recv.discarded_messages.is_empty());
+            assert_eq!(recv.memory_usage_bytes, 0);
+        }
+
+        // Streams are not blocked by the discarded messages
+        let mut available_bytes = u64::MAX;
+        let mut sequence: u64 = 0;
+        let mut recv = ReceiveChannelReliable::new(10000, false, SLICE_SIZE).with_streams();
+        let mut send = SendChannelReliable::new(0, ResendStrategy::Fixed(Duration::from_millis(100)), 10000, SLICE_SIZE).with_streams();
+        send.send_message_on_stream(1, vec![1, 0].into()).unwrap();
+        send.send_message_on_stream(1, vec![1, 1].into()).unwrap();
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        let Packet::SmallReliable { messages, .. } = &packets[0] else {
+            unreachable!();
+        };
+
+        recv.discard_message(messages[0].1.clone(), messages[0].0, 0, current_time).unwrap();
+        recv.process_message(messages[1].1.clone(), messages[1].0, 0, current_time).unwrap();
+        assert_eq!(recv.receive_message().unwrap().payload, vec![1, 1]);
+        assert!(recv.receive_message().is_none());
+        assert!(recv.discarded_messages.is_empty());
+    }
+
+    #[test]
+    fn ordering_buffer_full() {
+        let ordering = OrderingConfig {
+            max_buffered_messages: 2,
+            deliver_after: None,
+        };
+        let mut recv = ReceiveChannelReliable::new(10000, true, SLICE_SIZE).with_ordering(ordering);
+
+        recv.process_message(Bytes::from(vec![1]), 1, 0, Duration::ZERO).unwrap();
+        recv.process_message(Bytes::from(vec![2]), 2, 0, Duration::ZERO).unwrap();
+        assert_eq!(recv.blocked_on_message_id(), Some(0));
+        assert!(recv.receive_message().is_none());
+
+        let error = recv.process_message(Bytes::from(vec![3]), 3, 0, Duration::ZERO);
+        assert_eq!(
+            error,
+            Err(ChannelError::OrderingBufferFull {
+                missing_message_id: 0,
+                max_buffered_messages: 2
+            })
+        );
+
+        // The missing message is always accepted
+        recv.process_message(Bytes::from(vec![0]), 0, 0, Duration::ZERO).unwrap();
+        assert_eq!(recv.blocked_on_message_id(), None);
+        for i in 0..3 {
+            assert_eq!(recv.receive_message().unwrap().payload, vec![i]);
+        }
+    }
+
+    #[test]
+    fn ordering_deliver_after() {
+        let ordering = OrderingConfig {
+            max_buffered_messages: 2,
+            deliver_after: Some(Duration::from_millis(200)),
+        };
+        let mut recv = ReceiveChannelReliable::new(10000, true, SLICE_SIZE).with_ordering(ordering);
+
+        recv.process_message(Bytes::from(vec![2]), 2, 0, Duration::ZERO).unwrap();
+        recv.update(Duration::from_millis(100));
+        assert_eq!(recv.blocked_on_message_id(), Some(0));
+
+        // Skip the missing messages after the timeout
+        recv.update(Duration::from_millis(200));
+        assert_eq!(recv.blocked_on_message_id(), None);
+        assert_eq!(recv.receive_message().unwrap().payload, vec![2]);
+
+        // Skipped messages are discarded
+        recv.process_message(Bytes::from(vec![1]), 1, 0, Duration::ZERO).unwrap();
+        assert!(recv.receive_message().is_none());
+
+        // With a full buffer the missing message is skipped
+        recv.process_message(Bytes::from(vec![4]), 4, 0, Duration::ZERO).unwrap();
+        recv.process_message(Bytes::from(vec![5]), 5, 0, Duration::ZERO).unwrap();
+        recv.process_message(Bytes::from(vec![6]), 6, 0, Duration::ZERO).unwrap();
+        assert_eq!(recv.receive_message().unwrap().payload, vec![4]);
+        assert_eq!(recv.receive_message().unwrap().payload, vec![5]);
+        assert_eq!(recv.receive_message().unwrap().payload, vec![6]);
+        assert_eq!(recv.memory_usage_bytes, 0);
+    }
+
+    #[test]
+    fn invalid_stream_message() {
+        let mut recv = ReceiveChannelReliable::new(10000, false, SLICE_SIZE).with_streams();
+        let error = recv.process_message(Bytes::from(vec![0xff]), 0, 0, Duration::ZERO);
+        assert_eq!(error, Err(ChannelError::InvalidStreamMessage { message_id: 0 }));
+    }
+
     #[test]
     fn slice_packet() {
         let max_memory: usize = 10000;
@@ -513,8 +1268,8 @@ mod tests {
         let mut sequence: u64 = 0;
         let mut current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
-        let mut recv = ReceiveChannelReliable::new(max_memory, true);
-        let mut send = SendChannelReliable::new(0, resend_time, max_memory);
+        let mut recv = ReceiveChannelReliable::new(max_memory, true, SLICE_SIZE);
+        let mut send = SendChannelReliable::new(0, ResendStrategy::Fixed(resend_time), max_memory, SLICE_SIZE);
 
         let message = vec![5; SLICE_SIZE * 3];
 
@@ -522,14 +1277,22 @@ mod tests {
 
         let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
         for packet in packets {
-            let Packet::ReliableSlice { channel_id: 0, slice, .. } = packet else {
+            let Packet::ReliableSlice {
+                sequence,
+                channel_id: 0,
+                slice,
+                ..
+            } = packet
+            else {
                 unreachable!();
             };
-            recv.process_slice(slice).unwrap();
+            recv.process_slice(slice, sequence, current_time).unwrap();
         }
 
         let new_message = recv.receive_message().unwrap();
-        assert_eq!(message, new_message);
+        assert_eq!(message, new_message.payload);
+        // The message is completed by the packet with the last slice
+        assert_eq!(new_message.packet_sequence, 2);
 
         // Should not resend anything
         let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
@@ -539,6 +1302,10 @@ mod tests {
         // Should resend now
         let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
         assert_eq!(packets.len(), 3);
+        assert_eq!(send.send_counts(), (6, 3));
+        // Resent slices are not counted in the slice stats
+        assert_eq!(send.slice_stats().slices_sent, 3);
+        assert_eq!(recv.slice_stats().sliced_messages_received, 1);
 
         // Should not resend after ack
         current_time += resend_time;
@@ -550,14 +1317,73 @@ mod tests {
         assert!(packets.is_empty());
     }
 
+    #[test]
+    fn adaptive_resend_time() {
+        let resend_strategy = ResendStrategy::Adaptive {
+            min: Duration::from_millis(50),
+            max: Duration::from_millis(1000),
+        };
+        let mut send = SendChannelReliable::new(0, resend_strategy, usize::MAX, SLICE_SIZE);
+        // Use max until rtt is measured
+        assert_eq!(send.resend_time, Duration::from_millis(1000));
+
+        send.update_resend_time(0.1, 0.01);
+        assert_eq!(send.resend_time, Duration::from_millis(165));
+
+        // Clamped to min and max
+        send.update_resend_time(0.001, 0.);
+        assert_eq!(send.resend_time, Duration::from_millis(50));
+        send.update_resend_time(2., 0.);
+        assert_eq!(send.resend_time, Duration::from_millis(1000));
+
+        let mut send = SendChannelReliable::new(0, ResendStrategy::Fixed(Duration::from_millis(300)), usize::MAX, SLICE_SIZE);
+        send.update_resend_time(0.1, 0.01);
+        assert_eq!(send.resend_time, Duration::from_millis(300));
+    }
+
+    #[test]
+    fn nack_resend() {
+        let current_time = Duration::ZERO;
+        let mut sequence: u64 = 0;
+        let mut available_bytes = u64::MAX;
+        let resend_time = Duration::from_millis(300);
+        let mut send = SendChannelReliable::new(0, ResendStrategy::Nack { resend_time }, usize::MAX, SLICE_SIZE);
+
+        send.send_message(vec![1, 2, 3].into()).unwrap();
+        send.send_message(vec![5; SLICE_SIZE * 2].into()).unwrap();
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        assert_eq!(packets.len(), 3);
+
+        // Resend before the resend time after receiving the nacks
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        assert!(packets.is_empty());
+        send.process_message_nack(0);
+        send.process_slice_message_nack(1, 1);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        assert_eq!(packets.len(), 2);
+        assert_eq!(send.send_counts(), (5, 2));
+
+        // Only resend once for each nack
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        assert!(packets.is_empty());
+
+        // Nacks are ignored by other strategies
+        let mut send = SendChannelReliable::new(0, ResendStrategy::Fixed(resend_time), usize::MAX, SLICE_SIZE);
+        send.send_message(vec![1, 2, 3].into()).unwrap();
+        send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        send.process_message_nack(0);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, current_time);
+        assert!(packets.is_empty());
+    }
+
     #[test]
     fn max_memory() {
         let mut available_bytes = u64::MAX;
         let mut sequence: u64 = 0;
         let current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
-        let mut recv = ReceiveChannelReliable::new(99, true);
-        let mut send = SendChannelReliable::new(0, resend_time, 101);
+        let mut recv = ReceiveChannelReliable::new(99, true, SLICE_SIZE);
+        let mut send = SendChannelReliable::new(0, ResendStrategy::Fixed(resend_time), 101, SLICE_SIZE);
 
         let message = vec![5; 100];
 
@@ -574,18 +1400,33 @@ mod tests {
             else {
                 unreachable!();
             };
-            for (message, message_id) in messages {
-                let Err(e) = recv.process_message(message_id, message) else {
+            for (message_id, message) in messages {
+                let Err(e) = recv.process_message(message, message_id, 0, current_time) else {
                     unreachable!();
                 };
-                assert_eq!(e, ChannelError::ReliableChannelMaxMemoryReached);
+                assert_eq!(
+                    e,
+                    ChannelError::ReliableChannelMaxMemoryReached {
+                        message_size: 100,
+                        memory_usage_bytes: 0,
+                        max_memory_usage_bytes: 99,
+                    }
+                );
             }
         }
 
         let Err(send_err) = send.send_message(message.into()) else {
             unreachable!()
         };
-        assert_eq!(send_err, ChannelError::ReliableChannelMaxMemoryReached);
+        assert_eq!(
+            send_err,
+            ChannelError::ReliableChannelMaxMemoryReached {
+                message_size: 100,
+                memory_usage_bytes: 100,
+                max_memory_usage_bytes: 101,
+            }
+        );
+        assert!(send_err.is_fatal());
     }
 
     #[test]
@@ -593,7 +1434,7 @@ mod tests {
         let mut sequence: u64 = 0;
         let current_time: Duration = Duration::ZERO;
         let resend_time = Duration::from_millis(100);
-        let mut send = SendChannelReliable::new(0, resend_time, usize::MAX);
+        let mut send = SendChannelReliable::new(0, ResendStrategy::Fixed(resend_time), usize::MAX, SLICE_SIZE);
 
         let message: Bytes = vec![0u8; 100].into();
         send.send_message(message.clone()).unwrap();
@@ -626,7 +1467,7 @@ mod tests {
         let current_time: Duration = Duration::ZERO;
         let mut available_bytes = u64::MAX;
         let resend_time = Duration::from_millis(100);
-        let mut send = SendChannelReliable::new(0, resend_time, usize::MAX);
+        let mut send = SendChannelReliable::new(0, ResendStrategy::Fixed(resend_time), usize::MAX, SLICE_SIZE);
 
         // 4 bytes
         let message: Bytes = vec![0, 1, 2, 3].into();
@@ -1,41 +1,55 @@
 use bytes::Bytes;
 
-use crate::{error::ChannelError, packet::SLICE_SIZE};
+use crate::error::ChannelError;
 
 #[derive(Debug, Clone)]
 pub struct SliceConstructor {
     message_id: u64,
     pub num_slices: usize,
+    slice_size: usize,
     num_received_slices: usize,
     received: Vec<bool>,
     sliced_data: Vec<u8>,
 }
 
 impl SliceConstructor {
-    pub fn new(message_id: u64, num_slices: usize) -> Self {
+    pub fn new(message_id: u64, num_slices: usize, slice_size: usize) -> Self {
         SliceConstructor {
             message_id,
             num_slices,
+            slice_size,
             num_received_slices: 0,
             received: vec![false; num_slices],
-            sliced_data: vec![0; num_slices * SLICE_SIZE],
+            sliced_data: vec![0; num_slices * slice_size],
         }
     }
 
     pub fn process_slice(&mut self, slice_index: usize, bytes: &[u8]) -> Result<Option<Bytes>, ChannelError> {
         let is_last_slice = slice_index == self.num_slices - 1;
         if is_last_slice {
-            if bytes.len() > SLICE_SIZE {
+            if bytes.len() > self.slice_size {
                 log::error!(
                     "Invalid last slice_size for SliceMessage, got {}, expected less than {}.",
                     bytes.len(),
-                    SLICE_SIZE,
+                    self.slice_size,
                 );
-                return Err(ChannelError::InvalidSliceMessage);
+                return Err(ChannelError::InvalidSliceMessage {
+                    message_id: self.message_id,
+                    slice_index,
+                    slice_size: bytes.len(),
+                });
             }
-        } else if bytes.len() != SLICE_SIZE {
-            log::error!("Invalid slice_size for SliceMessage, got {}, expected {}.", bytes.len(), SLICE_SIZE);
-            return Err(ChannelError::InvalidSliceMessage);
+        } else if bytes.len() != self.slice_size {
+            log::error!(
+                "Invalid slice_size for SliceMessage, got {}, expected {}.",
+                bytes.len(),
+                self.slice_size
+            );
+            return Err(ChannelError::InvalidSliceMessage {
+                message_id: self.message_id,
+                slice_index,
+                slice_size: bytes.len(),
+            });
         }
 
         if !self.received[slice_index] {
@@ -43,15 +57,15 @@ impl SliceConstructor {
             self.num_received_slices += 1;
 
             if is_last_slice {
-                let len = (self.num_slices - 1) * SLICE_SIZE + bytes.len();
+                let len = (self.num_slices - 1) * self.slice_size + bytes.len();
                 self.sliced_data.resize(len, 0);
             }
 
-            let start = slice_index * SLICE_SIZE;
+            let start = slice_index * self.slice_size;
             let end = if slice_index == self.num_slices - 1 {
-                (self.num_slices - 1) * SLICE_SIZE + bytes.len()
+                (self.num_slices - 1) * self.slice_size + bytes.len()
             } else {
-                (slice_index + 1) * SLICE_SIZE
+                (slice_index + 1) * self.slice_size
             };
 
             self.sliced_data[start..end].copy_from_slice(bytes);
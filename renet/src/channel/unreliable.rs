@@ -6,68 +6,150 @@ use std::{
 use bytes::Bytes;
 
 use crate::{
-    channel::SliceConstructor,
+    channel::{ReceivedMessage, SliceConstructor, SliceStats},
     error::ChannelError,
-    packet::{Packet, Slice, SLICE_SIZE},
+    packet::{Packet, Slice},
 };
 
 #[derive(Debug)]
 pub struct SendChannelUnreliable {
     channel_id: u8,
-    unreliable_messages: VecDeque<Bytes>,
+    // Ordered from the highest priority
+    unreliable_messages: VecDeque<UnreliableMessage>,
     sliced_message_id: u64,
+    slice_size: usize,
+    // Small messages are aggregated in packets up to this size
+    max_batch_size: usize,
     max_memory_usage_bytes: usize,
     memory_usage_bytes: usize,
+    slice_stats: SliceStats,
+    // Time of the last update, messages sent are queued at this time
+    current_time: Duration,
+}
+
+#[derive(Debug)]
+struct UnreliableMessage {
+    priority: u8,
+    queued_at: Duration,
+    // Messages with an expiration are kept queued until they can be sent or expire,
+    // messages without are dropped when there are no available bytes to send them
+    expires_at: Option<Duration>,
+    payload: Bytes,
 }
 
 #[derive(Debug)]
 pub struct ReceiveChannelUnreliable {
     channel_id: u8,
-    messages: VecDeque<Bytes>,
+    messages: VecDeque<ReceivedMessage>,
     slices: BTreeMap<u64, SliceConstructor>,
     slices_last_received: BTreeMap<u64, Duration>,
+    slice_size: usize,
     max_memory_usage_bytes: usize,
     memory_usage_bytes: usize,
+    slice_stats: SliceStats,
 }
 
 impl SendChannelUnreliable {
-    pub fn new(channel_id: u8, max_memory_usage_bytes: usize) -> Self {
+    pub fn new(channel_id: u8, max_memory_usage_bytes: usize, slice_size: usize) -> Self {
         Self {
             channel_id,
             unreliable_messages: VecDeque::new(),
             sliced_message_id: 0,
+            slice_size,
+            max_batch_size: slice_size,
             max_memory_usage_bytes,
             memory_usage_bytes: 0,
+            slice_stats: SliceStats::default(),
+            current_time: Duration::ZERO,
         }
     }
 
+    pub fn slice_size(&self) -> usize {
+        self.slice_size
+    }
+
+    /// Limits the bytes of small messages aggregated in a packet, it's never above the slice size.
+    pub fn set_max_batch_size(&mut self, max_batch_size: usize) {
+        self.max_batch_size = max_batch_size.min(self.slice_size);
+    }
+
+    pub fn slice_stats(&self) -> SliceStats {
+        self.slice_stats
+    }
+
+    /// Returns the size of the biggest message the channel can hold.
+    pub fn max_message_size(&self) -> usize {
+        self.max_memory_usage_bytes
+    }
+
     pub fn can_send_message(&self, size_bytes: usize) -> bool {
         size_bytes + self.memory_usage_bytes <= self.max_memory_usage_bytes
     }
 
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage_bytes
+    }
+
     pub fn available_memory(&self) -> usize {
         self.max_memory_usage_bytes - self.memory_usage_bytes
     }
 
-    pub fn get_packets_to_send(&mut self, packet_sequence: &mut u64, available_bytes: &mut u64) -> Vec<Packet> {
+    /// Returns whether there are messages not sent.
+    pub fn has_pending_messages(&self) -> bool {
+        !self.unreliable_messages.is_empty()
+    }
+
+    pub fn update(&mut self, current_time: Duration) {
+        self.current_time = current_time;
+    }
+
+    /// Returns the number of messages that were not sent yet.
+    pub fn queue_len(&self) -> usize {
+        self.unreliable_messages.len()
+    }
+
+    /// Returns the time since the oldest message not sent yet was queued.
+    pub fn oldest_unsent_age(&self) -> Option<Duration> {
+        let queued_at = self.unreliable_messages.iter().map(|message| message.queued_at).min()?;
+        Some(self.current_time - queued_at)
+    }
+
+    pub fn get_packets_to_send(&mut self, packet_sequence: &mut u64, available_bytes: &mut u64, current_time: Duration) -> Vec<Packet> {
         let mut packets: Vec<Packet> = vec![];
         let mut small_messages: Vec<Bytes> = vec![];
         let mut small_messages_bytes = 0;
+        let mut unsent_messages: VecDeque<UnreliableMessage> = VecDeque::new();
 
-        while let Some(message) = self.unreliable_messages.pop_front() {
-            self.memory_usage_bytes -= message.len();
-            if *available_bytes < message.len() as u64 {
-                // Drop message, no available bytes to send
+        while let Some(unreliable_message) = self.unreliable_messages.pop_front() {
+            if unreliable_message.expires_at.is_some_and(|expires_at| current_time > expires_at) {
+                // Drop expired message
+                self.memory_usage_bytes -= unreliable_message.payload.len();
+                continue;
+            }
+
+            if *available_bytes < unreliable_message.payload.len() as u64 {
+                if unreliable_message.expires_at.is_some() {
+                    // Keep the message until it expires
+                    unsent_messages.push_back(unreliable_message);
+                } else {
+                    // Drop message, no available bytes to send
+                    self.memory_usage_bytes -= unreliable_message.payload.len();
+                }
                 continue;
             }
 
+            let message = unreliable_message.payload;
+            self.memory_usage_bytes -= message.len();
+
             *available_bytes -= message.len() as u64;
-            if message.len() > SLICE_SIZE {
-                let num_slices = message.len().div_ceil(SLICE_SIZE);
+            if message.len() > self.slice_size {
+                let num_slices = message.len().div_ceil(self.slice_size);
+                self.slice_stats.sliced_messages_sent += 1;
+                self.slice_stats.slices_sent += num_slices as u64;
 
                 for slice_index in 0..num_slices {
-                    let start = slice_index * SLICE_SIZE;
-                    let end = if slice_index == num_slices - 1 { message.len() } else { (slice_index + 1) * SLICE_SIZE };
+                    let start = slice_index * self.slice_size;
+                    let end = if slice_index == num_slices - 1 { message.len() } else { (slice_index + 1) * self.slice_size };
                     let payload = message.slice(start..end);
 
                     let slice = Slice {
@@ -88,7 +170,7 @@ impl SendChannelUnreliable {
                 self.sliced_message_id += 1;
             } else {
                 let serialized_size = message.len() + octets::varint_len(message.len() as u64);
-                if small_messages_bytes + serialized_size > SLICE_SIZE {
+                if !small_messages.is_empty() && small_messages_bytes + serialized_size > self.max_batch_size {
                     packets.push(Packet::SmallUnreliable {
                         sequence: *packet_sequence,
                         channel_id: self.channel_id,
@@ -113,10 +195,22 @@ impl SendChannelUnreliable {
             *packet_sequence += 1;
         }
 
+        self.unreliable_messages = unsent_messages;
+
         packets
     }
 
-    pub fn send_message(&mut self, message: Bytes) {
+    /// Queues the message after the messages with the same or higher priority.
+    pub fn send_message_with_priority(&mut self, message: Bytes, priority: u8) {
+        self.queue_message(message, priority, None);
+    }
+
+    /// Queues the message until it can be sent, it's dropped if it's not sent before the expiration time.
+    pub fn send_message_with_expiration(&mut self, message: Bytes, expires_at: Duration) {
+        self.queue_message(message, 0, Some(expires_at));
+    }
+
+    fn queue_message(&mut self, message: Bytes, priority: u8, expires_at: Option<Duration>) {
         if self.memory_usage_bytes + message.len() > self.max_memory_usage_bytes {
             log::warn!(
                 "dropped unreliable message sent because channel {} is memory limited",
@@ -125,7 +219,7 @@ impl SendChannelUnreliable {
             return;
         }
 
-        let num_fragments = message.len() / SLICE_SIZE;
+        let num_fragments = message.len() / self.slice_size;
         if num_fragments > 20 {
             log::warn!(
                 "Sending an unreliable message with {num_fragments} fragments, messages with this many fragments are susceptible to packet loss. \
@@ -133,23 +227,46 @@ impl SendChannelUnreliable {
         }
 
         self.memory_usage_bytes += message.len();
-        self.unreliable_messages.push_back(message);
+        let index = self
+            .unreliable_messages
+            .iter()
+            .position(|queued_message| queued_message.priority < priority)
+            .unwrap_or(self.unreliable_messages.len());
+        self.unreliable_messages.insert(
+            index,
+            UnreliableMessage {
+                priority,
+                queued_at: self.current_time,
+                expires_at,
+                payload: message,
+            },
+        );
     }
 }
 
 impl ReceiveChannelUnreliable {
-    pub fn new(channel_id: u8, max_memory_usage_bytes: usize) -> Self {
+    pub fn new(channel_id: u8, max_memory_usage_bytes: usize, slice_size: usize) -> Self {
         Self {
             channel_id,
             slices: BTreeMap::new(),
             slices_last_received: BTreeMap::new(),
             messages: VecDeque::new(),
+            slice_size,
             memory_usage_bytes: 0,
             max_memory_usage_bytes,
+            slice_stats: SliceStats::default(),
         }
     }
 
-    pub fn process_message(&mut self, message: Bytes) {
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage_bytes
+    }
+
+    pub fn slice_stats(&self) -> SliceStats {
+        self.slice_stats
+    }
+
+    pub fn process_message(&mut self, message: Bytes, packet_sequence: u64, current_time: Duration) {
         if self.memory_usage_bytes + message.len() > self.max_memory_usage_bytes {
             log::warn!(
                 "dropped unreliable message received because channel {} is memory limited",
@@ -159,12 +276,16 @@ impl ReceiveChannelUnreliable {
         }
 
         self.memory_usage_bytes += message.len();
-        self.messages.push_back(message);
+        self.messages.push_back(ReceivedMessage {
+            payload: message,
+            receive_time: current_time,
+            packet_sequence,
+        });
     }
 
-    pub fn process_slice(&mut self, slice: Slice, current_time: Duration) -> Result<(), ChannelError> {
+    pub fn process_slice(&mut self, slice: Slice, packet_sequence: u64, current_time: Duration) -> Result<(), ChannelError> {
         if !self.slices.contains_key(&slice.message_id) {
-            let message_len = slice.num_slices * SLICE_SIZE;
+            let message_len = slice.num_slices * self.slice_size;
             if self.memory_usage_bytes + message_len > self.max_memory_usage_bytes {
                 log::warn!(
                     "dropped unreliable slice message received because channel {} is memory limited",
@@ -179,14 +300,19 @@ impl ReceiveChannelUnreliable {
         let slice_constructor = self
             .slices
             .entry(slice.message_id)
-            .or_insert_with(|| SliceConstructor::new(slice.message_id, slice.num_slices));
+            .or_insert_with(|| SliceConstructor::new(slice.message_id, slice.num_slices, self.slice_size));
 
         if let Some(message) = slice_constructor.process_slice(slice.slice_index, &slice.payload)? {
             self.slices.remove(&slice.message_id);
             self.slices_last_received.remove(&slice.message_id);
-            self.memory_usage_bytes -= slice.num_slices * SLICE_SIZE;
+            self.memory_usage_bytes -= slice.num_slices * self.slice_size;
             self.memory_usage_bytes += message.len();
-            self.messages.push_back(message);
+            self.slice_stats.sliced_messages_received += 1;
+            self.messages.push_back(ReceivedMessage {
+                payload: message,
+                receive_time: current_time,
+                packet_sequence,
+            });
         } else {
             self.slices_last_received.insert(slice.message_id, current_time);
         }
@@ -210,13 +336,14 @@ impl ReceiveChannelUnreliable {
         for message_id in lost_messages.iter() {
             self.slices_last_received.remove(message_id);
             let slice = self.slices.remove(message_id).expect("discarded slice should exist");
-            self.memory_usage_bytes -= slice.num_slices * SLICE_SIZE;
+            self.memory_usage_bytes -= slice.num_slices * self.slice_size;
+            self.slice_stats.incomplete_messages_discarded += 1;
         }
     }
 
-    pub fn receive_message(&mut self) -> Option<Bytes> {
+    pub fn receive_message(&mut self) -> Option<ReceivedMessage> {
         if let Some(message) = self.messages.pop_front() {
-            self.memory_usage_bytes -= message.len();
+            self.memory_usage_bytes -= message.payload.len();
             return Some(message);
         };
 
@@ -229,28 +356,30 @@ mod tests {
     use octets::OctetsMut;
 
     use super::*;
+    use crate::packet::SLICE_SIZE;
 
     #[test]
     fn small_packet() {
         let max_memory: usize = 10000;
         let mut available_bytes = u64::MAX;
         let mut sequence: u64 = 0;
-        let mut recv = ReceiveChannelUnreliable::new(0, max_memory);
-        let mut send = SendChannelUnreliable::new(0, max_memory);
+        let current_time = Duration::from_millis(100);
+        let mut recv = ReceiveChannelUnreliable::new(0, max_memory, SLICE_SIZE);
+        let mut send = SendChannelUnreliable::new(0, max_memory, SLICE_SIZE);
 
         let message1 = vec![1, 2, 3];
         let message2 = vec![3, 4, 5];
 
-        send.send_message(message1.clone().into());
-        send.send_message(message2.clone().into());
+        send.send_message_with_priority(message1.clone().into(), 0);
+        send.send_message_with_priority(message2.clone().into(), 0);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
         for packet in packets {
-            let Packet::SmallUnreliable { messages, .. } = packet else {
+            let Packet::SmallUnreliable { sequence, messages, .. } = packet else {
                 unreachable!();
             };
             for message in messages {
-                recv.process_message(message);
+                recv.process_message(message, sequence, current_time);
             }
         }
 
@@ -258,10 +387,12 @@ mod tests {
         let new_message2 = recv.receive_message().unwrap();
         assert!(recv.receive_message().is_none());
 
-        assert_eq!(message1, new_message1);
-        assert_eq!(message2, new_message2);
+        assert_eq!(message1, new_message1.payload);
+        assert_eq!(message2, new_message2.payload);
+        assert_eq!(new_message1.receive_time, current_time);
+        assert_eq!(new_message1.packet_sequence, 0);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
         assert!(packets.is_empty());
     }
 
@@ -271,43 +402,99 @@ mod tests {
         let mut available_bytes = u64::MAX;
         let mut sequence: u64 = 0;
         let current_time = Duration::ZERO;
-        let mut recv = ReceiveChannelUnreliable::new(0, max_memory);
-        let mut send = SendChannelUnreliable::new(0, max_memory);
+        let mut recv = ReceiveChannelUnreliable::new(0, max_memory, SLICE_SIZE);
+        let mut send = SendChannelUnreliable::new(0, max_memory, SLICE_SIZE);
 
         let message = vec![5; SLICE_SIZE * 3];
 
-        send.send_message(message.clone().into());
+        send.send_message_with_priority(message.clone().into(), 0);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
         for packet in packets {
-            let Packet::UnreliableSlice { slice, .. } = packet else {
+            let Packet::UnreliableSlice { sequence, slice, .. } = packet else {
                 unreachable!();
             };
-            recv.process_slice(slice, current_time).unwrap();
+            recv.process_slice(slice, sequence, current_time).unwrap();
         }
 
         let new_message = recv.receive_message().unwrap();
         assert!(recv.receive_message().is_none());
 
-        assert_eq!(message, new_message);
+        assert_eq!(message, new_message.payload);
+        // The message is completed by the packet with the last slice
+        assert_eq!(new_message.packet_sequence, 2);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
         assert!(packets.is_empty());
     }
 
+    #[test]
+    fn custom_slice_size() {
+        let max_memory: usize = 10000;
+        let mut available_bytes = u64::MAX;
+        let mut sequence: u64 = 0;
+        let slice_size = 500;
+        let mut recv = ReceiveChannelUnreliable::new(0, max_memory, slice_size);
+        let mut send = SendChannelUnreliable::new(0, max_memory, slice_size);
+
+        let message = vec![5; 1100];
+        send.send_message_with_priority(message.clone().into(), 0);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
+        assert_eq!(packets.len(), 3);
+        for packet in packets {
+            let Packet::UnreliableSlice { sequence, slice, .. } = packet else {
+                unreachable!();
+            };
+            assert!(slice.payload.len() <= slice_size);
+            recv.process_slice(slice, sequence, Duration::ZERO).unwrap();
+        }
+
+        assert_eq!(recv.receive_message().unwrap().payload, message);
+        assert_eq!(send.slice_stats().sliced_messages_sent, 1);
+        assert_eq!(send.slice_stats().average_slices_per_message(), 3.0);
+        assert_eq!(recv.slice_stats().sliced_messages_received, 1);
+    }
+
+    #[test]
+    fn discard_incomplete_slices() {
+        let max_memory: usize = 10000;
+        let mut available_bytes = u64::MAX;
+        let mut sequence: u64 = 0;
+        let mut recv = ReceiveChannelUnreliable::new(0, max_memory, SLICE_SIZE);
+        let mut send = SendChannelUnreliable::new(0, max_memory, SLICE_SIZE);
+
+        send.send_message_with_priority(vec![5; SLICE_SIZE * 3].into(), 0);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
+        // Lose the last slice
+        for packet in packets.into_iter().take(2) {
+            let Packet::UnreliableSlice { sequence, slice, .. } = packet else {
+                unreachable!();
+            };
+            recv.process_slice(slice, sequence, Duration::ZERO).unwrap();
+        }
+
+        recv.discard_incomplete_old_slices(Duration::from_secs(1));
+        assert_eq!(recv.slice_stats().incomplete_messages_discarded, 0);
+
+        recv.discard_incomplete_old_slices(Duration::from_secs(3));
+        assert_eq!(recv.slice_stats().incomplete_messages_discarded, 1);
+        assert_eq!(recv.memory_usage_bytes, 0);
+        assert!(recv.receive_message().is_none());
+    }
+
     #[test]
     fn max_memory() {
         let mut sequence: u64 = 0;
         let mut available_bytes = u64::MAX;
-        let mut recv = ReceiveChannelUnreliable::new(0, 50);
-        let mut send = SendChannelUnreliable::new(0, 40);
+        let mut recv = ReceiveChannelUnreliable::new(0, 50, SLICE_SIZE);
+        let mut send = SendChannelUnreliable::new(0, 40, SLICE_SIZE);
 
         let message = vec![5; 50];
 
-        send.send_message(message.clone().into());
-        send.send_message(message.into());
+        send.send_message_with_priority(message.clone().into(), 0);
+        send.send_message_with_priority(message.into(), 0);
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
         for packet in packets {
             let Packet::SmallUnreliable { messages, .. } = packet else {
                 unreachable!();
@@ -316,7 +503,7 @@ mod tests {
             // Second message was dropped
             assert_eq!(messages.len(), 1);
             for message in messages {
-                recv.process_message(message);
+                recv.process_message(message, 0, Duration::ZERO);
             }
         }
 
@@ -327,50 +514,76 @@ mod tests {
     #[test]
     fn available_bytes() {
         let mut sequence: u64 = 0;
-        let mut send = SendChannelUnreliable::new(0, usize::MAX);
+        let mut send = SendChannelUnreliable::new(0, usize::MAX, SLICE_SIZE);
 
         let message: Bytes = vec![0u8; 100].into();
-        send.send_message(message.clone());
+        send.send_message_with_priority(message.clone(), 0);
 
         // No available bytes
         let mut available_bytes: u64 = 50;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
         assert_eq!(packets.len(), 0);
 
         // Available space but message was dropped
         let mut available_bytes: u64 = u64::MAX;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
         assert_eq!(packets.len(), 0);
 
-        send.send_message(message.clone());
-        send.send_message(message);
+        send.send_message_with_priority(message.clone(), 0);
+        send.send_message_with_priority(message, 0);
 
         // Space for 1 message
         let mut available_bytes: u64 = 100;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
         assert_eq!(packets.len(), 1);
 
         // Second message was dropped
         let mut available_bytes: u64 = u64::MAX;
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
+        assert_eq!(packets.len(), 0);
+    }
+
+    #[test]
+    fn message_expiration() {
+        let mut sequence: u64 = 0;
+        let mut send = SendChannelUnreliable::new(0, usize::MAX, SLICE_SIZE);
+
+        let message: Bytes = vec![0u8; 100].into();
+        send.send_message_with_expiration(message.clone(), Duration::from_millis(100));
+
+        // No available bytes, message is kept until it expires
+        let mut available_bytes: u64 = 50;
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::from_millis(50));
+        assert_eq!(packets.len(), 0);
+        assert_eq!(send.available_memory(), usize::MAX - 100);
+
+        let mut available_bytes: u64 = u64::MAX;
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::from_millis(100));
+        assert_eq!(packets.len(), 1);
+
+        // Expired message is dropped
+        send.send_message_with_expiration(message, Duration::from_millis(150));
+        let mut available_bytes: u64 = u64::MAX;
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::from_millis(200));
         assert_eq!(packets.len(), 0);
+        assert_eq!(send.available_memory(), usize::MAX);
     }
 
     #[test]
     fn small_packet_max_size() {
         let mut sequence: u64 = 0;
         let mut available_bytes = u64::MAX;
-        let mut send = SendChannelUnreliable::new(0, usize::MAX);
+        let mut send = SendChannelUnreliable::new(0, usize::MAX, SLICE_SIZE);
 
         // 4 bytes
         let message: Bytes = vec![0, 1, 2, 3].into();
 
         // (4 + 1) * 400 = 2000 = 2 packets
         for _ in 0..400 {
-            send.send_message(message.clone());
+            send.send_message_with_priority(message.clone(), 0);
         }
 
-        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes);
+        let packets = send.get_packets_to_send(&mut sequence, &mut available_bytes, Duration::ZERO);
         assert_eq!(packets.len(), 2);
         let mut buffer = [0u8; 1400];
         for packet in packets {
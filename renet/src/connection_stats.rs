@@ -1,47 +1,174 @@
 use std::time::Duration;
 
 const RESOLUTION: Duration = Duration::from_millis(300);
-const WINDOW: Duration = Duration::from_millis(6000);
-const SIZE: usize = (WINDOW.as_millis() / RESOLUTION.as_millis()) as usize;
-
-#[derive(Debug, Default)]
+pub const DEFAULT_WINDOW: Duration = Duration::from_millis(6000);
+pub const DEFAULT_SMOOTHING: f64 = 0.1;
+// The packet loss ignores the last 3 resolutions, so the window has at least one more
+const MIN_SIZE: usize = 4;
+const SETTLING_RESOLUTIONS: usize = 3;
+// Above this packet loss the connection is considered congested, lower values can be random loss
+pub const CONGESTION_PACKET_LOSS: f64 = 0.05;
+// Growth of the bandwidth estimate, every resolution, while the connection is used near it without congestion
+const BANDWIDTH_PROBE_GROWTH: f64 = 0.05;
+
+#[derive(Debug)]
 pub struct ConnectionStats {
-    packets_sent: [u64; SIZE],
-    packets_acked: [u64; SIZE],
-    bytes_sent: [u64; SIZE],
-    bytes_received: [u64; SIZE],
+    packets_sent: Vec<u64>,
+    packets_acked: Vec<u64>,
+    bytes_sent: Vec<u64>,
+    payload_bytes_sent: Vec<u64>,
+    bytes_received: Vec<u64>,
+    reliable_sent: Vec<u64>,
+    reliable_resent: Vec<u64>,
     current_index: usize,
+    packet_loss_smoothing: f64,
+    bandwidth_smoothing: f64,
+    // Exponential moving averages, updated every resolution
+    smoothed: Option<SmoothedStats>,
+    // Maximum sustainable send rate in bytes per second, available after the first window
+    estimated_bandwidth: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SmoothedStats {
+    packet_loss: f64,
+    bytes_sent_per_second: f64,
+    bytes_received_per_second: f64,
+}
+
+impl Default for ConnectionStats {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW, DEFAULT_SMOOTHING, DEFAULT_SMOOTHING)
+    }
 }
 
 impl ConnectionStats {
-    pub fn new() -> Self {
+    pub fn new(window: Duration, packet_loss_smoothing: f64, bandwidth_smoothing: f64) -> Self {
+        let size = ((window.as_millis() / RESOLUTION.as_millis()) as usize).max(MIN_SIZE);
         Self {
-            packets_sent: [0; SIZE],
-            packets_acked: [0; SIZE],
-            bytes_sent: [0; SIZE],
-            bytes_received: [0; SIZE],
+            packets_sent: vec![0; size],
+            packets_acked: vec![0; size],
+            bytes_sent: vec![0; size],
+            payload_bytes_sent: vec![0; size],
+            bytes_received: vec![0; size],
+            reliable_sent: vec![0; size],
+            reliable_resent: vec![0; size],
             current_index: 0,
+            packet_loss_smoothing: packet_loss_smoothing.clamp(0.0, 1.0),
+            bandwidth_smoothing: bandwidth_smoothing.clamp(0.0, 1.0),
+            smoothed: None,
+            estimated_bandwidth: None,
         }
     }
 
-    fn index(time: Duration) -> usize {
-        (time.as_millis() / RESOLUTION.as_millis()) as usize % SIZE
+    fn size(&self) -> usize {
+        self.packets_sent.len()
+    }
+
+    fn window(&self) -> Duration {
+        RESOLUTION * self.size() as u32
+    }
+
+    fn index(&self, time: Duration) -> usize {
+        (time.as_millis() / RESOLUTION.as_millis()) as usize % self.size()
     }
 
     pub fn update(&mut self, current_time: Duration) {
-        let i = Self::index(current_time);
+        let i = self.index(current_time);
         if self.current_index != i {
+            self.update_smoothed(current_time);
+
             self.current_index = i;
             self.packets_sent[i] = 0;
             self.bytes_sent[i] = 0;
+            self.payload_bytes_sent[i] = 0;
             self.bytes_received[i] = 0;
             self.packets_acked[i] = 0;
+            self.reliable_sent[i] = 0;
+            self.reliable_resent[i] = 0;
+
+            self.update_estimated_bandwidth(current_time);
         }
     }
 
-    pub fn sent_packets(&mut self, num_packets: u64, bytes: u64) {
+    // Called when a resolution is completed, before it's reset
+    fn update_smoothed(&mut self, current_time: Duration) {
+        let current = SmoothedStats {
+            packet_loss: self.packet_loss(),
+            bytes_sent_per_second: self.bytes_sent_per_second(current_time),
+            bytes_received_per_second: self.bytes_received_per_second(current_time),
+        };
+
+        let smoothed = match self.smoothed {
+            None => current,
+            Some(smoothed) => {
+                let ewma = |smoothed: f64, value: f64, factor: f64| smoothed + factor * (value - smoothed);
+                SmoothedStats {
+                    packet_loss: ewma(smoothed.packet_loss, current.packet_loss, self.packet_loss_smoothing),
+                    bytes_sent_per_second: ewma(
+                        smoothed.bytes_sent_per_second,
+                        current.bytes_sent_per_second,
+                        self.bandwidth_smoothing,
+                    ),
+                    bytes_received_per_second: ewma(
+                        smoothed.bytes_received_per_second,
+                        current.bytes_received_per_second,
+                        self.bandwidth_smoothing,
+                    ),
+                }
+            }
+        };
+        self.smoothed = Some(smoothed);
+    }
+
+    // Bandwidth estimation based on the bytes delivered, the bytes sent without the lost packets.
+    // When congested the estimate drops to the delivered rate, otherwise it grows while the connection
+    // sends near it, probing for more bandwidth.
+    fn update_estimated_bandwidth(&mut self, current_time: Duration) {
+        if current_time < self.window() {
+            return;
+        }
+
+        let bytes_sent_per_second = self.settled_bytes_sent_per_second();
+        if bytes_sent_per_second == 0.0 {
+            // Nothing to estimate with
+            return;
+        }
+
+        let packet_loss = self.packet_loss();
+        let delivered = bytes_sent_per_second * (1.0 - packet_loss);
+        let estimate = match self.estimated_bandwidth {
+            None => delivered,
+            Some(_) if packet_loss > CONGESTION_PACKET_LOSS => delivered,
+            Some(estimate) if bytes_sent_per_second >= estimate * 0.9 => estimate * (1.0 + BANDWIDTH_PROBE_GROWTH),
+            Some(estimate) => estimate.max(delivered),
+        };
+        self.estimated_bandwidth = Some(estimate);
+    }
+
+    // Bytes sent per second in the resolutions that had time to be acked, the same used by the packet loss
+    fn settled_bytes_sent_per_second(&self) -> f64 {
+        let size = self.size();
+        let total_bytes: u64 = (SETTLING_RESOLUTIONS..size)
+            .map(|offset| self.bytes_sent[(self.current_index + size - offset) % size])
+            .sum();
+        total_bytes as f64 / (RESOLUTION * (size - SETTLING_RESOLUTIONS) as u32).as_secs_f64()
+    }
+
+    /// Maximum sustainable send rate in bytes per second, None until a full window of packets was sent.
+    pub fn estimated_bandwidth(&self) -> Option<f64> {
+        self.estimated_bandwidth
+    }
+
+    pub fn sent_packets(&mut self, num_packets: u64, bytes: u64, payload_bytes: u64) {
         self.packets_sent[self.current_index] += num_packets;
         self.bytes_sent[self.current_index] += bytes;
+        self.payload_bytes_sent[self.current_index] += payload_bytes;
+    }
+
+    pub fn sent_reliable(&mut self, num_sent: u64, num_resent: u64) {
+        self.reliable_sent[self.current_index] += num_sent;
+        self.reliable_resent[self.current_index] += num_resent;
     }
 
     pub fn received_packet(&mut self, bytes: u64) {
@@ -50,56 +177,69 @@ impl ConnectionStats {
 
     pub fn acked_packet(&mut self, sent_at: Duration, current_time: Duration) {
         let delta = current_time - sent_at;
-        if delta > WINDOW {
+        if delta > self.window() {
             // Out of the duration window, discard it
             return;
         }
 
-        self.packets_acked[Self::index(sent_at)] += 1;
+        let index = self.index(sent_at);
+        self.packets_acked[index] += 1;
     }
 
-    pub fn bytes_sent_per_second(&self, current_time: Duration) -> f64 {
-        let mut total_bytes: u64 = self.bytes_sent.iter().sum();
+    // Bytes per second over the window, ignoring the current incomplete resolution
+    fn per_second(&self, values: &[u64], current_time: Duration) -> f64 {
+        let mut total_bytes: u64 = values.iter().sum();
 
-        if current_time < WINDOW {
+        let window = self.window();
+        if current_time < window {
             return total_bytes as f64 / current_time.as_secs_f64();
         }
 
-        // Ignore the current incomplete resolution
-        total_bytes -= self.bytes_sent[self.current_index];
+        total_bytes -= values[self.current_index];
+        total_bytes as f64 / (window - RESOLUTION).as_secs_f64()
+    }
 
-        total_bytes as f64 / (WINDOW - RESOLUTION).as_secs_f64()
+    pub fn bytes_sent_per_second(&self, current_time: Duration) -> f64 {
+        self.per_second(&self.bytes_sent, current_time)
     }
 
-    pub fn bytes_received_per_second(&self, current_time: Duration) -> f64 {
-        let mut total_bytes: u64 = self.bytes_received.iter().sum();
+    pub fn payload_bytes_sent_per_second(&self, current_time: Duration) -> f64 {
+        self.per_second(&self.payload_bytes_sent, current_time)
+    }
 
-        if current_time < WINDOW {
-            return total_bytes as f64 / current_time.as_secs_f64();
+    /// Ratio of the bytes sent that are message payload, the rest are packet headers and acks.
+    pub fn efficiency(&self) -> f64 {
+        let total_bytes: u64 = self.bytes_sent.iter().sum();
+        if total_bytes == 0 {
+            return 0.0;
         }
 
-        // Ignore the current incomplete resolution
-        total_bytes -= self.bytes_received[self.current_index];
-        total_bytes as f64 / (WINDOW - RESOLUTION).as_secs_f64()
+        let total_payload_bytes: u64 = self.payload_bytes_sent.iter().sum();
+        total_payload_bytes as f64 / total_bytes as f64
+    }
+
+    pub fn bytes_received_per_second(&self, current_time: Duration) -> f64 {
+        self.per_second(&self.bytes_received, current_time)
     }
 
     pub fn packet_loss(&self) -> f64 {
+        let size = self.size();
         let total_packets_sent = {
             let mut sum: u64 = self.packets_sent.iter().sum();
 
             // Ignore the current and last 2 resolutions,
             // because the message or its ack could be in flight
             sum -= self.packets_sent[self.current_index];
-            sum -= self.packets_sent[(self.current_index + SIZE - 1) % SIZE];
-            sum -= self.packets_sent[(self.current_index + SIZE - 2) % SIZE];
+            sum -= self.packets_sent[(self.current_index + size - 1) % size];
+            sum -= self.packets_sent[(self.current_index + size - 2) % size];
             sum as f64
         };
 
         let total_packets_acked = {
             let mut sum: u64 = self.packets_acked.iter().sum();
             sum -= self.packets_acked[self.current_index];
-            sum -= self.packets_acked[(self.current_index + SIZE - 1) % SIZE];
-            sum -= self.packets_acked[(self.current_index + SIZE - 2) % SIZE];
+            sum -= self.packets_acked[(self.current_index + size - 1) % size];
+            sum -= self.packets_acked[(self.current_index + size - 2) % size];
             sum as f64
         };
 
@@ -109,12 +249,61 @@ impl ConnectionStats {
 
         (total_packets_sent - total_packets_acked) / total_packets_sent
     }
+
+    /// Exponential moving average of the packet loss, the same as the packet loss until the first resolution is completed.
+    pub fn smoothed_packet_loss(&self) -> f64 {
+        match self.smoothed {
+            Some(smoothed) => smoothed.packet_loss,
+            None => self.packet_loss(),
+        }
+    }
+
+    pub fn smoothed_bytes_sent_per_second(&self, current_time: Duration) -> f64 {
+        match self.smoothed {
+            Some(smoothed) => smoothed.bytes_sent_per_second,
+            None => self.bytes_sent_per_second(current_time),
+        }
+    }
+
+    pub fn smoothed_bytes_received_per_second(&self, current_time: Duration) -> f64 {
+        match self.smoothed {
+            Some(smoothed) => smoothed.bytes_received_per_second,
+            None => self.bytes_received_per_second(current_time),
+        }
+    }
+
+    /// Ratio of reliable messages and slices sent that were resends.
+    pub fn retransmission_ratio(&self) -> f64 {
+        let total_sent: u64 = self.reliable_sent.iter().sum();
+        if total_sent == 0 {
+            return 0.0;
+        }
+
+        let total_resent: u64 = self.reliable_resent.iter().sum();
+        total_resent as f64 / total_sent as f64
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn efficiency() {
+        let mut current_time = Duration::ZERO;
+        let mut window = ConnectionStats::default();
+        assert_eq!(window.efficiency(), 0.0);
+
+        for _ in 0..10 {
+            window.update(current_time);
+            window.sent_packets(10, 100, 80);
+            current_time += Duration::from_millis(100);
+        }
+
+        assert_eq!(window.efficiency(), 0.8);
+        assert_eq!(window.payload_bytes_sent_per_second(current_time), 800.);
+    }
+
     #[test]
     fn bytes_per_sec() {
         let mut current_time = Duration::ZERO;
@@ -122,7 +311,7 @@ mod tests {
 
         for _ in 0..10 {
             window.update(current_time);
-            window.sent_packets(10, 100);
+            window.sent_packets(10, 100, 0);
             current_time += Duration::from_millis(100);
         }
 
@@ -131,13 +320,13 @@ mod tests {
 
         for _ in 0..50 {
             window.update(current_time);
-            window.sent_packets(10, 100);
+            window.sent_packets(10, 100, 0);
             current_time += Duration::from_millis(100);
         }
 
         // Check after 6 seconds
-        assert_eq!(window.packets_sent, [30; SIZE]);
-        assert_eq!(window.bytes_sent, [300; SIZE]);
+        assert_eq!(window.packets_sent, vec![30; 20]);
+        assert_eq!(window.bytes_sent, vec![300; 20]);
         assert_eq!(window.bytes_sent_per_second(current_time), 1000.);
     }
 
@@ -149,7 +338,7 @@ mod tests {
         for _ in 0..20 {
             window.update(current_time);
             // Send 2, ack only 1
-            window.sent_packets(2, 100);
+            window.sent_packets(2, 100, 0);
             window.acked_packet(current_time, current_time);
             current_time += Duration::from_millis(100);
         }
@@ -159,14 +348,113 @@ mod tests {
 
         for _ in 0..40 {
             window.update(current_time);
-            window.sent_packets(2, 100);
+            window.sent_packets(2, 100, 0);
             window.acked_packet(current_time, current_time);
             current_time += Duration::from_millis(100);
         }
 
         // Check after 6 seconds
-        assert_eq!(window.packets_sent, [6; SIZE]);
-        assert_eq!(window.packets_acked, [3; SIZE]);
+        assert_eq!(window.packets_sent, vec![6; 20]);
+        assert_eq!(window.packets_acked, vec![3; 20]);
+        assert_eq!(window.packet_loss(), 0.5);
+    }
+
+    #[test]
+    fn custom_window() {
+        let mut current_time = Duration::ZERO;
+        let mut window = ConnectionStats::new(Duration::from_secs(3), 1.0, 1.0);
+        assert_eq!(window.packets_sent.len(), 10);
+
+        for _ in 0..60 {
+            window.update(current_time);
+            window.sent_packets(10, 100, 0);
+            current_time += Duration::from_millis(100);
+        }
+
+        assert_eq!(window.bytes_sent, vec![300; 10]);
+        assert!((window.bytes_sent_per_second(current_time) - 1000.).abs() < 0.001);
+
+        // Windows smaller than the packet loss delay are increased
+        let window = ConnectionStats::new(Duration::ZERO, 1.0, 1.0);
+        assert_eq!(window.packets_sent.len(), MIN_SIZE);
+    }
+
+    #[test]
+    fn smoothed_packet_loss() {
+        let mut current_time = Duration::ZERO;
+        let mut window = ConnectionStats::new(DEFAULT_WINDOW, 0.5, 0.5);
+
+        for _ in 0..60 {
+            window.update(current_time);
+            // Send 2, ack only 1
+            window.sent_packets(2, 100, 0);
+            window.acked_packet(current_time, current_time);
+            current_time += Duration::from_millis(100);
+        }
+
         assert_eq!(window.packet_loss(), 0.5);
+        assert!((window.smoothed_packet_loss() - 0.5).abs() < 0.01);
+
+        for _ in 0..60 {
+            window.update(current_time);
+            // No more packet loss
+            window.sent_packets(2, 100, 0);
+            window.acked_packet(current_time, current_time);
+            window.acked_packet(current_time, current_time);
+            current_time += Duration::from_millis(100);
+        }
+
+        assert_eq!(window.packet_loss(), 0.0);
+        // The smoothed loss follows with a delay of one resolution
+        assert!(window.smoothed_packet_loss() > 0.0);
+        assert!(window.smoothed_packet_loss() < 0.1);
+    }
+
+    #[test]
+    fn estimated_bandwidth() {
+        let mut current_time = Duration::ZERO;
+        let mut window = ConnectionStats::default();
+
+        let send = |window: &mut ConnectionStats, current_time: &mut Duration, ticks: usize, acks: u64| {
+            for _ in 0..ticks {
+                window.update(*current_time);
+                // Send 4 packets of 250 bytes
+                window.sent_packets(4, 1000, 0);
+                for _ in 0..acks {
+                    window.acked_packet(*current_time, *current_time);
+                }
+                *current_time += Duration::from_millis(100);
+            }
+        };
+
+        send(&mut window, &mut current_time, 60, 4);
+        // No estimate before a full window
+        assert!(window.estimated_bandwidth().is_none());
+
+        send(&mut window, &mut current_time, 30, 4);
+        // Sending at the estimated rate without loss, the estimate grows
+        let estimate = window.estimated_bandwidth().unwrap();
+        assert!(estimate > 10_000.);
+
+        // Half of the packets are lost, the estimate drops to the delivered rate
+        send(&mut window, &mut current_time, 60, 2);
+        let estimate = window.estimated_bandwidth().unwrap();
+        assert!((estimate - 5000.).abs() < 0.001);
+    }
+
+    #[test]
+    fn retransmission_ratio() {
+        let mut current_time = Duration::ZERO;
+        let mut window = ConnectionStats::default();
+        assert_eq!(window.retransmission_ratio(), 0.0);
+
+        for _ in 0..60 {
+            window.update(current_time);
+            // Send 4, 1 is a resend
+            window.sent_reliable(4, 1);
+            current_time += Duration::from_millis(100);
+        }
+
+        assert_eq!(window.retransmission_ratio(), 0.25);
     }
 }
@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+use crate::channel::{ChannelConfig, ResendStrategy, SendType};
+
+/// Channel id reserved for the control messages that renet sends from the server to the client,
+/// it can't be used in the channels of the [`ConnectionConfig`](crate::ConnectionConfig).
+pub const CONTROL_CHANNEL_ID: u8 = u8::MAX;
+
+// Type of the control message, the first byte of each message
+const TRANSFER_MESSAGE_TYPE: u8 = 0;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ControlMessage {
+    /// Connect to another server with the connect token.
+    Transfer { connect_token: Bytes },
+}
+
+impl ControlMessage {
+    pub fn to_bytes(&self) -> Bytes {
+        match self {
+            ControlMessage::Transfer { connect_token } => {
+                let mut message = BytesMut::with_capacity(1 + connect_token.len());
+                message.put_u8(TRANSFER_MESSAGE_TYPE);
+                message.put_slice(connect_token);
+                message.freeze()
+            }
+        }
+    }
+
+    /// Returns None for unknown control messages, so newer message types are ignored.
+    pub fn from_bytes(mut message: Bytes) -> Option<Self> {
+        if message.is_empty() {
+            return None;
+        }
+
+        match message.split_to(1)[0] {
+            TRANSFER_MESSAGE_TYPE => Some(ControlMessage::Transfer { connect_token: message }),
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn control_channel_config() -> ChannelConfig {
+    ChannelConfig {
+        channel_id: CONTROL_CHANNEL_ID,
+        max_memory_usage_bytes: 64 * 1024,
+        send_type: SendType::ReliableOrdered {
+            resend_time: ResendStrategy::Fixed(Duration::from_millis(300)),
+        },
+        slice_size: None,
+        ordering: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn control_message_serialization() {
+        let transfer = ControlMessage::Transfer {
+            connect_token: Bytes::from_static(&[1, 2, 3]),
+        };
+        let message = transfer.to_bytes();
+        assert_eq!(message[..], [TRANSFER_MESSAGE_TYPE, 1, 2, 3]);
+        assert_eq!(ControlMessage::from_bytes(message), Some(transfer));
+
+        assert_eq!(ControlMessage::from_bytes(Bytes::new()), None);
+        assert_eq!(ControlMessage::from_bytes(Bytes::from_static(&[200, 1])), None);
+    }
+}
@@ -1,9 +1,11 @@
 use std::fmt;
+use std::time::Duration;
 
 use crate::packet::SerializationError;
 
 /// Possible reasons for a disconnection.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum DisconnectReason {
     /// Connection was terminated by the transport layer
     Transport,
@@ -12,24 +14,114 @@ pub enum DisconnectReason {
     /// Connection was terminated by the server
     DisconnectedByServer,
     /// Failed to serialize packet
-    PacketSerialization(SerializationError),
+    PacketSerialization { sequence: u64, error: SerializationError },
     /// Failed to deserialize packet
-    PacketDeserialization(SerializationError),
+    PacketDeserialization { packet_len: usize, error: SerializationError },
+    /// Received a packet with another wire format version, the remote version is 0
+    /// for renet versions before the versioned format
+    WireFormatMismatch { version: u8, remote_version: u8 },
     /// Received message from channel with invalid id
     ReceivedInvalidChannelId(u8),
     /// Error occurred in a send channel
     SendChannelError { channel_id: u8, error: ChannelError },
     /// Error occurred in a receive channel
     ReceiveChannelError { channel_id: u8, error: ChannelError },
+    /// The server reached its memory limit and this connection was using the most memory
+    MemoryLimitReached,
+    /// The connection quality was too poor for too long, see `RenetServer::set_stale_connection_policy`
+    StaleConnection,
+}
+
+/// Possible reasons for the transport layer to deny a connection attempt.
+///
+/// The variants match the `ConnectionDeniedReason` of the netcode protocol (renetcode) one to one, `renet_netcode` converts them.
+/// Other transports use the variant closest to their own reasons, new variants can be added as transports need them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConnectionDeniedReason {
+    /// The connect token has expired
+    TokenExpired,
+    /// The client uses a different protocol version
+    InvalidVersion,
+    /// The client uses a different protocol id
+    InvalidProtocolId,
+    /// The server address is not in the connect token
+    NotInHostList,
+    /// The client is banned
+    Banned,
+    /// The server reached the maximum number of clients
+    ServerFull,
+    /// The client was not approved by the server
+    NotApproved,
+}
+
+/// Possible errors of a remote procedure call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RpcError {
+    /// No response was received before the call timeout
+    TimedOut,
+    /// The server has no handler for the method
+    UnknownMethod,
+    /// The server failed to deserialize the request
+    InvalidRequest,
+    /// Failed to deserialize the response
+    InvalidResponse,
 }
 
 /// Possibles errors that can occur in a channel.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ChannelError {
     /// Reliable channel reached maximum allowed memory
-    ReliableChannelMaxMemoryReached,
+    ReliableChannelMaxMemoryReached {
+        message_size: usize,
+        memory_usage_bytes: usize,
+        max_memory_usage_bytes: usize,
+    },
     /// Received an invalid slice message in the channel.
-    InvalidSliceMessage,
+    InvalidSliceMessage {
+        message_id: u64,
+        slice_index: usize,
+        slice_size: usize,
+    },
+    /// Received a message without a valid stream header in a streams channel.
+    InvalidStreamMessage { message_id: u64 },
+    /// Ordered channel reached the maximum number of messages waiting for a missing message.
+    OrderingBufferFull {
+        missing_message_id: u64,
+        max_buffered_messages: usize,
+    },
+}
+
+impl ChannelError {
+    /// Returns whether this error terminates the connection.
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            ChannelError::ReliableChannelMaxMemoryReached { .. }
+            | ChannelError::InvalidSliceMessage { .. }
+            | ChannelError::InvalidStreamMessage { .. }
+            | ChannelError::OrderingBufferFull { .. } => true,
+        }
+    }
+}
+
+impl DisconnectReason {
+    /// Returns whether the disconnection was caused by an error,
+    /// instead of being requested by the client or the server.
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            DisconnectReason::DisconnectedByClient | DisconnectReason::DisconnectedByServer => false,
+            DisconnectReason::Transport
+            | DisconnectReason::PacketSerialization { .. }
+            | DisconnectReason::PacketDeserialization { .. }
+            | DisconnectReason::WireFormatMismatch { .. }
+            | DisconnectReason::ReceivedInvalidChannelId(_)
+            | DisconnectReason::MemoryLimitReached
+            | DisconnectReason::StaleConnection => true,
+            DisconnectReason::SendChannelError { error, .. } | DisconnectReason::ReceiveChannelError { error, .. } => error.is_fatal(),
+        }
+    }
 }
 
 impl fmt::Display for ChannelError {
@@ -37,8 +129,30 @@ impl fmt::Display for ChannelError {
         use ChannelError::*;
 
         match *self {
-            ReliableChannelMaxMemoryReached => write!(fmt, "reliable channel memory usage was exausted"),
-            InvalidSliceMessage => write!(fmt, "received an invalid slice packet"),
+            ReliableChannelMaxMemoryReached {
+                message_size,
+                memory_usage_bytes,
+                max_memory_usage_bytes,
+            } => write!(
+                fmt,
+                "reliable channel memory usage was exausted, message with {message_size} bytes does not fit ({memory_usage_bytes}/{max_memory_usage_bytes} bytes used)"
+            ),
+            InvalidSliceMessage {
+                message_id,
+                slice_index,
+                slice_size,
+            } => write!(
+                fmt,
+                "received an invalid slice packet (message {message_id}, slice {slice_index} with {slice_size} bytes)"
+            ),
+            InvalidStreamMessage { message_id } => write!(fmt, "received message {message_id} with an invalid stream header"),
+            OrderingBufferFull {
+                missing_message_id,
+                max_buffered_messages,
+            } => write!(
+                fmt,
+                "ordered channel has {max_buffered_messages} messages waiting for the missing message {missing_message_id}"
+            ),
         }
     }
 }
@@ -51,20 +165,197 @@ impl fmt::Display for DisconnectReason {
             Transport => write!(fmt, "connection terminated by the transport layer"),
             DisconnectedByClient => write!(fmt, "connection terminated by the client"),
             DisconnectedByServer => write!(fmt, "connection terminated by the server"),
-            PacketSerialization(err) => write!(fmt, "failed to serialize packet: {err}"),
-            PacketDeserialization(err) => write!(fmt, "failed to deserialize packet: {err}"),
+            PacketSerialization { sequence, error } => write!(fmt, "failed to serialize packet {sequence}: {error}"),
+            PacketDeserialization { packet_len, error } => write!(fmt, "failed to deserialize packet with {packet_len} bytes: {error}"),
+            WireFormatMismatch { version, remote_version } => {
+                write!(fmt, "received packet with wire format version {remote_version}, expected {version}")
+            }
             ReceivedInvalidChannelId(id) => write!(fmt, "received message with invalid channel {id}"),
             SendChannelError { channel_id, error } => write!(fmt, "send channel {channel_id} with error: {error}"),
             ReceiveChannelError { channel_id, error } => write!(fmt, "receive channel {channel_id} with error: {error}"),
+            MemoryLimitReached => write!(fmt, "server memory limit reached"),
+            StaleConnection => write!(fmt, "connection quality too poor"),
+        }
+    }
+}
+
+impl fmt::Display for ConnectionDeniedReason {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use ConnectionDeniedReason::*;
+
+        match *self {
+            TokenExpired => write!(fmt, "connect token expired"),
+            InvalidVersion => write!(fmt, "invalid protocol version"),
+            InvalidProtocolId => write!(fmt, "invalid protocol id"),
+            NotInHostList => write!(fmt, "server address not in the connect token"),
+            Banned => write!(fmt, "client is banned"),
+            ServerFull => write!(fmt, "server is full"),
+            NotApproved => write!(fmt, "client was not approved"),
+        }
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use RpcError::*;
+
+        match *self {
+            TimedOut => write!(fmt, "remote procedure call timed out"),
+            UnknownMethod => write!(fmt, "remote procedure method is not registered in the server"),
+            InvalidRequest => write!(fmt, "remote procedure request is invalid"),
+            InvalidResponse => write!(fmt, "remote procedure response is invalid"),
         }
     }
 }
 
 impl std::error::Error for ChannelError {}
 
+impl std::error::Error for RpcError {}
+
 #[derive(Debug)]
 pub struct ClientNotFound;
 
+/// The channel id is not in the channels of the connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidChannelId(pub u8);
+
+impl std::error::Error for InvalidChannelId {}
+
+/// Possible errors when sending a message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SendError {
+    /// The channel id is not in the channels of the connection.
+    InvalidChannelId(u8),
+    /// The message is bigger than the channel can hold, it can never be sent.
+    /// Big messages should be split in smaller ones, or the memory of the channel increased.
+    MessageTooLarge { size: usize, limit: usize },
+}
+
+impl std::error::Error for SendError {}
+
+impl fmt::Display for SendError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SendError::InvalidChannelId(channel_id) => write!(fmt, "invalid channel {channel_id}"),
+            SendError::MessageTooLarge { size, limit } => {
+                write!(fmt, "message with {size} bytes is bigger than the channel limit of {limit} bytes")
+            }
+        }
+    }
+}
+
+/// Invalid [`ConnectionConfig`](crate::ConnectionConfig), returned by [`ConnectionConfigBuilder::build`](crate::ConnectionConfigBuilder::build).
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// The channel id is repeated in the server or client channels.
+    DuplicateChannelId { channel_id: u8, server_channel: bool },
+    /// The channel id is [`CONTROL_CHANNEL_ID`](crate::CONTROL_CHANNEL_ID), reserved by renet.
+    ReservedChannelId(u8),
+    /// The slice size of the channel is 0 or bigger than the maximum.
+    InvalidSliceSize { channel_id: u8, slice_size: usize, max: usize },
+    /// The channel can't hold any message.
+    ZeroChannelMemory { channel_id: u8 },
+    /// The sum of the memory of all channels is above the limit set in the builder.
+    MemoryLimitExceeded { total: usize, limit: usize },
+    /// The budget per tick can't fit a single packet, so no message would be sent.
+    BudgetTooSmall { available_bytes_per_tick: u64, min: u64 },
+    /// The resend time of the channel is shorter than the expected round-trip time,
+    /// so every message would be resent before its ack could arrive.
+    ResendTimeBelowRtt {
+        channel_id: u8,
+        resend_time: Duration,
+        rtt: Duration,
+    },
+    /// The adaptive resend time of the channel has its minimum above its maximum.
+    InvalidAdaptiveResend { channel_id: u8, min: Duration, max: Duration },
+    /// Sent packets are discarded before their ack could arrive with the expected round-trip time.
+    DiscardTimeBelowRtt { sent_packet_discard_time: Duration, rtt: Duration },
+    /// A smoothing factor is not between 0 and 1.
+    InvalidSmoothing(f64),
+}
+
+impl std::error::Error for ConfigError {}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use ConfigError::*;
+
+        match *self {
+            DuplicateChannelId {
+                channel_id,
+                server_channel,
+            } => {
+                let list = if server_channel { "server" } else { "client" };
+                write!(fmt, "channel {channel_id} is repeated in the {list} channels")
+            }
+            ReservedChannelId(channel_id) => write!(fmt, "channel {channel_id} is reserved for the control channel"),
+            InvalidSliceSize {
+                channel_id,
+                slice_size,
+                max,
+            } => {
+                write!(
+                    fmt,
+                    "invalid slice size {slice_size} for channel {channel_id}, it must be between 1 and {max} bytes"
+                )
+            }
+            ZeroChannelMemory { channel_id } => write!(fmt, "channel {channel_id} has no memory for messages"),
+            MemoryLimitExceeded { total, limit } => {
+                write!(fmt, "channels use {total} bytes of memory, above the limit of {limit} bytes")
+            }
+            BudgetTooSmall {
+                available_bytes_per_tick,
+                min,
+            } => {
+                write!(
+                    fmt,
+                    "{available_bytes_per_tick} bytes per tick can't fit a packet, the minimum is {min} bytes"
+                )
+            }
+            ResendTimeBelowRtt {
+                channel_id,
+                resend_time,
+                rtt,
+            } => {
+                write!(
+                    fmt,
+                    "resend time {resend_time:?} of channel {channel_id} is shorter than the expected rtt {rtt:?}"
+                )
+            }
+            InvalidAdaptiveResend { channel_id, min, max } => {
+                write!(
+                    fmt,
+                    "adaptive resend time of channel {channel_id} has min {min:?} above max {max:?}"
+                )
+            }
+            DiscardTimeBelowRtt {
+                sent_packet_discard_time,
+                rtt,
+            } => {
+                write!(
+                    fmt,
+                    "sent packet discard time {sent_packet_discard_time:?} is shorter than the expected rtt {rtt:?}"
+                )
+            }
+            InvalidSmoothing(smoothing) => write!(fmt, "smoothing factor {smoothing} is not between 0 and 1"),
+        }
+    }
+}
+
+impl From<InvalidChannelId> for SendError {
+    fn from(error: InvalidChannelId) -> Self {
+        SendError::InvalidChannelId(error.0)
+    }
+}
+
+impl fmt::Display for InvalidChannelId {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "invalid channel {}", self.0)
+    }
+}
+
 impl std::error::Error for ClientNotFound {}
 
 impl fmt::Display for ClientNotFound {
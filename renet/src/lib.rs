@@ -1,14 +1,46 @@
+// With the `tracing` feature the log events are emitted as tracing events,
+// they are still forwarded to the logger when no tracing subscriber is set.
+#[cfg(feature = "tracing")]
+extern crate tracing as log;
+
 mod channel;
 mod connection_stats;
+mod control;
 mod error;
+mod lockstep;
+mod middleware;
+mod observer;
+#[cfg(feature = "low-level")]
+pub mod packet;
+#[cfg(not(feature = "low-level"))]
 mod packet;
+#[cfg(feature = "pcap")]
+mod pcap;
+mod rate_limiter;
 mod remote_connection;
+mod rpc;
+mod sequence_buffer;
 mod server;
+mod shard;
+mod telemetry;
 
-pub use channel::{ChannelConfig, DefaultChannel, SendType};
-pub use error::{ChannelError, ClientNotFound, DisconnectReason};
-pub use remote_connection::{ConnectionConfig, NetworkInfo, RenetClient, RenetConnectionStatus};
-pub use server::{RenetServer, ServerEvent};
+pub use channel::{ChannelConfig, DefaultChannel, OrderingConfig, ReceivedMessage, ResendStrategy, SendType, SliceStats};
+pub use control::CONTROL_CHANNEL_ID;
+pub use error::{
+    ChannelError, ClientNotFound, ConfigError, ConnectionDeniedReason, DisconnectReason, InvalidChannelId, RpcError, SendError,
+};
+pub use lockstep::{LockstepChannel, LockstepRelay, LockstepTick, LOCKSTEP_MAX_TICKS_AHEAD};
+pub use middleware::{Middleware, MiddlewareAction};
+pub use observer::PacketDirection;
+pub use packet::WIRE_FORMAT_VERSION;
+#[cfg(feature = "pcap")]
+pub use pcap::{PcapWriter, PCAP_LINK_TYPE};
+pub use rate_limiter::{RateLimit, RateLimiter};
+pub use remote_connection::{ConnectionConfig, ConnectionConfigBuilder, NetworkInfo, RenetClient, RenetConnectionStatus};
+pub use rpc::{RpcCall, RpcClient, RpcMessage, RpcMethod, RpcServer};
+pub use server::{RenetServer, ServerEvent, ServerTiming, SnapshotRatePolicy, StaleConnectionPolicy};
+pub use shard::{RenetShard, ShardRouter, ShardedRenetServer};
+pub use telemetry::{TelemetryClient, TelemetryServer};
 
 pub use bytes::Bytes;
 
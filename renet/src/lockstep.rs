@@ -0,0 +1,369 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::{ClientId, RenetClient, RenetServer};
+
+/// The default number of ticks ahead of the next tick that inputs are accepted,
+/// see [`LockstepChannel::set_max_ticks_ahead`] and [`LockstepRelay::set_max_ticks_ahead`].
+pub const LOCKSTEP_MAX_TICKS_AHEAD: u64 = 256;
+
+/// Inputs from all the expected peers for a tick.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockstepTick {
+    pub tick: u64,
+    /// Inputs ordered by the client id, so all peers process them in the same order.
+    pub inputs: Vec<(ClientId, Bytes)>,
+}
+
+/// Helper for deterministic lockstep games using the server as a relay.
+///
+/// Each client sends its input for a tick with [`LockstepChannel::send_input`], the server relays the inputs
+/// to all clients with [`LockstepRelay::relay`], and each client waits until the inputs from all peers have
+/// arrived before simulating the tick. The channel used must be reliable ordered in both directions.
+///
+/// # Usage
+/// ```
+/// # use renet::{ConnectionConfig, DefaultChannel, LockstepChannel, RenetClient};
+/// # let mut client = RenetClient::new(ConnectionConfig::default());
+/// # let duration = std::time::Duration::from_millis(16);
+/// let mut lockstep = LockstepChannel::new(DefaultChannel::ReliableOrdered, [0, 1]);
+/// lockstep.send_input(&mut client, 0, vec![1, 2, 3]);
+///
+/// lockstep.update(duration, &mut client);
+/// while let Some(tick) = lockstep.next_ready_tick() {
+///     // simulate tick with the inputs
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LockstepChannel {
+    channel_id: u8,
+    peers: BTreeSet<ClientId>,
+    ticks: BTreeMap<u64, BTreeMap<ClientId, Bytes>>,
+    next_tick: u64,
+    max_ticks_ahead: u64,
+    stalled_for: Duration,
+}
+
+impl LockstepChannel {
+    pub fn new<I: Into<u8>>(channel_id: I, peers: impl IntoIterator<Item = ClientId>) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            peers: peers.into_iter().collect(),
+            ticks: BTreeMap::new(),
+            next_tick: 0,
+            max_ticks_ahead: LOCKSTEP_MAX_TICKS_AHEAD,
+            stalled_for: Duration::ZERO,
+        }
+    }
+
+    /// Update how many ticks ahead of the next tick inputs are accepted, inputs further ahead are discarded.
+    /// The default is [`LOCKSTEP_MAX_TICKS_AHEAD`].
+    pub fn set_max_ticks_ahead(&mut self, max_ticks_ahead: u64) {
+        self.max_ticks_ahead = max_ticks_ahead;
+    }
+
+    /// Adds a peer that must send its inputs for the next ticks.
+    pub fn add_peer(&mut self, client_id: ClientId) {
+        self.peers.insert(client_id);
+    }
+
+    /// Removes a peer, the next ticks no longer wait for its inputs.
+    pub fn remove_peer(&mut self, client_id: ClientId) {
+        self.peers.remove(&client_id);
+    }
+
+    /// Sends the local input for a tick to the server.
+    pub fn send_input<B: Into<Bytes>>(&self, client: &mut RenetClient, tick: u64, input: B) {
+        let input = input.into();
+        let mut message = Vec::with_capacity(8 + input.len());
+        message.extend_from_slice(&tick.to_le_bytes());
+        message.extend_from_slice(&input);
+        client.send_message(self.channel_id, message);
+    }
+
+    /// Advances the stall timer and receives the inputs relayed by the server.
+    pub fn update(&mut self, duration: Duration, client: &mut RenetClient) {
+        if !self.is_next_tick_ready() {
+            self.stalled_for += duration;
+        }
+
+        while let Some(message) = client.receive_message(self.channel_id) {
+            let Some((client_id, tick, input)) = decode_relayed_input(message) else {
+                log::warn!("Received invalid lockstep input in channel {}", self.channel_id);
+                continue;
+            };
+            self.receive_input(tick, client_id, input);
+        }
+    }
+
+    /// Adds an input received from a peer, inputs for ticks already processed,
+    /// or too far ahead of the next tick, are discarded.
+    pub fn receive_input(&mut self, tick: u64, client_id: ClientId, input: Bytes) {
+        if tick < self.next_tick {
+            return;
+        }
+
+        if tick - self.next_tick > self.max_ticks_ahead {
+            log::warn!(
+                "Discarded lockstep input from client {client_id} for tick {tick}, more than {} ticks ahead of tick {}",
+                self.max_ticks_ahead,
+                self.next_tick
+            );
+            return;
+        }
+
+        self.ticks.entry(tick).or_default().insert(client_id, input);
+    }
+
+    /// Returns the ticks, in order, that have the inputs from all peers.
+    pub fn ready_ticks(&self) -> Vec<u64> {
+        let mut ready_ticks = vec![];
+        let mut tick = self.next_tick;
+        while self.is_tick_ready(tick) {
+            ready_ticks.push(tick);
+            tick += 1;
+        }
+
+        ready_ticks
+    }
+
+    /// Returns the inputs for the next tick if all peers inputs have arrived.
+    pub fn next_ready_tick(&mut self) -> Option<LockstepTick> {
+        if !self.is_next_tick_ready() {
+            return None;
+        }
+
+        let tick = self.next_tick;
+        let inputs = self.ticks.remove(&tick).unwrap_or_default();
+        self.next_tick += 1;
+        self.stalled_for = Duration::ZERO;
+
+        Some(LockstepTick {
+            tick,
+            inputs: inputs.into_iter().filter(|(client_id, _)| self.peers.contains(client_id)).collect(),
+        })
+    }
+
+    /// Returns the next tick to be processed.
+    pub fn next_tick(&self) -> u64 {
+        self.next_tick
+    }
+
+    /// Returns the peers that have not sent their input for the next tick yet.
+    pub fn missing_peers(&self) -> Vec<ClientId> {
+        let inputs = self.ticks.get(&self.next_tick);
+        self.peers
+            .iter()
+            .filter(|client_id| !inputs.is_some_and(|inputs| inputs.contains_key(client_id)))
+            .copied()
+            .collect()
+    }
+
+    /// Returns how long the next tick has been waiting for inputs.
+    /// Can be used to show a waiting screen or to drop unresponsive peers.
+    pub fn stalled_for(&self) -> Duration {
+        self.stalled_for
+    }
+
+    fn is_next_tick_ready(&self) -> bool {
+        self.is_tick_ready(self.next_tick)
+    }
+
+    fn is_tick_ready(&self, tick: u64) -> bool {
+        let Some(inputs) = self.ticks.get(&tick) else {
+            return false;
+        };
+
+        self.peers.iter().all(|client_id| inputs.contains_key(client_id))
+    }
+}
+
+/// Relays the lockstep inputs received from each client to all clients.
+/// See [`LockstepChannel`].
+///
+/// Inputs more ticks ahead than the slowest client are not relayed, see [`LockstepRelay::set_max_ticks_ahead`].
+#[derive(Debug)]
+pub struct LockstepRelay {
+    channel_id: u8,
+    max_ticks_ahead: u64,
+    // Next tick expected from each client that sent inputs
+    next_ticks: BTreeMap<ClientId, u64>,
+}
+
+impl LockstepRelay {
+    pub fn new<I: Into<u8>>(channel_id: I) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            max_ticks_ahead: LOCKSTEP_MAX_TICKS_AHEAD,
+            next_ticks: BTreeMap::new(),
+        }
+    }
+
+    /// Update how many ticks ahead of the slowest client inputs are relayed, inputs further ahead are discarded.
+    /// The default is [`LOCKSTEP_MAX_TICKS_AHEAD`].
+    pub fn set_max_ticks_ahead(&mut self, max_ticks_ahead: u64) {
+        self.max_ticks_ahead = max_ticks_ahead;
+    }
+
+    /// Receives the inputs from all clients and broadcasts them with the client id that sent it.
+    /// Should be called every tick, before sending packets.
+    pub fn relay(&mut self, server: &mut RenetServer) {
+        let clients_id = server.clients_id();
+        self.next_ticks.retain(|client_id, _| clients_id.contains(client_id));
+
+        for client_id in clients_id {
+            while let Some(message) = server.receive_message(client_id, self.channel_id) {
+                if message.len() < 8 {
+                    log::warn!("Received invalid lockstep input from client {client_id}");
+                    continue;
+                }
+
+                let tick = u64::from_le_bytes(message[..8].try_into().unwrap());
+                let slowest_tick = self.next_ticks.values().min().copied().unwrap_or(0);
+                if tick.saturating_sub(slowest_tick) > self.max_ticks_ahead {
+                    log::warn!(
+                        "Discarded lockstep input from client {client_id} for tick {tick}, more than {} ticks ahead of tick {slowest_tick}",
+                        self.max_ticks_ahead
+                    );
+                    continue;
+                }
+
+                let next_tick = self.next_ticks.entry(client_id).or_insert(0);
+                *next_tick = (*next_tick).max(tick.saturating_add(1));
+
+                let mut relayed = Vec::with_capacity(8 + message.len());
+                relayed.extend_from_slice(&client_id.to_le_bytes());
+                relayed.extend_from_slice(&message);
+                server.broadcast_message(self.channel_id, relayed);
+            }
+        }
+    }
+}
+
+fn decode_relayed_input(message: Bytes) -> Option<(ClientId, u64, Bytes)> {
+    if message.len() < 16 {
+        return None;
+    }
+
+    let client_id = ClientId::from_le_bytes(message[..8].try_into().unwrap());
+    let tick = u64::from_le_bytes(message[8..16].try_into().unwrap());
+    Some((client_id, tick, message.slice(16..)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ready_ticks() {
+        let mut lockstep = LockstepChannel::new(0, [0, 1]);
+        lockstep.receive_input(0, 0, Bytes::from_static(b"a"));
+        lockstep.receive_input(1, 0, Bytes::from_static(b"b"));
+        lockstep.receive_input(1, 1, Bytes::from_static(b"c"));
+        assert!(lockstep.ready_ticks().is_empty());
+        assert_eq!(lockstep.missing_peers(), vec![1]);
+        assert!(lockstep.next_ready_tick().is_none());
+
+        lockstep.receive_input(0, 1, Bytes::from_static(b"d"));
+        assert_eq!(lockstep.ready_ticks(), vec![0, 1]);
+
+        let tick = lockstep.next_ready_tick().unwrap();
+        assert_eq!(tick.tick, 0);
+        assert_eq!(tick.inputs, vec![(0, Bytes::from_static(b"a")), (1, Bytes::from_static(b"d"))]);
+        assert_eq!(lockstep.next_ready_tick().unwrap().tick, 1);
+        assert_eq!(lockstep.next_tick(), 2);
+
+        // Old inputs are discarded
+        lockstep.receive_input(0, 0, Bytes::from_static(b"a"));
+        assert!(lockstep.ticks.is_empty());
+    }
+
+    #[test]
+    fn remove_peer() {
+        let mut lockstep = LockstepChannel::new(0, [0, 1]);
+        lockstep.receive_input(0, 0, Bytes::from_static(b"a"));
+        assert!(lockstep.ready_ticks().is_empty());
+
+        lockstep.remove_peer(1);
+        assert_eq!(lockstep.ready_ticks(), vec![0]);
+    }
+
+    #[test]
+    fn relay() {
+        use crate::{ConnectionConfig, DefaultChannel};
+
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let mut relay = LockstepRelay::new(DefaultChannel::ReliableOrdered);
+        let mut clients: Vec<(ClientId, RenetClient, LockstepChannel)> = (0..2)
+            .map(|client_id| {
+                let client = server.new_local_client(client_id);
+                let lockstep = LockstepChannel::new(DefaultChannel::ReliableOrdered, [0, 1]);
+                (client_id, client, lockstep)
+            })
+            .collect();
+
+        for (client_id, client, lockstep) in clients.iter_mut() {
+            lockstep.send_input(client, 0, vec![*client_id as u8]);
+            server.process_local_client(*client_id, client).unwrap();
+        }
+
+        relay.relay(&mut server);
+
+        for (client_id, client, lockstep) in clients.iter_mut() {
+            server.process_local_client(*client_id, client).unwrap();
+            lockstep.update(Duration::from_millis(16), client);
+            let tick = lockstep.next_ready_tick().unwrap();
+            assert_eq!(tick.inputs, vec![(0, Bytes::from(vec![0])), (1, Bytes::from(vec![1]))]);
+            assert_eq!(lockstep.stalled_for(), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn max_ticks_ahead() {
+        let mut lockstep = LockstepChannel::new(0, [0]);
+        lockstep.set_max_ticks_ahead(2);
+        lockstep.receive_input(u64::MAX, 0, Bytes::from_static(b"a"));
+        lockstep.receive_input(3, 0, Bytes::from_static(b"a"));
+        assert!(lockstep.ticks.is_empty());
+
+        lockstep.receive_input(2, 0, Bytes::from_static(b"a"));
+        assert_eq!(lockstep.ticks.len(), 1);
+    }
+
+    #[test]
+    fn relay_max_ticks_ahead() {
+        use crate::{ConnectionConfig, DefaultChannel};
+
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let mut relay = LockstepRelay::new(DefaultChannel::ReliableOrdered);
+        relay.set_max_ticks_ahead(2);
+        let mut client = server.new_local_client(0);
+        let mut lockstep = LockstepChannel::new(DefaultChannel::ReliableOrdered, [0]);
+
+        for tick in [u64::MAX, 3, 0, 1, 3] {
+            lockstep.send_input(&mut client, tick, vec![0]);
+        }
+        server.process_local_client(0, &mut client).unwrap();
+        relay.relay(&mut server);
+        server.process_local_client(0, &mut client).unwrap();
+        lockstep.update(Duration::ZERO, &mut client);
+
+        // Only inputs up to 2 ticks ahead of the next tick of the slowest client are relayed
+        assert_eq!(lockstep.ticks.keys().copied().collect::<Vec<_>>(), vec![0, 1, 3]);
+    }
+
+    #[test]
+    fn stall() {
+        let mut client = RenetClient::new(crate::ConnectionConfig::default());
+        let mut lockstep = LockstepChannel::new(0, [0]);
+        lockstep.update(Duration::from_millis(100), &mut client);
+        lockstep.update(Duration::from_millis(100), &mut client);
+        assert_eq!(lockstep.stalled_for(), Duration::from_millis(200));
+
+        lockstep.receive_input(0, 0, Bytes::new());
+        lockstep.next_ready_tick().unwrap();
+        assert_eq!(lockstep.stalled_for(), Duration::ZERO);
+    }
+}
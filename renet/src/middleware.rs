@@ -0,0 +1,118 @@
+use std::fmt;
+
+use bytes::Bytes;
+
+/// Whether a message inspected by a [`Middleware`] is kept or dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiddlewareAction {
+    Keep,
+    Drop,
+}
+
+/// Hooks called for every message sent or received by a connection, they can inspect, modify or drop the messages.
+/// Useful for metrics, anti-cheat sampling or experimenting with the message encoding, without changing the connection.
+///
+/// Middlewares are added with [`RenetClient::add_middleware`](crate::RenetClient::add_middleware) or
+/// [`RenetServer::add_middleware`](crate::RenetServer::add_middleware) for each connection,
+/// and are called in the order they were added. When a middleware drops a message, the next ones are not called.
+///
+/// # Usage
+/// ```
+/// # use renet::{Bytes, ConnectionConfig, Middleware, MiddlewareAction, RenetClient};
+/// struct MaxMessageSize(usize);
+///
+/// impl Middleware for MaxMessageSize {
+///     fn on_receive(&mut self, channel_id: u8, message: &mut Bytes) -> MiddlewareAction {
+///         if message.len() > self.0 {
+///             return MiddlewareAction::Drop;
+///         }
+///         MiddlewareAction::Keep
+///     }
+/// }
+///
+/// let mut client = RenetClient::new(ConnectionConfig::default());
+/// client.add_middleware(MaxMessageSize(1024));
+/// ```
+pub trait Middleware: Send + Sync {
+    /// Called when a message is sent, before it's queued in the channel.
+    fn on_send(&mut self, _channel_id: u8, _message: &mut Bytes) -> MiddlewareAction {
+        MiddlewareAction::Keep
+    }
+
+    /// Called when a message is received, before it's returned to the application.
+    fn on_receive(&mut self, _channel_id: u8, _message: &mut Bytes) -> MiddlewareAction {
+        MiddlewareAction::Keep
+    }
+}
+
+impl fmt::Debug for dyn Middleware {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Middleware")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConnectionConfig, DefaultChannel, RenetServer};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    struct DropEmpty;
+
+    impl Middleware for DropEmpty {
+        fn on_send(&mut self, _channel_id: u8, message: &mut Bytes) -> MiddlewareAction {
+            if message.is_empty() {
+                return MiddlewareAction::Drop;
+            }
+            MiddlewareAction::Keep
+        }
+    }
+
+    struct Reverse;
+
+    impl Middleware for Reverse {
+        fn on_send(&mut self, _channel_id: u8, message: &mut Bytes) -> MiddlewareAction {
+            *message = message.iter().rev().copied().collect();
+            MiddlewareAction::Keep
+        }
+
+        fn on_receive(&mut self, _channel_id: u8, message: &mut Bytes) -> MiddlewareAction {
+            *message = message.iter().rev().copied().collect();
+            MiddlewareAction::Keep
+        }
+    }
+
+    struct CountReceived(Arc<AtomicUsize>);
+
+    impl Middleware for CountReceived {
+        fn on_receive(&mut self, _channel_id: u8, _message: &mut Bytes) -> MiddlewareAction {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            MiddlewareAction::Keep
+        }
+    }
+
+    #[test]
+    fn middlewares() {
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let mut client = server.new_local_client(0);
+        let received = Arc::new(AtomicUsize::new(0));
+
+        client.add_middleware(DropEmpty);
+        client.add_middleware(Reverse);
+        server.add_middleware(0, Reverse).unwrap();
+        server.add_middleware(0, CountReceived(received.clone())).unwrap();
+
+        client.send_message(DefaultChannel::ReliableOrdered, vec![]);
+        client.send_message(DefaultChannel::ReliableOrdered, vec![1, 2, 3]);
+        server.process_local_client(0, &mut client).unwrap();
+
+        assert_eq!(server.receive_message(0, DefaultChannel::ReliableOrdered).unwrap(), vec![1, 2, 3]);
+        assert!(server.receive_message(0, DefaultChannel::ReliableOrdered).is_none());
+        assert_eq!(received.load(Ordering::Relaxed), 1);
+
+        assert!(server.add_middleware(1, DropEmpty).is_err());
+    }
+}
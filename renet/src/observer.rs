@@ -0,0 +1,62 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+};
+
+use crate::ClientId;
+
+/// Direction of a packet between the server and a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketDirection {
+    ClientToServer = 0,
+    ServerToClient = 1,
+}
+
+type ObserverFn = Box<dyn FnMut(PacketDirection, ClientId, &[u8]) + Send + Sync>;
+
+/// Calls a function with a random sample of the packets, see [`RenetServer::set_packet_observer`](crate::RenetServer::set_packet_observer).
+pub(crate) struct PacketObserver {
+    sample_rate: f64,
+    // State of the xorshift generator used to sample the packets
+    rng_state: u64,
+    observer: ObserverFn,
+}
+
+impl PacketObserver {
+    pub fn new(sample_rate: f64, observer: ObserverFn) -> Self {
+        // Random seed without depending on a random crate, the state must not be zero
+        let seed = RandomState::new().build_hasher().finish() | 1;
+
+        Self {
+            sample_rate: sample_rate.clamp(0.0, 1.0),
+            rng_state: seed,
+            observer,
+        }
+    }
+
+    pub fn observe(&mut self, direction: PacketDirection, client_id: ClientId, packet: &[u8]) {
+        if self.sample() {
+            (self.observer)(direction, client_id, packet);
+        }
+    }
+
+    fn sample(&mut self) -> bool {
+        if self.sample_rate >= 1.0 {
+            return true;
+        }
+
+        self.rng_state ^= self.rng_state << 13;
+        self.rng_state ^= self.rng_state >> 7;
+        self.rng_state ^= self.rng_state << 17;
+        // Uniform value in [0, 1) from the 53 most significant bits
+        let value = (self.rng_state >> 11) as f64 / (1u64 << 53) as f64;
+        value < self.sample_rate
+    }
+}
+
+impl fmt::Debug for PacketObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketObserver").field("sample_rate", &self.sample_rate).finish()
+    }
+}
@@ -1,3 +1,27 @@
+//! Serialization of renet packets, available with the `low-level` feature.
+//!
+//! The format is described in the [WIRE_FORMAT.md](https://github.com/lucaspoffo/renet/blob/master/renet/WIRE_FORMAT.md) document,
+//! the `test_vectors/packets.txt` file has packets serialized by this module, to validate other implementations of the format.
+//!
+// The module is only public with the `low-level` feature, so is the usage example
+#![cfg_attr(
+    feature = "low-level",
+    doc = r#"
+# Usage
+```
+# use renet::packet::{Packet, MAX_PACKET_SIZE};
+let packet = Packet::SmallUnreliable {
+    sequence: 0,
+    channel_id: 1,
+    messages: vec![b"hello".to_vec().into()],
+};
+
+let mut buffer = [0u8; MAX_PACKET_SIZE];
+let len = packet.encode(&mut buffer).unwrap();
+assert_eq!(Packet::decode(&buffer[..len]).unwrap(), packet);
+```
+"#
+)]
 use bytes::Bytes;
 use std::{fmt, ops::Range};
 
@@ -6,6 +30,28 @@ pub type Payload = Vec<u8>;
 // Sliced messages are split into SLICE_SIZE bytes chunks
 pub const SLICE_SIZE: usize = 1200;
 
+// Packets are never bigger than MAX_PACKET_SIZE bytes, transports can set a smaller limit
+pub const MAX_PACKET_SIZE: usize = 1400;
+
+// Largest packet overhead besides the payload: the header of slice packets,
+// or the header of small messages packets with the message id and length of a single message
+pub const MAX_PACKET_OVERHEAD: usize = 29;
+
+/// Version of the packets wire format, incremented on every incompatible change of the format.
+/// Packets from a connection with another version are rejected with [`DisconnectReason::WireFormatMismatch`].
+///
+/// [`DisconnectReason::WireFormatMismatch`]: crate::DisconnectReason::WireFormatMismatch
+pub const WIRE_FORMAT_VERSION: u8 = 1;
+
+// The version is written with the most significant bit set, renet versions before the versioned
+// format read it as an invalid packet type, instead of misinterpreting the packet.
+const VERSION_MARKER: u8 = 0x80;
+
+// Sequences and message ids are serialized as varints, so the largest value allowed is 2^62 - 1.
+// At one million packets per second this takes more than 100 thousand years, so they never wrap around,
+// if it's ever reached the packets fail to serialize and the connection is disconnected.
+pub const MAX_SEQUENCE: u64 = (1 << 62) - 1;
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Slice {
     pub message_id: u64,
@@ -34,6 +80,12 @@ pub enum Packet {
         channel_id: u8,
         slice: Slice,
     },
+    // Small messages in a redundant channel, each packet also contains the last messages sent
+    SmallRedundant {
+        sequence: u64,
+        channel_id: u8,
+        messages: Vec<(u64, Bytes)>,
+    },
     // A big reliable messages is sliced in multiples slice packets
     ReliableSlice {
         sequence: u64,
@@ -42,9 +94,11 @@ pub enum Packet {
     },
     // Contains the packets that were acked
     // Acks are saved in multiples ranges, all values in the ranges are considered acked.
+    // Nack ranges contains the packets that are newly considered lost, they are always smaller than the largest ack.
     Ack {
         sequence: u64,
         ack_ranges: Vec<Range<u64>>,
+        nack_ranges: Vec<Range<u64>>,
     },
 }
 
@@ -56,6 +110,10 @@ pub enum SerializationError {
     EmptySlice,
     InvalidAckRange,
     InvalidPacketType,
+    ValueAboveLimit,
+    /// The packet was written with another wire format version,
+    /// 0 is for packets from renet versions before the versioned format.
+    InvalidWireFormatVersion(u8),
 }
 
 impl std::error::Error for SerializationError {}
@@ -71,6 +129,8 @@ impl fmt::Display for SerializationError {
             InvalidPacketType => write!(fmt, "invalid packet type"),
             SliceSizeAboveLimit => write!(fmt, "invalid slice size, it's above the limit of {} bytes", SLICE_SIZE),
             EmptySlice => write!(fmt, "invalid slice, slices cannot be empty"),
+            ValueAboveLimit => write!(fmt, "value is above the limit of {} that can be serialized", MAX_SEQUENCE),
+            InvalidWireFormatVersion(version) => write!(fmt, "invalid wire format version {version}, expected {WIRE_FORMAT_VERSION}"),
         }
     }
 }
@@ -88,26 +148,70 @@ impl Packet {
             | Packet::SmallUnreliable { sequence, .. }
             | Packet::UnreliableSlice { sequence, .. }
             | Packet::ReliableSlice { sequence, .. }
+            | Packet::SmallRedundant { sequence, .. }
             | Packet::Ack { sequence, .. } => *sequence,
         }
     }
 
+    /// Returns the channel of the messages in the packet, or None for acks.
+    pub fn channel_id(&self) -> Option<u8> {
+        match self {
+            Packet::SmallReliable { channel_id, .. }
+            | Packet::SmallUnreliable { channel_id, .. }
+            | Packet::UnreliableSlice { channel_id, .. }
+            | Packet::ReliableSlice { channel_id, .. }
+            | Packet::SmallRedundant { channel_id, .. } => Some(*channel_id),
+            Packet::Ack { .. } => None,
+        }
+    }
+
+    /// Returns the number of message bytes contained in the packet.
+    pub fn payload_len(&self) -> usize {
+        match self {
+            Packet::SmallReliable { messages, .. } | Packet::SmallRedundant { messages, .. } => {
+                messages.iter().map(|(_, message)| message.len()).sum()
+            }
+            Packet::SmallUnreliable { messages, .. } => messages.iter().map(|message| message.len()).sum(),
+            Packet::ReliableSlice { slice, .. } | Packet::UnreliableSlice { slice, .. } => slice.payload.len(),
+            Packet::Ack { .. } => 0,
+        }
+    }
+
+    /// Writes the packet in the buffer, returns the number of bytes written.
+    pub fn encode(&self, buffer: &mut [u8]) -> Result<usize, SerializationError> {
+        let mut b = octets::OctetsMut::with_slice(buffer);
+        self.to_bytes(&mut b)
+    }
+
+    /// Reads a packet from the buffer.
+    pub fn decode(buffer: &[u8]) -> Result<Packet, SerializationError> {
+        let mut b = octets::Octets::with_slice(buffer);
+        Packet::from_bytes(&mut b)
+    }
+
     pub fn to_bytes(&self, b: &mut octets::OctetsMut) -> Result<usize, SerializationError> {
         let before = b.cap();
+        b.put_u8(VERSION_MARKER | WIRE_FORMAT_VERSION)?;
 
         match self {
             Packet::SmallReliable {
                 sequence,
                 channel_id,
                 messages,
+            }
+            | Packet::SmallRedundant {
+                sequence,
+                channel_id,
+                messages,
             } => {
-                b.put_u8(0)?;
-                b.put_varint(*sequence)?;
+                let packet_type = if matches!(self, Packet::SmallReliable { .. }) { 0 } else { 5 };
+                b.put_u8(packet_type)?;
+                put_varint(b, *sequence)?;
                 b.put_u8(*channel_id)?;
                 b.put_u16(messages.len() as u16)?;
                 for (message_id, message) in messages {
-                    b.put_varint(*message_id)?;
-                    b.put_varint(message.len() as u64)?;
+                    put_varint(b, *message_id)?;
+                    put_varint(b, message.len() as u64)?;
                     b.put_bytes(message)?;
                 }
             }
@@ -117,11 +221,11 @@ impl Packet {
                 messages,
             } => {
                 b.put_u8(1)?;
-                b.put_varint(*sequence)?;
+                put_varint(b, *sequence)?;
                 b.put_u8(*channel_id)?;
                 b.put_u16(messages.len() as u16)?;
                 for message in messages {
-                    b.put_varint(message.len() as u64)?;
+                    put_varint(b, message.len() as u64)?;
                     b.put_bytes(message)?;
                 }
             }
@@ -131,12 +235,12 @@ impl Packet {
                 slice,
             } => {
                 b.put_u8(2)?;
-                b.put_varint(*sequence)?;
+                put_varint(b, *sequence)?;
                 b.put_u8(*channel_id)?;
-                b.put_varint(slice.message_id)?;
-                b.put_varint(slice.slice_index as u64)?;
-                b.put_varint(slice.num_slices as u64)?;
-                b.put_varint(slice.payload.len() as u64)?;
+                put_varint(b, slice.message_id)?;
+                put_varint(b, slice.slice_index as u64)?;
+                put_varint(b, slice.num_slices as u64)?;
+                put_varint(b, slice.payload.len() as u64)?;
                 b.put_bytes(&slice.payload)?;
             }
             Packet::UnreliableSlice {
@@ -145,17 +249,21 @@ impl Packet {
                 slice,
             } => {
                 b.put_u8(3)?;
-                b.put_varint(*sequence)?;
+                put_varint(b, *sequence)?;
                 b.put_u8(*channel_id)?;
-                b.put_varint(slice.message_id)?;
-                b.put_varint(slice.slice_index as u64)?;
-                b.put_varint(slice.num_slices as u64)?;
-                b.put_varint(slice.payload.len() as u64)?;
+                put_varint(b, slice.message_id)?;
+                put_varint(b, slice.slice_index as u64)?;
+                put_varint(b, slice.num_slices as u64)?;
+                put_varint(b, slice.payload.len() as u64)?;
                 b.put_bytes(&slice.payload)?;
             }
-            Packet::Ack { sequence, ack_ranges } => {
+            Packet::Ack {
+                sequence,
+                ack_ranges,
+                nack_ranges,
+            } => {
                 b.put_u8(4)?;
-                b.put_varint(*sequence)?;
+                put_varint(b, *sequence)?;
 
                 // Consider this ranges:
                 // [20010..20020   ,  20035..20040]
@@ -181,11 +289,11 @@ impl Packet {
                 let last = it.next().unwrap();
                 let last_range_size = (last.end - 1) - last.start;
 
-                b.put_varint(last.end - 1)?;
-                b.put_varint(last_range_size)?;
+                put_varint(b, last.end - 1)?;
+                put_varint(b, last_range_size)?;
 
                 // Write the number of remaining ranges
-                b.put_varint(it.len() as u64)?;
+                put_varint(b, it.len() as u64)?;
 
                 let mut previous_range_start = last.start;
                 // For each subsequent range:
@@ -194,11 +302,19 @@ impl Packet {
                     let gap = previous_range_start - range.end - 1;
                     let range_size = (range.end - 1) - range.start;
 
-                    b.put_varint(gap)?;
-                    b.put_varint(range_size)?;
+                    put_varint(b, gap)?;
+                    put_varint(b, range_size)?;
 
                     previous_range_start = range.start;
                 }
+
+                // Nack ranges are serialized based on the largest ack: distance to the start and size of the range
+                let largest_ack = last.end - 1;
+                put_varint(b, nack_ranges.len() as u64)?;
+                for range in nack_ranges.iter() {
+                    put_varint(b, largest_ack - range.start)?;
+                    put_varint(b, range.end - range.start)?;
+                }
             }
         }
 
@@ -206,10 +322,16 @@ impl Packet {
     }
 
     pub fn from_bytes(b: &mut octets::Octets) -> Result<Packet, SerializationError> {
+        let version = b.get_u8()?;
+        if version != VERSION_MARKER | WIRE_FORMAT_VERSION {
+            let version = if version & VERSION_MARKER == 0 { 0 } else { version & !VERSION_MARKER };
+            return Err(SerializationError::InvalidWireFormatVersion(version));
+        }
+
         let packet_type = b.get_u8()?;
         match packet_type {
-            0 => {
-                // SmallReliable
+            0 | 5 => {
+                // SmallReliable or SmallRedundant
                 let sequence = b.get_varint()?;
                 let channel_id = b.get_u8()?;
                 let messages_len = b.get_u16()?;
@@ -221,11 +343,19 @@ impl Packet {
                     messages.push((message_id, payload.to_vec().into()));
                 }
 
-                Ok(Packet::SmallReliable {
-                    sequence,
-                    channel_id,
-                    messages,
-                })
+                if packet_type == 0 {
+                    Ok(Packet::SmallReliable {
+                        sequence,
+                        channel_id,
+                        messages,
+                    })
+                } else {
+                    Ok(Packet::SmallRedundant {
+                        sequence,
+                        channel_id,
+                        messages,
+                    })
+                }
             }
             1 => {
                 // SmallUnreliable
@@ -344,13 +474,40 @@ impl Packet {
 
                 ack_ranges.reverse();
 
-                Ok(Packet::Ack { sequence, ack_ranges })
+                let num_nack_ranges = b.get_varint()?;
+                let mut nack_ranges: Vec<Range<u64>> = Vec::new();
+                for _ in 0..num_nack_ranges {
+                    let distance = b.get_varint()?;
+                    let range_size = b.get_varint()?;
+                    if distance > first_range_end || range_size == 0 || range_size > distance {
+                        return Err(SerializationError::InvalidAckRange);
+                    }
+
+                    let range_start = first_range_end - distance;
+                    nack_ranges.push(range_start..range_start + range_size);
+                }
+
+                Ok(Packet::Ack {
+                    sequence,
+                    ack_ranges,
+                    nack_ranges,
+                })
             }
             _ => Err(SerializationError::InvalidPacketType),
         }
     }
 }
 
+// Octets panics when serializing a varint above MAX_SEQUENCE
+fn put_varint(b: &mut octets::OctetsMut, value: u64) -> Result<(), SerializationError> {
+    if value > MAX_SEQUENCE {
+        return Err(SerializationError::ValueAboveLimit);
+    }
+
+    b.put_varint(value)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -372,6 +529,24 @@ mod tests {
         assert_eq!(packet, recv_packet);
     }
 
+    #[test]
+    fn serialize_small_redundant_packet() {
+        let mut buffer = [0u8; 1300];
+
+        let packet = Packet::SmallRedundant {
+            sequence: 0,
+            channel_id: 0,
+            messages: vec![(0, vec![0, 0, 0].into()), (1, vec![1, 1, 1].into())],
+        };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        packet.to_bytes(&mut b).unwrap();
+
+        let mut b = octets::Octets::with_slice(&buffer);
+        let recv_packet = Packet::from_bytes(&mut b).unwrap();
+        assert_eq!(packet, recv_packet);
+    }
+
     #[test]
     fn serialize_small_unreliable_packet() {
         let mut buffer = [0u8; 1300];
@@ -442,6 +617,70 @@ mod tests {
         let packet = Packet::Ack {
             sequence: 0,
             ack_ranges: vec![3..7, 10..20, 30..100],
+            nack_ranges: vec![],
+        };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        packet.to_bytes(&mut b).unwrap();
+
+        let mut b = octets::Octets::with_slice(&buffer);
+        let recv_packet = Packet::from_bytes(&mut b).unwrap();
+        assert_eq!(packet, recv_packet);
+    }
+
+    #[test]
+    fn serialize_ack_packet_extreme_sequences() {
+        let mut buffer = [0u8; 1300];
+
+        let packet = Packet::Ack {
+            sequence: MAX_SEQUENCE,
+            ack_ranges: vec![0..1, 5..6, MAX_SEQUENCE - 10..MAX_SEQUENCE + 1],
+            nack_ranges: vec![1..5, 6..MAX_SEQUENCE - 10],
+        };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        packet.to_bytes(&mut b).unwrap();
+
+        let mut b = octets::Octets::with_slice(&buffer);
+        let recv_packet = Packet::from_bytes(&mut b).unwrap();
+        assert_eq!(packet, recv_packet);
+    }
+
+    #[test]
+    fn serialize_sequence_above_max() {
+        let mut buffer = [0u8; 1300];
+        let packets = [
+            Packet::SmallUnreliable {
+                sequence: MAX_SEQUENCE + 1,
+                channel_id: 0,
+                messages: vec![],
+            },
+            Packet::SmallReliable {
+                sequence: 0,
+                channel_id: 0,
+                messages: vec![(u64::MAX, Bytes::new())],
+            },
+            Packet::Ack {
+                sequence: 0,
+                ack_ranges: vec![0..1, 5..u64::MAX],
+                nack_ranges: vec![],
+            },
+        ];
+
+        for packet in packets {
+            let mut b = octets::OctetsMut::with_slice(&mut buffer);
+            assert!(packet.to_bytes(&mut b).is_err());
+        }
+    }
+
+    #[test]
+    fn serialize_ack_packet_with_nacks() {
+        let mut buffer = [0u8; 1300];
+
+        let packet = Packet::Ack {
+            sequence: 0,
+            ack_ranges: vec![3..7, 10..20, 30..100],
+            nack_ranges: vec![7..10, 20..30],
         };
 
         let mut b = octets::OctetsMut::with_slice(&mut buffer);
@@ -451,4 +690,127 @@ mod tests {
         let recv_packet = Packet::from_bytes(&mut b).unwrap();
         assert_eq!(packet, recv_packet);
     }
+
+    #[test]
+    fn deserialize_other_wire_format_version() {
+        let mut buffer = [0u8; 1300];
+        let packet = Packet::SmallUnreliable {
+            sequence: 0,
+            channel_id: 0,
+            messages: vec![vec![1, 2, 3].into()],
+        };
+
+        let mut b = octets::OctetsMut::with_slice(&mut buffer);
+        let len = packet.to_bytes(&mut b).unwrap();
+
+        buffer[0] = VERSION_MARKER | (WIRE_FORMAT_VERSION + 1);
+        let mut b = octets::Octets::with_slice(&buffer[..len]);
+        assert_eq!(
+            Packet::from_bytes(&mut b),
+            Err(SerializationError::InvalidWireFormatVersion(WIRE_FORMAT_VERSION + 1))
+        );
+
+        // Packets from renet versions before the versioned format start with the packet type
+        let mut b = octets::Octets::with_slice(&buffer[1..len]);
+        assert_eq!(Packet::from_bytes(&mut b), Err(SerializationError::InvalidWireFormatVersion(0)));
+    }
+
+    // Packets of the test vectors, in the same order as the test_vectors/packets.txt file
+    fn test_vector_packets() -> Vec<(&'static str, Packet)> {
+        vec![
+            (
+                "small_reliable",
+                Packet::SmallReliable {
+                    sequence: 1,
+                    channel_id: 0,
+                    messages: vec![(0, Bytes::from_static(b"hello")), (1, Bytes::from_static(b"world"))],
+                },
+            ),
+            (
+                "small_unreliable",
+                Packet::SmallUnreliable {
+                    sequence: 300,
+                    channel_id: 1,
+                    messages: vec![Bytes::from_static(&[1, 2, 3]), Bytes::new()],
+                },
+            ),
+            (
+                "small_redundant",
+                Packet::SmallRedundant {
+                    sequence: 70000,
+                    channel_id: 3,
+                    messages: vec![(41, Bytes::from_static(&[41])), (42, Bytes::from_static(&[42]))],
+                },
+            ),
+            (
+                "reliable_slice",
+                Packet::ReliableSlice {
+                    sequence: 2,
+                    channel_id: 0,
+                    slice: Slice {
+                        message_id: 5,
+                        slice_index: 1,
+                        num_slices: 3,
+                        payload: Bytes::from_static(&[0xAA; 16]),
+                    },
+                },
+            ),
+            (
+                "unreliable_slice",
+                Packet::UnreliableSlice {
+                    sequence: 3,
+                    channel_id: 1,
+                    slice: Slice {
+                        message_id: 0,
+                        slice_index: 0,
+                        num_slices: 2,
+                        payload: Bytes::from_static(&[0xBB; 16]),
+                    },
+                },
+            ),
+            (
+                "ack",
+                Packet::Ack {
+                    sequence: 4,
+                    ack_ranges: vec![20010..20020, 20035..20040],
+                    nack_ranges: vec![],
+                },
+            ),
+            (
+                "ack_with_nacks",
+                Packet::Ack {
+                    sequence: MAX_SEQUENCE,
+                    ack_ranges: vec![3..7, 10..20, 30..100],
+                    nack_ranges: vec![7..10, 20..30],
+                },
+            ),
+        ]
+    }
+
+    fn decode_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_vectors() {
+        let vectors = include_str!("../test_vectors/packets.txt");
+        let mut lines = vectors.lines().filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+        for (name, packet) in test_vector_packets() {
+            let line = lines.next().unwrap_or_else(|| panic!("missing test vector {name}"));
+            let (vector_name, hex) = line.split_once(' ').unwrap();
+            assert_eq!(vector_name, name);
+            let bytes = decode_hex(hex);
+
+            let mut buffer = [0u8; MAX_PACKET_SIZE];
+            let len = packet.encode(&mut buffer).unwrap();
+            assert_eq!(buffer[..len], bytes[..], "test vector {name}");
+            assert_eq!(Packet::decode(&bytes).unwrap(), packet, "test vector {name}");
+        }
+
+        assert_eq!(lines.next(), None, "test vector without packet");
+    }
 }
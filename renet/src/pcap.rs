@@ -0,0 +1,206 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use crate::{ClientId, PacketDirection};
+
+/// Link-type of the captures, `LINKTYPE_USER0` is reserved for private use.
+/// The renet Wireshark dissector is registered for this link-type.
+pub const PCAP_LINK_TYPE: u32 = 147;
+
+// Direction and client id written before each packet
+const RECORD_HEADER_BYTES: usize = 1 + 8;
+
+/// Writes renet packets into a pcap file, to debug protocol issues in Wireshark with the dissector
+/// in `renet/wireshark/renet.lua`. The captured packets are the renet packets before being encrypted
+/// by the transport, each one prefixed with its direction and the client id, see `renet/WIRE_FORMAT.md`.
+///
+/// Transports capture the packets when a writer is set, for example with
+/// `NetcodeServerTransport::set_packet_capture` from the `renet_netcode` crate.
+///
+/// # Usage
+/// ```no_run
+/// # use renet::{PacketDirection, PcapWriter};
+/// # let packet: Vec<u8> = vec![];
+/// let mut pcap = PcapWriter::create("capture.pcap").unwrap();
+/// pcap.write_packet(PacketDirection::ClientToServer, 0, &packet).unwrap();
+/// ```
+pub struct PcapWriter {
+    writer: Box<dyn Write + Send + Sync>,
+}
+
+impl fmt::Debug for PcapWriter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PcapWriter").finish_non_exhaustive()
+    }
+}
+
+impl PcapWriter {
+    /// Creates the pcap file, replacing it if it already exists.
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::create(path)?;
+        Self::new(BufWriter::new(file))
+    }
+
+    /// Creates a pcap writer, writing the pcap header.
+    pub fn new<W: Write + Send + Sync + 'static>(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&0xa1b2c3d4u32.to_le_bytes())?; // Magic number, timestamps in microseconds
+        writer.write_all(&2u16.to_le_bytes())?; // Major version
+        writer.write_all(&4u16.to_le_bytes())?; // Minor version
+        writer.write_all(&0i32.to_le_bytes())?; // Timezone, always UTC
+        writer.write_all(&0u32.to_le_bytes())?; // Timestamps accuracy
+        writer.write_all(&65535u32.to_le_bytes())?; // Max length of the packets
+        writer.write_all(&PCAP_LINK_TYPE.to_le_bytes())?;
+
+        Ok(Self { writer: Box::new(writer) })
+    }
+
+    /// Writes a packet with the current system time.
+    pub fn write_packet(&mut self, direction: PacketDirection, client_id: ClientId, packet: &[u8]) -> io::Result<()> {
+        let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+        self.write_packet_at(timestamp, direction, client_id, packet)
+    }
+
+    /// Writes a packet with the timestamp, the time since the unix epoch.
+    pub fn write_packet_at(
+        &mut self,
+        timestamp: Duration,
+        direction: PacketDirection,
+        client_id: ClientId,
+        packet: &[u8],
+    ) -> io::Result<()> {
+        let len = (RECORD_HEADER_BYTES + packet.len()) as u32;
+        self.writer.write_all(&(timestamp.as_secs() as u32).to_le_bytes())?;
+        self.writer.write_all(&timestamp.subsec_micros().to_le_bytes())?;
+        self.writer.write_all(&len.to_le_bytes())?; // Captured length
+        self.writer.write_all(&len.to_le_bytes())?; // Original length
+
+        self.writer.write_all(&[direction as u8])?;
+        self.writer.write_all(&client_id.to_le_bytes())?;
+        self.writer.write_all(packet)?;
+
+        Ok(())
+    }
+
+    /// Flushes the buffered packets to the file, the packets are also flushed when the writer is dropped.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{Packet, Slice};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_packets() {
+        let buffer = SharedBuffer::default();
+        let mut pcap = PcapWriter::new(buffer.clone()).unwrap();
+        pcap.write_packet_at(Duration::from_micros(1_500_000), PacketDirection::ServerToClient, 7, &[1, 2, 3])
+            .unwrap();
+
+        let bytes = buffer.0.lock().unwrap();
+        assert_eq!(bytes.len(), 24 + 16 + RECORD_HEADER_BYTES + 3);
+        assert_eq!(bytes[..4], [0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(bytes[20..24], PCAP_LINK_TYPE.to_le_bytes());
+
+        let record = &bytes[24..];
+        assert_eq!(record[..4], 1u32.to_le_bytes());
+        assert_eq!(record[4..8], 500_000u32.to_le_bytes());
+        assert_eq!(record[8..12], 12u32.to_le_bytes());
+        assert_eq!(record[16], PacketDirection::ServerToClient as u8);
+        assert_eq!(record[17..25], 7u64.to_le_bytes());
+        assert_eq!(record[25..], [1, 2, 3]);
+    }
+
+    // The dissector is written by hand, check that its packet types match the packet definitions
+    #[test]
+    fn dissector_packet_types() {
+        let dissector = include_str!("../wireshark/renet.lua");
+        let slice = Slice {
+            message_id: 0,
+            slice_index: 0,
+            num_slices: 1,
+            payload: vec![0].into(),
+        };
+        let packets = [
+            (
+                "SmallReliable",
+                Packet::SmallReliable {
+                    sequence: 0,
+                    channel_id: 0,
+                    messages: vec![],
+                },
+            ),
+            (
+                "SmallUnreliable",
+                Packet::SmallUnreliable {
+                    sequence: 0,
+                    channel_id: 0,
+                    messages: vec![],
+                },
+            ),
+            (
+                "ReliableSlice",
+                Packet::ReliableSlice {
+                    sequence: 0,
+                    channel_id: 0,
+                    slice: slice.clone(),
+                },
+            ),
+            (
+                "UnreliableSlice",
+                Packet::UnreliableSlice {
+                    sequence: 0,
+                    channel_id: 0,
+                    slice,
+                },
+            ),
+            (
+                "Ack",
+                Packet::Ack {
+                    sequence: 0,
+                    ack_ranges: vec![0..2, 4..6],
+                    nack_ranges: vec![],
+                },
+            ),
+            (
+                "SmallRedundant",
+                Packet::SmallRedundant {
+                    sequence: 0,
+                    channel_id: 0,
+                    messages: vec![],
+                },
+            ),
+        ];
+
+        for (name, packet) in packets {
+            let mut buffer = [0u8; 64];
+            let mut b = octets::OctetsMut::with_slice(&mut buffer);
+            packet.to_bytes(&mut b).unwrap();
+
+            let entry = format!("[{}] = \"{name}\"", buffer[1]);
+            assert!(dissector.contains(&entry), "dissector is missing the packet type {entry}");
+        }
+    }
+}
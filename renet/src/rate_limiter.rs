@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::ClientId;
+
+/// Maximum rate of messages allowed in a channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// Messages per second allowed in the long run.
+    pub messages_per_second: f64,
+    /// Maximum number of messages allowed at once, after the client was idle.
+    pub burst: u32,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Duration,
+    dropped: u64,
+}
+
+/// Limits the rate of messages that each client can send in each channel, using a token bucket.
+/// Useful to throttle chat spam and input flooding.
+///
+/// Can be used standalone with [`RateLimiter::check`], or set in the server with
+/// [`RenetServer::set_rate_limiter`](crate::RenetServer::set_rate_limiter), so messages above the limit
+/// are automatically dropped when receiving them.
+///
+/// # Usage
+/// ```
+/// # use renet::{RateLimit, RateLimiter};
+/// let mut rate_limiter = RateLimiter::default();
+/// rate_limiter.set_limit(0, RateLimit { messages_per_second: 2.0, burst: 5 });
+///
+/// let client_id = 0;
+/// if rate_limiter.check(client_id, 0) {
+///     // Handle message
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    limits: HashMap<u8, RateLimit>,
+    buckets: HashMap<(ClientId, u8), TokenBucket>,
+    current_time: Duration,
+}
+
+impl RateLimiter {
+    /// Sets the rate limit for a channel.
+    pub fn set_limit<I: Into<u8>>(&mut self, channel_id: I, limit: RateLimit) {
+        self.limits.insert(channel_id.into(), limit);
+    }
+
+    /// Removes the rate limit for a channel, all messages are allowed.
+    pub fn remove_limit<I: Into<u8>>(&mut self, channel_id: I) {
+        let channel_id = channel_id.into();
+        self.limits.remove(&channel_id);
+        self.buckets.retain(|(_, bucket_channel_id), _| *bucket_channel_id != channel_id);
+    }
+
+    /// Advances the rate limiter by the duration.
+    pub fn update(&mut self, duration: Duration) {
+        self.current_time += duration;
+    }
+
+    /// Consumes one message from the client allowance in the channel.
+    /// Returns false if the client is above the rate limit, the message should be dropped.
+    pub fn check<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> bool {
+        let channel_id = channel_id.into();
+        let Some(limit) = self.limits.get(&channel_id) else {
+            return true;
+        };
+
+        let bucket = self.buckets.entry((client_id, channel_id)).or_insert(TokenBucket {
+            tokens: limit.burst as f64,
+            last_refill: self.current_time,
+            dropped: 0,
+        });
+
+        let elapsed = (self.current_time - bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * limit.messages_per_second).min(limit.burst as f64);
+        bucket.last_refill = self.current_time;
+
+        if bucket.tokens < 1.0 {
+            bucket.dropped += 1;
+            return false;
+        }
+
+        bucket.tokens -= 1.0;
+        true
+    }
+
+    /// Returns the number of messages from the client that were above the rate limit in the channel.
+    pub fn dropped_messages<I: Into<u8>>(&self, client_id: ClientId, channel_id: I) -> u64 {
+        self.buckets.get(&(client_id, channel_id.into())).map_or(0, |bucket| bucket.dropped)
+    }
+
+    /// Removes all the state for the client, should be called when the client disconnects.
+    pub fn remove_client(&mut self, client_id: ClientId) {
+        self.buckets.retain(|(bucket_client_id, _), _| *bucket_client_id != client_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limit() {
+        let mut rate_limiter = RateLimiter::default();
+        rate_limiter.set_limit(
+            0,
+            RateLimit {
+                messages_per_second: 10.0,
+                burst: 3,
+            },
+        );
+
+        for _ in 0..3 {
+            assert!(rate_limiter.check(0, 0));
+        }
+        assert!(!rate_limiter.check(0, 0));
+        assert_eq!(rate_limiter.dropped_messages(0, 0), 1);
+
+        // Other clients and channels are not affected
+        assert!(rate_limiter.check(1, 0));
+        for _ in 0..10 {
+            assert!(rate_limiter.check(0, 1));
+        }
+
+        rate_limiter.update(Duration::from_millis(100));
+        assert!(rate_limiter.check(0, 0));
+        assert!(!rate_limiter.check(0, 0));
+
+        // Tokens do not accumulate above the burst
+        rate_limiter.update(Duration::from_secs(10));
+        for _ in 0..3 {
+            assert!(rate_limiter.check(0, 0));
+        }
+        assert!(!rate_limiter.check(0, 0));
+        assert_eq!(rate_limiter.dropped_messages(0, 0), 3);
+
+        rate_limiter.remove_client(0);
+        assert_eq!(rate_limiter.dropped_messages(0, 0), 0);
+    }
+}
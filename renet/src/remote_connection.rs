@@ -1,13 +1,18 @@
-use crate::channel::reliable::{ReceiveChannelReliable, SendChannelReliable};
+use crate::channel::redundant::{ReceiveChannelRedundant, SendChannelRedundant};
+use crate::channel::reliable::{DedupWindow, ReceiveChannelReliable, SendChannelReliable};
 use crate::channel::unreliable::{ReceiveChannelUnreliable, SendChannelUnreliable};
-use crate::channel::{ChannelConfig, DefaultChannel, SendType};
-use crate::connection_stats::ConnectionStats;
-use crate::error::DisconnectReason;
-use crate::packet::{Packet, Payload};
+use crate::channel::{ChannelConfig, DefaultChannel, ReceivedMessage, ResendStrategy, SendType, SliceStats};
+use crate::connection_stats::{self, ConnectionStats};
+use crate::control::{control_channel_config, ControlMessage, CONTROL_CHANNEL_ID};
+use crate::error::{ConfigError, DisconnectReason, InvalidChannelId, SendError};
+use crate::middleware::{Middleware, MiddlewareAction};
+use crate::packet::{
+    Packet, Payload, SerializationError, MAX_PACKET_OVERHEAD, MAX_PACKET_SIZE, MAX_SEQUENCE, SLICE_SIZE, WIRE_FORMAT_VERSION,
+};
+use crate::sequence_buffer::SequenceBuffer;
 use bytes::Bytes;
-use octets::OctetsMut;
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{HashMap, HashSet};
 use std::ops::Range;
 use std::time::Duration;
 
@@ -15,8 +20,16 @@ use std::time::Duration;
 #[derive(Debug, Clone)]
 pub struct ConnectionConfig {
     /// The number of bytes that is available per update tick to send messages.
+    /// The budget is shared by all calls to `get_packets_to_send` between two updates.
     /// Default: 60_000, at 60hz this is becomes 28.8 Mbps
     pub available_bytes_per_tick: u64,
+    /// Bytes per tick available for the client to send messages to the server, the upstream budget.
+    /// Game clients usually have far less upstream than the server has downstream.
+    /// Default: None, uses `available_bytes_per_tick`
+    pub client_available_bytes_per_tick: Option<u64>,
+    /// Bytes per tick available for the server to send messages to each client, the downstream budget.
+    /// Default: None, uses `available_bytes_per_tick`
+    pub server_available_bytes_per_tick: Option<u64>,
     /// The channels that the server sends to the client.
     /// The order of the channels in this Vec determines which channel has priority when generating packets.
     /// Each tick, the first channel can consume up to `available_bytes_per_tick`,
@@ -27,6 +40,28 @@ pub struct ConnectionConfig {
     /// Each tick, the first channel can consume up to `available_bytes_per_tick`,
     /// used bytes are removed from it and passed to the next channel
     pub client_channels_config: Vec<ChannelConfig>,
+    /// Maximum rate in bytes per second that packets are sent, to avoid bursts that routers drop.
+    /// Each tick, only the bytes that can be sent at this rate until the next tick are generated, at least one packet,
+    /// and the transport spreads the packets of the tick across the tick interval instead of sending them at once.
+    /// The transports of `renet_netcode` support pacing.
+    /// Default: None, all packets of a tick are sent at once.
+    pub pacing_rate: Option<u64>,
+    /// Time that a sent packet waits for its ack, after that it's considered lost and its ack is ignored.
+    /// Should be bigger than the highest expected round-trip time, high values use more memory on high packet rates.
+    /// Default: 3 seconds
+    pub sent_packet_discard_time: Duration,
+    /// Duration of the sample window used to calculate the packet loss and bandwidth stats, in intervals of 300ms.
+    /// Longer windows are more stable, shorter windows react faster to changes in the network.
+    /// Default: 6 seconds, the minimum is 1.2 seconds
+    pub stats_window: Duration,
+    /// Smoothing factor, between 0 and 1, of the exponential moving average of the packet loss, updated every 300ms.
+    /// Lower values change slower, 1 disables the smoothing.
+    /// Default: 0.1
+    pub packet_loss_smoothing: f64,
+    /// Smoothing factor, between 0 and 1, of the exponential moving average of the bytes sent and received per second,
+    /// updated every 300ms. Lower values change slower, 1 disables the smoothing.
+    /// Default: 0.1
+    pub bandwidth_smoothing: f64,
 }
 
 #[derive(Debug, Clone)]
@@ -59,15 +94,38 @@ enum PacketSentInfo {
 enum ChannelOrder {
     Reliable(u8),
     Unreliable(u8),
+    Redundant(u8),
 }
 
 /// Describes the stats of a connection.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct NetworkInfo {
     /// Round-trip Time
     pub rtt: f64,
+    /// Variation of the round-trip time in seconds, the smoothed difference between consecutive samples.
+    /// Useful to choose the interpolation delay, that should cover the jitter and not only the mean latency.
+    pub jitter: f64,
+    /// Mean deviation of the round-trip time samples from the smoothed round-trip time, in seconds.
+    /// Used with the round-trip time for the adaptive resend time of the reliable channels.
+    pub rtt_variation: f64,
     pub packet_loss: f64,
+    /// Ratio of reliable messages sent that were resends.
+    /// High retransmission with low packet loss indicates a `resend_time` that is too low.
+    pub retransmission_ratio: f64,
     pub bytes_sent_per_second: f64,
     pub bytes_received_per_second: f64,
+    /// Message payload bytes sent per second, excluding packet headers and acks.
+    /// Resent and repeated messages are also counted.
+    pub goodput_bytes_per_second: f64,
+    /// Ratio of the bytes sent that are message payload.
+    /// Low efficiency with small messages can be improved by sending fewer and bigger messages.
+    pub efficiency: f64,
+    /// Exponential moving average of the packet loss, see [`ConnectionConfig::packet_loss_smoothing`].
+    pub smoothed_packet_loss: f64,
+    /// Exponential moving average of the bytes sent per second, see [`ConnectionConfig::bandwidth_smoothing`].
+    pub smoothed_bytes_sent_per_second: f64,
+    /// Exponential moving average of the bytes received per second, see [`ConnectionConfig::bandwidth_smoothing`].
+    pub smoothed_bytes_received_per_second: f64,
 }
 
 /// The connection status of a [`RenetClient`].
@@ -83,17 +141,55 @@ pub enum RenetConnectionStatus {
 pub struct RenetClient {
     packet_sequence: u64,
     current_time: Duration,
-    sent_packets: BTreeMap<u64, PacketSent>,
+    // Time of the last packet received, or of the connection creation
+    last_packet_received_at: Duration,
+    // Time of the last packet with messages received, or of the connection creation
+    last_message_received_at: Duration,
+    idle_timeout: Option<Duration>,
+    sent_packets: SequenceBuffer<PacketSent>,
+    sent_packet_discard_time: Duration,
     pending_acks: Vec<Range<u64>>,
+    // Packets below this sequence were already reported as lost
+    next_nack_sequence: u64,
     channel_send_order: Vec<ChannelOrder>,
     send_unreliable_channels: HashMap<u8, SendChannelUnreliable>,
     receive_unreliable_channels: HashMap<u8, ReceiveChannelUnreliable>,
     send_reliable_channels: HashMap<u8, SendChannelReliable>,
     receive_reliable_channels: HashMap<u8, ReceiveChannelReliable>,
+    send_redundant_channels: HashMap<u8, SendChannelRedundant>,
+    receive_redundant_channels: HashMap<u8, ReceiveChannelRedundant>,
     stats: ConnectionStats,
     available_bytes_per_tick: u64,
+    // The available_bytes_per_tick of the config, the maximum recommended bytes per tick
+    config_bytes_per_tick: u64,
+    // Duration of the last update, used to convert rates to bytes per tick
+    tick_duration: Duration,
+    // Bytes that can still be sent in the current tick
+    available_bytes: u64,
+    // Budget of the current tick, and ratio of the budget used in the last tick
+    tick_budget: u64,
+    budget_usage: f64,
+    // Acks are sent once per tick, and again only when new packets are received
+    should_send_ack: bool,
+    // Packets with messages were received since the last ack sent
+    has_unsent_acks: bool,
     connection_status: RenetConnectionStatus,
+    // The app is in the background, time doesn't advance and no packets are sent
+    paused: bool,
     rtt: f64,
+    rtt_variation: f64,
+    // Smoothed difference between consecutive rtt samples
+    jitter: f64,
+    last_rtt_sample: f64,
+    pacing_rate: Option<u64>,
+    max_packet_size: usize,
+    middlewares: Vec<Box<dyn Middleware>>,
+    // Used to reset the connection when transferring to another server, only set in clients
+    config: Option<ConnectionConfig>,
+    // Restricted channels that this connection can't use, messages in them are dropped
+    denied_channels: HashSet<u8>,
+    channel_access_violations: u64,
+    receive_dedup_window: Option<usize>,
 }
 
 impl Default for ConnectionConfig {
@@ -101,26 +197,310 @@ impl Default for ConnectionConfig {
         Self {
             // At 60hz this is becomes 28.8 Mbps
             available_bytes_per_tick: 60_000,
+            client_available_bytes_per_tick: None,
+            server_available_bytes_per_tick: None,
             server_channels_config: DefaultChannel::config(),
             client_channels_config: DefaultChannel::config(),
+            pacing_rate: None,
+            sent_packet_discard_time: Duration::from_secs(3),
+            stats_window: connection_stats::DEFAULT_WINDOW,
+            packet_loss_smoothing: connection_stats::DEFAULT_SMOOTHING,
+            bandwidth_smoothing: connection_stats::DEFAULT_SMOOTHING,
         }
     }
 }
 
+impl ConnectionConfig {
+    fn stats(&self) -> ConnectionStats {
+        ConnectionStats::new(self.stats_window, self.packet_loss_smoothing, self.bandwidth_smoothing)
+    }
+
+    /// Returns the bytes per tick available for the client to send messages, the upstream budget.
+    pub fn client_bytes_per_tick(&self) -> u64 {
+        self.client_available_bytes_per_tick.unwrap_or(self.available_bytes_per_tick)
+    }
+
+    /// Returns the bytes per tick available for the server to send messages to each client, the downstream budget.
+    pub fn server_bytes_per_tick(&self) -> u64 {
+        self.server_available_bytes_per_tick.unwrap_or(self.available_bytes_per_tick)
+    }
+
+    /// Configuration for turn-based and asynchronous games, that send few messages at a low tick rate.
+    ///
+    /// Only has the reliable channels of [`DefaultChannel`], `ReliableUnordered` and `ReliableOrdered`, with small budgets
+    /// and long resend times, so few packets are sent. With [`RenetClient::is_idle`] the game can stop updating the connection
+    /// while waiting for the other players, instead of ticking at 60hz, this saves battery on mobile.
+    /// The transport keep-alive should also be reduced, like the `keep_alive_interval` of the netcode server.
+    pub fn turn_based() -> Self {
+        let channel = |channel_id: u8, send_type: SendType| ChannelConfig {
+            channel_id,
+            max_memory_usage_bytes: 256 * 1024,
+            send_type,
+            slice_size: None,
+            ordering: None,
+        };
+        let resend_time = ResendStrategy::Adaptive {
+            min: Duration::from_millis(500),
+            max: Duration::from_secs(2),
+        };
+        let channels_config = vec![
+            channel(
+                DefaultChannel::ReliableUnordered.into(),
+                SendType::ReliableUnordered { resend_time },
+            ),
+            channel(DefaultChannel::ReliableOrdered.into(), SendType::ReliableOrdered { resend_time }),
+        ];
+
+        Self {
+            available_bytes_per_tick: 16 * 1024,
+            server_channels_config: channels_config.clone(),
+            client_channels_config: channels_config,
+            pacing_rate: None,
+            sent_packet_discard_time: Duration::from_secs(10),
+            ..Default::default()
+        }
+    }
+
+    /// Returns a builder, starting from the default configuration, that validates the configuration when built.
+    ///
+    /// ```
+    /// # use renet::{ConnectionConfig, DefaultChannel};
+    /// # use std::time::Duration;
+    /// let config = ConnectionConfig::builder()
+    ///     .channels(DefaultChannel::config())
+    ///     .expected_rtt(Duration::from_millis(150))
+    ///     .max_total_memory_bytes(32 * 1024 * 1024)
+    ///     .build()
+    ///     .expect("invalid connection config");
+    /// ```
+    pub fn builder() -> ConnectionConfigBuilder {
+        ConnectionConfigBuilder::default()
+    }
+}
+
+/// Builder for [`ConnectionConfig`], see [`ConnectionConfig::builder`].
+///
+/// Instead of panicking when the connection is created, [`build`](Self::build) returns a [`ConfigError`]
+/// describing the invalid setting, so the configuration can be checked at startup.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionConfigBuilder {
+    config: ConnectionConfig,
+    expected_rtt: Option<Duration>,
+    max_total_memory_bytes: Option<usize>,
+}
+
+impl ConnectionConfigBuilder {
+    /// Sets [`ConnectionConfig::available_bytes_per_tick`].
+    pub fn available_bytes_per_tick(mut self, available_bytes_per_tick: u64) -> Self {
+        self.config.available_bytes_per_tick = available_bytes_per_tick;
+        self
+    }
+
+    /// Sets [`ConnectionConfig::client_available_bytes_per_tick`].
+    pub fn client_available_bytes_per_tick(mut self, client_available_bytes_per_tick: u64) -> Self {
+        self.config.client_available_bytes_per_tick = Some(client_available_bytes_per_tick);
+        self
+    }
+
+    /// Sets [`ConnectionConfig::server_available_bytes_per_tick`].
+    pub fn server_available_bytes_per_tick(mut self, server_available_bytes_per_tick: u64) -> Self {
+        self.config.server_available_bytes_per_tick = Some(server_available_bytes_per_tick);
+        self
+    }
+
+    /// Sets [`ConnectionConfig::server_channels_config`].
+    pub fn server_channels(mut self, channels_config: Vec<ChannelConfig>) -> Self {
+        self.config.server_channels_config = channels_config;
+        self
+    }
+
+    /// Sets [`ConnectionConfig::client_channels_config`].
+    pub fn client_channels(mut self, channels_config: Vec<ChannelConfig>) -> Self {
+        self.config.client_channels_config = channels_config;
+        self
+    }
+
+    /// Uses the same channels for the server and the client.
+    pub fn channels(self, channels_config: Vec<ChannelConfig>) -> Self {
+        self.server_channels(channels_config.clone()).client_channels(channels_config)
+    }
+
+    /// Sets [`ConnectionConfig::pacing_rate`].
+    pub fn pacing_rate(mut self, pacing_rate: u64) -> Self {
+        self.config.pacing_rate = Some(pacing_rate);
+        self
+    }
+
+    /// Sets [`ConnectionConfig::sent_packet_discard_time`].
+    pub fn sent_packet_discard_time(mut self, sent_packet_discard_time: Duration) -> Self {
+        self.config.sent_packet_discard_time = sent_packet_discard_time;
+        self
+    }
+
+    /// Sets [`ConnectionConfig::stats_window`].
+    pub fn stats_window(mut self, stats_window: Duration) -> Self {
+        self.config.stats_window = stats_window;
+        self
+    }
+
+    /// Sets [`ConnectionConfig::packet_loss_smoothing`].
+    pub fn packet_loss_smoothing(mut self, packet_loss_smoothing: f64) -> Self {
+        self.config.packet_loss_smoothing = packet_loss_smoothing;
+        self
+    }
+
+    /// Sets [`ConnectionConfig::bandwidth_smoothing`].
+    pub fn bandwidth_smoothing(mut self, bandwidth_smoothing: f64) -> Self {
+        self.config.bandwidth_smoothing = bandwidth_smoothing;
+        self
+    }
+
+    /// Round-trip time expected for the connections, only used for validation.
+    /// When set, resend times and the sent packet discard time shorter than it are rejected.
+    pub fn expected_rtt(mut self, expected_rtt: Duration) -> Self {
+        self.expected_rtt = Some(expected_rtt);
+        self
+    }
+
+    /// Limit of the sum of the memory of all server and client channels, the memory a single connection can use.
+    pub fn max_total_memory_bytes(mut self, max_total_memory_bytes: usize) -> Self {
+        self.max_total_memory_bytes = Some(max_total_memory_bytes);
+        self
+    }
+
+    /// Validates and returns the configuration.
+    pub fn build(self) -> Result<ConnectionConfig, ConfigError> {
+        let config = self.config;
+
+        let min_budget = MAX_PACKET_SIZE as u64;
+        for available_bytes_per_tick in [config.client_bytes_per_tick(), config.server_bytes_per_tick()] {
+            if available_bytes_per_tick < min_budget {
+                return Err(ConfigError::BudgetTooSmall {
+                    available_bytes_per_tick,
+                    min: min_budget,
+                });
+            }
+        }
+
+        for smoothing in [config.packet_loss_smoothing, config.bandwidth_smoothing] {
+            if !(0.0..=1.0).contains(&smoothing) {
+                return Err(ConfigError::InvalidSmoothing(smoothing));
+            }
+        }
+
+        if let Some(rtt) = self.expected_rtt {
+            if config.sent_packet_discard_time < rtt {
+                return Err(ConfigError::DiscardTimeBelowRtt {
+                    sent_packet_discard_time: config.sent_packet_discard_time,
+                    rtt,
+                });
+            }
+        }
+
+        for (channels_config, server_channel) in [(&config.server_channels_config, true), (&config.client_channels_config, false)] {
+            let mut channel_ids = HashSet::new();
+            for channel_config in channels_config {
+                let channel_id = channel_config.channel_id;
+                if channel_id == CONTROL_CHANNEL_ID {
+                    return Err(ConfigError::ReservedChannelId(channel_id));
+                }
+                if !channel_ids.insert(channel_id) {
+                    return Err(ConfigError::DuplicateChannelId {
+                        channel_id,
+                        server_channel,
+                    });
+                }
+                validate_channel(channel_config, self.expected_rtt)?;
+            }
+        }
+
+        if let Some(limit) = self.max_total_memory_bytes {
+            let total = config
+                .server_channels_config
+                .iter()
+                .chain(config.client_channels_config.iter())
+                .map(|channel_config| channel_config.max_memory_usage_bytes)
+                .sum();
+            if total > limit {
+                return Err(ConfigError::MemoryLimitExceeded { total, limit });
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn validate_channel(channel_config: &ChannelConfig, expected_rtt: Option<Duration>) -> Result<(), ConfigError> {
+    let channel_id = channel_config.channel_id;
+    if channel_config.max_memory_usage_bytes == 0 {
+        return Err(ConfigError::ZeroChannelMemory { channel_id });
+    }
+
+    if let Some(slice_size) = channel_config.slice_size {
+        if slice_size == 0 || slice_size > SLICE_SIZE {
+            return Err(ConfigError::InvalidSliceSize {
+                channel_id,
+                slice_size,
+                max: SLICE_SIZE,
+            });
+        }
+    }
+
+    let resend_strategy = match channel_config.send_type {
+        SendType::ReliableOrdered { resend_time }
+        | SendType::ReliableUnordered { resend_time }
+        | SendType::ReliableOrderedStreams { resend_time } => resend_time,
+        SendType::Unreliable | SendType::UnreliableRedundant { .. } => return Ok(()),
+    };
+    // For adaptive resends, only the maximum needs to be above the rtt, the minimum is used for low rtt connections
+    let resend_time = match resend_strategy {
+        ResendStrategy::Fixed(resend_time) | ResendStrategy::Nack { resend_time } => resend_time,
+        ResendStrategy::Adaptive { min, max } => {
+            if min > max {
+                return Err(ConfigError::InvalidAdaptiveResend { channel_id, min, max });
+            }
+            max
+        }
+    };
+    if let Some(rtt) = expected_rtt {
+        if resend_time < rtt {
+            return Err(ConfigError::ResendTimeBelowRtt {
+                channel_id,
+                resend_time,
+                rtt,
+            });
+        }
+    }
+
+    Ok(())
+}
+
 impl RenetClient {
     pub fn new(config: ConnectionConfig) -> Self {
-        Self::from_channels(
-            config.available_bytes_per_tick,
-            config.client_channels_config,
-            config.server_channels_config,
-        )
+        let mut receive_channels_config = config.server_channels_config.clone();
+        receive_channels_config.push(control_channel_config());
+
+        let mut client = Self::from_channels(
+            config.client_bytes_per_tick(),
+            config.pacing_rate,
+            config.sent_packet_discard_time,
+            config.stats(),
+            config.client_channels_config.clone(),
+            receive_channels_config,
+        );
+        client.config = Some(config);
+        client
     }
 
     // When creating a client from the server, the server_channels_config are used as send channels,
     // and the client_channels_config is used as recv channels.
-    pub(crate) fn new_from_server(config: ConnectionConfig) -> Self {
+    pub(crate) fn new_from_server(mut config: ConnectionConfig) -> Self {
+        config.server_channels_config.push(control_channel_config());
         Self::from_channels(
-            config.available_bytes_per_tick,
+            config.server_bytes_per_tick(),
+            config.pacing_rate,
+            config.sent_packet_discard_time,
+            config.stats(),
             config.server_channels_config,
             config.client_channels_config,
         )
@@ -128,67 +508,149 @@ impl RenetClient {
 
     fn from_channels(
         available_bytes_per_tick: u64,
+        pacing_rate: Option<u64>,
+        sent_packet_discard_time: Duration,
+        stats: ConnectionStats,
         send_channels_config: Vec<ChannelConfig>,
         receive_channels_config: Vec<ChannelConfig>,
     ) -> Self {
         let mut send_unreliable_channels = HashMap::new();
         let mut send_reliable_channels = HashMap::new();
+        let mut send_redundant_channels = HashMap::new();
         let mut channel_send_order: Vec<ChannelOrder> = Vec::with_capacity(send_channels_config.len());
         for channel_config in send_channels_config.iter() {
             match channel_config.send_type {
                 SendType::Unreliable => {
-                    let channel = SendChannelUnreliable::new(channel_config.channel_id, channel_config.max_memory_usage_bytes);
+                    let channel = SendChannelUnreliable::new(
+                        channel_config.channel_id,
+                        channel_config.max_memory_usage_bytes,
+                        channel_config.slice_size(),
+                    );
                     let old = send_unreliable_channels.insert(channel_config.channel_id, channel);
                     assert!(old.is_none(), "already exists send channel {}", channel_config.channel_id);
 
                     channel_send_order.push(ChannelOrder::Unreliable(channel_config.channel_id));
                 }
                 SendType::ReliableOrdered { resend_time } | SendType::ReliableUnordered { resend_time } => {
-                    let channel = SendChannelReliable::new(channel_config.channel_id, resend_time, channel_config.max_memory_usage_bytes);
+                    let channel = SendChannelReliable::new(
+                        channel_config.channel_id,
+                        resend_time,
+                        channel_config.max_memory_usage_bytes,
+                        channel_config.slice_size(),
+                    );
                     let old = send_reliable_channels.insert(channel_config.channel_id, channel);
                     assert!(old.is_none(), "already exists send channel {}", channel_config.channel_id);
 
                     channel_send_order.push(ChannelOrder::Reliable(channel_config.channel_id));
                 }
+                SendType::ReliableOrderedStreams { resend_time } => {
+                    let channel = SendChannelReliable::new(
+                        channel_config.channel_id,
+                        resend_time,
+                        channel_config.max_memory_usage_bytes,
+                        channel_config.slice_size(),
+                    )
+                    .with_streams();
+                    let old = send_reliable_channels.insert(channel_config.channel_id, channel);
+                    assert!(old.is_none(), "already exists send channel {}", channel_config.channel_id);
+
+                    channel_send_order.push(ChannelOrder::Reliable(channel_config.channel_id));
+                }
+                SendType::UnreliableRedundant { redundancy } => {
+                    let channel = SendChannelRedundant::new(
+                        channel_config.channel_id,
+                        redundancy,
+                        channel_config.max_memory_usage_bytes,
+                        channel_config.slice_size(),
+                    );
+                    let old = send_redundant_channels.insert(channel_config.channel_id, channel);
+                    assert!(old.is_none(), "already exists send channel {}", channel_config.channel_id);
+
+                    channel_send_order.push(ChannelOrder::Redundant(channel_config.channel_id));
+                }
             }
         }
 
         let mut receive_unreliable_channels = HashMap::new();
         let mut receive_reliable_channels = HashMap::new();
+        let mut receive_redundant_channels = HashMap::new();
         for channel_config in receive_channels_config.iter() {
             match channel_config.send_type {
                 SendType::Unreliable => {
-                    let channel = ReceiveChannelUnreliable::new(channel_config.channel_id, channel_config.max_memory_usage_bytes);
+                    let channel = ReceiveChannelUnreliable::new(
+                        channel_config.channel_id,
+                        channel_config.max_memory_usage_bytes,
+                        channel_config.slice_size(),
+                    );
                     let old = receive_unreliable_channels.insert(channel_config.channel_id, channel);
                     assert!(old.is_none(), "already exists receive channel {}", channel_config.channel_id);
                 }
                 SendType::ReliableOrdered { .. } => {
-                    let channel = ReceiveChannelReliable::new(channel_config.max_memory_usage_bytes, true);
+                    let mut channel = ReceiveChannelReliable::new(channel_config.max_memory_usage_bytes, true, channel_config.slice_size());
+                    if let Some(ordering) = channel_config.ordering {
+                        channel = channel.with_ordering(ordering);
+                    }
                     let old = receive_reliable_channels.insert(channel_config.channel_id, channel);
                     assert!(old.is_none(), "already exists receive channel {}", channel_config.channel_id);
                 }
                 SendType::ReliableUnordered { .. } => {
-                    let channel = ReceiveChannelReliable::new(channel_config.max_memory_usage_bytes, false);
+                    let channel = ReceiveChannelReliable::new(channel_config.max_memory_usage_bytes, false, channel_config.slice_size());
+                    let old = receive_reliable_channels.insert(channel_config.channel_id, channel);
+                    assert!(old.is_none(), "already exists receive channel {}", channel_config.channel_id);
+                }
+                SendType::ReliableOrderedStreams { .. } => {
+                    let channel = ReceiveChannelReliable::new(channel_config.max_memory_usage_bytes, false, channel_config.slice_size())
+                        .with_streams();
                     let old = receive_reliable_channels.insert(channel_config.channel_id, channel);
                     assert!(old.is_none(), "already exists receive channel {}", channel_config.channel_id);
                 }
+                SendType::UnreliableRedundant { .. } => {
+                    let channel = ReceiveChannelRedundant::new(channel_config.channel_id, channel_config.max_memory_usage_bytes);
+                    let old = receive_redundant_channels.insert(channel_config.channel_id, channel);
+                    assert!(old.is_none(), "already exists receive channel {}", channel_config.channel_id);
+                }
             }
         }
 
         Self {
             packet_sequence: 0,
             current_time: Duration::ZERO,
-            sent_packets: BTreeMap::new(),
+            last_packet_received_at: Duration::ZERO,
+            last_message_received_at: Duration::ZERO,
+            idle_timeout: None,
+            sent_packets: SequenceBuffer::default(),
+            sent_packet_discard_time,
             pending_acks: Vec::new(),
+            next_nack_sequence: 0,
             channel_send_order,
             send_unreliable_channels,
             receive_unreliable_channels,
             send_reliable_channels,
             receive_reliable_channels,
-            stats: ConnectionStats::new(),
+            send_redundant_channels,
+            receive_redundant_channels,
+            stats,
             rtt: 0.0,
+            rtt_variation: 0.0,
+            jitter: 0.0,
+            last_rtt_sample: 0.0,
+            pacing_rate,
+            max_packet_size: MAX_PACKET_SIZE,
+            middlewares: Vec::new(),
+            config: None,
+            denied_channels: HashSet::new(),
+            channel_access_violations: 0,
+            receive_dedup_window: None,
             available_bytes_per_tick,
+            config_bytes_per_tick: available_bytes_per_tick,
+            tick_duration: Duration::ZERO,
+            available_bytes: available_bytes_per_tick,
+            tick_budget: available_bytes_per_tick,
+            budget_usage: 0.0,
+            should_send_ack: false,
+            has_unsent_acks: false,
             connection_status: RenetConnectionStatus::Connecting,
+            paused: false,
         }
     }
 
@@ -197,11 +659,54 @@ impl RenetClient {
         self.rtt
     }
 
+    /// Returns the jitter for the connection, the variation between consecutive round-trip times in seconds.
+    pub fn jitter(&self) -> f64 {
+        self.jitter
+    }
+
+    /// Returns the time since the last packet was received, or since the connection was created if no packet was received.
+    /// Only the packets processed by the connection are counted, including acks, but not the keep-alive packets of the transport.
+    pub fn time_since_last_packet(&self) -> Duration {
+        self.current_time - self.last_packet_received_at
+    }
+
+    /// Returns the time since the last packet with messages was received, or since the connection was created
+    /// if no messages were received. Unlike [`RenetClient::time_since_last_packet`], packets with only acks are not counted.
+    pub fn time_since_last_message(&self) -> Duration {
+        self.current_time - self.last_message_received_at
+    }
+
+    /// Sets the duration without receiving messages after which the other side of the connection is idle,
+    /// see [`RenetClient::idle_for`].
+    pub fn set_idle_timeout(&mut self, idle_timeout: Duration) {
+        self.idle_timeout = Some(idle_timeout);
+    }
+
+    /// Removes the idle timeout, the other side is never considered idle.
+    pub fn clear_idle_timeout(&mut self) {
+        self.idle_timeout = None;
+    }
+
+    /// Returns the time without receiving messages, when it's longer than the idle timeout.
+    /// Returns None while messages are received, or without an idle timeout.
+    pub fn idle_for(&self) -> Option<Duration> {
+        let idle_for = self.time_since_last_message();
+        match self.idle_timeout {
+            Some(idle_timeout) if idle_for >= idle_timeout => Some(idle_for),
+            _ => None,
+        }
+    }
+
     /// Returns the packet loss for the connection.
     pub fn packet_loss(&self) -> f64 {
         self.stats.packet_loss()
     }
 
+    /// Returns the ratio of reliable messages sent that were resends.
+    pub fn retransmission_ratio(&self) -> f64 {
+        self.stats.retransmission_ratio()
+    }
+
     /// Returns the bytes sent per second in the connection.
     pub fn bytes_sent_per_sec(&self) -> f64 {
         self.stats.bytes_sent_per_second(self.current_time)
@@ -212,16 +717,130 @@ impl RenetClient {
         self.stats.bytes_received_per_second(self.current_time)
     }
 
+    /// Returns the estimated maximum send rate in bytes per second that the connection can sustain.
+    /// The estimate is based on the bytes delivered to the other side, it drops when the connection is congested
+    /// and grows while the connection sends near it without packet loss.
+    /// Returns None until the connection has sent packets for the duration of the [`ConnectionConfig::stats_window`].
+    pub fn estimated_bandwidth(&self) -> Option<f64> {
+        self.stats.estimated_bandwidth()
+    }
+
+    /// Returns the bytes that should be sent per tick, from the [`RenetClient::estimated_bandwidth`] and the duration
+    /// of the last update, capped by the `available_bytes_per_tick` of the [`ConnectionConfig`]. Games can use it to adapt
+    /// how often and how much they send to each connection, like the snapshot rate, instead of using the same budget
+    /// for all connections. Returns the `available_bytes_per_tick` of the config while there is no estimate.
+    pub fn recommended_bytes_per_tick(&self) -> u64 {
+        match self.stats.estimated_bandwidth() {
+            Some(estimate) if !self.tick_duration.is_zero() => {
+                let bytes_per_tick = (estimate * self.tick_duration.as_secs_f64()) as u64;
+                // Always allow at least one packet per tick
+                bytes_per_tick.clamp(MAX_PACKET_SIZE as u64, self.config_bytes_per_tick.max(MAX_PACKET_SIZE as u64))
+            }
+            _ => self.config_bytes_per_tick,
+        }
+    }
+
+    /// Returns whether the connection is not congested and sends less than the [`RenetClient::estimated_bandwidth`],
+    /// so the game can send more often, like raising the tick rate for this connection.
+    pub fn can_increase_send_rate(&self) -> bool {
+        match self.stats.estimated_bandwidth() {
+            Some(estimate) => {
+                self.stats.packet_loss() <= connection_stats::CONGESTION_PACKET_LOSS
+                    && self.stats.bytes_sent_per_second(self.current_time) < estimate
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the number of bytes available per tick to send messages.
+    pub fn available_bytes_per_tick(&self) -> u64 {
+        self.available_bytes_per_tick
+    }
+
+    /// Sets the number of bytes available per tick to send messages, applied from the next update.
+    /// Can be used with [`RenetClient::recommended_bytes_per_tick`] to adapt the budget of each connection.
+    pub fn set_available_bytes_per_tick(&mut self, available_bytes_per_tick: u64) {
+        self.available_bytes_per_tick = available_bytes_per_tick;
+    }
+
+    /// Returns the ratio, between 0 and 1, of the bytes per tick budget used to send messages in the last tick.
+    /// The budget is for the direction this connection sends: upstream for clients, downstream for the connections in the server.
+    /// Usage close to 1 means messages are waiting for budget, and the budget of that direction may need to be raised.
+    pub fn budget_usage(&self) -> f64 {
+        self.budget_usage
+    }
+
     /// Returns all network informations for the connection.
     pub fn network_info(&self) -> NetworkInfo {
         NetworkInfo {
             rtt: self.rtt,
+            jitter: self.jitter,
+            rtt_variation: self.rtt_variation,
             packet_loss: self.stats.packet_loss(),
+            retransmission_ratio: self.stats.retransmission_ratio(),
             bytes_sent_per_second: self.stats.bytes_sent_per_second(self.current_time),
             bytes_received_per_second: self.stats.bytes_received_per_second(self.current_time),
+            goodput_bytes_per_second: self.stats.payload_bytes_sent_per_second(self.current_time),
+            efficiency: self.stats.efficiency(),
+            smoothed_packet_loss: self.stats.smoothed_packet_loss(),
+            smoothed_bytes_sent_per_second: self.stats.smoothed_bytes_sent_per_second(self.current_time),
+            smoothed_bytes_received_per_second: self.stats.smoothed_bytes_received_per_second(self.current_time),
         }
     }
 
+    /// Returns the statistics of the sliced messages sent and received in all channels.
+    /// A high average of slices per message indicates messages that could be split or compressed.
+    pub fn slice_stats(&self) -> SliceStats {
+        let mut slice_stats = SliceStats::default();
+        for channel in self.send_unreliable_channels.values() {
+            slice_stats += channel.slice_stats();
+        }
+        for channel in self.send_reliable_channels.values() {
+            slice_stats += channel.slice_stats();
+        }
+        for channel in self.receive_unreliable_channels.values() {
+            slice_stats += channel.slice_stats();
+        }
+        for channel in self.receive_reliable_channels.values() {
+            slice_stats += channel.slice_stats();
+        }
+
+        slice_stats
+    }
+
+    /// Returns an estimate of the memory in bytes used by the connection: the messages held in the channels,
+    /// the packets waiting to be sent and the information kept about the packets waiting for an ack.
+    pub fn memory_usage(&self) -> usize {
+        let mut memory_usage = 0;
+        for channel in self.send_unreliable_channels.values() {
+            memory_usage += channel.memory_usage();
+        }
+        for channel in self.send_reliable_channels.values() {
+            memory_usage += channel.memory_usage();
+        }
+        for channel in self.send_redundant_channels.values() {
+            memory_usage += channel.memory_usage();
+        }
+        for channel in self.receive_unreliable_channels.values() {
+            memory_usage += channel.memory_usage();
+        }
+        for channel in self.receive_reliable_channels.values() {
+            memory_usage += channel.memory_usage();
+        }
+        for channel in self.receive_redundant_channels.values() {
+            memory_usage += channel.memory_usage();
+        }
+
+        memory_usage += self.sent_packets.capacity() * std::mem::size_of::<Option<PacketSent>>();
+        for sent_packet in self.sent_packets.values() {
+            if let PacketSentInfo::ReliableMessages { message_ids, .. } = &sent_packet.info {
+                memory_usage += message_ids.len() * std::mem::size_of::<u64>();
+            }
+        }
+
+        memory_usage
+    }
+
     /// Returns whether the client is connected.
     #[inline]
     pub fn is_connected(&self) -> bool {
@@ -292,6 +911,88 @@ impl RenetClient {
         self.disconnect_with_reason(DisconnectReason::Transport);
     }
 
+    /// Resets a client disconnected by the transport to connecting, keeping the messages not yet acked,
+    /// so a new transport can resume the session when the server has session resumption enabled,
+    /// see [`RenetServer::set_session_resumption`][crate::RenetServer::set_session_resumption].
+    /// Returns false if the client was disconnected for another reason, a new client must be created then.
+    ///
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> The new transport must present the resume ticket of the previous one,
+    /// otherwise the server considers it a new client and the messages can't be delivered.
+    /// </p>
+    pub fn resume_session(&mut self) -> bool {
+        if self.disconnect_reason() != Some(DisconnectReason::Transport) {
+            return false;
+        }
+
+        self.connection_status = RenetConnectionStatus::Connecting;
+        true
+    }
+
+    /// Returns the connect token sent by the server with [`RenetServer::transfer_client`][crate::RenetServer::transfer_client],
+    /// the transport should connect to the new server with it and reset the connection with [`RenetClient::reset_connection`].
+    ///
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn take_transfer(&mut self) -> Option<Bytes> {
+        let control_channel = self.receive_reliable_channels.get_mut(&CONTROL_CHANNEL_ID)?;
+        let mut connect_token = None;
+        while let Some(message) = control_channel.receive_message() {
+            match ControlMessage::from_bytes(message.payload) {
+                Some(ControlMessage::Transfer { connect_token: token }) => connect_token = Some(token),
+                None => log::debug!("Ignored unknown control message"),
+            }
+        }
+
+        connect_token
+    }
+
+    /// Resets the client to connect to another server, discarding the messages and the state of the previous connection.
+    /// The middlewares are kept. Does nothing for the connections of a [`RenetServer`][crate::RenetServer].
+    ///
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn reset_connection(&mut self) {
+        let Some(config) = self.config.take() else {
+            return;
+        };
+
+        let mut client = Self::new(config);
+        client.middlewares = std::mem::take(&mut self.middlewares);
+        client.max_packet_size = self.max_packet_size;
+        client.paused = self.paused;
+        // The new server starts again from the first message id, the dedup windows start empty
+        if let Some(window) = self.receive_dedup_window {
+            client.set_receive_dedup_window(window);
+        }
+        *self = client;
+    }
+
+    /// Discards the messages received in reliable unordered channels with the same id as one of the last `window` messages delivered.
+    ///
+    /// The window lasts for the session with the peer: it's kept through [`RenetClient::set_connecting`], [`RenetClient::set_connected`]
+    /// and [`RenetClient::resume_session`], so the messages resent by the peer after resuming are not delivered twice.
+    /// When the connection is reset with [`RenetClient::reset_connection`] to connect to another server,
+    /// the window starts empty, since the new server starts again from the first message id.
+    pub fn set_receive_dedup_window(&mut self, window: usize) {
+        self.receive_dedup_window = Some(window);
+        for channel in self.receive_reliable_channels.values_mut() {
+            if channel.is_unordered() {
+                channel.set_dedup_window(Some(DedupWindow::new(window)));
+            }
+        }
+    }
+
+    /// Disables the dedup window, see [`RenetClient::set_receive_dedup_window`].
+    pub fn clear_receive_dedup_window(&mut self) {
+        self.receive_dedup_window = None;
+        for channel in self.receive_reliable_channels.values_mut() {
+            channel.set_dedup_window(None);
+        }
+    }
+
     /// Returns the available memory in bytes for the given channel.
     pub fn channel_available_memory<I: Into<u8>>(&self, channel_id: I) -> usize {
         let channel_id = channel_id.into();
@@ -299,6 +1000,8 @@ impl RenetClient {
             reliable_channel.available_memory()
         } else if let Some(unreliable_channel) = self.send_unreliable_channels.get(&channel_id) {
             unreliable_channel.available_memory()
+        } else if let Some(redundant_channel) = self.send_redundant_channels.get(&channel_id) {
+            redundant_channel.available_memory()
         } else {
             panic!("Called 'channel_available_memory' with invalid channel {channel_id}");
         }
@@ -311,70 +1014,394 @@ impl RenetClient {
             reliable_channel.can_send_message(size_bytes)
         } else if let Some(unreliable_channel) = self.send_unreliable_channels.get(&channel_id) {
             unreliable_channel.can_send_message(size_bytes)
+        } else if let Some(redundant_channel) = self.send_redundant_channels.get(&channel_id) {
+            redundant_channel.can_send_message(size_bytes)
         } else {
             panic!("Called 'can_send_message' with invalid channel {channel_id}");
         }
     }
 
+    /// Returns the number of messages queued in the channel that were not sent yet.
+    pub fn channel_queue_len<I: Into<u8>>(&self, channel_id: I) -> usize {
+        let channel_id = channel_id.into();
+        if let Some(reliable_channel) = self.send_reliable_channels.get(&channel_id) {
+            reliable_channel.queue_len()
+        } else if let Some(unreliable_channel) = self.send_unreliable_channels.get(&channel_id) {
+            unreliable_channel.queue_len()
+        } else if let Some(redundant_channel) = self.send_redundant_channels.get(&channel_id) {
+            redundant_channel.queue_len()
+        } else {
+            panic!("Called 'channel_queue_len' with invalid channel {channel_id}");
+        }
+    }
+
+    /// Returns how long the oldest message not sent yet has been queued in the channel,
+    /// or None if all messages were sent. Useful to detect a channel backing up when the bandwidth is limited.
+    pub fn channel_oldest_unsent_age<I: Into<u8>>(&self, channel_id: I) -> Option<Duration> {
+        let channel_id = channel_id.into();
+        if let Some(reliable_channel) = self.send_reliable_channels.get(&channel_id) {
+            reliable_channel.oldest_unsent_age()
+        } else if let Some(unreliable_channel) = self.send_unreliable_channels.get(&channel_id) {
+            unreliable_channel.oldest_unsent_age()
+        } else if let Some(redundant_channel) = self.send_redundant_channels.get(&channel_id) {
+            redundant_channel.oldest_unsent_age()
+        } else {
+            panic!("Called 'channel_oldest_unsent_age' with invalid channel {channel_id}");
+        }
+    }
+
     /// Send a message to the server over a channel.
     pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) {
+        self.send_message_with_priority(channel_id, 0, message);
+    }
+
+    /// Send a message to the server over a channel, ahead of the messages in the channel with a lower priority
+    /// that were not sent yet. When the bandwidth is limited, important messages are not delayed by a backlog
+    /// of less important ones. Messages with the same priority keep their order.
+    ///
+    /// The priority is ignored in channels with [`SendType::UnreliableRedundant`].
+    ///
+    /// A message bigger than a reliable channel can hold disconnects with [`DisconnectReason::SendChannelError`],
+    /// use [`RenetClient::try_send_message_with_priority`] to check the message size instead.
+    pub fn send_message_with_priority<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, priority: u8, message: B) {
+        if let Err(error) = self.send_message_to_channel(channel_id.into(), priority, message.into(), false) {
+            panic!("Called 'send_message' with {error}");
+        }
+    }
+
+    /// Send a message to the server over a channel, returns an error instead of panicking if the channel does not exist,
+    /// and instead of disconnecting if the message is bigger than the channel can hold.
+    pub fn try_send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) -> Result<(), SendError> {
+        self.try_send_message_with_priority(channel_id, 0, message)
+    }
+
+    /// Send a message to the server over a channel with a priority, returns an error instead of panicking if the channel does not exist,
+    /// and instead of disconnecting if the message is bigger than the channel can hold.
+    /// See [`RenetClient::send_message_with_priority`].
+    pub fn try_send_message_with_priority<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        channel_id: I,
+        priority: u8,
+        message: B,
+    ) -> Result<(), SendError> {
+        self.send_message_to_channel(channel_id.into(), priority, message.into(), true)
+    }
+
+    // Messages bigger than the channel can hold return an error when checking the size,
+    // otherwise the channel handles them: reliable channels disconnect and unreliable channels drop them
+    fn send_message_to_channel(&mut self, channel_id: u8, priority: u8, message: Bytes, check_size: bool) -> Result<(), SendError> {
         if self.is_disconnected() {
-            return;
+            return Ok(());
+        }
+
+        let Some(limit) = self.max_message_size(channel_id) else {
+            return Err(SendError::InvalidChannelId(channel_id));
+        };
+        let Some(message) = self.apply_send_middlewares(channel_id, message) else {
+            return Ok(());
+        };
+        if check_size && message.len() > limit {
+            return Err(SendError::MessageTooLarge {
+                size: message.len(),
+                limit,
+            });
         }
 
-        let channel_id = channel_id.into();
         if let Some(reliable_channel) = self.send_reliable_channels.get_mut(&channel_id) {
-            if let Err(error) = reliable_channel.send_message(message.into()) {
+            if let Err(error) = reliable_channel.send_message_with_priority(message, priority) {
+                log::error!("Error in send channel {channel_id}: {error}");
                 self.disconnect_with_reason(DisconnectReason::SendChannelError { channel_id, error });
             }
         } else if let Some(unreliable_channel) = self.send_unreliable_channels.get_mut(&channel_id) {
-            unreliable_channel.send_message(message.into());
-        } else {
-            panic!("Called 'send_message' with invalid channel {channel_id}");
+            unreliable_channel.send_message_with_priority(message, priority);
+        } else if let Some(redundant_channel) = self.send_redundant_channels.get_mut(&channel_id) {
+            redundant_channel.send_message(message);
+        }
+
+        Ok(())
+    }
+
+    /// Send a message to the server over a channel with [`SendType::Unreliable`], that is dropped if it could not be sent
+    /// within the time to live. Unlike other unreliable messages, that are dropped when there are no available bytes
+    /// to send them, the message is kept queued until it's sent or expires, so a congestion does not result in stale messages.
+    ///
+    /// Panics if the channel is not unreliable.
+    pub fn send_message_with_ttl<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B, ttl: Duration) {
+        if self.is_disconnected() {
+            return;
+        }
+
+        let channel_id = channel_id.into();
+        assert!(
+            self.send_unreliable_channels.contains_key(&channel_id),
+            "Called 'send_message_with_ttl' with invalid channel {channel_id}, it must be an unreliable channel"
+        );
+        let Some(message) = self.apply_send_middlewares(channel_id, message.into()) else {
+            return;
+        };
+
+        let unreliable_channel = self.send_unreliable_channels.get_mut(&channel_id).unwrap();
+        unreliable_channel.send_message_with_expiration(message, self.current_time + ttl);
+    }
+
+    /// Send a message to the server over a stream of a channel with [`SendType::ReliableOrderedStreams`].
+    /// Messages are only received in order with the other messages of the same stream,
+    /// so a lost message does not delay the messages of other streams.
+    ///
+    /// Panics if the channel does not have streams or if the stream id is above 2^62 - 1.
+    pub fn send_message_on_stream<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, stream_id: u64, message: B) {
+        assert!(
+            stream_id <= MAX_SEQUENCE,
+            "stream id {stream_id} is above the limit of {MAX_SEQUENCE}"
+        );
+        if self.is_disconnected() {
+            return;
+        }
+
+        let channel_id = channel_id.into();
+        assert!(
+            self.send_reliable_channels
+                .get(&channel_id)
+                .is_some_and(|channel| channel.has_streams()),
+            "Called 'send_message_on_stream' with invalid channel {channel_id}, it must be a channel with streams"
+        );
+        let Some(message) = self.apply_send_middlewares(channel_id, message.into()) else {
+            return;
+        };
+
+        let reliable_channel = self.send_reliable_channels.get_mut(&channel_id).unwrap();
+        if let Err(error) = reliable_channel.send_message_on_stream(stream_id, message) {
+            log::error!("Error in send channel {channel_id}: {error}");
+            self.disconnect_with_reason(DisconnectReason::SendChannelError { channel_id, error });
         }
     }
 
     /// Receive a message from the server over a channel.
     pub fn receive_message<I: Into<u8>>(&mut self, channel_id: I) -> Option<Bytes> {
+        self.receive_message_meta(channel_id).map(|message| message.payload)
+    }
+
+    /// Receives all the messages of the channel, appending them to `messages` and reusing its capacity.
+    /// Returns the number of messages received. Panics if the channel does not exist.
+    ///
+    /// # Usage
+    /// ```
+    /// # use renet::{ConnectionConfig, DefaultChannel, RenetClient};
+    /// # let mut client = RenetClient::new(ConnectionConfig::default());
+    /// let mut messages = Vec::new();
+    /// // Every frame
+    /// client.receive_all_messages(DefaultChannel::ReliableOrdered, &mut messages);
+    /// for message in messages.drain(..) {
+    ///     // Handle received message
+    /// }
+    /// ```
+    pub fn receive_all_messages<I: Into<u8>>(&mut self, channel_id: I, messages: &mut Vec<Bytes>) -> usize {
+        let channel_id = channel_id.into();
+        let len = messages.len();
+        while let Some(message) = self.receive_message(channel_id) {
+            messages.push(message);
+        }
+
+        messages.len() - len
+    }
+
+    /// Receive a message from the server over a channel, along with when and in which packet it arrived.
+    pub fn receive_message_meta<I: Into<u8>>(&mut self, channel_id: I) -> Option<ReceivedMessage> {
+        match self.try_receive_message_meta(channel_id) {
+            Ok(message) => message,
+            Err(error) => panic!("Called 'receive_message' with {error}"),
+        }
+    }
+
+    /// Receive a message from the server over a channel, returns an error instead of panicking if the channel does not exist.
+    pub fn try_receive_message<I: Into<u8>>(&mut self, channel_id: I) -> Result<Option<Bytes>, InvalidChannelId> {
+        let message = self.try_receive_message_meta(channel_id)?;
+        Ok(message.map(|message| message.payload))
+    }
+
+    /// Receive a message from the server over a channel, along with when and in which packet it arrived.
+    /// Returns an error instead of panicking if the channel does not exist.
+    pub fn try_receive_message_meta<I: Into<u8>>(&mut self, channel_id: I) -> Result<Option<ReceivedMessage>, InvalidChannelId> {
         if self.is_disconnected() {
-            return None;
+            return Ok(None);
         }
 
         let channel_id = channel_id.into();
-        if let Some(reliable_channel) = self.receive_reliable_channels.get_mut(&channel_id) {
-            reliable_channel.receive_message()
-        } else if let Some(unreliable_channel) = self.receive_unreliable_channels.get_mut(&channel_id) {
-            unreliable_channel.receive_message()
+        loop {
+            let message = if let Some(reliable_channel) = self.receive_reliable_channels.get_mut(&channel_id) {
+                reliable_channel.receive_message()
+            } else if let Some(unreliable_channel) = self.receive_unreliable_channels.get_mut(&channel_id) {
+                unreliable_channel.receive_message()
+            } else if let Some(redundant_channel) = self.receive_redundant_channels.get_mut(&channel_id) {
+                redundant_channel.receive_message()
+            } else {
+                return Err(InvalidChannelId(channel_id));
+            };
+            let Some(mut message) = message else {
+                return Ok(None);
+            };
+
+            let dropped = self
+                .middlewares
+                .iter_mut()
+                .any(|middleware| middleware.on_receive(channel_id, &mut message.payload) == MiddlewareAction::Drop);
+            if !dropped {
+                return Ok(Some(message));
+            }
+        }
+    }
+
+    /// Adds a middleware that inspects the messages sent and received by this connection,
+    /// it's called after the middlewares already added. See [`Middleware`].
+    pub fn add_middleware<M: Middleware + 'static>(&mut self, middleware: M) {
+        self.middlewares.push(Box::new(middleware));
+    }
+
+    /// Removes all middlewares from this connection.
+    pub fn clear_middlewares(&mut self) {
+        self.middlewares.clear();
+    }
+
+    // Control messages are not passed to the middlewares
+    pub(crate) fn send_control_message(&mut self, message: ControlMessage) {
+        if self.is_disconnected() {
+            return;
+        }
+
+        let Some(control_channel) = self.send_reliable_channels.get_mut(&CONTROL_CHANNEL_ID) else {
+            return;
+        };
+        if let Err(error) = control_channel.send_message(message.to_bytes()) {
+            log::error!("Error in send channel {CONTROL_CHANNEL_ID}: {error}");
+            self.disconnect_with_reason(DisconnectReason::SendChannelError {
+                channel_id: CONTROL_CHANNEL_ID,
+                error,
+            });
+        }
+    }
+
+    pub(crate) fn deny_channel(&mut self, channel_id: u8) {
+        self.denied_channels.insert(channel_id);
+    }
+
+    pub(crate) fn allow_channel(&mut self, channel_id: u8) {
+        self.denied_channels.remove(&channel_id);
+    }
+
+    pub(crate) fn can_access_channel(&self, channel_id: u8) -> bool {
+        !self.denied_channels.contains(&channel_id)
+    }
+
+    pub(crate) fn channel_access_violations(&self) -> u64 {
+        self.channel_access_violations
+    }
+
+    // Returns None if the channel does not exist
+    fn max_message_size(&self, channel_id: u8) -> Option<usize> {
+        if let Some(reliable_channel) = self.send_reliable_channels.get(&channel_id) {
+            Some(reliable_channel.max_message_size())
+        } else if let Some(unreliable_channel) = self.send_unreliable_channels.get(&channel_id) {
+            Some(unreliable_channel.max_message_size())
         } else {
-            panic!("Called 'receive_message' with invalid channel {channel_id}");
+            self.send_redundant_channels
+                .get(&channel_id)
+                .map(|redundant_channel| redundant_channel.max_message_size())
         }
     }
 
-    /// Advances the client by the duration.
-    /// Should be called every tick
+    fn apply_send_middlewares(&mut self, channel_id: u8, mut message: Bytes) -> Option<Bytes> {
+        // Messages to restricted channels are dropped, all send methods go through here
+        if self.denied_channels.contains(&channel_id) {
+            return None;
+        }
+
+        let dropped = self
+            .middlewares
+            .iter_mut()
+            .any(|middleware| middleware.on_send(channel_id, &mut message) == MiddlewareAction::Drop);
+
+        (!dropped).then_some(message)
+    }
+
+    /// Returns the id of the missing message that blocks the delivery of the next messages in an ordered channel.
+    /// Returns None if the channel is not blocked, or if it's not an ordered channel.
+    pub fn blocked_on_message_id<I: Into<u8>>(&self, channel_id: I) -> Option<u64> {
+        let reliable_channel = self.receive_reliable_channels.get(&channel_id.into())?;
+        reliable_channel.blocked_on_message_id()
+    }
+
+    /// Pauses the client when the app goes to the background, for example on mobile platforms.
+    /// While paused, updates don't advance the client time, so messages are not resent and the connection doesn't time out,
+    /// and no packets are sent. Messages sent while paused are queued until the client is resumed.
+    ///
+    /// The transport should let the server know the client is suspended, so it's not timed out,
+    /// `NetcodeClientTransport` from the `renet_netcode` crate sends it in the next `send_packets`.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Resumes the client when the app returns to the foreground.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Returns whether the client is paused, see [`RenetClient::pause`].
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Advances the client by the duration, starting a new tick.
+    /// Should be called once every tick, before the packets are sent.
     pub fn update(&mut self, duration: Duration) {
+        if self.paused {
+            return;
+        }
+
         self.current_time += duration;
+        if !duration.is_zero() {
+            self.tick_duration = duration;
+        }
         self.stats.update(self.current_time);
+        if self.tick_budget > 0 {
+            self.budget_usage = (self.tick_budget - self.available_bytes) as f64 / self.tick_budget as f64;
+        }
+        let mut tick_budget = self.available_bytes_per_tick;
+        if let Some(pacing_rate) = self.pacing_rate {
+            // Only generate the packets the transport can send at the pacing rate until the next tick
+            let pacing_bytes = (pacing_rate as f64 * duration.as_secs_f64()) as u64;
+            tick_budget = tick_budget.min(pacing_bytes.max(self.max_packet_size as u64));
+        }
+        self.available_bytes = tick_budget;
+        self.tick_budget = tick_budget;
+        self.should_send_ack = true;
 
         for unreliable_channel in self.receive_unreliable_channels.values_mut() {
             unreliable_channel.discard_incomplete_old_slices(self.current_time);
         }
 
-        // Discard lost packets
-        let mut lost_packets: Vec<u64> = Vec::new();
-        for (&sequence, sent_packet) in self.sent_packets.iter() {
-            const DISCARD_AFTER: Duration = Duration::from_secs(3);
-            if self.current_time - sent_packet.sent_at >= DISCARD_AFTER {
-                lost_packets.push(sequence);
-            } else {
-                // If the current packet is not lost, the next ones will not be lost
-                // since all the next packets were sent after this one.
-                break;
-            }
+        for reliable_channel in self.receive_reliable_channels.values_mut() {
+            reliable_channel.update(self.current_time);
+        }
+
+        for reliable_channel in self.send_reliable_channels.values_mut() {
+            reliable_channel.update(self.current_time);
+        }
+
+        for unreliable_channel in self.send_unreliable_channels.values_mut() {
+            unreliable_channel.update(self.current_time);
+        }
+
+        for redundant_channel in self.send_redundant_channels.values_mut() {
+            redundant_channel.update(self.current_time);
         }
 
-        for sequence in lost_packets.iter() {
-            self.sent_packets.remove(sequence);
+        // Discard lost packets, if the first packet is not lost the next ones
+        // will not be lost since all the next packets were sent after this one.
+        while let Some((_, sent_packet)) = self.sent_packets.first() {
+            if self.current_time - sent_packet.sent_at < self.sent_packet_discard_time {
+                break;
+            }
+            self.sent_packets.pop_first();
         }
     }
 
@@ -382,22 +1409,68 @@ impl RenetClient {
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(len = packet.len())))]
     pub fn process_packet(&mut self, packet: &[u8]) {
         if self.is_disconnected() {
             return;
         }
 
+        self.last_packet_received_at = self.current_time;
         self.stats.received_packet(packet.len() as u64);
-        let mut octets = octets::Octets::with_slice(packet);
-        let packet = match Packet::from_bytes(&mut octets) {
-            Err(err) => {
-                self.disconnect_with_reason(DisconnectReason::PacketDeserialization(err));
+        let packet = match Packet::decode(packet) {
+            Err(SerializationError::InvalidWireFormatVersion(remote_version)) => {
+                self.disconnect_with_reason(DisconnectReason::WireFormatMismatch {
+                    version: WIRE_FORMAT_VERSION,
+                    remote_version,
+                });
+                return;
+            }
+            Err(error) => {
+                self.disconnect_with_reason(DisconnectReason::PacketDeserialization {
+                    packet_len: packet.len(),
+                    error,
+                });
                 return;
             }
             Ok(packet) => packet,
         };
 
-        self.add_pending_ack(packet.sequence());
+        self.process_decoded_packet(packet);
+    }
+
+    /// Process a packet received from a local connection, without going through deserialization.
+    pub(crate) fn process_local_packet(&mut self, packet: Packet) {
+        if self.is_disconnected() {
+            return;
+        }
+
+        self.last_packet_received_at = self.current_time;
+        self.process_decoded_packet(packet);
+    }
+
+    fn process_decoded_packet(&mut self, packet: Packet) {
+        log::trace!("Received packet {}", packet.sequence());
+
+        let packet_sequence = packet.sequence();
+        self.add_pending_ack(packet_sequence);
+        self.should_send_ack = true;
+        if !matches!(packet, Packet::Ack { .. }) {
+            self.has_unsent_acks = true;
+            self.last_message_received_at = self.current_time;
+        }
+
+        // The packet was already acked, messages in reliable channels are discarded without their payload,
+        // so the channel does not wait for their ids if the channel is allowed later
+        let denied = packet
+            .channel_id()
+            .is_some_and(|channel_id| self.denied_channels.contains(&channel_id));
+        if denied {
+            log::debug!("Discarded packet in restricted channel {}", packet.channel_id().unwrap());
+            self.channel_access_violations += 1;
+            if !matches!(packet, Packet::SmallReliable { .. } | Packet::ReliableSlice { .. }) {
+                return;
+            }
+        }
 
         match packet {
             Packet::SmallReliable { channel_id, messages, .. } => {
@@ -407,7 +1480,12 @@ impl RenetClient {
                 };
 
                 for (message_id, message) in messages {
-                    if let Err(error) = channel.process_message(message, message_id) {
+                    let result = match denied {
+                        true => channel.discard_message(message, message_id, packet_sequence, self.current_time),
+                        false => channel.process_message(message, message_id, packet_sequence, self.current_time),
+                    };
+                    if let Err(error) = result {
+                        log::error!("Error in receive channel {channel_id}: {error}");
                         self.disconnect_with_reason(DisconnectReason::ReceiveChannelError { channel_id, error });
                         return;
                     }
@@ -420,7 +1498,17 @@ impl RenetClient {
                 };
 
                 for message in messages {
-                    channel.process_message(message);
+                    channel.process_message(message, packet_sequence, self.current_time);
+                }
+            }
+            Packet::SmallRedundant { channel_id, messages, .. } => {
+                let Some(channel) = self.receive_redundant_channels.get_mut(&channel_id) else {
+                    self.disconnect_with_reason(DisconnectReason::ReceivedInvalidChannelId(channel_id));
+                    return;
+                };
+
+                for (message_id, message) in messages {
+                    channel.process_message(message, message_id, packet_sequence, self.current_time);
                 }
             }
             Packet::ReliableSlice { channel_id, slice, .. } => {
@@ -429,7 +1517,12 @@ impl RenetClient {
                     return;
                 };
 
-                if let Err(error) = channel.process_slice(slice) {
+                let result = match denied {
+                    true => channel.discard_slice(slice, packet_sequence, self.current_time),
+                    false => channel.process_slice(slice, packet_sequence, self.current_time),
+                };
+                if let Err(error) = result {
+                    log::error!("Error in receive channel {channel_id}: {error}");
                     self.disconnect_with_reason(DisconnectReason::ReceiveChannelError { channel_id, error });
                 }
             }
@@ -439,31 +1532,39 @@ impl RenetClient {
                     return;
                 };
 
-                if let Err(error) = channel.process_slice(slice, self.current_time) {
+                if let Err(error) = channel.process_slice(slice, packet_sequence, self.current_time) {
+                    log::error!("Error in receive channel {channel_id}: {error}");
                     self.disconnect_with_reason(DisconnectReason::ReceiveChannelError { channel_id, error });
                 }
             }
-            Packet::Ack { ack_ranges, .. } => {
+            Packet::Ack {
+                ack_ranges, nack_ranges, ..
+            } => {
                 // Create list with just new acks
                 // This prevents DoS from huge ack ranges
                 let mut new_acks: Vec<u64> = Vec::new();
                 for range in ack_ranges {
-                    for (&sequence, _) in self.sent_packets.range(range) {
+                    for (sequence, _) in self.sent_packets.range(range) {
                         new_acks.push(sequence)
                     }
                 }
 
                 for packet_sequence in new_acks {
-                    let sent_packet = self.sent_packets.remove(&packet_sequence).unwrap();
+                    let sent_packet = self.sent_packets.remove(packet_sequence).unwrap();
                     self.stats.acked_packet(sent_packet.sent_at, self.current_time);
 
                     // Update rtt
                     let rtt = (self.current_time - sent_packet.sent_at).as_secs_f64();
                     if self.rtt < f64::EPSILON {
                         self.rtt = rtt;
+                        self.rtt_variation = rtt / 2.;
                     } else {
+                        self.rtt_variation = self.rtt_variation * 0.75 + (self.rtt - rtt).abs() * 0.25;
                         self.rtt = self.rtt * 0.875 + rtt * 0.125;
+                        // Interarrival jitter as in RFC 3550, with the rtt samples instead of one way delays
+                        self.jitter += ((rtt - self.last_rtt_sample).abs() - self.jitter) / 16.;
                     }
+                    self.last_rtt_sample = rtt;
 
                     match sent_packet.info {
                         PacketSentInfo::ReliableMessages { channel_id, message_ids } => {
@@ -486,38 +1587,188 @@ impl RenetClient {
                         PacketSentInfo::None => {}
                     }
                 }
+
+                for reliable_channel in self.send_reliable_channels.values_mut() {
+                    reliable_channel.update_resend_time(self.rtt, self.rtt_variation);
+                }
+
+                for range in nack_ranges {
+                    for (_, sent_packet) in self.sent_packets.range(range) {
+                        match &sent_packet.info {
+                            PacketSentInfo::ReliableMessages { channel_id, message_ids } => {
+                                let reliable_channel = self.send_reliable_channels.get_mut(channel_id).unwrap();
+                                for &message_id in message_ids {
+                                    reliable_channel.process_message_nack(message_id);
+                                }
+                            }
+                            PacketSentInfo::ReliableSliceMessage {
+                                channel_id,
+                                message_id,
+                                slice_index,
+                            } => {
+                                let reliable_channel = self.send_reliable_channels.get_mut(channel_id).unwrap();
+                                reliable_channel.process_slice_message_nack(*message_id, *slice_index);
+                            }
+                            PacketSentInfo::Ack { .. } | PacketSentInfo::None => {}
+                        }
+                    }
+                }
             }
         }
     }
 
     /// Returns a list of packets to be sent to the server.
+    /// Can be called multiple times per tick, the packets generated in the tick share the `available_bytes_per_tick`,
+    /// and acks are only sent again when new packets were received, so the stats are not affected.
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn get_packets_to_send(&mut self) -> Vec<Payload> {
-        let mut packets: Vec<Packet> = vec![];
-        if self.is_disconnected() {
+        if self.is_disconnected() || self.paused {
+            return vec![];
+        }
+
+        let packets = self.generate_packets();
+
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+        let mut serialized_packets = Vec::with_capacity(packets.len());
+        for packet in packets {
+            let len = match packet.encode(&mut buffer[..self.max_packet_size]) {
+                Err(error) => {
+                    self.disconnect_with_reason(DisconnectReason::PacketSerialization {
+                        sequence: packet.sequence(),
+                        error,
+                    });
+                    return vec![];
+                }
+                Ok(len) => len,
+            };
+
+            log::trace!("Sent packet {} with {len} bytes", packet.sequence());
+
+            serialized_packets.push((buffer[..len].to_vec(), packet.payload_len()));
+        }
+
+        let bytes_sent: usize = serialized_packets.iter().map(|(packet, _)| packet.len()).sum();
+        let payload_bytes_sent: usize = serialized_packets.iter().map(|(_, payload_len)| payload_len).sum();
+        self.stats
+            .sent_packets(serialized_packets.len() as u64, bytes_sent as u64, payload_bytes_sent as u64);
+
+        serialized_packets.into_iter().map(|(packet, _)| packet).collect()
+    }
+
+    /// Returns the maximum size in bytes of the packets returned by [`RenetClient::get_packets_to_send`].
+    /// Default: 1400 bytes.
+    pub fn max_packet_size(&self) -> usize {
+        self.max_packet_size
+    }
+
+    /// Sets the maximum size of the packets returned by [`RenetClient::get_packets_to_send`],
+    /// limits above 1400 bytes are ignored.
+    /// Small messages are aggregated in smaller packets to fit the limit, but the `slice_size` of the channels
+    /// must fit in it, otherwise the connection is disconnected when sending sliced messages.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer, with the maximum payload it can send.
+    /// </p>
+    pub fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        let max_packet_size = max_packet_size.min(MAX_PACKET_SIZE);
+        if max_packet_size == self.max_packet_size {
+            return;
+        }
+
+        self.max_packet_size = max_packet_size;
+        let max_batch_size = max_packet_size.saturating_sub(MAX_PACKET_OVERHEAD);
+        let mut slice_sizes = Vec::with_capacity(self.channel_send_order.len());
+        for (channel_id, channel) in self.send_unreliable_channels.iter_mut() {
+            channel.set_max_batch_size(max_batch_size);
+            slice_sizes.push((*channel_id, channel.slice_size()));
+        }
+        for (channel_id, channel) in self.send_reliable_channels.iter_mut() {
+            channel.set_max_batch_size(max_batch_size);
+            slice_sizes.push((*channel_id, channel.slice_size()));
+        }
+        for (channel_id, channel) in self.send_redundant_channels.iter_mut() {
+            channel.set_max_batch_size(max_batch_size);
+            slice_sizes.push((*channel_id, channel.slice_size()));
+        }
+
+        for (channel_id, slice_size) in slice_sizes {
+            if slice_size > max_batch_size {
+                log::warn!(
+                    "Channel {channel_id} with slice size of {slice_size} bytes doesn't fit in packets of {max_packet_size} bytes, \
+                     the slice size should be at most {max_batch_size} bytes"
+                );
+            }
+        }
+    }
+
+    /// Returns the rate in bytes per second that the transport should spread the packets of each tick,
+    /// see [`ConnectionConfig::pacing_rate`].
+    pub fn pacing_rate(&self) -> Option<u64> {
+        self.pacing_rate
+    }
+
+    /// Returns whether the connection has nothing to send: no messages waiting to be sent or acked,
+    /// and the packets with messages received were already acked.
+    /// While idle, the connection doesn't need to be updated until a new packet is received or a new message is sent,
+    /// so low-rate games can stop ticking, see [`ConnectionConfig::turn_based`].
+    pub fn is_idle(&self) -> bool {
+        !self.has_unsent_acks
+            && !self.send_unreliable_channels.values().any(|channel| channel.has_pending_messages())
+            && !self.send_reliable_channels.values().any(|channel| channel.has_pending_messages())
+            && !self.send_redundant_channels.values().any(|channel| channel.has_pending_messages())
+    }
+
+    /// Returns a list of packets to be sent to a local connection, without going through serialization.
+    pub(crate) fn get_local_packets_to_send(&mut self) -> Vec<Packet> {
+        if self.is_disconnected() || self.paused {
             return vec![];
         }
 
-        let mut available_bytes = self.available_bytes_per_tick;
+        let packets = self.generate_packets();
+        // No bytes are sent over the wire for local connections
+        self.stats.sent_packets(packets.len() as u64, 0, 0);
+
+        packets
+    }
+
+    fn generate_packets(&mut self) -> Vec<Packet> {
+        let mut packets: Vec<Packet> = vec![];
+        let mut available_bytes = self.available_bytes;
         for order in self.channel_send_order.iter() {
             match order {
                 ChannelOrder::Reliable(channel_id) => {
                     let channel = self.send_reliable_channels.get_mut(channel_id).unwrap();
+                    let (sent_before, resent_before) = channel.send_counts();
                     packets.append(&mut channel.get_packets_to_send(&mut self.packet_sequence, &mut available_bytes, self.current_time));
+                    let (sent, resent) = channel.send_counts();
+                    self.stats.sent_reliable(sent - sent_before, resent - resent_before);
                 }
                 ChannelOrder::Unreliable(channel_id) => {
                     let channel = self.send_unreliable_channels.get_mut(channel_id).unwrap();
+                    packets.append(&mut channel.get_packets_to_send(&mut self.packet_sequence, &mut available_bytes, self.current_time));
+                }
+                ChannelOrder::Redundant(channel_id) => {
+                    let channel = self.send_redundant_channels.get_mut(channel_id).unwrap();
                     packets.append(&mut channel.get_packets_to_send(&mut self.packet_sequence, &mut available_bytes));
                 }
             }
         }
 
-        if !self.pending_acks.is_empty() {
+        debug_assert!(
+            available_bytes <= self.available_bytes,
+            "channels consumed more bytes than available"
+        );
+        self.available_bytes = available_bytes;
+
+        if self.should_send_ack && !self.pending_acks.is_empty() {
+            self.should_send_ack = false;
+            self.has_unsent_acks = false;
             let ack_packet = Packet::Ack {
                 sequence: self.packet_sequence,
                 ack_ranges: self.pending_acks.clone(),
+                nack_ranges: self.new_nack_ranges(),
             };
             self.packet_sequence += 1;
             packets.push(ack_packet);
@@ -568,7 +1819,7 @@ impl RenetClient {
                         },
                     );
                 }
-                Packet::UnreliableSlice { sequence, .. } => {
+                Packet::UnreliableSlice { sequence, .. } | Packet::SmallRedundant { sequence, .. } => {
                     self.sent_packets.insert(
                         *sequence,
                         PacketSent {
@@ -577,7 +1828,7 @@ impl RenetClient {
                         },
                     );
                 }
-                Packet::Ack { sequence, ack_ranges } => {
+                Packet::Ack { sequence, ack_ranges, .. } => {
                     let last_range = ack_ranges.last().unwrap();
                     let largest_acked_packet = last_range.end - 1;
                     self.sent_packets.insert(
@@ -591,29 +1842,12 @@ impl RenetClient {
             }
         }
 
-        let mut buffer = [0u8; 1400];
-        let mut serialized_packets = Vec::with_capacity(packets.len());
-        let mut bytes_sent: u64 = 0;
-        for packet in packets {
-            let mut oct = OctetsMut::with_slice(&mut buffer);
-            let len = match packet.to_bytes(&mut oct) {
-                Err(err) => {
-                    self.disconnect_with_reason(DisconnectReason::PacketSerialization(err));
-                    return vec![];
-                }
-                Ok(len) => len,
-            };
-
-            bytes_sent += len as u64;
-            serialized_packets.push(buffer[..len].to_vec());
-        }
-
-        self.stats.sent_packets(serialized_packets.len() as u64, bytes_sent);
-
-        serialized_packets
+        packets
     }
 
     fn add_pending_ack(&mut self, sequence: u64) {
+        // Decoded sequences are never above MAX_SEQUENCE, so the ranges end never overflows
+        debug_assert!(sequence <= MAX_SEQUENCE);
         if self.pending_acks.is_empty() {
             self.pending_acks.push(sequence..sequence + 1);
             return;
@@ -647,6 +1881,7 @@ impl RenetClient {
                 // New sequence is before this range and not extensible to it
                 // Add new range to the left
                 self.pending_acks.insert(index, sequence..sequence + 1);
+                self.limit_pending_acks();
                 return;
             }
         }
@@ -654,13 +1889,36 @@ impl RenetClient {
         // New sequence was not before or adjacent to any range
         // Add new range with only this sequence at the end
         self.pending_acks.push(sequence..sequence + 1);
+        self.limit_pending_acks();
+    }
 
-        // Limit to 64 pending ranges
+    // Limit to 64 pending ranges, removing the oldest one.
+    // Old packets received out of order can also add ranges, not only new ones.
+    fn limit_pending_acks(&mut self) {
         if self.pending_acks.len() > 64 {
             self.pending_acks.remove(0);
         }
     }
 
+    // Returns the gaps between the pending acks that were not reported yet.
+    // Each lost packet is only reported once, if the report is lost the sender falls back to its resend time.
+    fn new_nack_ranges(&mut self) -> Vec<Range<u64>> {
+        let mut nack_ranges = vec![];
+        for window in self.pending_acks.windows(2) {
+            let start = window[0].end.max(self.next_nack_sequence);
+            let end = window[1].start;
+            if start < end {
+                nack_ranges.push(start..end);
+            }
+        }
+
+        if let Some(last_range) = self.pending_acks.last() {
+            self.next_nack_sequence = self.next_nack_sequence.max(last_range.start);
+        }
+
+        nack_ranges
+    }
+
     fn acked_largest(&mut self, largest_ack: u64) {
         while !self.pending_acks.is_empty() {
             let range: &mut Range<u64> = &mut self.pending_acks[0];
@@ -689,6 +1947,7 @@ impl RenetClient {
 
     pub(crate) fn disconnect_with_reason(&mut self, reason: DisconnectReason) {
         if !self.is_disconnected() {
+            log::debug!("Connection disconnected: {reason}");
             self.connection_status = RenetConnectionStatus::Disconnected { reason };
         }
     }
@@ -726,6 +1985,75 @@ mod tests {
         assert_eq!(connection.pending_acks, vec![0..8]);
     }
 
+    #[test]
+    fn pending_acks_extreme_sequences() {
+        let mut connection = RenetClient::new(ConnectionConfig::default());
+        connection.add_pending_ack(MAX_SEQUENCE);
+        connection.add_pending_ack(0);
+        connection.add_pending_ack(MAX_SEQUENCE - 1);
+        assert_eq!(connection.pending_acks, vec![0..1, MAX_SEQUENCE - 1..MAX_SEQUENCE + 1]);
+        assert_eq!(connection.new_nack_ranges(), vec![1..MAX_SEQUENCE - 1]);
+
+        connection.acked_largest(MAX_SEQUENCE);
+        assert!(connection.pending_acks.is_empty());
+    }
+
+    #[test]
+    fn pending_acks_limit() {
+        let mut connection = RenetClient::new(ConnectionConfig::default());
+        // Old packets received in descending order also add ranges
+        for sequence in (0..500).rev().map(|i| i * 2) {
+            connection.add_pending_ack(sequence);
+        }
+        assert_eq!(connection.pending_acks.len(), 64);
+        assert_eq!(connection.pending_acks.last().unwrap(), &(998..999));
+
+        for sequence in (1000..2000).step_by(2) {
+            connection.add_pending_ack(sequence);
+        }
+        assert_eq!(connection.pending_acks.len(), 64);
+        assert_eq!(connection.pending_acks.last().unwrap(), &(1998..1999));
+    }
+
+    #[test]
+    fn high_packet_sequences() {
+        // Connections that lasted billions of packets, and the last sequences that can be serialized
+        for start_sequence in [u32::MAX as u64 - 5, MAX_SEQUENCE - 20] {
+            let mut client = RenetClient::new(ConnectionConfig::default());
+            let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+            client.set_connected();
+            server.set_connected();
+            client.packet_sequence = start_sequence;
+            server.packet_sequence = start_sequence;
+
+            for i in 0..10u8 {
+                client.send_message(DefaultChannel::ReliableOrdered, vec![i]);
+                for packet in client.get_packets_to_send() {
+                    server.process_packet(&packet);
+                }
+                for packet in server.get_packets_to_send() {
+                    client.process_packet(&packet);
+                }
+                assert_eq!(server.receive_message(DefaultChannel::ReliableOrdered).unwrap(), vec![i]);
+            }
+
+            assert!(client.is_connected());
+            assert!(server.is_connected());
+            assert_eq!(client.sent_packets.len(), 0);
+            assert!(client.packet_sequence > start_sequence + 10);
+        }
+
+        // Sequences above the limit can't be serialized
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        client.packet_sequence = MAX_SEQUENCE + 1;
+        client.send_message(DefaultChannel::Unreliable, vec![0]);
+        assert!(client.get_packets_to_send().is_empty());
+        assert!(matches!(
+            client.disconnect_reason(),
+            Some(DisconnectReason::PacketSerialization { .. })
+        ));
+    }
+
     #[test]
     fn ack_pending_acks() {
         let mut connection = RenetClient::new(ConnectionConfig::default());
@@ -752,6 +2080,117 @@ mod tests {
         assert_eq!(connection.pending_acks, vec![]);
     }
 
+    #[test]
+    fn nack_ranges() {
+        let mut connection = RenetClient::new(ConnectionConfig::default());
+        connection.add_pending_ack(0);
+        connection.add_pending_ack(3);
+        connection.add_pending_ack(4);
+        assert_eq!(connection.new_nack_ranges(), vec![1..3]);
+        // Lost packets are only reported once
+        assert_eq!(connection.new_nack_ranges(), vec![]);
+
+        connection.add_pending_ack(8);
+        assert_eq!(connection.new_nack_ranges(), vec![5..8]);
+
+        // Packet arrived later, it's no longer a gap
+        connection.add_pending_ack(6);
+        connection.add_pending_ack(10);
+        assert_eq!(connection.new_nack_ranges(), vec![9..10]);
+    }
+
+    #[test]
+    fn pacing() {
+        let config = ConnectionConfig {
+            pacing_rate: Some(60_000),
+            ..Default::default()
+        };
+        let mut connection = RenetClient::new(config);
+        connection.set_connected();
+        for _ in 0..3 {
+            connection.send_message(DefaultChannel::ReliableOrdered, vec![0; 1000]);
+        }
+
+        // At 60_000 bytes/s a 16ms tick sends 960 bytes, rounded up to one packet
+        for _ in 0..3 {
+            connection.update(Duration::from_millis(16));
+            assert_eq!(connection.get_packets_to_send().len(), 1);
+        }
+        connection.update(Duration::from_millis(16));
+        assert!(connection.get_packets_to_send().is_empty());
+
+        // Longer ticks send more packets, unused budget is not accumulated between ticks
+        for _ in 0..10 {
+            connection.send_message(DefaultChannel::ReliableOrdered, vec![0; 1000]);
+        }
+        connection.update(Duration::from_millis(50));
+        assert_eq!(connection.get_packets_to_send().len(), 3);
+    }
+
+    #[test]
+    fn jitter() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+
+        // The acks arrive alternating between 10ms and 50ms
+        for i in 0..40 {
+            client.send_message(DefaultChannel::Unreliable, vec![0; 10]);
+            for packet in client.get_packets_to_send() {
+                server.process_packet(&packet);
+            }
+            server.update(Duration::ZERO);
+            let acks = server.get_packets_to_send();
+            let delay = if i % 2 == 1 { 50 } else { 10 };
+            client.update(Duration::from_millis(delay));
+            for packet in acks {
+                client.process_packet(&packet);
+            }
+        }
+
+        let network_info = client.network_info();
+        assert_eq!(network_info.jitter, client.jitter());
+        assert!(network_info.jitter > 0.01 && network_info.jitter < 0.04);
+        assert!(network_info.rtt_variation > 0.0);
+    }
+
+    #[test]
+    fn recommended_bytes_per_tick() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+        client.set_connected();
+        server.set_connected();
+        assert_eq!(client.estimated_bandwidth(), None);
+        assert_eq!(client.recommended_bytes_per_tick(), 60_000);
+        assert!(!client.can_increase_send_rate());
+
+        // 8 seconds at 10hz, 1000 bytes per tick
+        for _ in 0..80 {
+            client.update(Duration::from_millis(100));
+            server.update(Duration::from_millis(100));
+            client.send_message(DefaultChannel::Unreliable, vec![0; 1000]);
+            for packet in client.get_packets_to_send() {
+                server.process_packet(&packet);
+            }
+            for packet in server.get_packets_to_send() {
+                client.process_packet(&packet);
+            }
+        }
+
+        // The estimate probes above the bytes sent, but is capped by the available bytes
+        let estimate = client.estimated_bandwidth().unwrap();
+        assert!(estimate > client.bytes_sent_per_sec());
+        assert!(client.can_increase_send_rate());
+        let recommended = client.recommended_bytes_per_tick();
+        assert!(recommended > 1000 && recommended < 60_000);
+
+        // Applying the recommendation doesn't change the next recommendations
+        client.set_available_bytes_per_tick(recommended);
+        assert_eq!(client.available_bytes_per_tick(), recommended);
+        assert_eq!(client.recommended_bytes_per_tick(), recommended);
+    }
+
     #[test]
     fn discard_old_packets() {
         let mut connection = RenetClient::new(ConnectionConfig::default());
@@ -767,4 +2206,95 @@ mod tests {
         connection.update(Duration::from_secs(4));
         assert_eq!(connection.sent_packets.len(), 0);
     }
+
+    #[test]
+    fn sent_packet_discard_time() {
+        let config = ConnectionConfig {
+            sent_packet_discard_time: Duration::from_secs(10),
+            ..Default::default()
+        };
+        let mut connection = RenetClient::new(config);
+        connection.send_message(DefaultChannel::Unreliable, vec![5; 5]);
+        connection.get_packets_to_send();
+
+        connection.update(Duration::from_secs(5));
+        connection.send_message(DefaultChannel::Unreliable, vec![5; 5]);
+        connection.get_packets_to_send();
+        assert_eq!(connection.sent_packets.len(), 2);
+
+        // Only the packets sent before the discard time are removed
+        connection.update(Duration::from_secs(6));
+        assert_eq!(connection.sent_packets.len(), 1);
+        assert_eq!(connection.sent_packets.first().unwrap().0, 1);
+    }
+
+    #[test]
+    fn wire_format_mismatch() {
+        let mut connection = RenetClient::new(ConnectionConfig::default());
+        let mut packet = [0u8; 1400];
+        let len = Packet::SmallUnreliable {
+            sequence: 0,
+            channel_id: 0,
+            messages: vec![],
+        }
+        .encode(&mut packet)
+        .unwrap();
+
+        // Packet without the version byte
+        connection.process_packet(&packet[1..len]);
+        assert_eq!(
+            connection.disconnect_reason(),
+            Some(DisconnectReason::WireFormatMismatch {
+                version: WIRE_FORMAT_VERSION,
+                remote_version: 0
+            })
+        );
+    }
+
+    #[test]
+    fn turn_based_idle() {
+        let mut client = RenetClient::new(ConnectionConfig::turn_based());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::turn_based());
+        assert!(client.is_idle());
+
+        client.send_message(DefaultChannel::ReliableOrdered, vec![1, 2, 3]);
+        assert!(!client.is_idle());
+
+        for _ in 0..2 {
+            client.update(Duration::from_secs(1));
+            server.update(Duration::from_secs(1));
+            for packet in client.get_packets_to_send() {
+                server.process_packet(&packet);
+            }
+            for packet in server.get_packets_to_send() {
+                client.process_packet(&packet);
+            }
+        }
+
+        // The message was acked, only the acks of the acks are still sent
+        assert_eq!(server.receive_message(DefaultChannel::ReliableOrdered).unwrap(), vec![1, 2, 3]);
+        assert!(client.is_idle());
+        assert!(server.is_idle());
+    }
+
+    #[test]
+    fn paused_client() {
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        let mut server = RenetClient::new_from_server(ConnectionConfig::default());
+
+        client.pause();
+        assert!(client.is_paused());
+        client.send_message(DefaultChannel::ReliableOrdered, vec![1, 2, 3]);
+        client.update(Duration::from_secs(60));
+        assert_eq!(client.current_time, Duration::ZERO);
+        assert!(client.get_packets_to_send().is_empty());
+
+        // Messages queued while paused are sent after resuming
+        client.resume();
+        client.update(Duration::from_millis(16));
+        for packet in client.get_packets_to_send() {
+            server.process_packet(&packet);
+        }
+        assert_eq!(server.receive_message(DefaultChannel::ReliableOrdered).unwrap(), vec![1, 2, 3]);
+    }
 }
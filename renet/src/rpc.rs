@@ -0,0 +1,440 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::task::Poll;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::{ClientId, RenetClient, RenetServer, RpcError};
+
+const REQUEST: u8 = 0;
+const RESPONSE: u8 = 1;
+const ERROR: u8 = 2;
+
+const UNKNOWN_METHOD: u8 = 0;
+const INVALID_REQUEST: u8 = 1;
+
+// Message type and call id
+const HEADER_BYTES: usize = 1 + 8;
+
+/// A message that can be sent in a remote procedure call.
+pub trait RpcMessage: Sized {
+    fn to_bytes(&self) -> Bytes;
+
+    /// Returns None if the bytes are not a valid message.
+    fn from_bytes(bytes: Bytes) -> Option<Self>;
+}
+
+/// A remote procedure, called by the clients and handled by the server.
+///
+/// # Usage
+/// ```
+/// # use renet::{Bytes, RpcMethod, RpcMessage};
+/// struct JoinLobby;
+///
+/// impl RpcMethod for JoinLobby {
+///     const ID: u16 = 0;
+///     // Lobby name
+///     type Request = String;
+///     type Response = LobbyInfo;
+/// }
+///
+/// struct LobbyInfo {
+///     players: u8,
+/// }
+///
+/// impl RpcMessage for LobbyInfo {
+///     fn to_bytes(&self) -> Bytes {
+///         Bytes::from(vec![self.players])
+///     }
+///
+///     fn from_bytes(bytes: Bytes) -> Option<Self> {
+///         Some(LobbyInfo { players: *bytes.first()? })
+///     }
+/// }
+/// ```
+pub trait RpcMethod {
+    /// Identifier of the method, must be unique for each method.
+    const ID: u16;
+    type Request: RpcMessage;
+    type Response: RpcMessage;
+}
+
+impl RpcMessage for Bytes {
+    fn to_bytes(&self) -> Bytes {
+        self.clone()
+    }
+
+    fn from_bytes(bytes: Bytes) -> Option<Self> {
+        Some(bytes)
+    }
+}
+
+impl RpcMessage for Vec<u8> {
+    fn to_bytes(&self) -> Bytes {
+        Bytes::copy_from_slice(self)
+    }
+
+    fn from_bytes(bytes: Bytes) -> Option<Self> {
+        Some(bytes.into())
+    }
+}
+
+impl RpcMessage for String {
+    fn to_bytes(&self) -> Bytes {
+        Bytes::copy_from_slice(self.as_bytes())
+    }
+
+    fn from_bytes(bytes: Bytes) -> Option<Self> {
+        String::from_utf8(bytes.into()).ok()
+    }
+}
+
+impl RpcMessage for () {
+    fn to_bytes(&self) -> Bytes {
+        Bytes::new()
+    }
+
+    fn from_bytes(_: Bytes) -> Option<Self> {
+        Some(())
+    }
+}
+
+/// Handle of a remote procedure call, used to poll its response with [`RpcClient::poll`].
+pub struct RpcCall<M> {
+    id: u64,
+    method: PhantomData<fn() -> M>,
+}
+
+impl<M> RpcCall<M> {
+    /// Returns the correlation id of the call.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl<M> Clone for RpcCall<M> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<M> Copy for RpcCall<M> {}
+
+impl<M> std::fmt::Debug for RpcCall<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RpcCall").field("id", &self.id).finish()
+    }
+}
+
+/// Calls remote procedures in the server, see [`RpcServer`].
+///
+/// The channel used must be reliable, and only used for the remote procedure calls.
+///
+/// # Usage
+/// ```
+/// # use renet::{ConnectionConfig, DefaultChannel, RenetClient, RpcClient, RpcMethod};
+/// # use std::{task::Poll, time::Duration};
+/// # struct JoinLobby;
+/// # impl RpcMethod for JoinLobby { const ID: u16 = 0; type Request = String; type Response = String; }
+/// # let mut client = RenetClient::new(ConnectionConfig::default());
+/// # let duration = Duration::from_millis(16);
+/// let mut rpc = RpcClient::new(DefaultChannel::ReliableOrdered);
+/// let call = rpc.call::<JoinLobby>(&mut client, &"lobby".to_string(), Duration::from_secs(5));
+///
+/// // Every update
+/// rpc.update(duration, &mut client);
+/// match rpc.poll(&call) {
+///     Poll::Ready(Ok(response)) => { /* Handle response */ }
+///     Poll::Ready(Err(e)) => { /* Timed out or failed in the server */ }
+///     Poll::Pending => {}
+/// }
+/// ```
+#[derive(Debug)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
+pub struct RpcClient {
+    channel_id: u8,
+    next_call_id: u64,
+    current_time: Duration,
+    // Calls waiting for a response, with the time they time out
+    pending_calls: HashMap<u64, Duration>,
+    // Results not polled yet
+    results: HashMap<u64, Result<Bytes, RpcError>>,
+}
+
+impl RpcClient {
+    pub fn new<I: Into<u8>>(channel_id: I) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            next_call_id: 0,
+            current_time: Duration::ZERO,
+            pending_calls: HashMap::new(),
+            results: HashMap::new(),
+        }
+    }
+
+    /// Sends the request to the server, the response is available with [`RpcClient::poll`] until the timeout.
+    pub fn call<M: RpcMethod>(&mut self, client: &mut RenetClient, request: &M::Request, timeout: Duration) -> RpcCall<M> {
+        let id = self.next_call_id;
+        self.next_call_id += 1;
+
+        let request = request.to_bytes();
+        let mut message = Vec::with_capacity(HEADER_BYTES + 2 + request.len());
+        message.push(REQUEST);
+        message.extend_from_slice(&id.to_le_bytes());
+        message.extend_from_slice(&M::ID.to_le_bytes());
+        message.extend_from_slice(&request);
+        client.send_message(self.channel_id, message);
+
+        self.pending_calls.insert(id, self.current_time + timeout);
+        RpcCall { id, method: PhantomData }
+    }
+
+    /// Advances the call timeouts and receives the responses from the server.
+    pub fn update(&mut self, duration: Duration, client: &mut RenetClient) {
+        self.current_time += duration;
+
+        while let Some(message) = client.receive_message(self.channel_id) {
+            if message.len() < HEADER_BYTES {
+                log::warn!("Received invalid remote procedure response in channel {}", self.channel_id);
+                continue;
+            }
+
+            let id = u64::from_le_bytes(message[1..HEADER_BYTES].try_into().unwrap());
+            let result = match (message[0], message.get(HEADER_BYTES)) {
+                (RESPONSE, _) => Ok(message.slice(HEADER_BYTES..)),
+                (ERROR, Some(&UNKNOWN_METHOD)) => Err(RpcError::UnknownMethod),
+                (ERROR, Some(&INVALID_REQUEST)) => Err(RpcError::InvalidRequest),
+                _ => {
+                    log::warn!("Received invalid remote procedure response in channel {}", self.channel_id);
+                    continue;
+                }
+            };
+
+            // Responses for calls that timed out or were canceled are discarded
+            if self.pending_calls.remove(&id).is_some() {
+                self.results.insert(id, result);
+            }
+        }
+
+        let current_time = self.current_time;
+        let results = &mut self.results;
+        self.pending_calls.retain(|&id, &mut timeout_time| {
+            let timed_out = current_time >= timeout_time;
+            if timed_out {
+                results.insert(id, Err(RpcError::TimedOut));
+            }
+            !timed_out
+        });
+    }
+
+    /// Returns the response of the call, once ready the result is only returned once.
+    pub fn poll<M: RpcMethod>(&mut self, call: &RpcCall<M>) -> Poll<Result<M::Response, RpcError>> {
+        let Some(result) = self.results.remove(&call.id) else {
+            return Poll::Pending;
+        };
+
+        Poll::Ready(result.and_then(|response| M::Response::from_bytes(response).ok_or(RpcError::InvalidResponse)))
+    }
+
+    /// Cancels the call, its response is discarded.
+    pub fn cancel<M>(&mut self, call: &RpcCall<M>) {
+        self.pending_calls.remove(&call.id);
+        self.results.remove(&call.id);
+    }
+
+    /// Returns the number of calls waiting for a response.
+    pub fn pending_calls(&self) -> usize {
+        self.pending_calls.len()
+    }
+}
+
+/// Callback that handles a serialized request, returns the serialized response.
+type RpcHandlerFn = dyn FnMut(ClientId, Bytes) -> Option<Bytes> + Send + Sync;
+
+struct RpcHandler(Box<RpcHandlerFn>);
+
+impl std::fmt::Debug for RpcHandler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("RpcHandler")
+    }
+}
+
+/// Handles the remote procedures called by the clients, see [`RpcClient`].
+///
+/// # Usage
+/// ```
+/// # use renet::{ConnectionConfig, DefaultChannel, RenetServer, RpcMethod, RpcServer};
+/// # struct JoinLobby;
+/// # impl RpcMethod for JoinLobby { const ID: u16 = 0; type Request = String; type Response = String; }
+/// # let mut server = RenetServer::new(ConnectionConfig::default());
+/// let mut rpc = RpcServer::new(DefaultChannel::ReliableOrdered);
+/// rpc.register::<JoinLobby, _>(|client_id, lobby| format!("client {client_id} joined {lobby}"));
+///
+/// // Every update
+/// rpc.update(&mut server);
+/// ```
+#[derive(Debug)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
+pub struct RpcServer {
+    channel_id: u8,
+    handlers: HashMap<u16, RpcHandler>,
+}
+
+impl RpcServer {
+    pub fn new<I: Into<u8>>(channel_id: I) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Registers the handler for the method, replacing the previous handler.
+    pub fn register<M, F>(&mut self, mut handler: F)
+    where
+        M: RpcMethod + 'static,
+        F: FnMut(ClientId, M::Request) -> M::Response + Send + Sync + 'static,
+    {
+        let handler = move |client_id, request| {
+            let request = M::Request::from_bytes(request)?;
+            Some(handler(client_id, request).to_bytes())
+        };
+        self.handlers.insert(M::ID, RpcHandler(Box::new(handler)));
+    }
+
+    /// Removes the handler for the method, the calls to it fail with [`RpcError::UnknownMethod`].
+    pub fn unregister<M: RpcMethod>(&mut self) {
+        self.handlers.remove(&M::ID);
+    }
+
+    /// Receives the requests from all clients and sends the responses.
+    pub fn update(&mut self, server: &mut RenetServer) {
+        for client_id in server.clients_id() {
+            while let Some(message) = server.receive_message(client_id, self.channel_id) {
+                if message.len() < HEADER_BYTES + 2 || message[0] != REQUEST {
+                    log::warn!("Received invalid remote procedure request from client {client_id}");
+                    continue;
+                }
+
+                let id = &message[1..HEADER_BYTES];
+                let method_id = u16::from_le_bytes(message[HEADER_BYTES..HEADER_BYTES + 2].try_into().unwrap());
+                let result = match self.handlers.get_mut(&method_id) {
+                    None => Err(UNKNOWN_METHOD),
+                    Some(handler) => (handler.0)(client_id, message.slice(HEADER_BYTES + 2..)).ok_or(INVALID_REQUEST),
+                };
+
+                let response = match result {
+                    Ok(response) => {
+                        let mut reply = Vec::with_capacity(HEADER_BYTES + response.len());
+                        reply.push(RESPONSE);
+                        reply.extend_from_slice(id);
+                        reply.extend_from_slice(&response);
+                        reply
+                    }
+                    Err(error) => {
+                        log::debug!("Remote procedure {method_id} from client {client_id} failed with error {error}");
+                        let mut reply = Vec::with_capacity(HEADER_BYTES + 1);
+                        reply.push(ERROR);
+                        reply.extend_from_slice(id);
+                        reply.push(error);
+                        reply
+                    }
+                };
+                server.send_message(client_id, self.channel_id, response);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConnectionConfig, DefaultChannel};
+
+    struct Echo;
+
+    impl RpcMethod for Echo {
+        const ID: u16 = 0;
+        type Request = String;
+        type Response = String;
+    }
+
+    struct Unregistered;
+
+    impl RpcMethod for Unregistered {
+        const ID: u16 = 1;
+        type Request = ();
+        type Response = ();
+    }
+
+    #[test]
+    fn call() {
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let mut client = server.new_local_client(0);
+        let mut rpc_server = RpcServer::new(DefaultChannel::ReliableOrdered);
+        let mut rpc_client = RpcClient::new(DefaultChannel::ReliableOrdered);
+        rpc_server.register::<Echo, _>(|client_id, request| format!("{client_id}: {request}"));
+
+        let echo = rpc_client.call::<Echo>(&mut client, &"hello".to_string(), Duration::from_secs(1));
+        let unregistered = rpc_client.call::<Unregistered>(&mut client, &(), Duration::from_secs(1));
+        assert_eq!(rpc_client.pending_calls(), 2);
+        assert_eq!(rpc_client.poll(&echo), Poll::Pending);
+
+        server.process_local_client(0, &mut client).unwrap();
+        rpc_server.update(&mut server);
+        server.process_local_client(0, &mut client).unwrap();
+        rpc_client.update(Duration::ZERO, &mut client);
+
+        assert_eq!(rpc_client.pending_calls(), 0);
+        assert_eq!(rpc_client.poll(&echo), Poll::Ready(Ok("0: hello".to_string())));
+        assert_eq!(rpc_client.poll(&unregistered), Poll::Ready(Err(RpcError::UnknownMethod)));
+        // Results are only returned once
+        assert_eq!(rpc_client.poll(&echo), Poll::Pending);
+    }
+
+    #[test]
+    fn timeout() {
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let mut client = server.new_local_client(0);
+        let mut rpc_server = RpcServer::new(DefaultChannel::ReliableOrdered);
+        let mut rpc_client = RpcClient::new(DefaultChannel::ReliableOrdered);
+        rpc_server.register::<Echo, _>(|_, request| request);
+
+        let call = rpc_client.call::<Echo>(&mut client, &"hello".to_string(), Duration::from_secs(1));
+        rpc_client.update(Duration::from_millis(500), &mut client);
+        assert_eq!(rpc_client.poll(&call), Poll::Pending);
+        rpc_client.update(Duration::from_millis(500), &mut client);
+        assert_eq!(rpc_client.poll(&call), Poll::Ready(Err(RpcError::TimedOut)));
+
+        // Late responses are discarded
+        server.process_local_client(0, &mut client).unwrap();
+        rpc_server.update(&mut server);
+        server.process_local_client(0, &mut client).unwrap();
+        rpc_client.update(Duration::ZERO, &mut client);
+        assert_eq!(rpc_client.poll(&call), Poll::Pending);
+    }
+
+    #[test]
+    fn invalid_request() {
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let mut client = server.new_local_client(0);
+        let mut rpc_server = RpcServer::new(DefaultChannel::ReliableOrdered);
+        let mut rpc_client = RpcClient::new(DefaultChannel::ReliableOrdered);
+        rpc_server.register::<Echo, _>(|_, request| request);
+
+        // Call with a request that is not a valid utf8 string
+        struct InvalidEcho;
+        impl RpcMethod for InvalidEcho {
+            const ID: u16 = Echo::ID;
+            type Request = Vec<u8>;
+            type Response = String;
+        }
+
+        let call = rpc_client.call::<InvalidEcho>(&mut client, &vec![0xFF], Duration::from_secs(1));
+        server.process_local_client(0, &mut client).unwrap();
+        rpc_server.update(&mut server);
+        server.process_local_client(0, &mut client).unwrap();
+        rpc_client.update(Duration::ZERO, &mut client);
+        assert_eq!(rpc_client.poll(&call), Poll::Ready(Err(RpcError::InvalidRequest)));
+    }
+}
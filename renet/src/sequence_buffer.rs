@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// Ring buffer of values keyed by increasing sequences, with O(1) insert, access and removal.
+///
+/// Sequences must be inserted in increasing order, removed entries leave an empty slot
+/// until all the entries before them are also removed.
+#[derive(Debug)]
+pub(crate) struct SequenceBuffer<T> {
+    // Sequence of the first slot in the entries
+    first_sequence: u64,
+    entries: VecDeque<Option<T>>,
+}
+
+impl<T> Default for SequenceBuffer<T> {
+    fn default() -> Self {
+        Self {
+            first_sequence: 0,
+            entries: VecDeque::new(),
+        }
+    }
+}
+
+impl<T> SequenceBuffer<T> {
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.values().count()
+    }
+
+    /// Number of slots in the buffer, including the empty ones.
+    pub fn capacity(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Inserts the value with the sequence, the sequence must be bigger than any previous sequence inserted.
+    pub fn insert(&mut self, sequence: u64, value: T) {
+        if self.entries.is_empty() {
+            self.first_sequence = sequence;
+        }
+
+        let end_sequence = self.first_sequence + self.entries.len() as u64;
+        assert!(sequence >= end_sequence, "sequence {sequence} inserted out of order");
+        for _ in end_sequence..sequence {
+            self.entries.push_back(None);
+        }
+
+        self.entries.push_back(Some(value));
+    }
+
+    pub fn get(&self, sequence: u64) -> Option<&T> {
+        let index = self.index(sequence)?;
+        self.entries[index].as_ref()
+    }
+
+    pub fn remove(&mut self, sequence: u64) -> Option<T> {
+        let index = self.index(sequence)?;
+        let value = self.entries[index].take()?;
+        self.remove_empty_front();
+
+        Some(value)
+    }
+
+    /// Returns the value with the lowest sequence.
+    pub fn first(&self) -> Option<(u64, &T)> {
+        let value = self.entries.front()?.as_ref()?;
+        Some((self.first_sequence, value))
+    }
+
+    /// Removes the value with the lowest sequence.
+    pub fn pop_first(&mut self) -> Option<(u64, T)> {
+        let sequence = self.first_sequence;
+        let value = self.remove(sequence)?;
+        Some((sequence, value))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.entries.iter().flatten()
+    }
+
+    /// Returns the values with sequences in the range, in order.
+    /// Only the stored sequences are iterated, so big ranges are cheap.
+    pub fn range(&self, range: Range<u64>) -> impl Iterator<Item = (u64, &T)> {
+        let end_sequence = self.first_sequence + self.entries.len() as u64;
+        let start = range.start.clamp(self.first_sequence, end_sequence);
+        let end = range.end.clamp(start, end_sequence);
+
+        (start..end).filter_map(|sequence| self.get(sequence).map(|value| (sequence, value)))
+    }
+
+    fn index(&self, sequence: u64) -> Option<usize> {
+        if sequence < self.first_sequence {
+            return None;
+        }
+
+        let index = (sequence - self.first_sequence) as usize;
+        if index >= self.entries.len() {
+            return None;
+        }
+
+        Some(index)
+    }
+
+    fn remove_empty_front(&mut self) {
+        while let Some(None) = self.entries.front() {
+            self.entries.pop_front();
+            self.first_sequence += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_remove() {
+        let mut buffer: SequenceBuffer<u64> = SequenceBuffer::default();
+        for sequence in [5, 6, 8] {
+            buffer.insert(sequence, sequence * 10);
+        }
+        assert_eq!(buffer.len(), 3);
+        assert_eq!(buffer.capacity(), 4);
+        assert_eq!(buffer.get(7), None);
+        assert_eq!(buffer.get(8), Some(&80));
+        assert_eq!(buffer.first(), Some((5, &50)));
+
+        // Removing in the middle leaves an empty slot
+        assert_eq!(buffer.remove(6), Some(60));
+        assert_eq!(buffer.remove(6), None);
+        assert_eq!(buffer.capacity(), 4);
+
+        // Removing the first entry also removes the empty slots after it
+        assert_eq!(buffer.pop_first(), Some((5, 50)));
+        assert_eq!(buffer.first(), Some((8, &80)));
+        assert_eq!(buffer.capacity(), 1);
+
+        assert_eq!(buffer.remove(8), Some(80));
+        assert_eq!(buffer.len(), 0);
+        assert_eq!(buffer.capacity(), 0);
+        assert_eq!(buffer.first(), None);
+    }
+
+    #[test]
+    fn range() {
+        let mut buffer: SequenceBuffer<u64> = SequenceBuffer::default();
+        for sequence in 10..20 {
+            buffer.insert(sequence, sequence);
+        }
+        buffer.remove(12);
+
+        let values: Vec<u64> = buffer.range(0..14).map(|(sequence, _)| sequence).collect();
+        assert_eq!(values, vec![10, 11, 13]);
+
+        let values: Vec<u64> = buffer.range(18..u64::MAX).map(|(sequence, _)| sequence).collect();
+        assert_eq!(values, vec![18, 19]);
+
+        assert_eq!(buffer.range(30..40).count(), 0);
+        assert_eq!(buffer.values().count(), 9);
+    }
+}
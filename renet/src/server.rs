@@ -1,34 +1,200 @@
-use crate::error::{ClientNotFound, DisconnectReason};
+use crate::channel::{ReceivedMessage, SliceStats};
+use crate::control::ControlMessage;
+use crate::error::{ClientNotFound, ConnectionDeniedReason, DisconnectReason, InvalidChannelId, SendError};
+use crate::middleware::Middleware;
+use crate::observer::{PacketDirection, PacketObserver};
 use crate::packet::Payload;
+use crate::rate_limiter::RateLimiter;
 use crate::remote_connection::{ConnectionConfig, NetworkInfo, RenetClient};
 use crate::ClientId;
-use std::collections::{HashMap, VecDeque};
-use std::time::Duration;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 
 use bytes::Bytes;
 
+// Connection denied events come from unauthenticated packets, only this many are queued until read
+const MAX_CONNECTION_DENIED_EVENTS: usize = 64;
+
 /// Connection and disconnection events in the server.
 #[derive(Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Event))]
+#[non_exhaustive]
 pub enum ServerEvent {
-    ClientConnected { client_id: ClientId },
-    ClientDisconnected { client_id: ClientId, reason: DisconnectReason },
+    ClientConnected {
+        client_id: ClientId,
+    },
+    ClientDisconnected {
+        client_id: ClientId,
+        reason: DisconnectReason,
+    },
+    /// The transport layer denied a connection attempt, the client was never connected.
+    /// At most 64 of these events are queued until read, the others are dropped and counted in [`RenetServer::dropped_connection_denied_events`].
+    ConnectionDenied {
+        addr: SocketAddr,
+        reason: ConnectionDeniedReason,
+    },
+    /// The client didn't send messages for the idle timeout, see [`RenetServer::set_idle_timeout`].
+    /// Emitted once, and again only if the client sends messages and becomes idle again.
+    ClientIdle {
+        client_id: ClientId,
+        idle_for: Duration,
+    },
 }
 
+/// Server that manages the connections of multiple clients.
+///
+/// The connections are always iterated in ascending order of client id, including when broadcasting messages
+/// and returning the clients ids, so runs with the same inputs generate the same packets in the same order.
 #[derive(Debug)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
 pub struct RenetServer {
-    connections: HashMap<ClientId, RenetClient>,
+    connections: BTreeMap<ClientId, RenetClient>,
     connection_config: ConnectionConfig,
     events: VecDeque<ServerEvent>,
+    // Connection denied events in the queue, and how many were dropped when it was full
+    connection_denied_events: usize,
+    dropped_connection_denied_events: u64,
+    rate_limiter: Option<RateLimiter>,
+    memory_limit: Option<MemoryLimit>,
+    max_packet_size: Option<usize>,
+    session_resumption: Option<Duration>,
+    // Connections lost by the transport, with the remaining time to be resumed
+    held_connections: BTreeMap<ClientId, Duration>,
+    restricted_channels: BTreeSet<u8>,
+    late_packet_window: Duration,
+    // Connections recently removed, with the remaining time that their packets are considered late
+    removed_connections: BTreeMap<ClientId, Duration>,
+    late_packets: u64,
+    stale_connection_policy: Option<StaleConnectionPolicy>,
+    // Time that each connection has been stale, only for connections currently stale
+    stale_connections: BTreeMap<ClientId, Duration>,
+    idle_timeout: Option<Duration>,
+    // Clients that are idle and were already reported
+    idle_clients: BTreeSet<ClientId>,
+    snapshot_rate_policy: Option<SnapshotRatePolicy>,
+    // Number of updates, used to choose the clients that receive the snapshots in each tick
+    tick: u64,
+    timing: ServerTiming,
+    packet_observer: Option<PacketObserver>,
+}
+
+/// Policy to disconnect connections with a quality too poor to be playable, see [`RenetServer::set_stale_connection_policy`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StaleConnectionPolicy {
+    /// Packet loss above which the connection is stale, from 0 to 1.
+    pub max_packet_loss: f64,
+    /// Round-trip time in seconds above which the connection is stale.
+    pub max_rtt: f64,
+    /// Time that the connection must stay stale before being disconnected.
+    pub duration: Duration,
+}
+
+impl Default for StaleConnectionPolicy {
+    fn default() -> Self {
+        Self {
+            max_packet_loss: 0.5,
+            max_rtt: 2.0,
+            duration: Duration::from_secs(10),
+        }
+    }
+}
+
+impl StaleConnectionPolicy {
+    fn is_stale(&self, network_info: &NetworkInfo) -> bool {
+        network_info.packet_loss > self.max_packet_loss || network_info.rtt > self.max_rtt
+    }
+}
+
+/// Policy to send snapshots less often to the clients with low bandwidth, see [`RenetServer::set_snapshot_rate_policy`].
+///
+/// The snapshots are sent every tick, every 2 ticks or every 4 ticks, depending on the estimated bandwidth of the client,
+/// see [`RenetClient::estimated_bandwidth`]. Clients without an estimate yet receive the snapshots every tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapshotRatePolicy {
+    /// Estimated bandwidth in bytes per second needed to receive the snapshots every tick.
+    /// Clients with at least half of it receive the snapshots every 2 ticks, the others every 4 ticks.
+    pub full_rate_bandwidth: f64,
+}
+
+impl Default for SnapshotRatePolicy {
+    fn default() -> Self {
+        // At 60hz this is 1000 bytes per tick
+        Self {
+            full_rate_bandwidth: 60_000.,
+        }
+    }
+}
+
+impl SnapshotRatePolicy {
+    /// Returns the interval in ticks between snapshots for a client with the estimated bandwidth: 1, 2 or 4.
+    pub fn send_interval(&self, estimated_bandwidth: Option<f64>) -> u64 {
+        match estimated_bandwidth {
+            None => 1,
+            Some(bandwidth) if bandwidth >= self.full_rate_bandwidth => 1,
+            Some(bandwidth) if bandwidth >= self.full_rate_bandwidth / 2. => 2,
+            Some(_) => 4,
+        }
+    }
+}
+
+/// Time spent in each phase of the server networking since the start of the last [`RenetServer::update`],
+/// to see when the networking exceeds its frame budget.
+///
+/// The receive and send phases are reported by the transport layer, the transports in the renet crates report them.
+/// With the usual order of calls, updating the server, then the transport, and sending the packets at the end of the frame,
+/// reading the timing after sending the packets returns the durations of the whole frame.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ServerTiming {
+    /// Time spent by the transport receiving and processing the packets.
+    pub receive: Duration,
+    /// Time spent in [`RenetServer::update`].
+    pub update: Duration,
+    /// Time spent generating and serializing the packets, in [`RenetServer::get_packets_to_send`].
+    pub packing: Duration,
+    /// Time spent by the transport sending the packets, not including the packing.
+    pub send: Duration,
+}
+
+impl ServerTiming {
+    /// Returns the total time spent in all phases.
+    pub fn total(&self) -> Duration {
+        self.receive + self.update + self.packing + self.send
+    }
+}
+
+#[derive(Debug)]
+struct MemoryLimit {
+    max_memory_usage_bytes: usize,
+    // Memory usage measured in the last update plus the messages sent since then
+    memory_usage_bytes: usize,
 }
 
 impl RenetServer {
     pub fn new(connection_config: ConnectionConfig) -> Self {
         Self {
-            connections: HashMap::new(),
+            connections: BTreeMap::new(),
             connection_config,
             events: VecDeque::new(),
+            connection_denied_events: 0,
+            dropped_connection_denied_events: 0,
+            rate_limiter: None,
+            memory_limit: None,
+            max_packet_size: None,
+            session_resumption: None,
+            held_connections: BTreeMap::new(),
+            restricted_channels: BTreeSet::new(),
+            late_packet_window: Duration::from_secs(1),
+            removed_connections: BTreeMap::new(),
+            late_packets: 0,
+            stale_connection_policy: None,
+            stale_connections: BTreeMap::new(),
+            idle_timeout: None,
+            idle_clients: BTreeSet::new(),
+            snapshot_rate_policy: None,
+            tick: 0,
+            timing: ServerTiming::default(),
+            packet_observer: None,
         }
     }
 
@@ -37,17 +203,60 @@ impl RenetServer {
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
     pub fn add_connection(&mut self, client_id: ClientId) {
+        // A new connection with the id of a held connection ends the previous session
+        if self.held_connections.contains_key(&client_id) {
+            self.remove_connection(client_id);
+        }
+
         if self.connections.contains_key(&client_id) {
             return;
         }
+        self.removed_connections.remove(&client_id);
 
         let mut connection = RenetClient::new_from_server(self.connection_config.clone());
+        if let Some(max_packet_size) = self.max_packet_size {
+            connection.set_max_packet_size(max_packet_size);
+        }
+        for &channel_id in self.restricted_channels.iter() {
+            connection.deny_channel(channel_id);
+        }
         // Consider newly added connections as connected
         connection.set_connected();
         self.connections.insert(client_id, connection);
+
+        log::debug!("Client {client_id} connected");
+
         self.events.push_back(ServerEvent::ClientConnected { client_id })
     }
 
+    /// Adds a connection denied event.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn connection_denied(&mut self, addr: SocketAddr, reason: ConnectionDeniedReason) {
+        log::debug!("Connection from {addr} denied: {reason}");
+
+        if self.connection_denied_events >= MAX_CONNECTION_DENIED_EVENTS {
+            self.dropped_connection_denied_events += 1;
+            log::trace!("Dropped connection denied event from {addr}, too many events queued");
+            return;
+        }
+
+        self.connection_denied_events += 1;
+        self.events.push_back(ServerEvent::ConnectionDenied { addr, reason });
+    }
+
+    /// Returns the rate in bytes per second that the transport should spread the packets of each tick,
+    /// see [`ConnectionConfig::pacing_rate`].
+    pub fn pacing_rate(&self) -> Option<u64> {
+        self.connection_config.pacing_rate
+    }
+
+    /// Returns how many connection denied events were dropped because too many were queued.
+    pub fn dropped_connection_denied_events(&self) -> u64 {
+        self.dropped_connection_denied_events
+    }
+
     /// Returns a server event if available
     ///
     /// # Usage
@@ -62,11 +271,23 @@ impl RenetServer {
     ///         ServerEvent::ClientDisconnected { client_id, reason } => {
     ///             println!("Client {client_id} disconnected: {reason}");
     ///         }
+    ///         ServerEvent::ConnectionDenied { addr, reason } => {
+    ///             println!("Connection from {addr} denied: {reason}");
+    ///         }
+    ///         ServerEvent::ClientIdle { client_id, idle_for } => {
+    ///             println!("Client {client_id} idle for {idle_for:?}");
+    ///         }
+    ///         _ => {}
     ///     }
     /// }
     /// ```
     pub fn get_event(&mut self) -> Option<ServerEvent> {
-        self.events.pop_front()
+        let event = self.events.pop_front();
+        if let Some(ServerEvent::ConnectionDenied { .. }) = event {
+            self.connection_denied_events -= 1;
+        }
+
+        event
     }
 
     /// Returns whether or not the server has connections
@@ -91,6 +312,21 @@ impl RenetServer {
         }
     }
 
+    /// Returns the time since the last packet was received from the client, or None if the client is not found.
+    /// Measured by the packets passed to [`RenetServer::process_packet_from`], so it's the same with any transport.
+    /// Can be used to detect inactive clients, see [`RenetClient::time_since_last_packet`].
+    pub fn time_since_last_packet(&self, client_id: ClientId) -> Option<Duration> {
+        Some(self.connections.get(&client_id)?.time_since_last_packet())
+    }
+
+    /// Returns the jitter in seconds for the client or 0.0 if the client is not found
+    pub fn jitter(&self, client_id: ClientId) -> f64 {
+        match self.connections.get(&client_id) {
+            Some(connection) => connection.jitter(),
+            None => 0.0,
+        }
+    }
+
     /// Returns the packet loss for the client or 0.0 if the client is not found
     pub fn packet_loss(&self, client_id: ClientId) -> f64 {
         match self.connections.get(&client_id) {
@@ -99,6 +335,14 @@ impl RenetServer {
         }
     }
 
+    /// Returns the ratio of reliable messages resent for the client or 0.0 if the client is not found
+    pub fn retransmission_ratio(&self, client_id: ClientId) -> f64 {
+        match self.connections.get(&client_id) {
+            Some(connection) => connection.retransmission_ratio(),
+            None => 0.0,
+        }
+    }
+
     /// Returns the bytes sent per seconds for the client or 0.0 if the client is not found
     pub fn bytes_sent_per_sec(&self, client_id: ClientId) -> f64 {
         match self.connections.get(&client_id) {
@@ -115,6 +359,50 @@ impl RenetServer {
         }
     }
 
+    /// Returns the estimated maximum send rate in bytes per second to the client,
+    /// or None if there is no estimate yet or the client is not found. See [`RenetClient::estimated_bandwidth`].
+    pub fn estimated_bandwidth(&self, client_id: ClientId) -> Option<f64> {
+        self.connections.get(&client_id)?.estimated_bandwidth()
+    }
+
+    /// Returns the bytes that should be sent per tick to the client, see [`RenetClient::recommended_bytes_per_tick`].
+    pub fn recommended_bytes_per_tick(&self, client_id: ClientId) -> Result<u64, ClientNotFound> {
+        match self.connections.get(&client_id) {
+            Some(connection) => Ok(connection.recommended_bytes_per_tick()),
+            None => Err(ClientNotFound),
+        }
+    }
+
+    /// Returns whether more can be sent to the client, or false if the client is not found.
+    /// See [`RenetClient::can_increase_send_rate`].
+    pub fn can_increase_send_rate(&self, client_id: ClientId) -> bool {
+        match self.connections.get(&client_id) {
+            Some(connection) => connection.can_increase_send_rate(),
+            None => false,
+        }
+    }
+
+    /// Returns the ratio of the downstream budget used to send messages to the client in the last tick.
+    /// Returns 0 if the client is not found. See [`RenetClient::budget_usage`].
+    pub fn budget_usage(&self, client_id: ClientId) -> f64 {
+        match self.connections.get(&client_id) {
+            Some(connection) => connection.budget_usage(),
+            None => 0.0,
+        }
+    }
+
+    /// Sets the number of bytes available per tick to send messages to the client, replacing the
+    /// `server_available_bytes_per_tick` of the [`ConnectionConfig`] for this client.
+    pub fn set_available_bytes_per_tick(&mut self, client_id: ClientId, available_bytes_per_tick: u64) -> Result<(), ClientNotFound> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => {
+                connection.set_available_bytes_per_tick(available_bytes_per_tick);
+                Ok(())
+            }
+            None => Err(ClientNotFound),
+        }
+    }
+
     /// Returns all network informations for the client
     pub fn network_info(&self, client_id: ClientId) -> Result<NetworkInfo, ClientNotFound> {
         match self.connections.get(&client_id) {
@@ -123,18 +411,249 @@ impl RenetServer {
         }
     }
 
+    /// Adds a middleware that inspects the messages sent to and received from the client.
+    /// Middlewares are removed with the connection when the client disconnects. See [`Middleware`].
+    pub fn add_middleware<M: Middleware + 'static>(&mut self, client_id: ClientId, middleware: M) -> Result<(), ClientNotFound> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => {
+                connection.add_middleware(middleware);
+                Ok(())
+            }
+            None => Err(ClientNotFound),
+        }
+    }
+
+    /// Returns the statistics of the sliced messages sent and received for the client.
+    pub fn slice_stats(&self, client_id: ClientId) -> Result<SliceStats, ClientNotFound> {
+        match self.connections.get(&client_id) {
+            Some(connection) => Ok(connection.slice_stats()),
+            None => Err(ClientNotFound),
+        }
+    }
+
     /// Removes a connection from the server, emits an disconnect server event.
     /// It does nothing if the client does not exits.
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
     pub fn remove_connection(&mut self, client_id: ClientId) {
+        self.held_connections.remove(&client_id);
+        self.stale_connections.remove(&client_id);
+        self.idle_clients.remove(&client_id);
         if let Some(connection) = self.connections.remove(&client_id) {
+            if let Some(rate_limiter) = &mut self.rate_limiter {
+                rate_limiter.remove_client(client_id);
+            }
+
+            if !self.late_packet_window.is_zero() {
+                self.removed_connections.insert(client_id, self.late_packet_window);
+            }
+
             let reason = connection.disconnect_reason().unwrap_or(DisconnectReason::Transport);
+
+            log::debug!("Client {client_id} disconnected: {reason}");
+
             self.events.push_back(ServerEvent::ClientDisconnected { client_id, reason });
         }
     }
 
+    /// Notifies that the transport lost the connection unexpectedly, for example when it timed out.
+    /// With session resumption, the connection is held for the grace period so it can be resumed with
+    /// [`RenetServer::resume_connection`], otherwise it's removed like with [`RenetServer::remove_connection`].
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn connection_lost(&mut self, client_id: ClientId) {
+        let Some(grace_period) = self.session_resumption else {
+            self.remove_connection(client_id);
+            return;
+        };
+
+        match self.connections.get_mut(&client_id) {
+            Some(connection) if !connection.is_disconnected() => {
+                log::debug!("Holding connection of client {client_id} for {grace_period:?} to be resumed");
+                connection.set_connecting();
+                self.held_connections.insert(client_id, grace_period);
+            }
+            _ => self.remove_connection(client_id),
+        }
+    }
+
+    /// Notifies that the client requested the disconnection in the transport layer,
+    /// the connection is removed with [`DisconnectReason::DisconnectedByClient`].
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn client_disconnected(&mut self, client_id: ClientId) {
+        if let Some(connection) = self.connections.get_mut(&client_id) {
+            if !connection.is_disconnected() {
+                connection.disconnect_with_reason(DisconnectReason::DisconnectedByClient);
+            }
+        }
+        self.remove_connection(client_id);
+    }
+
+    /// Resumes a held connection, the client continues with the messages not yet acked in the previous connection.
+    /// Returns false if there is no held connection for the client, the connection should be added as new instead.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn resume_connection(&mut self, client_id: ClientId) -> bool {
+        if self.held_connections.remove(&client_id).is_none() {
+            return false;
+        }
+
+        let Some(connection) = self.connections.get_mut(&client_id) else {
+            return false;
+        };
+        connection.set_connected();
+
+        log::debug!("Client {client_id} resumed its connection");
+        true
+    }
+
+    /// Enables session resumption: connections lost by the transport, like timeouts, are held for the grace period,
+    /// so the client can reconnect with the same client id and the messages not yet acked, instead of as a new client.
+    ///
+    /// Held connections are not connected, but messages sent to them are kept until they resume.
+    /// The [`ServerEvent::ClientDisconnected`] is only emitted when the grace period ends without the client resuming.
+    /// The transport must support resumption, `NetcodeServerTransport` from the `renet_netcode` crate issues resume tickets
+    /// to the clients while it's enabled.
+    pub fn set_session_resumption(&mut self, grace_period: Duration) {
+        self.session_resumption = Some(grace_period);
+    }
+
+    /// Disables session resumption, connections already held are kept until their grace period ends.
+    pub fn clear_session_resumption(&mut self) {
+        self.session_resumption = None;
+    }
+
+    /// Returns the grace period of the session resumption, if enabled.
+    pub fn session_resumption(&self) -> Option<Duration> {
+        self.session_resumption
+    }
+
+    /// Sets a function called with a random sample of the packets sent and received, with the given fraction
+    /// of the packets from 0 to 1. Server side anomaly detection, like anti-cheat or telemetry, can inspect the traffic
+    /// without the overhead of capturing every packet. The packets are the ones given to and received from the transport,
+    /// packets of local clients are not observed.
+    ///
+    /// # Usage
+    /// ```
+    /// # use renet::{ConnectionConfig, PacketDirection, RenetServer};
+    /// # let mut server = RenetServer::new(ConnectionConfig::default());
+    /// // Inspect 1% of the packets
+    /// server.set_packet_observer(0.01, |direction, client_id, packet| {
+    ///     if direction == PacketDirection::ClientToServer && packet.len() > 1200 {
+    ///         log::warn!("Client {client_id} sent an unusual packet with {} bytes", packet.len());
+    ///     }
+    /// });
+    /// ```
+    pub fn set_packet_observer<F>(&mut self, sample_rate: f64, observer: F)
+    where
+        F: FnMut(PacketDirection, ClientId, &[u8]) + Send + Sync + 'static,
+    {
+        self.packet_observer = Some(PacketObserver::new(sample_rate, Box::new(observer)));
+    }
+
+    /// Removes the packet observer, see [`RenetServer::set_packet_observer`].
+    pub fn clear_packet_observer(&mut self) {
+        self.packet_observer = None;
+    }
+
+    /// Sets how long after a connection is removed its packets are still expected, when the transport
+    /// delivers packets that were in flight. These late packets are counted in [`RenetServer::late_packets`]
+    /// and ignored by [`RenetServer::process_packet_from`], instead of returning [`ClientNotFound`].
+    /// A zero window disables it. Default: 1 second
+    pub fn set_late_packet_window(&mut self, window: Duration) {
+        self.late_packet_window = window;
+        if window.is_zero() {
+            self.removed_connections.clear();
+        }
+    }
+
+    /// Returns the number of packets received for clients already removed, within the late packet window.
+    /// See [`RenetServer::set_late_packet_window`].
+    pub fn late_packets(&self) -> u64 {
+        self.late_packets
+    }
+
+    /// Returns whether the connection of the client was lost and is held to be resumed.
+    pub fn is_connection_held(&self, client_id: ClientId) -> bool {
+        self.held_connections.contains_key(&client_id)
+    }
+
+    /// Restricts the channel to the clients allowed with [`RenetServer::allow_channel`], for example admins or spectators.
+    /// The restriction applies to the server and client channels with this id. Messages sent to clients not allowed
+    /// are dropped, and messages received from them are dropped and counted in [`RenetServer::channel_access_violations`].
+    /// Reliable channels keep track of the dropped messages, so they keep working if the client is allowed later.
+    pub fn restrict_channel<I: Into<u8>>(&mut self, channel_id: I) {
+        let channel_id = channel_id.into();
+        if self.restricted_channels.insert(channel_id) {
+            for connection in self.connections.values_mut() {
+                connection.deny_channel(channel_id);
+            }
+        }
+    }
+
+    /// Removes the restriction of the channel, all clients can use it again.
+    pub fn unrestrict_channel<I: Into<u8>>(&mut self, channel_id: I) {
+        let channel_id = channel_id.into();
+        if self.restricted_channels.remove(&channel_id) {
+            for connection in self.connections.values_mut() {
+                connection.allow_channel(channel_id);
+            }
+        }
+    }
+
+    /// Returns whether the channel is restricted, see [`RenetServer::restrict_channel`].
+    pub fn is_channel_restricted<I: Into<u8>>(&self, channel_id: I) -> bool {
+        self.restricted_channels.contains(&channel_id.into())
+    }
+
+    /// Allows the client to send and receive messages in the restricted channel.
+    pub fn allow_channel<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Result<(), ClientNotFound> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => {
+                connection.allow_channel(channel_id.into());
+                Ok(())
+            }
+            None => Err(ClientNotFound),
+        }
+    }
+
+    /// Removes the access of the client to the restricted channel.
+    pub fn disallow_channel<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Result<(), ClientNotFound> {
+        let channel_id = channel_id.into();
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => {
+                if self.restricted_channels.contains(&channel_id) {
+                    connection.deny_channel(channel_id);
+                }
+                Ok(())
+            }
+            None => Err(ClientNotFound),
+        }
+    }
+
+    /// Returns whether the client can send and receive messages in the channel.
+    /// Returns false if the client does not exist.
+    pub fn can_access_channel<I: Into<u8>>(&self, client_id: ClientId, channel_id: I) -> bool {
+        let channel_id = channel_id.into();
+        self.connections
+            .get(&client_id)
+            .is_some_and(|connection| connection.can_access_channel(channel_id))
+    }
+
+    /// Returns the number of packets received from the client in restricted channels it's not allowed to use,
+    /// the packets are dropped. A high number can indicate a modified client.
+    pub fn channel_access_violations(&self, client_id: ClientId) -> Result<u64, ClientNotFound> {
+        match self.connections.get(&client_id) {
+            Some(connection) => Ok(connection.channel_access_violations()),
+            None => Err(ClientNotFound),
+        }
+    }
+
     /// Disconnects a client, it does nothing if the client does not exist.
     pub fn disconnect(&mut self, client_id: ClientId) {
         if let Some(connection) = self.connections.get_mut(&client_id) {
@@ -142,6 +661,21 @@ impl RenetServer {
         }
     }
 
+    /// Transfers the client to another server, for example when moving between zones or instances.
+    /// The connect token of the new server is sent in a reserved control message, then the client transport
+    /// disconnects from this server and connects to the new one, `NetcodeClientTransport` from the `renet_netcode` crate does it in its update.
+    pub fn transfer_client<B: Into<Bytes>>(&mut self, client_id: ClientId, connect_token: B) -> Result<(), ClientNotFound> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => {
+                connection.send_control_message(ControlMessage::Transfer {
+                    connect_token: connect_token.into(),
+                });
+                Ok(())
+            }
+            None => Err(ClientNotFound),
+        }
+    }
+
     /// Disconnects all client.
     pub fn disconnect_all(&mut self) {
         for connection in self.connections.values_mut() {
@@ -154,7 +688,20 @@ impl RenetServer {
         let channel_id = channel_id.into();
         let message = message.into();
         for connection in self.connections.values_mut() {
-            connection.send_message(channel_id, message.clone());
+            if reserve_memory(&mut self.memory_limit, message.len()) {
+                connection.send_message(channel_id, message.clone());
+            }
+        }
+    }
+
+    /// Send a message to all clients over a stream of a channel with [`SendType::ReliableOrderedStreams`](crate::SendType::ReliableOrderedStreams).
+    pub fn broadcast_message_on_stream<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, stream_id: u64, message: B) {
+        let channel_id = channel_id.into();
+        let message = message.into();
+        for connection in self.connections.values_mut() {
+            if reserve_memory(&mut self.memory_limit, message.len()) {
+                connection.send_message_on_stream(channel_id, stream_id, message.clone());
+            }
         }
     }
 
@@ -167,7 +714,9 @@ impl RenetServer {
                 continue;
             }
 
-            connection.send_message(channel_id, message.clone());
+            if reserve_memory(&mut self.memory_limit, message.len()) {
+                connection.send_message(channel_id, message.clone());
+            }
         }
     }
 
@@ -180,6 +729,24 @@ impl RenetServer {
         }
     }
 
+    /// Returns the number of messages queued in a channel that were not sent yet for the given client.
+    /// Returns 0 if the client is not found.
+    pub fn channel_queue_len<I: Into<u8>>(&self, client_id: ClientId, channel_id: I) -> usize {
+        match self.connections.get(&client_id) {
+            Some(connection) => connection.channel_queue_len(channel_id),
+            None => 0,
+        }
+    }
+
+    /// Returns how long the oldest message not sent yet has been queued in a channel for the given client.
+    /// Returns None if all messages were sent or the client is not found.
+    pub fn channel_oldest_unsent_age<I: Into<u8>>(&self, client_id: ClientId, channel_id: I) -> Option<Duration> {
+        match self.connections.get(&client_id) {
+            Some(connection) => connection.channel_oldest_unsent_age(channel_id),
+            None => None,
+        }
+    }
+
     /// Checks if can send a message with the given size in bytes over a channel for the given client.
     /// Returns false if the client is not found.
     pub fn can_send_message<I: Into<u8>>(&self, client_id: ClientId, channel_id: I, size_bytes: usize) -> bool {
@@ -191,36 +758,341 @@ impl RenetServer {
 
     /// Send a message to a client over a channel.
     pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: ClientId, channel_id: I, message: B) {
+        self.send_message_with_priority(client_id, channel_id, 0, message);
+    }
+
+    /// Send a message to a client over a channel, ahead of the messages with a lower priority not sent yet.
+    /// See [`RenetClient::send_message_with_priority`].
+    ///
+    /// A message bigger than a reliable channel can hold disconnects the client with [`DisconnectReason::SendChannelError`],
+    /// use [`RenetServer::try_send_message_with_priority`] to check the message size instead.
+    pub fn send_message_with_priority<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        client_id: ClientId,
+        channel_id: I,
+        priority: u8,
+        message: B,
+    ) {
         match self.connections.get_mut(&client_id) {
-            Some(connection) => connection.send_message(channel_id, message),
+            Some(connection) => {
+                let message = message.into();
+                if reserve_memory(&mut self.memory_limit, message.len()) {
+                    connection.send_message_with_priority(channel_id, priority, message);
+                }
+            }
+            None => log::error!("Tried to send a message to invalid client {:?}", client_id),
+        }
+    }
+
+    /// Send a message to a client over a channel, returns an error instead of panicking if the channel does not exist,
+    /// and instead of disconnecting the client if the message is bigger than the channel can hold.
+    /// Like [`RenetServer::send_message`], messages to clients not found are ignored.
+    pub fn try_send_message<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        client_id: ClientId,
+        channel_id: I,
+        message: B,
+    ) -> Result<(), SendError> {
+        self.try_send_message_with_priority(client_id, channel_id, 0, message)
+    }
+
+    /// Send a message to a client over a channel with a priority, returns an error instead of panicking if the channel does not exist,
+    /// and instead of disconnecting the client if the message is bigger than the channel can hold.
+    /// See [`RenetServer::send_message_with_priority`].
+    pub fn try_send_message_with_priority<I: Into<u8>, B: Into<Bytes>>(
+        &mut self,
+        client_id: ClientId,
+        channel_id: I,
+        priority: u8,
+        message: B,
+    ) -> Result<(), SendError> {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => {
+                let message = message.into();
+                if reserve_memory(&mut self.memory_limit, message.len()) {
+                    connection.try_send_message_with_priority(channel_id, priority, message)?;
+                }
+            }
+            None => log::error!("Tried to send a message to invalid client {:?}", client_id),
+        }
+
+        Ok(())
+    }
+
+    /// Send a message to a client over an unreliable channel, that is dropped if it could not be sent within the time to live.
+    /// See [`RenetClient::send_message_with_ttl`].
+    pub fn send_message_with_ttl<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: ClientId, channel_id: I, message: B, ttl: Duration) {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => {
+                let message = message.into();
+                if reserve_memory(&mut self.memory_limit, message.len()) {
+                    connection.send_message_with_ttl(channel_id, message, ttl);
+                }
+            }
+            None => log::error!("Tried to send a message to invalid client {:?}", client_id),
+        }
+    }
+
+    /// Send a message to a client over a stream of a channel with [`SendType::ReliableOrderedStreams`](crate::SendType::ReliableOrderedStreams).
+    /// Messages are only received in order with the other messages of the same stream.
+    pub fn send_message_on_stream<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: ClientId, channel_id: I, stream_id: u64, message: B) {
+        match self.connections.get_mut(&client_id) {
+            Some(connection) => {
+                let message = message.into();
+                if reserve_memory(&mut self.memory_limit, message.len()) {
+                    connection.send_message_on_stream(channel_id, stream_id, message);
+                }
+            }
             None => log::error!("Tried to send a message to invalid client {:?}", client_id),
         }
     }
 
     /// Receive a message from a client over a channel.
     pub fn receive_message<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Option<Bytes> {
-        if let Some(connection) = self.connections.get_mut(&client_id) {
-            return connection.receive_message(channel_id);
+        self.receive_message_meta(client_id, channel_id).map(|message| message.payload)
+    }
+
+    /// Receives all the messages from the client in the channel, appending them to `messages` and reusing its capacity.
+    /// Returns the number of messages received, 0 if the client is not found. Panics if the channel does not exist.
+    pub fn receive_all_messages<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I, messages: &mut Vec<Bytes>) -> usize {
+        let channel_id = channel_id.into();
+        let len = messages.len();
+        while let Some(message) = self.receive_message(client_id, channel_id) {
+            messages.push(message);
         }
-        None
+
+        messages.len() - len
+    }
+
+    /// Receive a message from a client over a channel, along with when and in which packet it arrived.
+    /// The receive time is measured from when the client connection was created.
+    pub fn receive_message_meta<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Option<ReceivedMessage> {
+        match self.try_receive_message_meta(client_id, channel_id) {
+            Ok(message) => message,
+            Err(error) => panic!("Called 'receive_message' with {error}"),
+        }
+    }
+
+    /// Receive a message from a client over a channel, returns an error instead of panicking if the channel does not exist.
+    /// Returns None if the client is not found.
+    pub fn try_receive_message<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Result<Option<Bytes>, InvalidChannelId> {
+        let message = self.try_receive_message_meta(client_id, channel_id)?;
+        Ok(message.map(|message| message.payload))
+    }
+
+    /// Receive a message from a client over a channel, along with when and in which packet it arrived.
+    /// Returns an error instead of panicking if the channel does not exist, and None if the client is not found.
+    pub fn try_receive_message_meta<I: Into<u8>>(
+        &mut self,
+        client_id: ClientId,
+        channel_id: I,
+    ) -> Result<Option<ReceivedMessage>, InvalidChannelId> {
+        let channel_id = channel_id.into();
+        let Some(connection) = self.connections.get_mut(&client_id) else {
+            return Ok(None);
+        };
+        loop {
+            let Some(message) = connection.try_receive_message_meta(channel_id)? else {
+                return Ok(None);
+            };
+            let allowed = match &mut self.rate_limiter {
+                Some(rate_limiter) => rate_limiter.check(client_id, channel_id),
+                None => true,
+            };
+            if allowed {
+                return Ok(Some(message));
+            }
+
+            log::debug!("Dropped message from client {client_id} in channel {channel_id}, above the rate limit");
+        }
+    }
+
+    /// Returns the id of the missing message that blocks the delivery of the next messages from the client in an ordered channel.
+    /// Returns None if the channel is not blocked, or if the client is not found.
+    pub fn blocked_on_message_id<I: Into<u8>>(&self, client_id: ClientId, channel_id: I) -> Option<u64> {
+        self.connections.get(&client_id)?.blocked_on_message_id(channel_id)
+    }
+
+    /// Sets a rate limiter, messages received above the limit are dropped when calling [`Self::receive_message`].
+    pub fn set_rate_limiter(&mut self, rate_limiter: RateLimiter) {
+        self.rate_limiter = Some(rate_limiter);
+    }
+
+    /// Removes the rate limiter, all messages are received.
+    pub fn clear_rate_limiter(&mut self) {
+        self.rate_limiter = None;
+    }
+
+    /// Returns the rate limiter if set, it can be used to check the dropped messages for each client.
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.rate_limiter.as_ref()
+    }
+
+    /// Returns an estimate of the memory in bytes used by all connections.
+    /// See [`RenetClient::memory_usage`].
+    pub fn memory_usage(&self) -> usize {
+        self.connections.values().map(|connection| connection.memory_usage()).sum()
+    }
+
+    /// Returns whether all connections have nothing to send, see [`RenetClient::is_idle`].
+    pub fn is_idle(&self) -> bool {
+        self.connections.values().all(|connection| connection.is_idle())
+    }
+
+    /// Sets a limit for the memory used by all connections.
+    /// When the limit is reached, new messages sent are dropped, and in the next update the connections
+    /// using the most memory are disconnected with [`DisconnectReason::MemoryLimitReached`] until the usage is below the limit.
+    pub fn set_memory_limit(&mut self, max_memory_usage_bytes: usize) {
+        self.memory_limit = Some(MemoryLimit {
+            max_memory_usage_bytes,
+            memory_usage_bytes: self.memory_usage(),
+        });
+    }
+
+    /// Removes the memory limit.
+    pub fn clear_memory_limit(&mut self) {
+        self.memory_limit = None;
+    }
+
+    /// Disconnects the connections for which the predicate returns true, for example to prune connections with a poor quality.
+    /// The connections are disconnected with [`DisconnectReason::StaleConnection`], the transport removes them
+    /// emitting [`ServerEvent::ClientDisconnected`]. Returns the number of connections disconnected.
+    pub fn remove_connections_where<F: FnMut(ClientId, &NetworkInfo) -> bool>(&mut self, mut predicate: F) -> usize {
+        let mut removed = 0;
+        for (&client_id, connection) in self.connections.iter_mut() {
+            if connection.is_disconnected() || !predicate(client_id, &connection.network_info()) {
+                continue;
+            }
+
+            log::debug!("Disconnecting stale connection of client {client_id}");
+            connection.disconnect_with_reason(DisconnectReason::StaleConnection);
+            removed += 1;
+        }
+
+        removed
+    }
+
+    /// Disconnects automatically the connections that stay stale for the duration of the policy,
+    /// with [`DisconnectReason::StaleConnection`]. The connections are checked in each update.
+    pub fn set_stale_connection_policy(&mut self, policy: StaleConnectionPolicy) {
+        self.stale_connection_policy = Some(policy);
+    }
+
+    /// Removes the stale connection policy.
+    pub fn clear_stale_connection_policy(&mut self) {
+        self.stale_connection_policy = None;
+        self.stale_connections.clear();
+    }
+
+    /// Emits [`ServerEvent::ClientIdle`] when a connected client doesn't send messages for the duration,
+    /// packets with only acks and the keep-alive packets of the transport are not counted.
+    /// Useful to kick idle lobby members, or players away from the keyboard.
+    pub fn set_idle_timeout(&mut self, idle_timeout: Duration) {
+        self.idle_timeout = Some(idle_timeout);
+    }
+
+    /// Removes the idle timeout, no more [`ServerEvent::ClientIdle`] are emitted.
+    pub fn clear_idle_timeout(&mut self) {
+        self.idle_timeout = None;
+        self.idle_clients.clear();
+    }
+
+    /// Sends snapshots less often to the clients with low bandwidth, in [`RenetServer::broadcast_snapshot`].
+    pub fn set_snapshot_rate_policy(&mut self, policy: SnapshotRatePolicy) {
+        self.snapshot_rate_policy = Some(policy);
     }
 
-    /// Return ids for all connected clients (iterator)
+    /// Removes the snapshot rate policy, all clients receive the snapshots every tick.
+    pub fn clear_snapshot_rate_policy(&mut self) {
+        self.snapshot_rate_policy = None;
+    }
+
+    /// Returns the interval in ticks between the snapshots sent to the client, 1 without a snapshot rate policy
+    /// or if the client is not found. See [`SnapshotRatePolicy::send_interval`].
+    pub fn snapshot_interval(&self, client_id: ClientId) -> u64 {
+        match (self.snapshot_rate_policy, self.connections.get(&client_id)) {
+            (Some(policy), Some(connection)) => policy.send_interval(connection.estimated_bandwidth()),
+            _ => 1,
+        }
+    }
+
+    /// Returns whether the client should receive a snapshot in the current tick, following the snapshot rate policy.
+    /// The clients with the same interval are spread between the ticks by their ids, so the bytes sent are spread as well.
+    pub fn should_send_snapshot(&self, client_id: ClientId) -> bool {
+        is_snapshot_tick(self.tick, client_id, self.snapshot_interval(client_id))
+    }
+
+    /// Sends a snapshot to the clients that should receive one in the current tick, see [`RenetServer::set_snapshot_rate_policy`].
+    /// Without a policy, it's the same as [`RenetServer::broadcast_message`].
+    pub fn broadcast_snapshot<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) {
+        let channel_id = channel_id.into();
+        let message = message.into();
+        for (&client_id, connection) in self.connections.iter_mut() {
+            if let Some(policy) = self.snapshot_rate_policy {
+                if !is_snapshot_tick(self.tick, client_id, policy.send_interval(connection.estimated_bandwidth())) {
+                    continue;
+                }
+            }
+
+            if reserve_memory(&mut self.memory_limit, message.len()) {
+                connection.send_message(channel_id, message.clone());
+            }
+        }
+    }
+
+    /// Sets the maximum size of the packets of all connections, including the ones added later.
+    /// See [`RenetClient::set_max_packet_size`].
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer, with the maximum payload it can send.
+    /// </p>
+    pub fn set_max_packet_size(&mut self, max_packet_size: usize) {
+        if self.max_packet_size == Some(max_packet_size) {
+            return;
+        }
+
+        self.max_packet_size = Some(max_packet_size);
+        for connection in self.connections.values_mut() {
+            connection.set_max_packet_size(max_packet_size);
+        }
+    }
+
+    /// Returns the time spent in each phase of the server networking since the start of the last update.
+    pub fn timing(&self) -> ServerTiming {
+        self.timing
+    }
+
+    /// Adds the time spent receiving and processing packets to the [`ServerTiming`].
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn record_receive_time(&mut self, duration: Duration) {
+        self.timing.receive += duration;
+    }
+
+    /// Adds the time spent sending packets to the [`ServerTiming`], without the time spent in [`RenetServer::get_packets_to_send`].
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn record_send_time(&mut self, duration: Duration) {
+        self.timing.send += duration;
+    }
+
+    /// Return ids for all connected clients, in ascending order (iterator)
     pub fn clients_id_iter(&self) -> impl Iterator<Item = ClientId> + '_ {
         self.connections.iter().filter(|(_, c)| c.is_connected()).map(|(id, _)| *id)
     }
 
-    /// Return ids for all connected clients
+    /// Return ids for all connected clients, in ascending order
     pub fn clients_id(&self) -> Vec<ClientId> {
         self.clients_id_iter().collect()
     }
 
-    /// Return ids for all disconnected clients (iterator)
+    /// Return ids for all disconnected clients, in ascending order (iterator)
     pub fn disconnections_id_iter(&self) -> impl Iterator<Item = ClientId> + '_ {
         self.connections.iter().filter(|(_, c)| c.is_disconnected()).map(|(id, _)| *id)
     }
 
-    /// Return ids for all disconnected clients
+    /// Return ids for all disconnected clients, in ascending order
     pub fn disconnections_id(&self) -> Vec<ClientId> {
         self.disconnections_id_iter().collect()
     }
@@ -241,32 +1113,180 @@ impl RenetServer {
     /// Advances the server by the duration.
     /// Should be called every tick
     pub fn update(&mut self, duration: Duration) {
+        let start = Instant::now();
+        self.timing = ServerTiming::default();
+        self.tick = self.tick.wrapping_add(1);
+
         for connection in self.connections.values_mut() {
             connection.update(duration);
         }
+
+        let mut expired_connections = vec![];
+        for (&client_id, remaining) in self.held_connections.iter_mut() {
+            *remaining = remaining.saturating_sub(duration);
+            if remaining.is_zero() || self.connections.get(&client_id).is_none_or(RenetClient::is_disconnected) {
+                expired_connections.push(client_id);
+            }
+        }
+        for client_id in expired_connections {
+            self.remove_connection(client_id);
+        }
+
+        self.removed_connections.retain(|_, remaining| {
+            *remaining = remaining.saturating_sub(duration);
+            !remaining.is_zero()
+        });
+
+        self.disconnect_stale_connections(duration);
+        self.detect_idle_clients();
+
+        if let Some(rate_limiter) = &mut self.rate_limiter {
+            rate_limiter.update(duration);
+        }
+
+        self.enforce_memory_limit();
+        self.timing.update = start.elapsed();
+    }
+
+    fn disconnect_stale_connections(&mut self, duration: Duration) {
+        let Some(policy) = self.stale_connection_policy else {
+            return;
+        };
+
+        for (&client_id, connection) in self.connections.iter_mut() {
+            if !connection.is_connected() || !policy.is_stale(&connection.network_info()) {
+                self.stale_connections.remove(&client_id);
+                continue;
+            }
+
+            let stale_time = self.stale_connections.entry(client_id).or_default();
+            *stale_time += duration;
+            if *stale_time >= policy.duration {
+                log::debug!("Disconnecting client {client_id}, the connection was stale for {stale_time:?}");
+                connection.disconnect_with_reason(DisconnectReason::StaleConnection);
+                self.stale_connections.remove(&client_id);
+            }
+        }
+    }
+
+    fn detect_idle_clients(&mut self) {
+        let Some(idle_timeout) = self.idle_timeout else {
+            return;
+        };
+
+        for (&client_id, connection) in self.connections.iter() {
+            let idle_for = connection.time_since_last_message();
+            if !connection.is_connected() || idle_for < idle_timeout {
+                self.idle_clients.remove(&client_id);
+                continue;
+            }
+
+            if self.idle_clients.insert(client_id) {
+                self.events.push_back(ServerEvent::ClientIdle { client_id, idle_for });
+            }
+        }
+    }
+
+    fn enforce_memory_limit(&mut self) {
+        let Some(memory_limit) = &mut self.memory_limit else {
+            return;
+        };
+
+        // Disconnected connections are not counted, their memory is released when the transport removes them
+        let mut memory_usages: Vec<(ClientId, usize)> = self
+            .connections
+            .iter()
+            .filter(|(_, connection)| !connection.is_disconnected())
+            .map(|(&client_id, connection)| (client_id, connection.memory_usage()))
+            .collect();
+        let mut memory_usage: usize = memory_usages.iter().map(|(_, usage)| usage).sum();
+
+        // Disconnect the connections using the most memory first
+        memory_usages.sort_by_key(|(_, usage)| std::cmp::Reverse(*usage));
+        for (client_id, usage) in memory_usages {
+            if memory_usage <= memory_limit.max_memory_usage_bytes {
+                break;
+            }
+
+            let connection = self.connections.get_mut(&client_id).unwrap();
+            log::warn!("Disconnecting client {client_id} using {usage} bytes, the server memory limit was reached");
+            connection.disconnect_with_reason(DisconnectReason::MemoryLimitReached);
+            memory_usage -= usage;
+        }
+
+        memory_limit.memory_usage_bytes = memory_usage;
     }
 
     /// Returns a list of packets to be sent to the client.
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self)))]
     pub fn get_packets_to_send(&mut self, client_id: ClientId) -> Result<Vec<Payload>, ClientNotFound> {
-        match self.connections.get_mut(&client_id) {
-            Some(connection) => Ok(connection.get_packets_to_send()),
-            None => Err(ClientNotFound),
+        let start = Instant::now();
+        let packets = match self.connections.get_mut(&client_id) {
+            Some(connection) => connection.get_packets_to_send(),
+            None => return Err(ClientNotFound),
+        };
+        self.timing.packing += start.elapsed();
+        if let Some(observer) = self.packet_observer.as_mut() {
+            for packet in packets.iter() {
+                observer.observe(PacketDirection::ServerToClient, client_id, packet);
+            }
         }
+        Ok(packets)
+    }
+
+    /// Returns the packets to be sent to all connected clients, grouped by client id in ascending order.
+    /// The packets of each connection are generated and serialized in parallel in the rayon thread pool,
+    /// for servers with hundreds of clients, the packets are the same as calling [`RenetServer::get_packets_to_send`] for each client.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    #[cfg(feature = "rayon")]
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn get_packets_to_send_parallel(&mut self) -> Vec<(ClientId, Vec<Payload>)> {
+        use rayon::prelude::*;
+
+        let start = Instant::now();
+        let packets = self
+            .connections
+            .par_iter_mut()
+            .filter(|(_, connection)| connection.is_connected())
+            .map(|(&client_id, connection)| (client_id, connection.get_packets_to_send()))
+            .collect::<Vec<_>>();
+        self.timing.packing += start.elapsed();
+        if let Some(observer) = self.packet_observer.as_mut() {
+            for (client_id, client_packets) in packets.iter() {
+                for packet in client_packets {
+                    observer.observe(PacketDirection::ServerToClient, *client_id, packet);
+                }
+            }
+        }
+        packets
     }
 
     /// Process a packet received from the client.
+    /// Packets for clients removed within the late packet window are ignored and counted,
+    /// see [`RenetServer::set_late_packet_window`], other unknown clients return [`ClientNotFound`].
     /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
     /// <strong>Note:</strong> This should only be called by the transport layer.
     /// </p>
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self, payload)))]
     pub fn process_packet_from(&mut self, payload: &[u8], client_id: ClientId) -> Result<(), ClientNotFound> {
         match self.connections.get_mut(&client_id) {
             Some(connection) => {
+                if let Some(observer) = self.packet_observer.as_mut() {
+                    observer.observe(PacketDirection::ClientToServer, client_id, payload);
+                }
                 connection.process_packet(payload);
                 Ok(())
             }
+            None if self.removed_connections.contains_key(&client_id) => {
+                log::trace!("Ignored late packet from removed client {client_id}");
+                self.late_packets += 1;
+                Ok(())
+            }
             None => Err(ClientNotFound),
         }
     }
@@ -288,26 +1308,57 @@ impl RenetServer {
             return;
         }
         client.disconnect();
-
-        if self.connections.remove(&client_id).is_some() {
-            self.events.push_back(ServerEvent::ClientDisconnected {
-                client_id,
-                reason: DisconnectReason::DisconnectedByClient,
-            });
-        }
+        self.client_disconnected(client_id);
     }
 
     /// Given a local [RenetClient], receive and send packets to/from it.
     /// Use this to update local client created from [`Self::new_local_client`].
+    ///
+    /// Packets are passed directly between the connections, skipping serialization.
+    /// Disconnections from either side are propagated to the other,
+    /// and the connection is removed from the server.
     pub fn process_local_client(&mut self, client_id: ClientId, client: &mut RenetClient) -> Result<(), ClientNotFound> {
-        for packet in self.get_packets_to_send(client_id)? {
-            client.process_packet(&packet);
+        let connection = self.connections.get_mut(&client_id).ok_or(ClientNotFound)?;
+
+        if let Some(reason) = client.disconnect_reason() {
+            connection.disconnect_with_reason(reason);
+        } else if let Some(reason) = connection.disconnect_reason() {
+            client.disconnect_with_reason(reason);
+        }
+
+        if connection.is_disconnected() {
+            self.remove_connection(client_id);
+            return Ok(());
         }
 
-        for packet in client.get_packets_to_send() {
-            self.process_packet_from(&packet, client_id)?
+        for packet in connection.get_local_packets_to_send() {
+            client.process_local_packet(packet);
+        }
+
+        for packet in client.get_local_packets_to_send() {
+            connection.process_local_packet(packet);
         }
 
         Ok(())
     }
 }
+
+// Returns false if the message must be dropped because the server memory limit was reached
+fn reserve_memory(memory_limit: &mut Option<MemoryLimit>, bytes: usize) -> bool {
+    let Some(memory_limit) = memory_limit else {
+        return true;
+    };
+
+    if memory_limit.memory_usage_bytes + bytes > memory_limit.max_memory_usage_bytes {
+        log::warn!("dropped message sent because the server memory limit was reached");
+        return false;
+    }
+
+    memory_limit.memory_usage_bytes += bytes;
+    true
+}
+
+// Clients are offset by their ids, so the clients with the same interval don't all receive the snapshots in the same tick
+fn is_snapshot_tick(tick: u64, client_id: ClientId, interval: u64) -> bool {
+    tick.wrapping_add(client_id).is_multiple_of(interval)
+}
@@ -0,0 +1,342 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::error::ClientNotFound;
+use crate::packet::Payload;
+use crate::remote_connection::ConnectionConfig;
+use crate::server::{RenetServer, ServerEvent};
+use crate::ClientId;
+
+/// Server split in shards, each shard is a [`RenetServer`] that owns the connections of part of the clients,
+/// so big servers can update the connections in multiple threads.
+///
+/// Clients are assigned to the shard `client_id % shard_count`. The methods of the sharded server route the calls
+/// to the shard of the client, so it can be used from a single thread like a [`RenetServer`]. To scale beyond one core,
+/// [`ShardedRenetServer::into_shards`] moves each shard to its own worker thread, with its own transport, and messages
+/// for clients of other shards are sent with the [`ShardRouter`].
+///
+/// The router sends the messages of a client to the shard that owns it, so each shard must only have connections
+/// of the clients assigned to it. When each shard has its own transport, the connect tokens for the address
+/// of the shard `k` must use client ids with `client_id % shard_count == k`.
+///
+/// # Usage
+/// ```
+/// # use renet::{ConnectionConfig, DefaultChannel, ShardedRenetServer};
+/// # use std::time::Duration;
+/// let server = ShardedRenetServer::new(ConnectionConfig::default(), 4);
+/// let router = server.router();
+///
+/// let workers: Vec<_> = server
+///     .into_shards()
+///     .into_iter()
+///     .map(|mut shard| {
+///         std::thread::spawn(move || {
+///             // Each worker updates its shard with its own transport
+///             shard.update(Duration::from_millis(16));
+///             // Messages to all clients are delivered to every shard
+///             shard.router().broadcast_message(DefaultChannel::ReliableOrdered, "hello");
+///         })
+///     })
+///     .collect();
+/// # for worker in workers { worker.join().unwrap(); }
+/// ```
+#[derive(Debug)]
+pub struct ShardedRenetServer {
+    shards: Vec<RenetShard>,
+    router: ShardRouter,
+}
+
+/// A shard of a [`ShardedRenetServer`], the [`RenetServer`] with the connections of the clients assigned to it.
+#[derive(Debug)]
+pub struct RenetShard {
+    index: usize,
+    server: RenetServer,
+    router: ShardRouter,
+    routed_messages: Receiver<RoutedMessage>,
+}
+
+/// Handle to send messages to clients of any shard, it can be cloned and sent to other threads.
+/// The messages are sent by the shard of the client in its next update.
+#[derive(Debug, Clone)]
+pub struct ShardRouter {
+    senders: Vec<Sender<RoutedMessage>>,
+}
+
+#[derive(Debug)]
+enum RoutedMessage {
+    Send {
+        client_id: ClientId,
+        channel_id: u8,
+        message: Bytes,
+    },
+    Broadcast {
+        except_id: Option<ClientId>,
+        channel_id: u8,
+        message: Bytes,
+    },
+}
+
+impl ShardRouter {
+    /// Returns the number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.senders.len()
+    }
+
+    /// Returns the index of the shard that owns the client connection.
+    pub fn shard_for(&self, client_id: ClientId) -> usize {
+        (client_id % self.senders.len() as u64) as usize
+    }
+
+    /// Sends a message to the client, from any shard.
+    pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&self, client_id: ClientId, channel_id: I, message: B) {
+        let routed_message = RoutedMessage::Send {
+            client_id,
+            channel_id: channel_id.into(),
+            message: message.into(),
+        };
+        // The shard was dropped, its connections no longer exist
+        let _ = self.senders[self.shard_for(client_id)].send(routed_message);
+    }
+
+    /// Sends a message to all clients of all shards.
+    pub fn broadcast_message<I: Into<u8>, B: Into<Bytes>>(&self, channel_id: I, message: B) {
+        self.broadcast(None, channel_id.into(), message.into());
+    }
+
+    /// Sends a message to all clients of all shards, except for one.
+    pub fn broadcast_message_except<I: Into<u8>, B: Into<Bytes>>(&self, except_id: ClientId, channel_id: I, message: B) {
+        self.broadcast(Some(except_id), channel_id.into(), message.into());
+    }
+
+    fn broadcast(&self, except_id: Option<ClientId>, channel_id: u8, message: Bytes) {
+        for sender in self.senders.iter() {
+            let _ = sender.send(RoutedMessage::Broadcast {
+                except_id,
+                channel_id,
+                message: message.clone(),
+            });
+        }
+    }
+}
+
+impl RenetShard {
+    /// Returns the index of the shard.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns whether the client is assigned to this shard.
+    pub fn owns(&self, client_id: ClientId) -> bool {
+        self.router.shard_for(client_id) == self.index
+    }
+
+    pub fn server(&self) -> &RenetServer {
+        &self.server
+    }
+
+    /// Returns the server of the shard, to be used with a transport or any [`RenetServer`] method.
+    ///
+    /// The transport must only add connections of clients assigned to this shard, see [`RenetShard::owns`].
+    /// Connections added with other client ids don't receive the messages sent with the [`ShardRouter`],
+    /// so the connect tokens for the address of this shard must use client ids it owns.
+    pub fn server_mut(&mut self) -> &mut RenetServer {
+        &mut self.server
+    }
+
+    /// Adds the connection if the client is assigned to this shard, returns false otherwise.
+    /// See [`RenetServer::add_connection`].
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn add_connection(&mut self, client_id: ClientId) -> bool {
+        if !self.owns(client_id) {
+            log::warn!(
+                "Rejected connection of client {client_id} in shard {}, it's assigned to shard {}",
+                self.index,
+                self.router.shard_for(client_id)
+            );
+            return false;
+        }
+
+        self.server.add_connection(client_id);
+        true
+    }
+
+    /// Returns the router to send messages to clients of other shards.
+    pub fn router(&self) -> &ShardRouter {
+        &self.router
+    }
+
+    /// Advances the server of the shard by the duration, and sends the messages routed to its clients.
+    pub fn update(&mut self, duration: Duration) {
+        self.server.update(duration);
+
+        while let Ok(routed_message) = self.routed_messages.try_recv() {
+            match routed_message {
+                RoutedMessage::Send {
+                    client_id,
+                    channel_id,
+                    message,
+                } => {
+                    if let Err(e) = self.server.try_send_message(client_id, channel_id, message) {
+                        log::error!("Failed to send routed message to client {client_id}: {e}");
+                    }
+                }
+                RoutedMessage::Broadcast {
+                    except_id: Some(except_id),
+                    channel_id,
+                    message,
+                } => self.server.broadcast_message_except(except_id, channel_id, message),
+                RoutedMessage::Broadcast {
+                    except_id: None,
+                    channel_id,
+                    message,
+                } => self.server.broadcast_message(channel_id, message),
+            }
+        }
+    }
+}
+
+impl ShardedRenetServer {
+    /// Creates the server with the number of shards, each shard uses the connection config.
+    ///
+    /// # Panics
+    /// Panics if the number of shards is 0.
+    pub fn new(connection_config: ConnectionConfig, shard_count: usize) -> Self {
+        assert!(shard_count > 0, "a sharded server needs at least one shard");
+
+        let (senders, receivers): (Vec<_>, Vec<_>) = (0..shard_count).map(|_| mpsc::channel()).unzip();
+        let router = ShardRouter { senders };
+        let shards = receivers
+            .into_iter()
+            .enumerate()
+            .map(|(index, routed_messages)| RenetShard {
+                index,
+                server: RenetServer::new(connection_config.clone()),
+                router: router.clone(),
+                routed_messages,
+            })
+            .collect();
+
+        Self { shards, router }
+    }
+
+    /// Returns a router to send messages to the clients of any shard.
+    pub fn router(&self) -> ShardRouter {
+        self.router.clone()
+    }
+
+    /// Returns the number of shards.
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns the index of the shard that owns the client connection.
+    pub fn shard_for(&self, client_id: ClientId) -> usize {
+        self.router.shard_for(client_id)
+    }
+
+    /// Returns the shard, panics if the index is out of bounds.
+    pub fn shard(&self, index: usize) -> &RenetShard {
+        &self.shards[index]
+    }
+
+    /// Returns the shard, panics if the index is out of bounds.
+    pub fn shard_mut(&mut self, index: usize) -> &mut RenetShard {
+        &mut self.shards[index]
+    }
+
+    /// Returns all shards, they can be updated in parallel with scoped threads.
+    pub fn shards_mut(&mut self) -> &mut [RenetShard] {
+        &mut self.shards
+    }
+
+    /// Splits the server in its shards, to move each one to a worker thread.
+    ///
+    /// Each shard must only have the connections of the clients assigned to it, see [`RenetShard::add_connection`].
+    /// When each shard has its own transport, the connect tokens for the address of the shard `k`
+    /// must use client ids with `client_id % shard_count == k`.
+    pub fn into_shards(self) -> Vec<RenetShard> {
+        self.shards
+    }
+
+    /// Advances all shards by the duration, see [`RenetShard::update`].
+    pub fn update(&mut self, duration: Duration) {
+        for shard in self.shards.iter_mut() {
+            shard.update(duration);
+        }
+    }
+
+    /// Returns the next event of any shard.
+    pub fn get_event(&mut self) -> Option<ServerEvent> {
+        self.shards.iter_mut().find_map(|shard| shard.server.get_event())
+    }
+
+    /// Returns the number of connected clients in all shards.
+    pub fn connected_clients(&self) -> usize {
+        self.shards.iter().map(|shard| shard.server.connected_clients()).sum()
+    }
+
+    pub fn is_connected(&self, client_id: ClientId) -> bool {
+        self.shards[self.shard_for(client_id)].server.is_connected(client_id)
+    }
+
+    /// Adds the connection to the shard of the client, see [`RenetServer::add_connection`].
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn add_connection(&mut self, client_id: ClientId) {
+        let index = self.shard_for(client_id);
+        self.shards[index].server.add_connection(client_id);
+    }
+
+    /// Removes the connection from the shard of the client, see [`RenetServer::remove_connection`].
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn remove_connection(&mut self, client_id: ClientId) {
+        let index = self.shard_for(client_id);
+        self.shards[index].server.remove_connection(client_id);
+    }
+
+    /// Process a packet received from the client in its shard.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn process_packet_from(&mut self, payload: &[u8], client_id: ClientId) -> Result<(), ClientNotFound> {
+        let index = self.shard_for(client_id);
+        self.shards[index].server.process_packet_from(payload, client_id)
+    }
+
+    /// Returns the packets to be sent to the client by its shard.
+    /// <p style="background:rgba(77,220,255,0.16);padding:0.5em;">
+    /// <strong>Note:</strong> This should only be called by the transport layer.
+    /// </p>
+    pub fn get_packets_to_send(&mut self, client_id: ClientId) -> Result<Vec<Payload>, ClientNotFound> {
+        let index = self.shard_for(client_id);
+        self.shards[index].server.get_packets_to_send(client_id)
+    }
+
+    /// Sends a message to the client, in its shard.
+    pub fn send_message<I: Into<u8>, B: Into<Bytes>>(&mut self, client_id: ClientId, channel_id: I, message: B) {
+        let index = self.shard_for(client_id);
+        self.shards[index].server.send_message(client_id, channel_id, message);
+    }
+
+    /// Sends a message to all clients of all shards.
+    pub fn broadcast_message<I: Into<u8>, B: Into<Bytes>>(&mut self, channel_id: I, message: B) {
+        let channel_id = channel_id.into();
+        let message = message.into();
+        for shard in self.shards.iter_mut() {
+            shard.server.broadcast_message(channel_id, message.clone());
+        }
+    }
+
+    /// Receives a message from the client, in its shard.
+    pub fn receive_message<I: Into<u8>>(&mut self, client_id: ClientId, channel_id: I) -> Option<Bytes> {
+        let index = self.shard_for(client_id);
+        self.shards[index].server.receive_message(client_id, channel_id)
+    }
+}
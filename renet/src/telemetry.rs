@@ -0,0 +1,219 @@
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use bytes::Bytes;
+
+use crate::{ClientId, NetworkInfo, RenetClient, RenetServer};
+
+// Client id followed by the 12 metrics of the NetworkInfo
+const CLIENT_INFO_BYTES: usize = 8 + 12 * 8;
+
+/// Streams the [`NetworkInfo`] of all clients to the observer clients, such as developers or admins,
+/// so the server can be monitored live from another machine.
+///
+/// Only clients added with [`TelemetryServer::add_observer`] receive the telemetry, the server decides who
+/// can observe it, for example with the user data from the connect token. The observers receive the telemetry
+/// with a [`TelemetryClient`] in a reserved channel, that can be unreliable since each message replaces the previous one.
+///
+/// # Usage
+/// ```
+/// # use renet::{ConnectionConfig, DefaultChannel, RenetServer, TelemetryServer};
+/// # use std::time::Duration;
+/// # let mut server = RenetServer::new(ConnectionConfig::default());
+/// # let admin_client_id = 0;
+/// # let duration = Duration::from_millis(16);
+/// let mut telemetry = TelemetryServer::new(DefaultChannel::Unreliable, Duration::from_secs(1));
+/// telemetry.add_observer(admin_client_id);
+///
+/// // Every update
+/// telemetry.update(duration, &mut server);
+/// ```
+#[derive(Debug)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
+pub struct TelemetryServer {
+    channel_id: u8,
+    interval: Duration,
+    elapsed: Duration,
+    observers: BTreeSet<ClientId>,
+}
+
+/// Receives the telemetry sent by a [`TelemetryServer`], in an observer client.
+///
+/// The network info of the server clients can be shown with the `RenetServerVisualizer` from the `renet_visualizer` crate.
+///
+/// # Usage
+/// ```
+/// # use renet::{ConnectionConfig, DefaultChannel, RenetClient, TelemetryClient};
+/// # let mut client = RenetClient::new(ConnectionConfig::default());
+/// let mut telemetry = TelemetryClient::new(DefaultChannel::Unreliable);
+///
+/// // Every update
+/// if telemetry.receive(&mut client) {
+///     for (client_id, network_info) in telemetry.network_infos() {
+///         println!("Client {client_id} RTT: {:.0}ms", network_info.rtt * 1000.);
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
+pub struct TelemetryClient {
+    channel_id: u8,
+    network_infos: Vec<(ClientId, NetworkInfo)>,
+}
+
+impl TelemetryServer {
+    /// Creates the telemetry server, sending the network info in the channel every interval.
+    pub fn new<I: Into<u8>>(channel_id: I, interval: Duration) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            interval,
+            elapsed: Duration::ZERO,
+            observers: BTreeSet::new(),
+        }
+    }
+
+    /// Starts sending the telemetry to the client.
+    pub fn add_observer(&mut self, client_id: ClientId) {
+        self.observers.insert(client_id);
+    }
+
+    /// Stops sending the telemetry to the client, disconnected clients are removed automatically.
+    pub fn remove_observer(&mut self, client_id: ClientId) {
+        self.observers.remove(&client_id);
+    }
+
+    pub fn is_observer(&self, client_id: ClientId) -> bool {
+        self.observers.contains(&client_id)
+    }
+
+    /// Advances the interval timer, and sends the network info of all clients to the observers when it elapses.
+    pub fn update(&mut self, duration: Duration, server: &mut RenetServer) {
+        self.observers.retain(|&client_id| server.is_connected(client_id));
+
+        self.elapsed += duration;
+        if self.elapsed < self.interval {
+            return;
+        }
+        self.elapsed = Duration::ZERO;
+
+        if self.observers.is_empty() {
+            return;
+        }
+
+        let mut message = Vec::with_capacity(server.connected_clients() * CLIENT_INFO_BYTES);
+        for client_id in server.clients_id_iter() {
+            let Ok(network_info) = server.network_info(client_id) else {
+                continue;
+            };
+            message.extend_from_slice(&client_id.to_le_bytes());
+            for value in [
+                network_info.rtt,
+                network_info.packet_loss,
+                network_info.retransmission_ratio,
+                network_info.bytes_sent_per_second,
+                network_info.bytes_received_per_second,
+                network_info.goodput_bytes_per_second,
+                network_info.efficiency,
+                network_info.smoothed_packet_loss,
+                network_info.smoothed_bytes_sent_per_second,
+                network_info.smoothed_bytes_received_per_second,
+                network_info.jitter,
+                network_info.rtt_variation,
+            ] {
+                message.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        let message = Bytes::from(message);
+        for &client_id in self.observers.iter() {
+            server.send_message(client_id, self.channel_id, message.clone());
+        }
+    }
+}
+
+impl TelemetryClient {
+    /// Creates the telemetry client, receiving the network info from the channel.
+    pub fn new<I: Into<u8>>(channel_id: I) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            network_infos: Vec::new(),
+        }
+    }
+
+    /// Receives the telemetry from the server, returns true when new network info was received.
+    /// Only the most recent telemetry is kept.
+    pub fn receive(&mut self, client: &mut RenetClient) -> bool {
+        let mut received = false;
+        while let Some(message) = client.receive_message(self.channel_id) {
+            if message.len() % CLIENT_INFO_BYTES != 0 {
+                log::error!("Received invalid telemetry message with {} bytes", message.len());
+                continue;
+            }
+
+            self.network_infos = message.chunks_exact(CLIENT_INFO_BYTES).map(deserialize_client_info).collect();
+            received = true;
+        }
+
+        received
+    }
+
+    /// Returns the network info of the server clients, from the last telemetry received.
+    pub fn network_infos(&self) -> impl Iterator<Item = (ClientId, NetworkInfo)> + '_ {
+        self.network_infos.iter().copied()
+    }
+}
+
+fn deserialize_client_info(bytes: &[u8]) -> (ClientId, NetworkInfo) {
+    let read = |index: usize| f64::from_le_bytes(bytes[8 + index * 8..16 + index * 8].try_into().unwrap());
+    let client_id = ClientId::from_le_bytes(bytes[..8].try_into().unwrap());
+    let network_info = NetworkInfo {
+        rtt: read(0),
+        packet_loss: read(1),
+        retransmission_ratio: read(2),
+        bytes_sent_per_second: read(3),
+        bytes_received_per_second: read(4),
+        goodput_bytes_per_second: read(5),
+        efficiency: read(6),
+        smoothed_packet_loss: read(7),
+        smoothed_bytes_sent_per_second: read(8),
+        smoothed_bytes_received_per_second: read(9),
+        jitter: read(10),
+        rtt_variation: read(11),
+    };
+
+    (client_id, network_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConnectionConfig, DefaultChannel};
+
+    #[test]
+    fn telemetry_observers() {
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let mut observer = server.new_local_client(0);
+        let mut player = server.new_local_client(1);
+
+        let mut telemetry_server = TelemetryServer::new(DefaultChannel::Unreliable, Duration::from_secs(1));
+        telemetry_server.add_observer(0);
+        let mut observer_telemetry = TelemetryClient::new(DefaultChannel::Unreliable);
+        let mut player_telemetry = TelemetryClient::new(DefaultChannel::Unreliable);
+
+        telemetry_server.update(Duration::from_millis(500), &mut server);
+        server.process_local_client(0, &mut observer).unwrap();
+        assert!(!observer_telemetry.receive(&mut observer));
+
+        telemetry_server.update(Duration::from_millis(500), &mut server);
+        server.process_local_client(0, &mut observer).unwrap();
+        server.process_local_client(1, &mut player).unwrap();
+        assert!(observer_telemetry.receive(&mut observer));
+        assert!(!player_telemetry.receive(&mut player));
+
+        let network_infos: Vec<_> = observer_telemetry.network_infos().collect();
+        assert_eq!(network_infos.len(), 2);
+        assert_eq!(network_infos[0].0, 0);
+        assert_eq!(network_infos[1].0, 1);
+        assert_eq!(network_infos[1].1.rtt, server.network_info(1).unwrap().rtt);
+    }
+}
@@ -1,5 +1,11 @@
 use bytes::Bytes;
-use renet::{ClientId, ConnectionConfig, DefaultChannel, DisconnectReason, RenetClient, RenetServer, ServerEvent};
+use renet::{
+    ChannelConfig, ClientId, ConfigError, ConnectionConfig, ConnectionDeniedReason, DefaultChannel, DisconnectReason, InvalidChannelId,
+    PacketDirection, RateLimit, RateLimiter, RenetClient, RenetServer, ResendStrategy, SendError, SendType, ServerEvent,
+    ShardedRenetServer, SnapshotRatePolicy, StaleConnectionPolicy,
+};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 pub fn init_log() {
     let _ = env_logger::builder().is_test(true).try_init();
@@ -69,6 +75,287 @@ fn test_remote_connection_reliable_channel() {
     );
 }
 
+#[test]
+fn test_redundant_channel() {
+    init_log();
+    let channels_config = vec![ChannelConfig {
+        channel_id: 0,
+        max_memory_usage_bytes: 1024,
+        send_type: SendType::UnreliableRedundant { redundancy: 2 },
+        slice_size: None,
+        ordering: None,
+    }];
+    let config = ConnectionConfig {
+        client_channels_config: channels_config.clone(),
+        server_channels_config: channels_config,
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(config.clone());
+    let mut client = RenetClient::new(config);
+
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    for input in 0..6u8 {
+        client.send_message(0, vec![input]);
+        let packets = client.get_packets_to_send();
+        // Lose two of every three packets
+        if input % 3 == 2 {
+            for packet in packets {
+                server.process_packet_from(&packet, client_id).unwrap();
+            }
+        }
+    }
+
+    for input in 0..6u8 {
+        assert_eq!(server.receive_message(client_id, 0).unwrap(), vec![input]);
+    }
+    assert!(server.receive_message(client_id, 0).is_none());
+}
+
+#[test]
+fn test_reliable_streams() {
+    init_log();
+    let channels_config = vec![ChannelConfig {
+        channel_id: 0,
+        max_memory_usage_bytes: 10 * 1024,
+        send_type: SendType::ReliableOrderedStreams {
+            resend_time: ResendStrategy::Fixed(Duration::from_millis(100)),
+        },
+        slice_size: None,
+        ordering: None,
+    }];
+    let config = ConnectionConfig {
+        client_channels_config: channels_config.clone(),
+        server_channels_config: channels_config,
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(config.clone());
+    let mut client = RenetClient::new(config);
+
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    // The packet with the first message of stream 0 is lost
+    client.send_message_on_stream(0, 0, vec![0]);
+    client.get_packets_to_send();
+
+    client.send_message_on_stream(0, 0, vec![1]);
+    client.send_message_on_stream(0, 1, vec![2]);
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+
+    // Stream 1 is not blocked by the lost message
+    assert_eq!(server.receive_message(client_id, 0).unwrap(), vec![2]);
+    assert!(server.receive_message(client_id, 0).is_none());
+
+    client.update(Duration::from_millis(100));
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    assert_eq!(server.receive_message(client_id, 0).unwrap(), vec![0]);
+    assert_eq!(server.receive_message(client_id, 0).unwrap(), vec![1]);
+}
+
+#[test]
+fn test_custom_slice_size() {
+    init_log();
+    let channels_config = vec![ChannelConfig {
+        channel_id: 0,
+        max_memory_usage_bytes: 10 * 1024,
+        send_type: SendType::ReliableOrdered {
+            resend_time: ResendStrategy::Fixed(Duration::from_millis(300)),
+        },
+        slice_size: Some(500),
+        ordering: None,
+    }];
+    let config = ConnectionConfig {
+        client_channels_config: channels_config.clone(),
+        server_channels_config: channels_config,
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(config.clone());
+    let mut client = RenetClient::new(config);
+
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    let message = Bytes::from(vec![7; 2100]);
+    client.send_message(0, message.clone());
+    let packets = client.get_packets_to_send();
+    assert_eq!(packets.len(), 5);
+    for packet in packets {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+
+    assert_eq!(server.receive_message(client_id, 0).unwrap(), message);
+
+    let client_stats = client.slice_stats();
+    assert_eq!(client_stats.sliced_messages_sent, 1);
+    assert_eq!(client_stats.average_slices_per_message(), 5.0);
+    let server_stats = server.slice_stats(client_id).unwrap();
+    assert_eq!(server_stats.sliced_messages_received, 1);
+}
+
+#[test]
+fn test_max_packet_size() {
+    init_log();
+    let channels_config = vec![ChannelConfig {
+        channel_id: 0,
+        max_memory_usage_bytes: 10 * 1024,
+        send_type: SendType::ReliableOrdered {
+            resend_time: ResendStrategy::Fixed(Duration::from_millis(300)),
+        },
+        slice_size: Some(500),
+        ordering: None,
+    }];
+    let config = ConnectionConfig {
+        client_channels_config: channels_config.clone(),
+        server_channels_config: channels_config,
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(config.clone());
+    let mut client = RenetClient::new(config);
+    assert_eq!(client.max_packet_size(), 1400);
+    client.set_max_packet_size(600);
+
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    for _ in 0..10 {
+        client.send_message(0, vec![3; 200]);
+    }
+    let sliced_message = Bytes::from(vec![7; 1200]);
+    client.send_message(0, sliced_message.clone());
+
+    let packets = client.get_packets_to_send();
+    assert!(packets.iter().all(|packet| packet.len() <= 600));
+    for packet in packets {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+
+    for _ in 0..10 {
+        assert_eq!(server.receive_message(client_id, 0).unwrap(), vec![3; 200]);
+    }
+    assert_eq!(server.receive_message(client_id, 0).unwrap(), sliced_message);
+}
+
+#[test]
+fn test_multiple_sends_per_tick() {
+    init_log();
+    let config = ConnectionConfig {
+        available_bytes_per_tick: 1000,
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(config.clone());
+    let mut client = RenetClient::new(config);
+
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    server.send_message(client_id, DefaultChannel::Unreliable, vec![0; 10]);
+    for packet in server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+
+    // The tick budget and the ack are shared by all sends in the tick
+    client.update(Duration::from_millis(16));
+    client.send_message(DefaultChannel::Unreliable, vec![1; 800]);
+    assert_eq!(client.get_packets_to_send().len(), 2);
+    client.send_message(DefaultChannel::Unreliable, vec![2; 800]);
+    assert!(client.get_packets_to_send().is_empty());
+
+    client.update(Duration::from_millis(16));
+    client.send_message(DefaultChannel::Unreliable, vec![3; 800]);
+    assert_eq!(client.get_packets_to_send().len(), 2);
+}
+
+#[test]
+fn test_rate_limiter() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut rate_limiter = RateLimiter::default();
+    rate_limiter.set_limit(
+        DefaultChannel::ReliableOrdered,
+        RateLimit {
+            messages_per_second: 1.0,
+            burst: 2,
+        },
+    );
+    server.set_rate_limiter(rate_limiter);
+
+    let client_id: ClientId = 0;
+    let mut client = server.new_local_client(client_id);
+    for _ in 0..5 {
+        client.send_message(DefaultChannel::ReliableOrdered, Bytes::from("spam"));
+        client.send_message(DefaultChannel::Unreliable, Bytes::from("input"));
+    }
+    server.process_local_client(client_id, &mut client).unwrap();
+
+    let mut count = 0;
+    while server.receive_message(client_id, DefaultChannel::ReliableOrdered).is_some() {
+        count += 1;
+    }
+    assert_eq!(count, 2);
+    assert_eq!(
+        server
+            .rate_limiter()
+            .unwrap()
+            .dropped_messages(client_id, DefaultChannel::ReliableOrdered),
+        3
+    );
+
+    // Channels without limit are not affected
+    let mut count = 0;
+    while server.receive_message(client_id, DefaultChannel::Unreliable).is_some() {
+        count += 1;
+    }
+    assert_eq!(count, 5);
+}
+
+#[test]
+fn test_memory_limit() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    server.add_connection(0);
+    server.add_connection(1);
+    assert_eq!(server.memory_usage(), 0);
+
+    server.send_message(0, DefaultChannel::ReliableOrdered, vec![0; 1000]);
+    server.send_message(1, DefaultChannel::ReliableOrdered, vec![0; 3000]);
+    assert_eq!(server.memory_usage(), 4000);
+
+    // New messages above the limit are dropped
+    server.set_memory_limit(4500);
+    server.send_message(0, DefaultChannel::ReliableOrdered, vec![0; 1000]);
+    assert_eq!(server.memory_usage(), 4000);
+
+    // The connection using the most memory is disconnected when above the limit
+    server.set_memory_limit(3500);
+    server.update(Duration::ZERO);
+    assert_eq!(server.disconnect_reason(1), Some(DisconnectReason::MemoryLimitReached));
+    assert!(server.disconnect_reason(0).is_none());
+
+    server.clear_memory_limit();
+    server.send_message(0, DefaultChannel::ReliableOrdered, vec![0; 5000]);
+    server.update(Duration::ZERO);
+    assert!(server.disconnect_reason(0).is_none());
+}
+
+#[test]
+fn test_deterministic_iteration_order() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    for client_id in [42, 7, 1000, 3] {
+        server.add_connection(client_id);
+    }
+    assert_eq!(server.clients_id(), vec![3, 7, 42, 1000]);
+
+    server.disconnect(1000);
+    server.disconnect(7);
+    assert_eq!(server.disconnections_id(), vec![7, 1000]);
+}
+
 #[test]
 fn test_local_client() {
     init_log();
@@ -102,3 +389,968 @@ fn test_local_client() {
             }
     );
 }
+
+#[test]
+fn test_local_client_sliced_message() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+
+    let client_id: ClientId = 0;
+    let mut client = server.new_local_client(client_id);
+
+    let message = Bytes::from("test".repeat(1000));
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, message.clone());
+    client.send_message(DefaultChannel::Unreliable, message.clone());
+
+    server.process_local_client(client_id, &mut client).unwrap();
+
+    let server_message = server.receive_message(client_id, DefaultChannel::Unreliable).unwrap();
+    assert_eq!(server_message, message);
+
+    let client_message = client.receive_message(DefaultChannel::ReliableOrdered).unwrap();
+    assert_eq!(client_message, message);
+
+    // Acks are also exchanged locally
+    server.process_local_client(client_id, &mut client).unwrap();
+    assert_eq!(
+        server.channel_available_memory(client_id, DefaultChannel::ReliableOrdered),
+        5 * 1024 * 1024
+    );
+}
+
+#[test]
+fn test_local_client_disconnect_propagation() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+
+    // Client disconnects itself
+    let client_id: ClientId = 0;
+    let mut client = server.new_local_client(client_id);
+    assert_eq!(server.get_event().unwrap(), ServerEvent::ClientConnected { client_id });
+
+    client.disconnect();
+    server.process_local_client(client_id, &mut client).unwrap();
+    assert_eq!(
+        server.get_event().unwrap(),
+        ServerEvent::ClientDisconnected {
+            client_id,
+            reason: DisconnectReason::DisconnectedByClient
+        }
+    );
+    assert!(server.process_local_client(client_id, &mut client).is_err());
+
+    // Server disconnects the client
+    let client_id: ClientId = 1;
+    let mut client = server.new_local_client(client_id);
+    assert_eq!(server.get_event().unwrap(), ServerEvent::ClientConnected { client_id });
+
+    server.disconnect(client_id);
+    server.process_local_client(client_id, &mut client).unwrap();
+    assert_eq!(client.disconnect_reason(), Some(DisconnectReason::DisconnectedByServer));
+    assert_eq!(
+        server.get_event().unwrap(),
+        ServerEvent::ClientDisconnected {
+            client_id,
+            reason: DisconnectReason::DisconnectedByServer
+        }
+    );
+}
+
+#[test]
+fn test_session_resumption() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    server.set_session_resumption(Duration::from_secs(5));
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+    assert_eq!(server.get_event().unwrap(), ServerEvent::ClientConnected { client_id });
+
+    // Messages sent before and while the connection is held are delivered after resuming
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, Bytes::from("before"));
+    server.connection_lost(client_id);
+    client.disconnect_due_to_transport();
+    assert!(server.is_connection_held(client_id));
+    assert!(!server.is_connected(client_id));
+    assert!(server.get_event().is_none());
+
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, Bytes::from("while held"));
+    server.update(Duration::from_secs(1));
+
+    assert!(client.resume_session());
+    assert!(server.resume_connection(client_id));
+    assert!(server.is_connected(client_id));
+    client.set_connected();
+
+    for packet in server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+    assert_eq!(client.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "before");
+    assert_eq!(client.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "while held");
+    assert!(server.get_event().is_none());
+
+    // Held connections are disconnected after the grace period
+    server.connection_lost(client_id);
+    server.update(Duration::from_secs(5));
+    assert!(!server.is_connection_held(client_id));
+    assert!(!server.resume_connection(client_id));
+    assert_eq!(
+        server.get_event().unwrap(),
+        ServerEvent::ClientDisconnected {
+            client_id,
+            reason: DisconnectReason::Transport
+        }
+    );
+
+    // Clients disconnected for other reasons can't resume
+    client.disconnect();
+    assert!(!client.resume_session());
+}
+
+#[test]
+fn test_disconnect_held_local_client() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    server.set_session_resumption(Duration::from_secs(5));
+
+    let client_id: ClientId = 0;
+    let mut client = server.new_local_client(client_id);
+    assert_eq!(server.get_event().unwrap(), ServerEvent::ClientConnected { client_id });
+
+    server.connection_lost(client_id);
+    assert!(server.is_connection_held(client_id));
+
+    server.disconnect_local_client(client_id, &mut client);
+    assert!(!server.is_connection_held(client_id));
+    assert!(!server.resume_connection(client_id));
+    assert_eq!(
+        server.get_event().unwrap(),
+        ServerEvent::ClientDisconnected {
+            client_id,
+            reason: DisconnectReason::DisconnectedByClient
+        }
+    );
+
+    server.update(Duration::from_secs(1));
+    assert!(server.get_event().is_none());
+}
+
+#[test]
+fn test_receive_dedup_window() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    server.set_session_resumption(Duration::from_secs(5));
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_receive_dedup_window(64);
+    client.set_connected();
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    server.send_message(client_id, DefaultChannel::ReliableUnordered, Bytes::from("first"));
+    for packet in server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+    assert_eq!(client.receive_message(DefaultChannel::ReliableUnordered).unwrap(), "first");
+
+    // The ack never reaches the server before the connection is lost, the session is resumed
+    server.connection_lost(client_id);
+    client.disconnect_due_to_transport();
+    server.update(Duration::from_secs(1));
+    assert!(client.resume_session());
+    assert!(server.resume_connection(client_id));
+    client.set_connected();
+
+    // The server resends the message with the next one, it's only delivered once
+    server.send_message(client_id, DefaultChannel::ReliableUnordered, Bytes::from("second"));
+    for packet in server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+    assert_eq!(client.receive_message(DefaultChannel::ReliableUnordered).unwrap(), "second");
+    assert!(client.receive_message(DefaultChannel::ReliableUnordered).is_none());
+
+    // Transferred to another server, its first messages have the ids already delivered by the previous one
+    let mut other_server = RenetServer::new(ConnectionConfig::default());
+    client.reset_connection();
+    client.set_connected();
+    other_server.add_connection(client_id);
+    other_server.send_message(client_id, DefaultChannel::ReliableUnordered, Bytes::from("other first"));
+    for packet in other_server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+    assert_eq!(client.receive_message(DefaultChannel::ReliableUnordered).unwrap(), "other first");
+}
+
+#[test]
+fn test_packet_observer() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    let observed = Arc::new(Mutex::new(vec![]));
+    let server_observed = observed.clone();
+    server.set_packet_observer(1.0, move |direction, client_id, packet| {
+        server_observed.lock().unwrap().push((direction, client_id, packet.len()));
+    });
+
+    client.send_message(DefaultChannel::ReliableOrdered, vec![0; 10]);
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, vec![0; 20]);
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    let server_packets = server.get_packets_to_send(client_id).unwrap();
+
+    let observed_packets = observed.lock().unwrap().clone();
+    assert_eq!(observed_packets.len(), 1 + server_packets.len());
+    assert_eq!(observed_packets[0].0, PacketDirection::ClientToServer);
+    assert!(observed_packets[1..]
+        .iter()
+        .all(|&(direction, id, _)| direction == PacketDirection::ServerToClient && id == client_id));
+
+    // No packets are observed with a zero sample rate
+    let observed = Arc::new(Mutex::new(0));
+    let server_observed = observed.clone();
+    server.set_packet_observer(0.0, move |_, _, _| *server_observed.lock().unwrap() += 1);
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, vec![0; 20]);
+    server.get_packets_to_send(client_id).unwrap();
+    assert_eq!(*observed.lock().unwrap(), 0);
+
+    server.clear_packet_observer();
+}
+
+#[test]
+fn test_transfer_client() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, Bytes::from("not received"));
+    server.transfer_client(client_id, Bytes::from_static(&[1, 2, 3])).unwrap();
+    assert!(server.transfer_client(1, Bytes::new()).is_err());
+
+    assert!(client.take_transfer().is_none());
+    for packet in server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+    assert_eq!(client.take_transfer().unwrap()[..], [1, 2, 3]);
+    assert!(client.take_transfer().is_none());
+
+    // The connection state of the previous server is discarded
+    client.reset_connection();
+    assert!(client.is_connecting());
+    assert!(client.receive_message(DefaultChannel::ReliableOrdered).is_none());
+
+    let mut new_server = RenetServer::new(ConnectionConfig::default());
+    new_server.add_connection(client_id);
+    client.set_connected();
+    client.send_message(DefaultChannel::ReliableOrdered, Bytes::from("hello"));
+    for packet in client.get_packets_to_send() {
+        new_server.process_packet_from(&packet, client_id).unwrap();
+    }
+    assert_eq!(
+        new_server.receive_message(client_id, DefaultChannel::ReliableOrdered).unwrap(),
+        "hello"
+    );
+}
+
+#[test]
+fn test_restricted_channel() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut admin = RenetClient::new(ConnectionConfig::default());
+    let mut player = RenetClient::new(ConnectionConfig::default());
+    let (admin_id, player_id): (ClientId, ClientId) = (0, 1);
+    server.add_connection(admin_id);
+    server.restrict_channel(DefaultChannel::ReliableOrdered);
+    server.add_connection(player_id);
+    server.allow_channel(admin_id, DefaultChannel::ReliableOrdered).unwrap();
+
+    assert!(server.is_channel_restricted(DefaultChannel::ReliableOrdered));
+    assert!(server.can_access_channel(admin_id, DefaultChannel::ReliableOrdered));
+    assert!(!server.can_access_channel(player_id, DefaultChannel::ReliableOrdered));
+    assert!(server.can_access_channel(player_id, DefaultChannel::Unreliable));
+
+    // Messages to clients not allowed are dropped
+    server.broadcast_message(DefaultChannel::ReliableOrdered, Bytes::from("admins only"));
+    for (client_id, client) in [(admin_id, &mut admin), (player_id, &mut player)] {
+        for packet in server.get_packets_to_send(client_id).unwrap() {
+            client.process_packet(&packet);
+        }
+    }
+    assert_eq!(admin.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "admins only");
+    assert!(player.receive_message(DefaultChannel::ReliableOrdered).is_none());
+
+    // Messages from clients not allowed are dropped and counted
+    for (client_id, client) in [(admin_id, &mut admin), (player_id, &mut player)] {
+        client.send_message(DefaultChannel::ReliableOrdered, Bytes::from("command"));
+        for packet in client.get_packets_to_send() {
+            server.process_packet_from(&packet, client_id).unwrap();
+        }
+    }
+    assert_eq!(
+        server.receive_message(admin_id, DefaultChannel::ReliableOrdered).unwrap(),
+        "command"
+    );
+    assert!(server.receive_message(player_id, DefaultChannel::ReliableOrdered).is_none());
+    assert_eq!(server.channel_access_violations(admin_id).unwrap(), 0);
+    assert_eq!(server.channel_access_violations(player_id).unwrap(), 1);
+    assert!(server.is_connected(player_id));
+
+    server.disallow_channel(admin_id, DefaultChannel::ReliableOrdered).unwrap();
+    assert!(!server.can_access_channel(admin_id, DefaultChannel::ReliableOrdered));
+    server.unrestrict_channel(DefaultChannel::ReliableOrdered);
+    assert!(server.can_access_channel(player_id, DefaultChannel::ReliableOrdered));
+}
+
+#[test]
+fn test_restricted_channel_allowed_later() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+    let client_id: ClientId = 0;
+    server.restrict_channel(DefaultChannel::ReliableOrdered);
+    server.add_connection(client_id);
+
+    // Sent before the client is allowed, the message is discarded
+    client.send_message(DefaultChannel::ReliableOrdered, vec![0; 10]);
+    client.send_message(DefaultChannel::ReliableOrdered, vec![1; 5000]);
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    assert_eq!(server.channel_access_violations(client_id).unwrap(), 6);
+    assert!(server.receive_message(client_id, DefaultChannel::ReliableOrdered).is_none());
+
+    // The channel does not wait for the discarded messages once the client is allowed
+    server.allow_channel(client_id, DefaultChannel::ReliableOrdered).unwrap();
+    for i in 0..20u8 {
+        client.send_message(DefaultChannel::ReliableOrdered, vec![i]);
+    }
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    for i in 0..20u8 {
+        assert_eq!(server.receive_message(client_id, DefaultChannel::ReliableOrdered).unwrap(), vec![i]);
+    }
+    assert!(server.receive_message(client_id, DefaultChannel::ReliableOrdered).is_none());
+    assert!(server.is_connected(client_id));
+}
+
+#[test]
+fn test_invalid_channel_id() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    assert_eq!(
+        client.try_send_message(100, Bytes::from("invalid")),
+        Err(SendError::InvalidChannelId(100))
+    );
+    assert_eq!(client.try_receive_message(100), Err(InvalidChannelId(100)));
+    assert_eq!(
+        server.try_send_message(client_id, 100, Bytes::from("invalid")),
+        Err(SendError::InvalidChannelId(100))
+    );
+    assert_eq!(server.try_receive_message(client_id, 100), Err(InvalidChannelId(100)));
+    assert!(client.is_connected());
+    assert!(server.is_connected(client_id));
+
+    client
+        .try_send_message(DefaultChannel::ReliableOrdered, Bytes::from("valid"))
+        .unwrap();
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    assert_eq!(
+        server
+            .try_receive_message(client_id, DefaultChannel::ReliableOrdered)
+            .unwrap()
+            .unwrap(),
+        "valid"
+    );
+    assert_eq!(server.try_receive_message(1, DefaultChannel::ReliableOrdered), Ok(None));
+}
+
+#[test]
+fn test_late_packets() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    client.send_message(DefaultChannel::ReliableOrdered, Bytes::from("in flight"));
+    let packets = client.get_packets_to_send();
+    server.remove_connection(client_id);
+
+    // Packets in flight when the client was removed are counted instead of erroring
+    server.process_packet_from(&packets[0], client_id).unwrap();
+    assert_eq!(server.late_packets(), 1);
+    assert!(server.process_packet_from(&packets[0], 1).is_err());
+
+    server.update(Duration::from_secs(1));
+    assert!(server.process_packet_from(&packets[0], client_id).is_err());
+    assert_eq!(server.late_packets(), 1);
+
+    server.set_late_packet_window(Duration::ZERO);
+    server.add_connection(client_id);
+    server.remove_connection(client_id);
+    assert!(server.process_packet_from(&packets[0], client_id).is_err());
+}
+
+#[test]
+fn test_stale_connections() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    server.set_stale_connection_policy(StaleConnectionPolicy {
+        duration: Duration::from_secs(1),
+        ..Default::default()
+    });
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+    let (lossy_id, client_id): (ClientId, ClientId) = (0, 1);
+    server.add_connection(lossy_id);
+    server.add_connection(client_id);
+
+    // The packets to the lossy client are never delivered
+    for _ in 0..30 {
+        let duration = Duration::from_millis(100);
+        server.update(duration);
+        client.update(duration);
+        server.broadcast_message(DefaultChannel::Unreliable, Bytes::from("hello"));
+
+        server.get_packets_to_send(lossy_id).unwrap();
+        for packet in server.get_packets_to_send(client_id).unwrap() {
+            client.process_packet(&packet);
+        }
+        for packet in client.get_packets_to_send() {
+            server.process_packet_from(&packet, client_id).unwrap();
+        }
+    }
+    assert_eq!(server.disconnect_reason(lossy_id), Some(DisconnectReason::StaleConnection));
+    assert!(server.is_connected(client_id));
+
+    assert_eq!(server.remove_connections_where(|id, _| id == client_id), 1);
+    assert_eq!(server.disconnect_reason(client_id), Some(DisconnectReason::StaleConnection));
+    assert_eq!(server.remove_connections_where(|_, _| true), 0);
+}
+
+#[test]
+fn test_receive_all_messages() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    for i in 0..10u8 {
+        client.send_message(DefaultChannel::ReliableOrdered, vec![i]);
+        server.send_message(client_id, DefaultChannel::ReliableOrdered, vec![i]);
+    }
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    for packet in server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+
+    let mut messages = Vec::with_capacity(16);
+    assert_eq!(
+        server.receive_all_messages(client_id, DefaultChannel::ReliableOrdered, &mut messages),
+        10
+    );
+    assert_eq!(messages, (0..10u8).map(|i| Bytes::from(vec![i])).collect::<Vec<_>>());
+    assert_eq!(server.receive_all_messages(1, DefaultChannel::ReliableOrdered, &mut messages), 0);
+
+    // Messages are appended
+    assert_eq!(client.receive_all_messages(DefaultChannel::ReliableOrdered, &mut messages), 10);
+    assert_eq!(messages.len(), 20);
+    assert_eq!(client.receive_all_messages(DefaultChannel::ReliableOrdered, &mut messages), 0);
+}
+
+#[test]
+fn test_channel_queue_introspection() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    let channels: [u8; 2] = [DefaultChannel::ReliableOrdered.into(), DefaultChannel::Unreliable.into()];
+    for channel in channels {
+        assert_eq!(client.channel_queue_len(channel), 0);
+        assert_eq!(client.channel_oldest_unsent_age(channel), None);
+    }
+
+    for channel in channels {
+        client.send_message(channel, vec![0]);
+        server.send_message(client_id, channel, vec![0]);
+    }
+    client.update(Duration::from_millis(100));
+    server.update(Duration::from_millis(100));
+    for channel in channels {
+        client.send_message(channel, vec![1]);
+    }
+
+    for channel in channels {
+        assert_eq!(client.channel_queue_len(channel), 2);
+        assert_eq!(client.channel_oldest_unsent_age(channel), Some(Duration::from_millis(100)));
+        assert_eq!(server.channel_queue_len(client_id, channel), 1);
+        assert_eq!(
+            server.channel_oldest_unsent_age(client_id, channel),
+            Some(Duration::from_millis(100))
+        );
+    }
+    assert_eq!(server.channel_queue_len(1, DefaultChannel::ReliableOrdered), 0);
+    assert_eq!(server.channel_oldest_unsent_age(1, DefaultChannel::ReliableOrdered), None);
+
+    client.get_packets_to_send();
+    for channel in channels {
+        assert_eq!(client.channel_queue_len(channel), 0);
+        assert_eq!(client.channel_oldest_unsent_age(channel), None);
+    }
+}
+
+#[test]
+fn test_asymmetric_budgets() {
+    init_log();
+    let config = ConnectionConfig {
+        client_available_bytes_per_tick: Some(2_000),
+        server_available_bytes_per_tick: Some(20_000),
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(config.clone());
+    let mut client = RenetClient::new(config);
+    client.set_connected();
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+    assert_eq!(client.available_bytes_per_tick(), 2_000);
+
+    for _ in 0..10 {
+        client.send_message(DefaultChannel::ReliableOrdered, vec![0; 1000]);
+        server.send_message(client_id, DefaultChannel::ReliableOrdered, vec![0; 1000]);
+    }
+    let client_packets = client.get_packets_to_send();
+    let server_packets = server.get_packets_to_send(client_id).unwrap();
+    // The client budget only fits two messages per tick, the server sends all of them
+    assert_eq!(client_packets.len(), 2);
+    for packet in server_packets {
+        client.process_packet(&packet);
+    }
+    let mut messages = vec![];
+    assert_eq!(client.receive_all_messages(DefaultChannel::ReliableOrdered, &mut messages), 10);
+
+    client.update(Duration::from_millis(16));
+    server.update(Duration::from_millis(16));
+    assert_eq!(client.budget_usage(), 1.0);
+    assert_eq!(server.budget_usage(client_id), 0.5);
+    assert_eq!(server.budget_usage(1), 0.0);
+}
+
+#[test]
+fn test_connection_config_builder() {
+    let channel = |channel_id: u8, resend_time: Duration| ChannelConfig {
+        channel_id,
+        max_memory_usage_bytes: 1024,
+        send_type: SendType::ReliableOrdered {
+            resend_time: ResendStrategy::Fixed(resend_time),
+        },
+        slice_size: None,
+        ordering: None,
+    };
+
+    let config = ConnectionConfig::builder()
+        .channels(vec![channel(0, Duration::from_millis(300))])
+        .expected_rtt(Duration::from_millis(100))
+        .max_total_memory_bytes(2048)
+        .build()
+        .unwrap();
+    assert_eq!(config.server_channels_config.len(), 1);
+    assert_eq!(config.client_channels_config.len(), 1);
+
+    let error = ConnectionConfig::builder()
+        .client_channels(vec![channel(0, Duration::from_millis(300)), channel(0, Duration::from_millis(300))])
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        error,
+        ConfigError::DuplicateChannelId {
+            channel_id: 0,
+            server_channel: false
+        }
+    );
+
+    let error = ConnectionConfig::builder()
+        .server_channels(vec![channel(u8::MAX, Duration::from_millis(300))])
+        .build()
+        .unwrap_err();
+    assert_eq!(error, ConfigError::ReservedChannelId(u8::MAX));
+
+    let error = ConnectionConfig::builder()
+        .channels(vec![channel(0, Duration::from_millis(50))])
+        .expected_rtt(Duration::from_millis(100))
+        .build()
+        .unwrap_err();
+    assert_eq!(
+        error,
+        ConfigError::ResendTimeBelowRtt {
+            channel_id: 0,
+            resend_time: Duration::from_millis(50),
+            rtt: Duration::from_millis(100)
+        }
+    );
+
+    let error = ConnectionConfig::builder()
+        .channels(vec![channel(0, Duration::from_millis(300))])
+        .max_total_memory_bytes(1024)
+        .build()
+        .unwrap_err();
+    assert_eq!(error, ConfigError::MemoryLimitExceeded { total: 2048, limit: 1024 });
+
+    let error = ConnectionConfig::builder().available_bytes_per_tick(100).build().unwrap_err();
+    assert!(matches!(error, ConfigError::BudgetTooSmall { .. }));
+}
+
+#[test]
+fn test_time_since_last_packet() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+    assert_eq!(server.time_since_last_packet(client_id), Some(Duration::ZERO));
+    assert_eq!(server.time_since_last_packet(1), None);
+
+    server.update(Duration::from_secs(2));
+    assert_eq!(server.time_since_last_packet(client_id), Some(Duration::from_secs(2)));
+
+    client.send_message(DefaultChannel::ReliableOrdered, "hello");
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    assert_eq!(server.time_since_last_packet(client_id), Some(Duration::ZERO));
+
+    server.update(Duration::from_millis(500));
+    assert_eq!(server.time_since_last_packet(client_id), Some(Duration::from_millis(500)));
+}
+
+#[test]
+fn test_idle_clients() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    server.set_idle_timeout(Duration::from_secs(5));
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+    client.set_idle_timeout(Duration::from_secs(5));
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+    assert!(matches!(server.get_event(), Some(ServerEvent::ClientConnected { .. })));
+
+    server.update(Duration::from_secs(4));
+    assert!(server.get_event().is_none());
+
+    server.update(Duration::from_secs(2));
+    match server.get_event() {
+        Some(ServerEvent::ClientIdle { client_id: id, idle_for }) => {
+            assert_eq!(id, client_id);
+            assert_eq!(idle_for, Duration::from_secs(6));
+        }
+        event => panic!("expected ClientIdle event, got {event:?}"),
+    }
+
+    // Only reported once while idle
+    server.update(Duration::from_secs(2));
+    assert!(server.get_event().is_none());
+
+    client.send_message(DefaultChannel::ReliableOrdered, "hello");
+    for packet in client.get_packets_to_send() {
+        server.process_packet_from(&packet, client_id).unwrap();
+    }
+    server.update(Duration::from_secs(1));
+    assert!(server.get_event().is_none());
+
+    server.update(Duration::from_secs(5));
+    assert!(matches!(server.get_event(), Some(ServerEvent::ClientIdle { .. })));
+
+    // Acks from the server don't count as messages for the client
+    for packet in server.get_packets_to_send(client_id).unwrap() {
+        client.process_packet(&packet);
+    }
+    client.update(Duration::from_secs(6));
+    assert_eq!(client.idle_for(), Some(Duration::from_secs(6)));
+    client.clear_idle_timeout();
+    assert_eq!(client.idle_for(), None);
+}
+
+#[test]
+fn test_snapshot_rate_policy() {
+    init_log();
+    let policy = SnapshotRatePolicy {
+        full_rate_bandwidth: 1_000_000.,
+    };
+    assert_eq!(policy.send_interval(None), 1);
+    assert_eq!(policy.send_interval(Some(2_000_000.)), 1);
+    assert_eq!(policy.send_interval(Some(600_000.)), 2);
+    assert_eq!(policy.send_interval(Some(1000.)), 4);
+
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    server.set_snapshot_rate_policy(policy);
+    let mut client = RenetClient::new(ConnectionConfig::default());
+    client.set_connected();
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+    assert_eq!(server.snapshot_interval(client_id), 1);
+
+    let update = |server: &mut RenetServer, client: &mut RenetClient| {
+        let duration = Duration::from_millis(100);
+        server.update(duration);
+        client.update(duration);
+        server.broadcast_snapshot(DefaultChannel::Unreliable, vec![0; 1000]);
+        for packet in server.get_packets_to_send(client_id).unwrap() {
+            client.process_packet(&packet);
+        }
+        for packet in client.get_packets_to_send() {
+            server.process_packet_from(&packet, client_id).unwrap();
+        }
+
+        let mut received = 0;
+        while client.receive_message(DefaultChannel::Unreliable).is_some() {
+            received += 1;
+        }
+        received
+    };
+
+    // Every tick until there is a bandwidth estimate, after the 6 seconds of the stats window
+    for _ in 0..59 {
+        assert_eq!(update(&mut server, &mut client), 1);
+    }
+    for _ in 0..10 {
+        update(&mut server, &mut client);
+    }
+
+    // The client receives about 10KB/s, below the bandwidth of the policy
+    assert_eq!(server.snapshot_interval(client_id), 4);
+    let received: usize = (0..8).map(|_| update(&mut server, &mut client)).sum();
+    assert_eq!(received, 2);
+
+    server.clear_snapshot_rate_policy();
+    assert_eq!(server.snapshot_interval(client_id), 1);
+    assert!(server.should_send_snapshot(client_id));
+}
+
+#[test]
+fn test_message_too_large() {
+    init_log();
+    let channels_config = vec![ChannelConfig {
+        channel_id: 0,
+        max_memory_usage_bytes: 1024,
+        send_type: SendType::ReliableOrdered {
+            resend_time: ResendStrategy::Fixed(Duration::from_millis(300)),
+        },
+        slice_size: None,
+        ordering: None,
+    }];
+    let config = ConnectionConfig {
+        client_channels_config: channels_config.clone(),
+        server_channels_config: channels_config,
+        ..Default::default()
+    };
+    let mut server = RenetServer::new(config.clone());
+    let mut client = RenetClient::new(config);
+    client.set_connected();
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    let message = Bytes::from(vec![0; 2000]);
+    assert_eq!(
+        client.try_send_message(0, message.clone()),
+        Err(SendError::MessageTooLarge { size: 2000, limit: 1024 })
+    );
+    assert_eq!(
+        server.try_send_message(client_id, 0, message.clone()),
+        Err(SendError::MessageTooLarge { size: 2000, limit: 1024 })
+    );
+
+    // The message is not sent and the connection is kept
+    assert!(client.is_connected());
+    assert!(server.is_connected(client_id));
+    client.try_send_message(0, vec![0; 1024]).unwrap();
+
+    // Without checking the size, reliable channels disconnect instead of losing the message
+    client.send_message(0, message.clone());
+    assert!(matches!(
+        client.disconnect_reason(),
+        Some(DisconnectReason::SendChannelError { channel_id: 0, .. })
+    ));
+
+    server.send_message(client_id, 0, message);
+    assert!(!server.is_connected(client_id));
+    assert_eq!(server.disconnections_id(), vec![client_id]);
+}
+
+#[test]
+fn test_server_timing() {
+    init_log();
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let client_id: ClientId = 0;
+    server.add_connection(client_id);
+
+    server.update(Duration::from_millis(16));
+    server.record_receive_time(Duration::from_millis(2));
+    server.send_message(client_id, DefaultChannel::ReliableOrdered, vec![0; 10_000]);
+    server.get_packets_to_send(client_id).unwrap();
+    server.record_send_time(Duration::from_millis(3));
+
+    let timing = server.timing();
+    assert_eq!(timing.receive, Duration::from_millis(2));
+    assert_eq!(timing.send, Duration::from_millis(3));
+    assert!(timing.packing > Duration::ZERO);
+    assert_eq!(timing.total(), timing.receive + timing.update + timing.packing + timing.send);
+
+    // The timing restarts on each update
+    server.update(Duration::from_millis(16));
+    assert_eq!(server.timing().receive, Duration::ZERO);
+    assert_eq!(server.timing().packing, Duration::ZERO);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_get_packets_to_send_parallel() {
+    init_log();
+    let mut serial_server = RenetServer::new(ConnectionConfig::default());
+    let mut parallel_server = RenetServer::new(ConnectionConfig::default());
+    for server in [&mut serial_server, &mut parallel_server] {
+        for client_id in 0..50 {
+            server.add_connection(client_id);
+            server.send_message(client_id, DefaultChannel::ReliableOrdered, vec![client_id as u8; 5000]);
+            server.send_message(client_id, DefaultChannel::Unreliable, vec![1, 2, 3]);
+        }
+        server.disconnect(10);
+    }
+
+    let serial_packets: Vec<(ClientId, Vec<Vec<u8>>)> = serial_server
+        .clients_id()
+        .into_iter()
+        .map(|client_id| (client_id, serial_server.get_packets_to_send(client_id).unwrap()))
+        .collect();
+    let parallel_packets = parallel_server.get_packets_to_send_parallel();
+
+    assert_eq!(parallel_packets.len(), 49);
+    assert_eq!(parallel_packets, serial_packets);
+}
+
+#[test]
+fn test_sharded_server() {
+    init_log();
+    let mut server = ShardedRenetServer::new(ConnectionConfig::default(), 2);
+    let mut clients: Vec<RenetClient> = (0..4).map(|_| RenetClient::new(ConnectionConfig::default())).collect();
+    for (client_id, client) in clients.iter_mut().enumerate() {
+        client.set_connected();
+        server.add_connection(client_id as ClientId);
+    }
+    assert_eq!(server.connected_clients(), 4);
+    assert_eq!(server.shard(0).server().clients_id(), vec![0, 2]);
+    assert_eq!(server.shard(1).server().clients_id(), vec![1, 3]);
+    assert!(server.shard(1).owns(3));
+
+    // Messages routed from one shard to clients of other shards
+    let router = server.shard(0).router().clone();
+    router.send_message(3, DefaultChannel::ReliableOrdered, "direct");
+    router.broadcast_message_except(0, DefaultChannel::ReliableOrdered, "broadcast");
+
+    std::thread::scope(|scope| {
+        for shard in server.shards_mut() {
+            scope.spawn(|| shard.update(Duration::ZERO));
+        }
+    });
+
+    for (client_id, client) in clients.iter_mut().enumerate() {
+        for packet in server.get_packets_to_send(client_id as ClientId).unwrap() {
+            client.process_packet(&packet);
+        }
+    }
+
+    assert_eq!(clients[0].receive_message(DefaultChannel::ReliableOrdered), None);
+    assert_eq!(clients[3].receive_message(DefaultChannel::ReliableOrdered).unwrap(), "direct");
+    for client in clients[1..].iter_mut() {
+        assert_eq!(client.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "broadcast");
+    }
+
+    server.remove_connection(2);
+    assert!(matches!(server.get_event(), Some(ServerEvent::ClientConnected { .. })));
+    assert_eq!(server.connected_clients(), 3);
+}
+
+#[test]
+fn test_shard_rejects_clients_of_other_shards() {
+    init_log();
+    let mut shards = ShardedRenetServer::new(ConnectionConfig::default(), 2).into_shards();
+    let mut owned_client = RenetClient::new(ConnectionConfig::default());
+    let mut other_client = RenetClient::new(ConnectionConfig::default());
+    owned_client.set_connected();
+    other_client.set_connected();
+
+    // Client 2 is assigned to shard 0, client 3 to shard 1
+    assert!(shards[0].add_connection(2));
+    assert!(!shards[0].add_connection(3));
+    assert_eq!(shards[0].server().clients_id(), vec![2]);
+    assert!(shards[1].add_connection(3));
+
+    let router = shards[0].router().clone();
+    router.send_message(2, DefaultChannel::ReliableOrdered, "owned");
+    router.send_message(3, DefaultChannel::ReliableOrdered, "other");
+    for shard in shards.iter_mut() {
+        shard.update(Duration::ZERO);
+    }
+
+    for packet in shards[0].server_mut().get_packets_to_send(2).unwrap() {
+        owned_client.process_packet(&packet);
+    }
+    for packet in shards[1].server_mut().get_packets_to_send(3).unwrap() {
+        other_client.process_packet(&packet);
+    }
+    assert_eq!(owned_client.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "owned");
+    assert_eq!(other_client.receive_message(DefaultChannel::ReliableOrdered).unwrap(), "other");
+}
+
+#[test]
+fn test_connection_denied_events_bounded() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let addr = "127.0.0.1:3000".parse().unwrap();
+    for _ in 0..100 {
+        server.connection_denied(addr, ConnectionDeniedReason::InvalidProtocolId);
+    }
+    assert_eq!(server.dropped_connection_denied_events(), 36);
+
+    let mut events = 0;
+    while let Some(event) = server.get_event() {
+        assert_eq!(
+            event,
+            ServerEvent::ConnectionDenied {
+                addr,
+                reason: ConnectionDeniedReason::InvalidProtocolId
+            }
+        );
+        events += 1;
+    }
+    assert_eq!(events, 64);
+
+    // Reading the events makes room for new ones
+    server.connection_denied(addr, ConnectionDeniedReason::Banned);
+    assert!(matches!(server.get_event(), Some(ServerEvent::ConnectionDenied { .. })));
+    assert_eq!(server.dropped_connection_denied_events(), 36);
+}
@@ -0,0 +1,195 @@
+-- Wireshark dissector for the renet packets captured with renet::PcapWriter.
+-- The wire format is described in renet/WIRE_FORMAT.md, the packet types must match renet/src/packet.rs.
+--
+-- Copy this file to the Wireshark personal Lua plugins folder (Help > About Wireshark > Folders),
+-- and open a capture written by PcapWriter, that uses the link-type USER0 (147).
+
+local renet = Proto("renet", "Renet")
+
+local directions = {
+    [0] = "Client to server",
+    [1] = "Server to client",
+}
+
+local packet_types = {
+    [0] = "SmallReliable",
+    [1] = "SmallUnreliable",
+    [2] = "ReliableSlice",
+    [3] = "UnreliableSlice",
+    [4] = "Ack",
+    [5] = "SmallRedundant",
+}
+
+local fields = {
+    direction = ProtoField.uint8("renet.direction", "Direction", base.DEC, directions),
+    client_id = ProtoField.uint64("renet.client_id", "Client id"),
+    version = ProtoField.uint8("renet.version", "Wire format version", base.DEC, nil, 0x7f),
+    packet_type = ProtoField.uint8("renet.packet_type", "Packet type", base.DEC, packet_types),
+    sequence = ProtoField.uint64("renet.sequence", "Sequence"),
+    channel_id = ProtoField.uint8("renet.channel_id", "Channel id"),
+    num_messages = ProtoField.uint16("renet.num_messages", "Number of messages"),
+    message_id = ProtoField.uint64("renet.message_id", "Message id"),
+    message_len = ProtoField.uint64("renet.message_len", "Message length"),
+    message = ProtoField.bytes("renet.message", "Message"),
+    slice_index = ProtoField.uint64("renet.slice_index", "Slice index"),
+    num_slices = ProtoField.uint64("renet.num_slices", "Number of slices"),
+    ack_range = ProtoField.string("renet.ack_range", "Ack range"),
+    nack_range = ProtoField.string("renet.nack_range", "Nack range"),
+}
+renet.fields = {}
+for _, field in pairs(fields) do
+    table.insert(renet.fields, field)
+end
+
+-- Wire format version supported by the dissector, see WIRE_FORMAT_VERSION in renet/src/packet.rs
+local WIRE_FORMAT_VERSION = 1
+
+local SMALL_RELIABLE = 0
+local SMALL_UNRELIABLE = 1
+local RELIABLE_SLICE = 2
+local UNRELIABLE_SLICE = 3
+local ACK = 4
+local SMALL_REDUNDANT = 5
+
+-- Varints use the QUIC encoding: the two most significant bits of the first byte
+-- are the log2 of the length in bytes, the remaining bits are the value in big-endian.
+local VARINT_LENGTHS = { [0] = 1, [1] = 2, [2] = 4, [3] = 8 }
+
+local function read_varint(tvb, offset)
+    local first = tvb(offset, 1):uint()
+    local len = VARINT_LENGTHS[math.floor(first / 64)]
+    local value = first % 64
+    for i = 1, len - 1 do
+        value = value * 256 + tvb(offset + i, 1):uint()
+    end
+    return value, len
+end
+
+local function add_varint(tree, field, tvb, offset)
+    local value, len = read_varint(tvb, offset)
+    tree:add(field, tvb(offset, len), value)
+    return value, offset + len
+end
+
+local function dissect_messages(tvb, tree, offset, packet_type)
+    local num_messages = tvb(offset, 2):uint()
+    tree:add(fields.num_messages, tvb(offset, 2))
+    offset = offset + 2
+
+    for i = 1, num_messages do
+        local start = offset
+        local message_tree = tree:add(renet, tvb(offset), "Message " .. i)
+        if packet_type ~= SMALL_UNRELIABLE then
+            local message_id
+            message_id, offset = add_varint(message_tree, fields.message_id, tvb, offset)
+            message_tree:append_text(", id " .. message_id)
+        end
+        local message_len
+        message_len, offset = add_varint(message_tree, fields.message_len, tvb, offset)
+        if message_len > 0 then
+            message_tree:add(fields.message, tvb(offset, message_len))
+        end
+        offset = offset + message_len
+        message_tree:append_text(", " .. message_len .. " bytes")
+        message_tree:set_len(offset - start)
+    end
+
+    return num_messages .. " messages"
+end
+
+local function dissect_slice(tvb, tree, offset)
+    local message_id, slice_index, num_slices, len
+    message_id, offset = add_varint(tree, fields.message_id, tvb, offset)
+    slice_index, offset = add_varint(tree, fields.slice_index, tvb, offset)
+    num_slices, offset = add_varint(tree, fields.num_slices, tvb, offset)
+    len, offset = add_varint(tree, fields.message_len, tvb, offset)
+    tree:add(fields.message, tvb(offset, len))
+
+    return "message " .. message_id .. ", slice " .. slice_index + 1 .. "/" .. num_slices
+end
+
+local function dissect_ack(tvb, tree, offset)
+    local function next_varint()
+        local value, len = read_varint(tvb, offset)
+        offset = offset + len
+        return value
+    end
+
+    -- The ranges are written from the largest one, based on the start of the previous range
+    local range_end = next_varint()
+    local range_start = range_end - next_varint()
+    local num_ranges = next_varint()
+    local largest_ack = range_end
+    tree:add(fields.ack_range, range_start .. ".." .. range_end)
+
+    for _ = 1, num_ranges do
+        local gap = next_varint()
+        range_end = range_start - gap - 2
+        range_start = range_end - next_varint()
+        tree:add(fields.ack_range, range_start .. ".." .. range_end)
+    end
+
+    -- Nack ranges are written as the distance from the largest ack to their start, and their size
+    local num_nack_ranges = next_varint()
+    for _ = 1, num_nack_ranges do
+        local start = largest_ack - next_varint()
+        local size = next_varint()
+        tree:add(fields.nack_range, start .. ".." .. start + size - 1)
+    end
+
+    return "largest ack " .. largest_ack
+end
+
+function renet.dissector(tvb, pinfo, tree)
+    pinfo.cols.protocol = "RENET"
+
+    local subtree = tree:add(renet, tvb(), "Renet")
+    local direction = tvb(0, 1):uint()
+    subtree:add(fields.direction, tvb(0, 1))
+    subtree:add_le(fields.client_id, tvb(1, 8))
+    local client_id = tvb(1, 8):le_uint64()
+    if direction == 0 then
+        pinfo.cols.src = "client " .. tostring(client_id)
+        pinfo.cols.dst = "server"
+    else
+        pinfo.cols.src = "server"
+        pinfo.cols.dst = "client " .. tostring(client_id)
+    end
+
+    local offset = 9
+    local version = tvb(offset, 1):uint()
+    subtree:add(fields.version, tvb(offset, 1))
+    offset = offset + 1
+    if version ~= 128 + WIRE_FORMAT_VERSION then
+        pinfo.cols.info = "Unsupported wire format version"
+        return
+    end
+
+    local packet_type = tvb(offset, 1):uint()
+    subtree:add(fields.packet_type, tvb(offset, 1))
+    offset = offset + 1
+
+    local sequence
+    sequence, offset = add_varint(subtree, fields.sequence, tvb, offset)
+
+    local info
+    if packet_type == ACK then
+        info = dissect_ack(tvb, subtree, offset)
+    else
+        local channel_id = tvb(offset, 1):uint()
+        subtree:add(fields.channel_id, tvb(offset, 1))
+        offset = offset + 1
+
+        if packet_type == SMALL_RELIABLE or packet_type == SMALL_UNRELIABLE or packet_type == SMALL_REDUNDANT then
+            info = dissect_messages(tvb, subtree, offset, packet_type)
+        elseif packet_type == RELIABLE_SLICE or packet_type == UNRELIABLE_SLICE then
+            info = dissect_slice(tvb, subtree, offset)
+        end
+        info = "channel " .. channel_id .. ", " .. (info or "")
+    end
+
+    local type_name = packet_types[packet_type] or "Unknown"
+    pinfo.cols.info = type_name .. " seq " .. sequence .. ", " .. info
+end
+
+DissectorTable.get("wtap_encap"):add(wtap.USER0, renet)
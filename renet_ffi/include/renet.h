@@ -0,0 +1,95 @@
+/*
+ * C bindings for the renet client and server, built by the renet_ffi crate.
+ *
+ * Clients and servers use the default connection config, with the channels:
+ * 0 unreliable, 1 reliable unordered and 2 reliable ordered.
+ *
+ * Every object returned is owned by the caller, and must be freed with its free function.
+ * Functions returning an int32_t return RENET_OK or one of the negative RENET_ERROR_* codes.
+ */
+#ifndef RENET_H
+#define RENET_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define RENET_OK 0
+#define RENET_ERROR_NULL_POINTER -1
+#define RENET_ERROR_INVALID_CHANNEL -2
+#define RENET_ERROR_MESSAGE_TOO_LARGE -3
+#define RENET_ERROR_CLIENT_NOT_FOUND -4
+
+typedef struct RenetClient RenetClient;
+typedef struct RenetServer RenetServer;
+typedef struct RenetMessage RenetMessage;
+typedef struct RenetPackets RenetPackets;
+
+typedef enum RenetServerEventType {
+    RENET_CLIENT_CONNECTED = 0,
+    RENET_CLIENT_DISCONNECTED = 1,
+    /* The transport layer denied a connection attempt, the client id is 0. */
+    RENET_CONNECTION_DENIED = 2,
+    /* The client didn't send messages for the idle timeout of the server. */
+    RENET_CLIENT_IDLE = 3,
+} RenetServerEventType;
+
+typedef struct RenetServerEvent {
+    RenetServerEventType event_type;
+    uint64_t client_id;
+} RenetServerEvent;
+
+/* Messages */
+const uint8_t *renet_message_data(const RenetMessage *message);
+size_t renet_message_len(const RenetMessage *message);
+void renet_message_free(RenetMessage *message);
+
+/* Packets to send */
+size_t renet_packets_count(const RenetPackets *packets);
+/* Returns NULL if the index is out of bounds. */
+const uint8_t *renet_packets_get(const RenetPackets *packets, size_t index, size_t *len);
+void renet_packets_free(RenetPackets *packets);
+
+/* Client */
+RenetClient *renet_client_new(void);
+void renet_client_free(RenetClient *client);
+void renet_client_update(RenetClient *client, double delta_seconds);
+bool renet_client_is_connected(const RenetClient *client);
+bool renet_client_is_disconnected(const RenetClient *client);
+void renet_client_set_connected(RenetClient *client);
+void renet_client_disconnect(RenetClient *client);
+double renet_client_rtt(const RenetClient *client);
+int32_t renet_client_send_message(RenetClient *client, uint8_t channel_id, const uint8_t *data, size_t len);
+/* Returns NULL when there are no messages. */
+RenetMessage *renet_client_receive_message(RenetClient *client, uint8_t channel_id);
+RenetPackets *renet_client_get_packets_to_send(RenetClient *client);
+int32_t renet_client_process_packet(RenetClient *client, const uint8_t *data, size_t len);
+
+/* Server */
+RenetServer *renet_server_new(void);
+void renet_server_free(RenetServer *server);
+void renet_server_update(RenetServer *server, double delta_seconds);
+/* Returns false when there are no events. */
+bool renet_server_get_event(RenetServer *server, RenetServerEvent *event);
+void renet_server_add_connection(RenetServer *server, uint64_t client_id);
+void renet_server_remove_connection(RenetServer *server, uint64_t client_id);
+void renet_server_disconnect(RenetServer *server, uint64_t client_id);
+size_t renet_server_connected_clients(const RenetServer *server);
+bool renet_server_is_connected(const RenetServer *server, uint64_t client_id);
+int32_t renet_server_send_message(RenetServer *server, uint64_t client_id, uint8_t channel_id, const uint8_t *data, size_t len);
+int32_t renet_server_broadcast_message(RenetServer *server, uint8_t channel_id, const uint8_t *data, size_t len);
+/* Returns NULL when there are no messages. */
+RenetMessage *renet_server_receive_message(RenetServer *server, uint64_t client_id, uint8_t channel_id);
+/* Returns NULL if the server has no connection with the client id. */
+RenetPackets *renet_server_get_packets_to_send(RenetServer *server, uint64_t client_id);
+int32_t renet_server_process_packet_from(RenetServer *server, const uint8_t *data, size_t len, uint64_t client_id);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RENET_H */
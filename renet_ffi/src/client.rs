@@ -0,0 +1,135 @@
+use renet::{ConnectionConfig, RenetClient};
+
+use crate::{borrow_bytes, copy_bytes, duration_from_secs, into_message, into_packets, send_result, RenetMessage, RenetPackets};
+use crate::{RENET_ERROR_NULL_POINTER, RENET_OK};
+
+/// Creates a client with the default connection config, freed with `renet_client_free`.
+/// The client starts connecting, the transport layer calls `renet_client_set_connected` when the connection is established.
+#[no_mangle]
+pub extern "C" fn renet_client_new() -> *mut RenetClient {
+    Box::into_raw(Box::new(RenetClient::new(ConnectionConfig::default())))
+}
+
+/// Frees the client, null is ignored.
+///
+/// # Safety
+/// `client` must be a client returned by `renet_client_new`, it can't be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn renet_client_free(client: *mut RenetClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
+
+/// Advances the client by `delta_seconds`, should be called once per frame.
+///
+/// # Safety
+/// `client` must be a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn renet_client_update(client: *mut RenetClient, delta_seconds: f64) {
+    if let Some(client) = client.as_mut() {
+        client.update(duration_from_secs(delta_seconds));
+    }
+}
+
+/// Returns whether the client is connected.
+///
+/// # Safety
+/// `client` must be a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn renet_client_is_connected(client: *const RenetClient) -> bool {
+    client.as_ref().is_some_and(|client| client.is_connected())
+}
+
+/// Returns whether the client is disconnected.
+///
+/// # Safety
+/// `client` must be a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn renet_client_is_disconnected(client: *const RenetClient) -> bool {
+    client.as_ref().is_none_or(|client| client.is_disconnected())
+}
+
+/// Sets the client as connected, called by the transport layer.
+///
+/// # Safety
+/// `client` must be a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn renet_client_set_connected(client: *mut RenetClient) {
+    if let Some(client) = client.as_mut() {
+        client.set_connected();
+    }
+}
+
+/// Disconnects the client.
+///
+/// # Safety
+/// `client` must be a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn renet_client_disconnect(client: *mut RenetClient) {
+    if let Some(client) = client.as_mut() {
+        client.disconnect();
+    }
+}
+
+/// Returns the round-trip time of the connection in seconds.
+///
+/// # Safety
+/// `client` must be a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn renet_client_rtt(client: *const RenetClient) -> f64 {
+    client.as_ref().map_or(0.0, |client| client.rtt())
+}
+
+/// Sends a message to the server in the channel, the bytes are copied.
+///
+/// # Safety
+/// `client` must be a valid client, and `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn renet_client_send_message(client: *mut RenetClient, channel_id: u8, data: *const u8, len: usize) -> i32 {
+    let (Some(client), Some(message)) = (client.as_mut(), copy_bytes(data, len)) else {
+        return RENET_ERROR_NULL_POINTER;
+    };
+
+    send_result(client.try_send_message(channel_id, message))
+}
+
+/// Receives a message from the channel, returns null when there are no messages.
+/// The message is freed with `renet_message_free`.
+///
+/// # Safety
+/// `client` must be a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn renet_client_receive_message(client: *mut RenetClient, channel_id: u8) -> *mut RenetMessage {
+    let Some(client) = client.as_mut() else {
+        return std::ptr::null_mut();
+    };
+
+    into_message(client.try_receive_message(channel_id).ok().flatten())
+}
+
+/// Returns the packets to send to the server, freed with `renet_packets_free`.
+///
+/// # Safety
+/// `client` must be a valid client.
+#[no_mangle]
+pub unsafe extern "C" fn renet_client_get_packets_to_send(client: *mut RenetClient) -> *mut RenetPackets {
+    match client.as_mut() {
+        Some(client) => into_packets(client.get_packets_to_send()),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Processes a packet received from the server.
+///
+/// # Safety
+/// `client` must be a valid client, and `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn renet_client_process_packet(client: *mut RenetClient, data: *const u8, len: usize) -> i32 {
+    let (Some(client), Some(packet)) = (client.as_mut(), borrow_bytes(data, len)) else {
+        return RENET_ERROR_NULL_POINTER;
+    };
+
+    client.process_packet(packet);
+    RENET_OK
+}
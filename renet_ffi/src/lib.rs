@@ -0,0 +1,256 @@
+//! C bindings for the [renet](https://github.com/lucaspoffo/renet) client and server,
+//! so engines written in other languages can use the same protocol implementation as the Rust server.
+//!
+//! The header with the declarations is in `include/renet.h`.
+//! Clients and servers use the default [`renet::ConnectionConfig`], with the channels of [`renet::DefaultChannel`]:
+//! 0 unreliable, 1 reliable unordered and 2 reliable ordered.
+//!
+//! As in Rust, the transport layer is up to the caller: packets returned by the `get_packets_to_send` functions
+//! are sent with any socket, and the packets received are passed to the `process_packet` functions.
+//!
+//! Every object returned by the bindings is owned by the caller, and must be freed with its `free` function.
+//! Functions returning an `int32_t` return `RENET_OK` or one of the negative `RENET_ERROR_*` codes.
+use std::slice;
+
+use renet::{Bytes, ClientNotFound, SendError};
+
+mod client;
+mod server;
+
+pub use client::*;
+pub use server::*;
+
+/// The call succeeded.
+pub const RENET_OK: i32 = 0;
+/// A required pointer argument was null.
+pub const RENET_ERROR_NULL_POINTER: i32 = -1;
+/// The channel id is not one of the configured channels.
+pub const RENET_ERROR_INVALID_CHANNEL: i32 = -2;
+/// The message is bigger than the channel can send.
+pub const RENET_ERROR_MESSAGE_TOO_LARGE: i32 = -3;
+/// The server has no connection with the client id.
+pub const RENET_ERROR_CLIENT_NOT_FOUND: i32 = -4;
+
+/// A message received, freed with `renet_message_free`.
+pub struct RenetMessage(Bytes);
+
+/// The packets to send to a connection, freed with `renet_packets_free`.
+pub struct RenetPackets(Vec<Vec<u8>>);
+
+/// Returns a pointer to the bytes of the message, valid until the message is freed.
+///
+/// # Safety
+/// `message` must be a message returned by the bindings that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn renet_message_data(message: *const RenetMessage) -> *const u8 {
+    match message.as_ref() {
+        Some(message) => message.0.as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+/// Returns the length of the message in bytes.
+///
+/// # Safety
+/// `message` must be a message returned by the bindings that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn renet_message_len(message: *const RenetMessage) -> usize {
+    match message.as_ref() {
+        Some(message) => message.0.len(),
+        None => 0,
+    }
+}
+
+/// Frees the message, null is ignored.
+///
+/// # Safety
+/// `message` must be a message returned by the bindings, it can't be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn renet_message_free(message: *mut RenetMessage) {
+    if !message.is_null() {
+        drop(Box::from_raw(message));
+    }
+}
+
+/// Returns the number of packets.
+///
+/// # Safety
+/// `packets` must be packets returned by the bindings that were not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn renet_packets_count(packets: *const RenetPackets) -> usize {
+    match packets.as_ref() {
+        Some(packets) => packets.0.len(),
+        None => 0,
+    }
+}
+
+/// Returns a pointer to the bytes of the packet at `index`, and writes its length in `len`.
+/// Returns null if the index is out of bounds. The pointer is valid until the packets are freed.
+///
+/// # Safety
+/// `packets` must be packets returned by the bindings that were not freed yet, `len` must be a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn renet_packets_get(packets: *const RenetPackets, index: usize, len: *mut usize) -> *const u8 {
+    let (Some(packets), Some(len)) = (packets.as_ref(), len.as_mut()) else {
+        return std::ptr::null();
+    };
+
+    match packets.0.get(index) {
+        Some(packet) => {
+            *len = packet.len();
+            packet.as_ptr()
+        }
+        None => {
+            *len = 0;
+            std::ptr::null()
+        }
+    }
+}
+
+/// Frees the packets, null is ignored.
+///
+/// # Safety
+/// `packets` must be packets returned by the bindings, they can't be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn renet_packets_free(packets: *mut RenetPackets) {
+    if !packets.is_null() {
+        drop(Box::from_raw(packets));
+    }
+}
+
+// Copies the bytes passed from C, a null pointer is only allowed for empty messages.
+unsafe fn copy_bytes(data: *const u8, len: usize) -> Option<Bytes> {
+    if len == 0 {
+        return Some(Bytes::new());
+    }
+    if data.is_null() {
+        return None;
+    }
+
+    Some(Bytes::copy_from_slice(slice::from_raw_parts(data, len)))
+}
+
+// Borrows the bytes passed from C, a null pointer is only allowed for empty packets.
+unsafe fn borrow_bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    if len == 0 {
+        return Some(&[]);
+    }
+    if data.is_null() {
+        return None;
+    }
+
+    Some(slice::from_raw_parts(data, len))
+}
+
+fn into_message(message: Option<Bytes>) -> *mut RenetMessage {
+    match message {
+        Some(message) => Box::into_raw(Box::new(RenetMessage(message))),
+        None => std::ptr::null_mut(),
+    }
+}
+
+fn into_packets(packets: Vec<Vec<u8>>) -> *mut RenetPackets {
+    Box::into_raw(Box::new(RenetPackets(packets)))
+}
+
+fn send_result(result: Result<(), SendError>) -> i32 {
+    match result {
+        Ok(()) => RENET_OK,
+        Err(SendError::MessageTooLarge { .. }) => RENET_ERROR_MESSAGE_TOO_LARGE,
+        Err(_) => RENET_ERROR_INVALID_CHANNEL,
+    }
+}
+
+fn client_not_found(_: ClientNotFound) -> i32 {
+    RENET_ERROR_CLIENT_NOT_FOUND
+}
+
+// Negative, NaN or too big durations are ignored instead of panicking across the FFI boundary.
+fn duration_from_secs(delta_seconds: f64) -> std::time::Duration {
+    std::time::Duration::try_from_secs_f64(delta_seconds).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn packets_to_vec(packets: *mut RenetPackets) -> Vec<Vec<u8>> {
+        let mut result = vec![];
+        for index in 0..renet_packets_count(packets) {
+            let mut len = 0;
+            let data = renet_packets_get(packets, index, &mut len);
+            result.push(slice::from_raw_parts(data, len).to_vec());
+        }
+        renet_packets_free(packets);
+        result
+    }
+
+    #[test]
+    fn test_client_server_messages() {
+        unsafe {
+            let server = renet_server_new();
+            let client = renet_client_new();
+            let client_id = 7;
+
+            renet_server_add_connection(server, client_id);
+            renet_client_set_connected(client);
+            assert!(renet_client_is_connected(client));
+
+            let mut event = RenetServerEvent::default();
+            assert!(renet_server_get_event(server, &mut event));
+            assert_eq!(event.event_type, RenetServerEventType::ClientConnected);
+            assert_eq!(event.client_id, client_id);
+            assert!(!renet_server_get_event(server, &mut event));
+
+            let message = b"hello";
+            assert_eq!(renet_client_send_message(client, 2, message.as_ptr(), message.len()), RENET_OK);
+            assert_eq!(
+                renet_client_send_message(client, 100, message.as_ptr(), message.len()),
+                RENET_ERROR_INVALID_CHANNEL
+            );
+            assert_eq!(renet_client_send_message(client, 2, std::ptr::null(), 1), RENET_ERROR_NULL_POINTER);
+
+            for packet in packets_to_vec(renet_client_get_packets_to_send(client)) {
+                assert_eq!(
+                    renet_server_process_packet_from(server, packet.as_ptr(), packet.len(), client_id),
+                    RENET_OK
+                );
+            }
+
+            let received = renet_server_receive_message(server, client_id, 2);
+            assert!(!received.is_null());
+            let data = slice::from_raw_parts(renet_message_data(received), renet_message_len(received));
+            assert_eq!(data, message);
+            renet_message_free(received);
+            assert!(renet_server_receive_message(server, client_id, 2).is_null());
+
+            assert_eq!(
+                renet_server_send_message(server, client_id, 0, message.as_ptr(), message.len()),
+                RENET_OK
+            );
+            assert_eq!(
+                renet_server_send_message(server, 99, 0, message.as_ptr(), message.len()),
+                RENET_ERROR_CLIENT_NOT_FOUND
+            );
+            assert!(renet_server_get_packets_to_send(server, 99).is_null());
+            for packet in packets_to_vec(renet_server_get_packets_to_send(server, client_id)) {
+                renet_client_process_packet(client, packet.as_ptr(), packet.len());
+            }
+
+            let received = renet_client_receive_message(client, 0);
+            assert!(!received.is_null());
+            assert_eq!(renet_message_len(received), message.len());
+            renet_message_free(received);
+
+            renet_server_disconnect(server, client_id);
+            assert!(!renet_server_is_connected(server, client_id));
+            renet_server_remove_connection(server, client_id);
+            assert!(renet_server_get_event(server, &mut event));
+            assert_eq!(event.event_type, RenetServerEventType::ClientDisconnected);
+            assert_eq!(renet_server_connected_clients(server), 0);
+
+            renet_client_free(client);
+            renet_server_free(server);
+        }
+    }
+}
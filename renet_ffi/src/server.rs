@@ -0,0 +1,226 @@
+use renet::{ConnectionConfig, RenetServer, ServerEvent};
+
+use crate::{
+    borrow_bytes, client_not_found, copy_bytes, duration_from_secs, into_message, into_packets, send_result, RenetMessage, RenetPackets,
+};
+use crate::{RENET_ERROR_CLIENT_NOT_FOUND, RENET_ERROR_NULL_POINTER, RENET_OK};
+
+/// Type of a [`RenetServerEvent`].
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RenetServerEventType {
+    #[default]
+    ClientConnected = 0,
+    ClientDisconnected = 1,
+    /// The transport layer denied a connection attempt, the client id is 0.
+    ConnectionDenied = 2,
+    /// The client didn't send messages for the idle timeout of the server.
+    ClientIdle = 3,
+}
+
+/// An event of the server, written by `renet_server_get_event`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RenetServerEvent {
+    pub event_type: RenetServerEventType,
+    pub client_id: u64,
+}
+
+/// Creates a server with the default connection config, freed with `renet_server_free`.
+#[no_mangle]
+pub extern "C" fn renet_server_new() -> *mut RenetServer {
+    Box::into_raw(Box::new(RenetServer::new(ConnectionConfig::default())))
+}
+
+/// Frees the server, null is ignored.
+///
+/// # Safety
+/// `server` must be a server returned by `renet_server_new`, it can't be used after this call.
+#[no_mangle]
+pub unsafe extern "C" fn renet_server_free(server: *mut RenetServer) {
+    if !server.is_null() {
+        drop(Box::from_raw(server));
+    }
+}
+
+/// Advances the server by `delta_seconds`, should be called once per frame.
+///
+/// # Safety
+/// `server` must be a valid server.
+#[no_mangle]
+pub unsafe extern "C" fn renet_server_update(server: *mut RenetServer, delta_seconds: f64) {
+    if let Some(server) = server.as_mut() {
+        server.update(duration_from_secs(delta_seconds));
+    }
+}
+
+/// Writes the next server event in `event`, returns false when there are no events.
+///
+/// # Safety
+/// `server` must be a valid server, and `event` a valid pointer.
+#[no_mangle]
+pub unsafe extern "C" fn renet_server_get_event(server: *mut RenetServer, event: *mut RenetServerEvent) -> bool {
+    let (Some(server), Some(event)) = (server.as_mut(), event.as_mut()) else {
+        return false;
+    };
+
+    // Events without an event type are skipped
+    loop {
+        *event = match server.get_event() {
+            Some(ServerEvent::ClientConnected { client_id }) => RenetServerEvent {
+                event_type: RenetServerEventType::ClientConnected,
+                client_id,
+            },
+            Some(ServerEvent::ClientDisconnected { client_id, .. }) => RenetServerEvent {
+                event_type: RenetServerEventType::ClientDisconnected,
+                client_id,
+            },
+            Some(ServerEvent::ConnectionDenied { .. }) => RenetServerEvent {
+                event_type: RenetServerEventType::ConnectionDenied,
+                client_id: 0,
+            },
+            Some(ServerEvent::ClientIdle { client_id, .. }) => RenetServerEvent {
+                event_type: RenetServerEventType::ClientIdle,
+                client_id,
+            },
+            Some(_) => continue,
+            None => return false,
+        };
+        return true;
+    }
+}
+
+/// Adds a connection with the client id, called by the transport layer.
+///
+/// # Safety
+/// `server` must be a valid server.
+#[no_mangle]
+pub unsafe extern "C" fn renet_server_add_connection(server: *mut RenetServer, client_id: u64) {
+    if let Some(server) = server.as_mut() {
+        server.add_connection(client_id);
+    }
+}
+
+/// Removes the connection with the client id, called by the transport layer.
+///
+/// # Safety
+/// `server` must be a valid server.
+#[no_mangle]
+pub unsafe extern "C" fn renet_server_remove_connection(server: *mut RenetServer, client_id: u64) {
+    if let Some(server) = server.as_mut() {
+        server.remove_connection(client_id);
+    }
+}
+
+/// Disconnects the client, the transport layer removes the connection with `renet_server_remove_connection`
+/// once `renet_server_is_connected` returns false.
+///
+/// # Safety
+/// `server` must be a valid server.
+#[no_mangle]
+pub unsafe extern "C" fn renet_server_disconnect(server: *mut RenetServer, client_id: u64) {
+    if let Some(server) = server.as_mut() {
+        server.disconnect(client_id);
+    }
+}
+
+/// Returns the number of connected clients.
+///
+/// # Safety
+/// `server` must be a valid server.
+#[no_mangle]
+pub unsafe extern "C" fn renet_server_connected_clients(server: *const RenetServer) -> usize {
+    server.as_ref().map_or(0, |server| server.connected_clients())
+}
+
+/// Returns whether the client is connected.
+///
+/// # Safety
+/// `server` must be a valid server.
+#[no_mangle]
+pub unsafe extern "C" fn renet_server_is_connected(server: *const RenetServer, client_id: u64) -> bool {
+    server.as_ref().is_some_and(|server| server.is_connected(client_id))
+}
+
+/// Sends a message to the client in the channel, the bytes are copied.
+///
+/// # Safety
+/// `server` must be a valid server, and `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn renet_server_send_message(
+    server: *mut RenetServer,
+    client_id: u64,
+    channel_id: u8,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    let (Some(server), Some(message)) = (server.as_mut(), copy_bytes(data, len)) else {
+        return RENET_ERROR_NULL_POINTER;
+    };
+    if !server.is_connected(client_id) {
+        return RENET_ERROR_CLIENT_NOT_FOUND;
+    }
+
+    send_result(server.try_send_message(client_id, channel_id, message))
+}
+
+/// Sends a message to all connected clients in the channel, the bytes are copied.
+///
+/// # Safety
+/// `server` must be a valid server, and `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn renet_server_broadcast_message(server: *mut RenetServer, channel_id: u8, data: *const u8, len: usize) -> i32 {
+    let (Some(server), Some(message)) = (server.as_mut(), copy_bytes(data, len)) else {
+        return RENET_ERROR_NULL_POINTER;
+    };
+
+    for client_id in server.clients_id() {
+        let result = send_result(server.try_send_message(client_id, channel_id, message.clone()));
+        if result != RENET_OK {
+            return result;
+        }
+    }
+    RENET_OK
+}
+
+/// Receives a message from the client in the channel, returns null when there are no messages.
+/// The message is freed with `renet_message_free`.
+///
+/// # Safety
+/// `server` must be a valid server.
+#[no_mangle]
+pub unsafe extern "C" fn renet_server_receive_message(server: *mut RenetServer, client_id: u64, channel_id: u8) -> *mut RenetMessage {
+    let Some(server) = server.as_mut() else {
+        return std::ptr::null_mut();
+    };
+
+    into_message(server.try_receive_message(client_id, channel_id).ok().flatten())
+}
+
+/// Returns the packets to send to the client, freed with `renet_packets_free`.
+/// Returns null if the server has no connection with the client id.
+///
+/// # Safety
+/// `server` must be a valid server.
+#[no_mangle]
+pub unsafe extern "C" fn renet_server_get_packets_to_send(server: *mut RenetServer, client_id: u64) -> *mut RenetPackets {
+    match server.as_mut().map(|server| server.get_packets_to_send(client_id)) {
+        Some(Ok(packets)) => into_packets(packets),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// Processes a packet received from the client.
+///
+/// # Safety
+/// `server` must be a valid server, and `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn renet_server_process_packet_from(server: *mut RenetServer, data: *const u8, len: usize, client_id: u64) -> i32 {
+    let (Some(server), Some(packet)) = (server.as_mut(), borrow_bytes(data, len)) else {
+        return RENET_ERROR_NULL_POINTER;
+    };
+
+    server
+        .process_packet_from(packet, client_id)
+        .map_or_else(client_not_found, |_| RENET_OK)
+}
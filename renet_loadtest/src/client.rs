@@ -0,0 +1,194 @@
+use std::{
+    net::{SocketAddr, UdpSocket},
+    time::Duration,
+};
+
+use renet::{Bytes, ConnectionConfig, NetworkInfo, RenetClient};
+use renet_netcode::{
+    ClientAuthentication, ConnectToken, NetcodeClientTransport, NetcodeTransportError, NETCODE_KEY_BYTES, NETCODE_USER_DATA_BYTES,
+};
+
+// The load test can take a while to ramp up, the token must not expire before the client connects
+const TOKEN_EXPIRE_SECONDS: u64 = 3600;
+const TIMEOUT_SECONDS: i32 = 15;
+// Zeroed instead of random, so servers reading the user data get an empty value
+const USER_DATA: [u8; NETCODE_USER_DATA_BYTES] = [0; NETCODE_USER_DATA_BYTES];
+
+/// Traffic sent by each simulated client, after it's connected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrafficPattern {
+    /// Only keeps the connection alive.
+    Idle,
+    /// Sends messages with the size in bytes at a fixed rate per second.
+    Constant { size: usize, rate: f64 },
+    /// Sends a number of messages with the size in bytes at once, every interval.
+    Burst { size: usize, count: usize, interval: Duration },
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    pub server_addr: SocketAddr,
+    pub protocol_id: u64,
+    /// Generates connect tokens with the key, otherwise connects with an unsecure connection.
+    pub private_key: Option<[u8; NETCODE_KEY_BYTES]>,
+    pub pattern: TrafficPattern,
+    pub channel_id: u8,
+}
+
+/// Client connected with its own socket, since the netcode server identifies the clients by address.
+#[derive(Debug)]
+pub struct SimulatedClient {
+    client: RenetClient,
+    transport: NetcodeClientTransport,
+    pattern: TrafficPattern,
+    channel_id: u8,
+    receive_channels: Vec<u8>,
+    message: Bytes,
+    // Time since the client started connecting
+    elapsed: Duration,
+    connect_time: Option<Duration>,
+    // Messages due but not sent yet, with the constant pattern
+    pending_messages: f64,
+    last_burst: Duration,
+    messages_sent: u64,
+    error: Option<String>,
+}
+
+impl SimulatedClient {
+    /// Creates the client and starts connecting, the current time must be the time since the unix epoch.
+    pub fn new(current_time: Duration, client_id: u64, config: &ClientConfig) -> Result<Self, NetcodeTransportError> {
+        let bind_addr: SocketAddr = if config.server_addr.is_ipv4() { "0.0.0.0:0".parse().unwrap() } else { "[::]:0".parse().unwrap() };
+        let socket = UdpSocket::bind(bind_addr)?;
+
+        let authentication = match config.private_key {
+            Some(private_key) => {
+                let connect_token = ConnectToken::generate(
+                    current_time,
+                    config.protocol_id,
+                    TOKEN_EXPIRE_SECONDS,
+                    client_id,
+                    TIMEOUT_SECONDS,
+                    vec![config.server_addr],
+                    Some(&USER_DATA),
+                    &private_key,
+                )?;
+                ClientAuthentication::Secure { connect_token }
+            }
+            None => ClientAuthentication::Unsecure {
+                protocol_id: config.protocol_id,
+                client_id,
+                server_addr: config.server_addr,
+                user_data: Some(USER_DATA),
+            },
+        };
+
+        let transport = NetcodeClientTransport::new(current_time, authentication, socket)?;
+        let message_size = match config.pattern {
+            TrafficPattern::Idle => 0,
+            TrafficPattern::Constant { size, .. } | TrafficPattern::Burst { size, .. } => size,
+        };
+
+        let connection_config = ConnectionConfig::default();
+        let receive_channels = connection_config
+            .server_channels_config
+            .iter()
+            .map(|channel| channel.channel_id)
+            .collect();
+
+        Ok(Self {
+            client: RenetClient::new(connection_config),
+            transport,
+            pattern: config.pattern.clone(),
+            channel_id: config.channel_id,
+            receive_channels,
+            message: vec![0u8; message_size].into(),
+            elapsed: Duration::ZERO,
+            connect_time: None,
+            pending_messages: 0.,
+            last_burst: Duration::ZERO,
+            messages_sent: 0,
+            error: None,
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client.is_connected()
+    }
+
+    /// Time it took to connect, None if the client never connected.
+    pub fn connect_time(&self) -> Option<Duration> {
+        self.connect_time
+    }
+
+    /// The error that disconnected the client, if it was disconnected.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn messages_sent(&self) -> u64 {
+        self.messages_sent
+    }
+
+    pub fn network_info(&self) -> NetworkInfo {
+        self.client.network_info()
+    }
+
+    /// Receives packets, sends the scripted traffic and sends packets.
+    pub fn update(&mut self, duration: Duration) {
+        if self.error.is_some() {
+            return;
+        }
+
+        self.elapsed += duration;
+        self.client.update(duration);
+        if let Err(e) = self.transport.update(duration, &mut self.client) {
+            self.error = Some(e.to_string());
+            return;
+        }
+
+        if !self.client.is_connected() {
+            return;
+        }
+
+        if self.connect_time.is_none() {
+            self.connect_time = Some(self.elapsed);
+            self.last_burst = self.elapsed;
+        }
+
+        // Messages from the server are not used, but they are received so they don't use memory
+        for &channel_id in self.receive_channels.iter() {
+            while self.client.receive_message(channel_id).is_some() {}
+        }
+
+        let messages = match self.pattern {
+            TrafficPattern::Idle => 0,
+            TrafficPattern::Constant { rate, .. } => {
+                self.pending_messages += rate * duration.as_secs_f64();
+                let messages = self.pending_messages.floor();
+                self.pending_messages -= messages;
+                messages as usize
+            }
+            TrafficPattern::Burst { count, interval, .. } => {
+                if self.elapsed - self.last_burst >= interval {
+                    self.last_burst = self.elapsed;
+                    count
+                } else {
+                    0
+                }
+            }
+        };
+
+        for _ in 0..messages {
+            self.client.send_message(self.channel_id, self.message.clone());
+        }
+        self.messages_sent += messages as u64;
+
+        if let Err(e) = self.transport.send_packets(&mut self.client) {
+            self.error = Some(e.to_string());
+        }
+    }
+
+    pub fn disconnect(&mut self) {
+        self.transport.disconnect();
+    }
+}
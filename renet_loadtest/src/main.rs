@@ -0,0 +1,215 @@
+use std::{
+    net::SocketAddr,
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use client::{ClientConfig, SimulatedClient, TrafficPattern};
+use renet_netcode::NETCODE_KEY_BYTES;
+use report::Report;
+
+mod client;
+mod report;
+
+const USAGE: &str = "Usage: renet_loadtest <SERVER_ADDR> [OPTIONS]
+
+Options:
+    --clients <N>            Number of simulated clients [default: 100]
+    --threads <N>            Number of threads updating the clients [default: 4]
+    --duration <SECONDS>     Duration of the test after the ramp up [default: 30]
+    --ramp-up <SECONDS>      Time to spread the connection of the clients [default: 10]
+    --tick-rate <HZ>         Updates per second of each client [default: 60]
+    --protocol-id <ID>       Protocol id of the server [default: 0]
+    --private-key <HEX>      Server private key to generate connect tokens, unsecure connection if not set
+    --first-client-id <ID>   Id of the first client, the next ones are sequential [default: 0]
+    --channel <ID>           Channel used to send the messages [default: 0]
+    --pattern <PATTERN>      Traffic sent by each client [default: idle]
+                             idle, constant:<SIZE>:<PER_SECOND> or burst:<SIZE>:<COUNT>:<INTERVAL_MS>
+
+The server must use the channels from ConnectionConfig::default().";
+
+#[derive(Debug, Clone)]
+struct Args {
+    clients: usize,
+    threads: usize,
+    duration: Duration,
+    ramp_up: Duration,
+    tick_rate: u32,
+    first_client_id: u64,
+    client_config: ClientConfig,
+}
+
+fn main() {
+    env_logger::init();
+
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "Load testing {} with {} clients on {} threads, pattern {:?}",
+        args.client_config.server_addr, args.clients, args.threads, args.client_config.pattern
+    );
+
+    let start = Instant::now();
+    let workers: Vec<_> = (0..args.threads)
+        .map(|worker_index| {
+            let args = args.clone();
+            thread::spawn(move || run_worker(&args, worker_index, start))
+        })
+        .collect();
+
+    let mut report = Report::default();
+    for worker in workers {
+        report.merge(worker.join().expect("worker thread panicked"));
+    }
+
+    println!("{report}");
+}
+
+// Each worker updates every n-th client, so the clients of all workers are spread over the ramp up
+fn run_worker(args: &Args, worker_index: usize, start: Instant) -> Report {
+    let tick = Duration::from_secs(1) / args.tick_rate;
+    let mut report = Report::default();
+    let mut clients: Vec<SimulatedClient> = Vec::new();
+    let mut pending: Vec<usize> = (worker_index..args.clients).step_by(args.threads).rev().collect();
+    let mut last_update = start;
+    let mut next_sample = args.ramp_up + Duration::from_secs(1);
+
+    loop {
+        let now = Instant::now();
+        let elapsed = now - start;
+        if elapsed >= args.ramp_up + args.duration {
+            break;
+        }
+
+        while let Some(&client_index) = pending.last() {
+            let connect_at = args.ramp_up.mul_f64(client_index as f64 / args.clients as f64);
+            if connect_at > elapsed {
+                break;
+            }
+
+            pending.pop();
+            let client_id = args.first_client_id + client_index as u64;
+            let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+            match SimulatedClient::new(current_time, client_id, &args.client_config) {
+                Ok(client) => clients.push(client),
+                Err(e) => {
+                    log::error!("Failed to create client {client_id}: {e}");
+                    report.attempted += 1;
+                }
+            }
+        }
+
+        let duration = now - last_update;
+        last_update = now;
+        for client in clients.iter_mut() {
+            client.update(duration);
+        }
+
+        // Only sample after the ramp up, when the server is at full load
+        if elapsed >= next_sample {
+            next_sample += Duration::from_secs(1);
+            for client in clients.iter().filter(|client| client.is_connected()) {
+                report.add_sample(client);
+            }
+        }
+
+        thread::sleep(tick.saturating_sub(now.elapsed()));
+    }
+
+    for client in clients.iter_mut() {
+        if let Some(e) = client.error() {
+            log::debug!("Client disconnected: {e}");
+        }
+        report.add_client(client);
+        client.disconnect();
+    }
+    report.duration = start.elapsed();
+
+    report
+}
+
+fn parse_args(mut iter: impl Iterator<Item = String>) -> Result<Args, String> {
+    let server_addr: SocketAddr = iter
+        .next()
+        .ok_or("missing server address")?
+        .parse()
+        .map_err(|e| format!("invalid server address: {e}"))?;
+
+    let mut args = Args {
+        clients: 100,
+        threads: 4,
+        duration: Duration::from_secs(30),
+        ramp_up: Duration::from_secs(10),
+        tick_rate: 60,
+        first_client_id: 0,
+        client_config: ClientConfig {
+            server_addr,
+            protocol_id: 0,
+            private_key: None,
+            pattern: TrafficPattern::Idle,
+            channel_id: 0,
+        },
+    };
+
+    while let Some(option) = iter.next() {
+        let value = iter.next().ok_or_else(|| format!("missing value for {option}"))?;
+        let invalid = |e: &dyn std::fmt::Display| format!("invalid value for {option}: {e}");
+        match option.as_str() {
+            "--clients" => args.clients = value.parse().map_err(|e| invalid(&e))?,
+            "--threads" => args.threads = value.parse().map_err(|e| invalid(&e))?,
+            "--duration" => args.duration = Duration::from_secs(value.parse().map_err(|e| invalid(&e))?),
+            "--ramp-up" => args.ramp_up = Duration::from_secs(value.parse().map_err(|e| invalid(&e))?),
+            "--tick-rate" => args.tick_rate = value.parse().map_err(|e| invalid(&e))?,
+            "--protocol-id" => args.client_config.protocol_id = value.parse().map_err(|e| invalid(&e))?,
+            "--private-key" => args.client_config.private_key = Some(parse_private_key(&value).map_err(|e| invalid(&e))?),
+            "--first-client-id" => args.first_client_id = value.parse().map_err(|e| invalid(&e))?,
+            "--channel" => args.client_config.channel_id = value.parse().map_err(|e| invalid(&e))?,
+            "--pattern" => args.client_config.pattern = parse_pattern(&value).map_err(|e| invalid(&e))?,
+            _ => return Err(format!("unknown option {option}")),
+        }
+    }
+
+    if args.clients == 0 || args.threads == 0 || args.tick_rate == 0 {
+        return Err("clients, threads and tick rate must be above zero".to_string());
+    }
+    args.threads = args.threads.min(args.clients);
+
+    Ok(args)
+}
+
+fn parse_private_key(value: &str) -> Result<[u8; NETCODE_KEY_BYTES], String> {
+    if value.len() != NETCODE_KEY_BYTES * 2 {
+        return Err(format!("expected {} hex characters", NETCODE_KEY_BYTES * 2));
+    }
+
+    let mut private_key = [0u8; NETCODE_KEY_BYTES];
+    for (i, byte) in private_key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&value[i * 2..i * 2 + 2], 16).map_err(|e| e.to_string())?;
+    }
+
+    Ok(private_key)
+}
+
+fn parse_pattern(value: &str) -> Result<TrafficPattern, String> {
+    let parts: Vec<&str> = value.split(':').collect();
+    let number = |part: &str| part.parse::<usize>().map_err(|e| format!("{part}: {e}"));
+    match parts.as_slice() {
+        ["idle"] => Ok(TrafficPattern::Idle),
+        ["constant", size, rate] => Ok(TrafficPattern::Constant {
+            size: number(size)?,
+            rate: rate.parse().map_err(|e| format!("{rate}: {e}"))?,
+        }),
+        ["burst", size, count, interval] => Ok(TrafficPattern::Burst {
+            size: number(size)?,
+            count: number(count)?,
+            interval: Duration::from_millis(number(interval)? as u64),
+        }),
+        _ => Err("expected idle, constant:<SIZE>:<PER_SECOND> or burst:<SIZE>:<COUNT>:<INTERVAL_MS>".to_string()),
+    }
+}
@@ -0,0 +1,108 @@
+use std::{fmt, time::Duration};
+
+use crate::client::SimulatedClient;
+
+/// Results of the load test, each worker thread creates its own report and they are merged at the end.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub attempted: usize,
+    pub connected: usize,
+    // Clients that connected but were disconnected before the end of the test
+    pub disconnected: usize,
+    pub messages_sent: u64,
+    pub duration: Duration,
+    connect_times_ms: Vec<f64>,
+    rtts_ms: Vec<f64>,
+    packet_losses: Vec<f64>,
+}
+
+impl Report {
+    /// Samples the network info of a connected client, should be called periodically.
+    pub fn add_sample(&mut self, client: &SimulatedClient) {
+        let network_info = client.network_info();
+        self.rtts_ms.push(network_info.rtt * 1000.);
+        self.packet_losses.push(network_info.packet_loss * 100.);
+    }
+
+    /// Adds the results of a client at the end of the test.
+    pub fn add_client(&mut self, client: &SimulatedClient) {
+        self.attempted += 1;
+        self.messages_sent += client.messages_sent();
+        if let Some(connect_time) = client.connect_time() {
+            self.connected += 1;
+            self.connect_times_ms.push(connect_time.as_secs_f64() * 1000.);
+            if !client.is_connected() {
+                self.disconnected += 1;
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: Report) {
+        self.attempted += other.attempted;
+        self.connected += other.connected;
+        self.disconnected += other.disconnected;
+        self.messages_sent += other.messages_sent;
+        self.duration = self.duration.max(other.duration);
+        self.connect_times_ms.extend(other.connect_times_ms);
+        self.rtts_ms.extend(other.rtts_ms);
+        self.packet_losses.extend(other.packet_losses);
+    }
+}
+
+struct Distribution(Vec<f64>);
+
+impl Distribution {
+    fn new(mut values: Vec<f64>) -> Self {
+        values.sort_by(f64::total_cmp);
+        Self(values)
+    }
+
+    fn percentile(&self, percentile: f64) -> f64 {
+        let index = ((self.0.len() - 1) as f64 * percentile).round() as usize;
+        self.0[index]
+    }
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "no samples");
+        }
+
+        let average = self.0.iter().sum::<f64>() / self.0.len() as f64;
+        write!(
+            f,
+            "avg {:.1}, p50 {:.1}, p90 {:.1}, p99 {:.1}, max {:.1} ({} samples)",
+            average,
+            self.percentile(0.5),
+            self.percentile(0.9),
+            self.percentile(0.99),
+            self.0[self.0.len() - 1],
+            self.0.len()
+        )
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let success_rate = if self.attempted > 0 { self.connected as f64 * 100. / self.attempted as f64 } else { 0. };
+        writeln!(
+            f,
+            "Clients: {} attempted, {} connected ({:.1}%), {} failed to connect, {} disconnected",
+            self.attempted,
+            self.connected,
+            success_rate,
+            self.attempted - self.connected,
+            self.disconnected
+        )?;
+        writeln!(f, "Connect time (ms): {}", Distribution::new(self.connect_times_ms.clone()))?;
+        writeln!(f, "RTT (ms): {}", Distribution::new(self.rtts_ms.clone()))?;
+        writeln!(f, "Packet loss (%): {}", Distribution::new(self.packet_losses.clone()))?;
+        write!(
+            f,
+            "Messages sent: {} ({:.0}/s)",
+            self.messages_sent,
+            self.messages_sent as f64 / self.duration.as_secs_f64().max(1.)
+        )
+    }
+}
@@ -0,0 +1,180 @@
+use std::{
+    net::{SocketAddr, UdpSocket},
+    time::Duration,
+};
+
+use renet::{ConnectionConfig, RenetClient};
+use renetcode::{ClientAuthentication, ConnectToken, NETCODE_KEY_BYTES, NETCODE_USER_DATA_BYTES};
+
+use crate::{NetcodeClientTransport, NetcodeTransportError};
+
+// Only used to connect, a new connect token is generated for each connection attempt
+const BACKEND_TOKEN_EXPIRE_SECONDS: u64 = 30;
+
+/// Configuration of a [`BackendLink`].
+#[derive(Debug, Clone)]
+pub struct BackendLinkConfig {
+    /// Address of the backend server.
+    pub server_addr: SocketAddr,
+    /// Protocol id of the backend server, should be different from the one used by game clients.
+    pub protocol_id: u64,
+    /// Key shared by the services, also used as the private key of the backend server.
+    pub private_key: [u8; NETCODE_KEY_BYTES],
+    /// Unique id of this service in the backend server.
+    pub client_id: u64,
+    /// Data sent to the backend server when connecting, like the service name.
+    pub user_data: Option<[u8; NETCODE_USER_DATA_BYTES]>,
+    /// Seconds without receiving packets before the link times out, negative values disable the timeout.
+    pub timeout_seconds: i32,
+    /// Time to wait before reconnecting after the link is disconnected.
+    pub reconnect_delay: Duration,
+    pub connection_config: ConnectionConfig,
+}
+
+/// Long-lived connection between trusted backend services (game server, chat server, matchmaking),
+/// authenticated with a pre-shared key instead of connect tokens issued by a web service.
+///
+/// The link generates its own connect tokens with the pre-shared key, and reconnects when disconnected.
+/// The other service runs a [`NetcodeServerTransport`](crate::NetcodeServerTransport) with
+/// [`ServerAuthentication::Secure`](crate::ServerAuthentication::Secure) and the same key, so both sides
+/// are authenticated: only services with the key can create the connect token, or read it to answer.
+/// The key must never be shared with game clients, the backend server should be a separate
+/// transport with its own port and protocol id.
+///
+/// Messages are sent and received with the [`RenetClient`] from [`BackendLink::client_mut`], messages not
+/// sent before a disconnection are lost, and the link starts with new channels when it reconnects.
+///
+/// # Usage
+/// ```no_run
+/// # use renet_netcode::{BackendLink, BackendLinkConfig};
+/// # use renet::{ConnectionConfig, DefaultChannel};
+/// # use std::{net::UdpSocket, time::{Duration, SystemTime}};
+/// # let duration = Duration::from_millis(16);
+/// let config = BackendLinkConfig {
+///     server_addr: "10.0.0.2:6000".parse().unwrap(),
+///     protocol_id: 7,
+///     private_key: *b"an example very very secret key.",
+///     client_id: 1,
+///     user_data: None,
+///     timeout_seconds: 15,
+///     reconnect_delay: Duration::from_secs(1),
+///     connection_config: ConnectionConfig::default(),
+/// };
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+/// let mut link = BackendLink::new(current_time, config, socket).unwrap();
+///
+/// // Every update
+/// if let Err(e) = link.update(duration) {
+///     println!("Backend link disconnected: {e}");
+/// }
+/// if link.is_connected() {
+///     link.client_mut().send_message(DefaultChannel::ReliableOrdered, "match found");
+/// }
+/// link.send_packets().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct BackendLink {
+    config: BackendLinkConfig,
+    socket: UdpSocket,
+    transport: NetcodeClientTransport,
+    client: RenetClient,
+    current_time: Duration,
+    // Time disconnected, waiting to reconnect
+    disconnected_for: Option<Duration>,
+}
+
+impl BackendLink {
+    /// Creates the link and starts connecting, the current time must be the time since the unix epoch.
+    pub fn new(current_time: Duration, config: BackendLinkConfig, socket: UdpSocket) -> Result<Self, NetcodeTransportError> {
+        let transport = new_transport(current_time, &config, &socket)?;
+        let client = RenetClient::new(config.connection_config.clone());
+
+        Ok(Self {
+            config,
+            socket,
+            transport,
+            client,
+            current_time,
+            disconnected_for: None,
+        })
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.client.is_connected()
+    }
+
+    pub fn client(&self) -> &RenetClient {
+        &self.client
+    }
+
+    pub fn client_mut(&mut self) -> &mut RenetClient {
+        &mut self.client
+    }
+
+    /// Advances the link by the duration, and receive packets from the network.
+    /// Returns the error that disconnected the link, it reconnects after the reconnect delay.
+    pub fn update(&mut self, duration: Duration) -> Result<(), NetcodeTransportError> {
+        self.current_time += duration;
+
+        if let Some(disconnected_for) = &mut self.disconnected_for {
+            *disconnected_for += duration;
+            if *disconnected_for < self.config.reconnect_delay {
+                return Ok(());
+            }
+
+            log::debug!("Reconnecting backend link to {}", self.config.server_addr);
+            self.transport = new_transport(self.current_time, &self.config, &self.socket)?;
+            self.client = RenetClient::new(self.config.connection_config.clone());
+            self.disconnected_for = None;
+        }
+
+        self.client.update(duration);
+        if let Err(e) = self.transport.update(duration, &mut self.client) {
+            log::debug!("Backend link to {} disconnected: {e}", self.config.server_addr);
+            self.disconnected_for = Some(Duration::ZERO);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Send packets to the backend server, should be called every update.
+    pub fn send_packets(&mut self) -> Result<(), NetcodeTransportError> {
+        // Disconnected while receiving packets, the next update returns the error and reconnects
+        if self.disconnected_for.is_some() || !self.client.is_connected() || self.transport.disconnect_reason().is_some() {
+            return Ok(());
+        }
+
+        self.transport.send_packets(&mut self.client)
+    }
+
+    /// Disconnects the link, this sends the disconnect packet instantly.
+    /// The link reconnects after the reconnect delay if it's still updated.
+    pub fn disconnect(&mut self) {
+        self.transport.disconnect();
+        self.client.disconnect();
+        self.disconnected_for = Some(Duration::ZERO);
+    }
+}
+
+fn new_transport(
+    current_time: Duration,
+    config: &BackendLinkConfig,
+    socket: &UdpSocket,
+) -> Result<NetcodeClientTransport, NetcodeTransportError> {
+    let connect_token = ConnectToken::generate(
+        current_time,
+        config.protocol_id,
+        BACKEND_TOKEN_EXPIRE_SECONDS,
+        config.client_id,
+        config.timeout_seconds,
+        vec![config.server_addr],
+        config.user_data.as_ref(),
+        &config.private_key,
+    )?;
+    let authentication = ClientAuthentication::Secure { connect_token };
+    let transport = NetcodeClientTransport::new(current_time, authentication, socket.try_clone()?)?;
+
+    Ok(transport)
+}
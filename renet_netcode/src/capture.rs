@@ -0,0 +1,29 @@
+use renet::ClientId;
+#[cfg(feature = "pcap")]
+use renet::{PacketDirection, PcapWriter};
+
+// Captures the packets of a transport when a pcap writer is set, does nothing without the pcap feature
+#[derive(Debug, Default)]
+pub(crate) struct PacketCapture {
+    #[cfg(feature = "pcap")]
+    writer: Option<PcapWriter>,
+}
+
+impl PacketCapture {
+    #[cfg(feature = "pcap")]
+    pub fn set_writer(&mut self, writer: Option<PcapWriter>) {
+        self.writer = writer;
+    }
+
+    #[allow(unused_variables)]
+    pub fn capture(&mut self, client_to_server: bool, client_id: ClientId, packet: &[u8]) {
+        #[cfg(feature = "pcap")]
+        if let Some(writer) = &mut self.writer {
+            let direction = if client_to_server { PacketDirection::ClientToServer } else { PacketDirection::ServerToClient };
+            if let Err(e) = writer.write_packet(direction, client_id, packet) {
+                log::error!("Failed to capture packet, stopping the packet capture: {e}");
+                self.writer = None;
+            }
+        }
+    }
+}
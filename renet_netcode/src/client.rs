@@ -1,32 +1,73 @@
 use std::{
+    collections::VecDeque,
     io,
     net::{SocketAddr, UdpSocket},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use renetcode::{ClientAuthentication, DisconnectReason, NetcodeClient, NetcodeError, NETCODE_MAX_PACKET_BYTES};
+use renetcode::{
+    ClientAuthentication, ConnectToken, DisconnectReason, NetcodeClient, NetcodeError, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES,
+    NETCODE_RESUME_TICKET_BYTES,
+};
+
+use renet::{Bytes, ClientId, RenetClient};
 
-use renet::{ClientId, RenetClient};
+use crate::capture::PacketCapture;
+use crate::pacing::PacedPackets;
+use crate::unconnected::{UnconnectedMessageConfig, UnconnectedMessageError, UnconnectedMessages};
 
 use super::NetcodeTransportError;
 
+/// Progress of a transfer to another server, requested with [`RenetServer::transfer_client`](renet::RenetServer::transfer_client).
+#[derive(Debug)]
+pub enum TransferEvent {
+    /// Disconnected from the previous server, connecting to the new one.
+    Started,
+    /// Connected to the new server, with the client id of the new connect token.
+    Completed { client_id: ClientId },
+    /// The transfer failed. If the connect token was invalid, the client stays connected to the previous server.
+    Failed { error: NetcodeError },
+}
+
 #[derive(Debug)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
 pub struct NetcodeClientTransport {
     socket: UdpSocket,
+    // Address of a pre-connected socket, the packets to it are sent with send instead of send_to
+    peer_addr: Option<SocketAddr>,
     netcode_client: NetcodeClient,
     buffer: [u8; NETCODE_MAX_PACKET_BYTES],
+    packet_capture: PacketCapture,
+    transferring: bool,
+    transfer_events: VecDeque<TransferEvent>,
+    unconnected_messages: Option<UnconnectedMessages>,
+    pacing_rate: Option<u64>,
+    paced_packets: PacedPackets,
 }
 
 impl NetcodeClientTransport {
+    /// Creates the transport with the socket, that is set as nonblocking.
+    ///
+    /// The socket can be created and configured by the game, like a socket bound to a specific interface,
+    /// or a socket that already completed NAT punching with the server. Sockets connected to the server address
+    /// with [`UdpSocket::connect`] are supported, the packets to the server are sent with [`UdpSocket::send`],
+    /// since some platforms don't allow [`UdpSocket::send_to`] with connected sockets.
     pub fn new(current_time: Duration, authentication: ClientAuthentication, socket: UdpSocket) -> Result<Self, NetcodeError> {
         socket.set_nonblocking(true)?;
         let netcode_client = NetcodeClient::new(current_time, authentication)?;
+        let peer_addr = socket.peer_addr().ok();
 
         Ok(Self {
             buffer: [0u8; NETCODE_MAX_PACKET_BYTES],
             socket,
+            peer_addr,
             netcode_client,
+            packet_capture: PacketCapture::default(),
+            transferring: false,
+            transfer_events: VecDeque::new(),
+            unconnected_messages: None,
+            pacing_rate: None,
+            paced_packets: PacedPackets::default(),
         })
     }
 
@@ -34,6 +75,17 @@ impl NetcodeClientTransport {
         self.socket.local_addr()
     }
 
+    /// Returns the socket of the transport, it can be used to send other packets on the same port, like NAT keep-alives.
+    /// The packets received by the socket are read by the transport, see [`NetcodeClientTransport::set_unconnected_messages`].
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Returns the socket, to reuse it after the transport is no longer used.
+    pub fn into_socket(self) -> UdpSocket {
+        self.socket
+    }
+
     pub fn client_id(&self) -> ClientId {
         self.netcode_client.client_id()
     }
@@ -44,6 +96,21 @@ impl NetcodeClientTransport {
         self.netcode_client.time_since_last_received_packet()
     }
 
+    /// Returns the interval that keep-alive packets are sent when no other packets are sent,
+    /// the transport must be updated at least this often to not time out.
+    pub fn keep_alive_interval(&self) -> Duration {
+        self.netcode_client.keep_alive_interval()
+    }
+
+    /// Blocks until a packet is received or the timeout elapses, returns whether a packet was received.
+    /// The packet is processed in the next update.
+    ///
+    /// Can be used to stop updating while the connection is idle (see [`RenetClient::is_idle`]),
+    /// waking up when a packet arrives, with a timeout of at most the [`keep_alive_interval`](Self::keep_alive_interval).
+    pub fn wait_for_packet(&mut self, timeout: Duration) -> io::Result<bool> {
+        crate::wait_for_packet(&self.socket, &mut self.buffer, timeout)
+    }
+
     /// Disconnect the client from the transport layer.
     /// This sends the disconnect packet instantly, use this when closing/exiting games,
     /// should use [RenetClient::disconnect][crate::RenetClient::disconnect] otherwise.
@@ -54,7 +121,7 @@ impl NetcodeClientTransport {
 
         match self.netcode_client.disconnect() {
             Ok((addr, packet)) => {
-                if let Err(e) = self.socket.send_to(packet, addr) {
+                if let Err(e) = send_packet(&self.socket, self.peer_addr, packet, addr) {
                     log::error!("Failed to send disconnect packet: {e}");
                 }
             }
@@ -62,6 +129,76 @@ impl NetcodeClientTransport {
         }
     }
 
+    /// Enables or disables connecting to the first two server addresses of the connect token concurrently,
+    /// preferring IPv6 (happy eyeballs). The socket must be able to send to both addresses.
+    /// See [`NetcodeClient::set_happy_eyeballs`](renetcode::NetcodeClient::set_happy_eyeballs).
+    pub fn set_happy_eyeballs(&mut self, enabled: bool) {
+        self.netcode_client.set_happy_eyeballs(enabled);
+    }
+
+    /// Returns the ticket to resume the connection, when the server has session resumption enabled.
+    /// See [`NetcodeClientTransport::set_resume_ticket`].
+    pub fn resume_ticket(&self) -> Option<[u8; NETCODE_RESUME_TICKET_BYTES]> {
+        self.netcode_client.resume_ticket()
+    }
+
+    /// Sets the ticket of a lost connection, so the server resumes it when connecting instead of adding a new client.
+    /// The [`RenetClient`] of the lost connection must be reused after calling [`RenetClient::resume_session`],
+    /// so the messages not yet acked are delivered. When resumed, the server uses the client id of the lost connection.
+    pub fn set_resume_ticket(&mut self, resume_ticket: [u8; NETCODE_RESUME_TICKET_BYTES]) {
+        self.netcode_client.set_resume_ticket(resume_ticket);
+    }
+
+    /// Captures the packets sent and received in a pcap file, before they are encrypted, to debug them in Wireshark.
+    /// See [`PcapWriter`](renet::PcapWriter).
+    #[cfg(feature = "pcap")]
+    pub fn set_packet_capture(&mut self, writer: renet::PcapWriter) {
+        self.packet_capture.set_writer(Some(writer));
+    }
+
+    /// Stops capturing the packets.
+    #[cfg(feature = "pcap")]
+    pub fn clear_packet_capture(&mut self) {
+        self.packet_capture.set_writer(None);
+    }
+
+    /// Enables sending and receiving unconnected messages, small unauthenticated messages to and from any address,
+    /// for custom ping, query or server browser protocols on the same socket. See [`UnconnectedMessageConfig`].
+    pub fn set_unconnected_messages(&mut self, config: UnconnectedMessageConfig) {
+        self.unconnected_messages = Some(UnconnectedMessages::new(config));
+    }
+
+    /// Disables the unconnected messages, the messages not yet received are dropped.
+    pub fn clear_unconnected_messages(&mut self) {
+        self.unconnected_messages = None;
+    }
+
+    /// Receives an unconnected message with the address that sent it, the messages are received in the transport update.
+    pub fn receive_unconnected_message(&mut self) -> Option<(SocketAddr, Bytes)> {
+        self.unconnected_messages.as_mut()?.receive()
+    }
+
+    /// Sends an unconnected message to the address, it doesn't need to be the server the client is connected to.
+    /// See [`NetcodeServerTransport::receive_unconnected_message`](crate::NetcodeServerTransport::receive_unconnected_message).
+    pub fn send_unconnected_message(&mut self, addr: SocketAddr, message: &[u8]) -> Result<(), UnconnectedMessageError> {
+        match &mut self.unconnected_messages {
+            Some(unconnected_messages) => {
+                unconnected_messages.send(message, |packet| send_packet(&self.socket, self.peer_addr, packet, addr))
+            }
+            None => Err(UnconnectedMessageError::Disabled),
+        }
+    }
+
+    /// Returns whether the client is connecting to another server, see [`TransferEvent`].
+    pub fn is_transferring(&self) -> bool {
+        self.transferring
+    }
+
+    /// Returns the progress of the transfers to another server.
+    pub fn get_transfer_event(&mut self) -> Option<TransferEvent> {
+        self.transfer_events.pop_front()
+    }
+
     /// If the client is disconnected, returns the reason.
     pub fn disconnect_reason(&self) -> Option<DisconnectReason> {
         self.netcode_client.disconnect_reason()
@@ -69,65 +206,167 @@ impl NetcodeClientTransport {
 
     /// Send packets to the server.
     /// Should be called every tick
+    ///
+    /// With a [`pacing_rate`](renet::ConnectionConfig::pacing_rate), only the packets due are sent,
+    /// the others are sent in [`NetcodeClientTransport::send_paced_packets`], or in the next call.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(client_id = self.client_id())))]
     pub fn send_packets(&mut self, connection: &mut RenetClient) -> Result<(), NetcodeTransportError> {
         if let Some(reason) = self.netcode_client.disconnect_reason() {
             return Err(NetcodeError::Disconnected(reason).into());
         }
 
+        self.sync_suspended(connection)?;
+
+        // Packets of the previous tick still queued are sent now, they are never delayed by more than a tick
+        self.paced_packets
+            .flush(|packet, addr| send_packet(&self.socket, self.peer_addr, packet, addr))?;
+        self.pacing_rate = connection.pacing_rate();
+
+        connection.set_max_packet_size(NETCODE_MAX_PAYLOAD_BYTES);
         let packets = connection.get_packets_to_send();
+        let now = Instant::now();
         for packet in packets {
+            self.packet_capture.capture(true, self.netcode_client.client_id(), &packet);
             let (addr, payload) = self.netcode_client.generate_payload_packet(&packet)?;
-            self.socket.send_to(payload, addr)?;
+            match self.pacing_rate {
+                Some(_) => self.paced_packets.push(now, addr, payload),
+                None => {
+                    send_packet(&self.socket, self.peer_addr, payload, addr)?;
+                }
+            }
+        }
+
+        self.send_paced_packets()
+    }
+
+    /// Sends the packets queued by the [`pacing_rate`](renet::ConnectionConfig::pacing_rate) whose time has come.
+    /// Should be called between ticks, as often as possible, to spread the packets of the tick across the tick interval.
+    pub fn send_paced_packets(&mut self) -> Result<(), NetcodeTransportError> {
+        if let Some(pacing_rate) = self.pacing_rate {
+            self.paced_packets.send_due(Instant::now(), pacing_rate, |packet, addr| {
+                send_packet(&self.socket, self.peer_addr, packet, addr)
+            })?;
         }
 
         Ok(())
     }
 
+    /// Returns the number of packets waiting to be sent because of the pacing rate.
+    pub fn paced_packets(&self) -> usize {
+        self.paced_packets.len()
+    }
+
     /// Advances the transport by the duration, and receive packets from the network.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(client_id = self.client_id())))]
     pub fn update(&mut self, duration: Duration, client: &mut RenetClient) -> Result<(), NetcodeTransportError> {
         if let Some(reason) = self.netcode_client.disconnect_reason() {
+            if self.transferring {
+                self.transferring = false;
+                let error = NetcodeError::Disconnected(reason);
+                self.transfer_events.push_back(TransferEvent::Failed { error });
+            }
+
             // Mark the client as disconnected if an error occured in the transport layer
             client.disconnect_due_to_transport();
 
             return Err(NetcodeError::Disconnected(reason).into());
         }
 
+        if let Some(unconnected_messages) = &mut self.unconnected_messages {
+            unconnected_messages.update(duration);
+        }
+
         if let Some(error) = client.disconnect_reason() {
             let (addr, disconnect_packet) = self.netcode_client.disconnect()?;
-            self.socket.send_to(disconnect_packet, addr)?;
+            send_packet(&self.socket, self.peer_addr, disconnect_packet, addr)?;
             return Err(error.into());
         }
 
         if self.netcode_client.is_connected() {
             client.set_connected();
+            if self.transferring {
+                self.transferring = false;
+                let client_id = self.netcode_client.client_id();
+                self.transfer_events.push_back(TransferEvent::Completed { client_id });
+            }
         } else if self.netcode_client.is_connecting() {
             client.set_connecting();
         }
 
         loop {
             let packet = match self.socket.recv_from(&mut self.buffer) {
-                Ok((len, addr)) => {
-                    if addr != self.netcode_client.server_addr() {
-                        log::debug!("Discarded packet from unknown server {:?}", addr);
-                        continue;
-                    }
-
-                    &mut self.buffer[..len]
-                }
+                Ok((len, addr)) => (&mut self.buffer[..len], addr),
                 Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
                 Err(ref e) if e.kind() == io::ErrorKind::Interrupted => break,
                 Err(e) => return Err(NetcodeTransportError::IO(e)),
             };
 
-            if let Some(payload) = self.netcode_client.process_packet(packet) {
+            let (packet, addr) = packet;
+            if let Some(unconnected_messages) = &mut self.unconnected_messages {
+                if unconnected_messages.process_packet(addr, packet) {
+                    continue;
+                }
+            }
+
+            if let Some(payload) = self.netcode_client.process_packet_from(packet, addr) {
+                self.packet_capture.capture(false, self.netcode_client.client_id(), payload);
                 client.process_packet(payload);
             }
         }
 
+        if let Some(connect_token) = client.take_transfer() {
+            self.transfer(&connect_token, client)?;
+        }
+
+        self.sync_suspended(client)?;
         if let Some((packet, addr)) = self.netcode_client.update(duration) {
-            self.socket.send_to(packet, addr)?;
+            send_packet(&self.socket, self.peer_addr, packet, addr)?;
+        }
+
+        Ok(())
+    }
+
+    // Gracefully disconnects from the current server and connects to the server of the connect token
+    fn transfer(&mut self, connect_token: &[u8], client: &mut RenetClient) -> Result<(), NetcodeTransportError> {
+        let netcode_client = match ConnectToken::read(&mut &connect_token[..]).and_then(|connect_token| {
+            NetcodeClient::new(self.netcode_client.current_time(), ClientAuthentication::Secure { connect_token })
+        }) {
+            Ok(netcode_client) => netcode_client,
+            Err(error) => {
+                log::error!("Failed to transfer to another server: {error}");
+                self.transfer_events.push_back(TransferEvent::Failed { error });
+                return Ok(());
+            }
+        };
+
+        self.disconnect();
+        // The queued packets are encrypted for the previous server
+        self.paced_packets.clear();
+        self.netcode_client = netcode_client;
+        client.reset_connection();
+        self.transferring = true;
+        self.transfer_events.push_back(TransferEvent::Started);
+
+        Ok(())
+    }
+
+    // Suspends the netcode client when the renet client is paused, letting the server know it went to the background
+    fn sync_suspended(&mut self, client: &RenetClient) -> Result<(), NetcodeTransportError> {
+        if client.is_paused() && !self.netcode_client.is_suspended() && self.netcode_client.is_connected() {
+            let (addr, suspend_packet) = self.netcode_client.suspend()?;
+            send_packet(&self.socket, self.peer_addr, suspend_packet, addr)?;
+        } else if !client.is_paused() && self.netcode_client.is_suspended() {
+            self.netcode_client.resume();
         }
 
         Ok(())
     }
 }
+
+// Connected sockets can only send to their peer, with send
+fn send_packet(socket: &UdpSocket, peer_addr: Option<SocketAddr>, packet: &[u8], addr: SocketAddr) -> io::Result<usize> {
+    match peer_addr {
+        Some(peer_addr) if peer_addr == addr => socket.send(packet),
+        _ => socket.send_to(packet, addr),
+    }
+}
@@ -1,16 +1,64 @@
-use std::{error::Error, fmt};
+use std::{error::Error, fmt, io, net::UdpSocket, time::Duration};
 
+// The `tracing` feature emits the log events with tracing
+#[cfg(feature = "tracing")]
+extern crate tracing as log;
+
+mod backend;
+mod capture;
 mod client;
+mod pacing;
+mod query;
 mod server;
+mod socket;
+mod unconnected;
 
+pub use backend::{BackendLink, BackendLinkConfig};
 pub use client::*;
+pub use query::{
+    ping_servers, server_query_packet, ServerInfo, ServerQueryConfig, SERVER_PING_ATTEMPTS, SERVER_QUERY_MAX_STRING_BYTES,
+    SERVER_QUERY_PACKET_BYTES, SERVER_QUERY_PREFIX,
+};
 pub use server::*;
+pub use socket::SocketOptions;
+pub use unconnected::{
+    read_unconnected_message, unconnected_message_packet, UnconnectedMessageConfig, UnconnectedMessageError, MAX_UNCONNECTED_MESSAGE_BYTES,
+    UNCONNECTED_MESSAGE_PREFIX,
+};
 
 pub use renetcode::{
-    generate_random_bytes, ClientAuthentication, ConnectToken, DisconnectReason as NetcodeDisconnectReason, NetcodeError,
-    ServerAuthentication, ServerConfig, TokenGenerationError, NETCODE_KEY_BYTES, NETCODE_USER_DATA_BYTES,
+    generate_random_bytes, ClientAuthentication, ConnectToken, ConnectTokenStore, DisconnectReason as NetcodeDisconnectReason,
+    NetcodeError, ServerAuthentication, ServerConfig, TokenGenerationError, NETCODE_KEY_BYTES, NETCODE_MAX_PAYLOAD_BYTES,
+    NETCODE_MAX_PENDING_CLIENTS, NETCODE_RESUME_TICKET_BYTES, NETCODE_SEND_RATE, NETCODE_USER_DATA_BYTES,
 };
 
+// Blocks until a packet can be read from the nonblocking socket, or the timeout elapses
+fn wait_for_packet(socket: &UdpSocket, buffer: &mut [u8], timeout: Duration) -> io::Result<bool> {
+    // Zero is not a valid read timeout
+    if timeout.is_zero() {
+        return Ok(false);
+    }
+
+    socket.set_nonblocking(false)?;
+    socket.set_read_timeout(Some(timeout))?;
+    let result = socket.peek_from(buffer);
+    socket.set_read_timeout(None)?;
+    socket.set_nonblocking(true)?;
+
+    match result {
+        Ok(_) => Ok(true),
+        Err(e)
+            if matches!(
+                e.kind(),
+                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted
+            ) =>
+        {
+            Ok(false)
+        }
+        Err(e) => Err(e),
+    }
+}
+
 #[derive(Debug)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Event))]
 pub enum NetcodeTransportError {
@@ -0,0 +1,59 @@
+use std::{
+    collections::VecDeque,
+    io,
+    net::SocketAddr,
+    time::{Duration, Instant},
+};
+
+// Encrypted packets of a tick waiting to be sent at the pacing rate, see `ConnectionConfig::pacing_rate`
+#[derive(Debug, Default)]
+pub(crate) struct PacedPackets {
+    packets: VecDeque<(SocketAddr, Vec<u8>)>,
+    next_send_time: Option<Instant>,
+}
+
+impl PacedPackets {
+    pub fn len(&self) -> usize {
+        self.packets.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.packets.clear();
+    }
+
+    pub fn push(&mut self, now: Instant, addr: SocketAddr, packet: &[u8]) {
+        // Time while nothing was queued is not accumulated, so it never results in a burst
+        if self.packets.is_empty() {
+            self.next_send_time = Some(self.next_send_time.map_or(now, |time| time.max(now)));
+        }
+        self.packets.push_back((addr, packet.to_vec()));
+    }
+
+    // Sends the packets whose time has come, each packet delays the next one by its length at the pacing rate
+    pub fn send_due(
+        &mut self,
+        now: Instant,
+        pacing_rate: u64,
+        mut send: impl FnMut(&[u8], SocketAddr) -> io::Result<usize>,
+    ) -> io::Result<()> {
+        while let Some(send_time) = self.next_send_time.filter(|time| *time <= now) {
+            let Some((addr, packet)) = self.packets.pop_front() else {
+                break;
+            };
+            let delay = Duration::from_secs_f64(packet.len() as f64 / pacing_rate.max(1) as f64);
+            self.next_send_time = Some(send_time + delay);
+            send(&packet, addr)?;
+        }
+
+        Ok(())
+    }
+
+    // Sends all queued packets, so packets are never delayed past the tick they were generated in
+    pub fn flush(&mut self, mut send: impl FnMut(&[u8], SocketAddr) -> io::Result<usize>) -> io::Result<()> {
+        while let Some((addr, packet)) = self.packets.pop_front() {
+            send(&packet, addr)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,302 @@
+use std::{
+    io,
+    net::{SocketAddr, UdpSocket},
+    time::{Duration, Instant},
+};
+
+/// Prefix of the server query packets, it's not a valid netcode packet prefix.
+pub const SERVER_QUERY_PREFIX: [u8; 4] = [0xFF; 4];
+/// Size of the query packets, queries are padded to this size so the response is never bigger than the query.
+pub const SERVER_QUERY_PACKET_BYTES: usize = 600;
+/// Maximum number of bytes of the server name and map in the query response.
+pub const SERVER_QUERY_MAX_STRING_BYTES: usize = 255;
+
+/// Number of pings sent to each server by [`ping_servers`], the lowest latency is used.
+pub const SERVER_PING_ATTEMPTS: usize = 3;
+
+const QUERY_TYPE: u8 = b'Q';
+const RESPONSE_TYPE: u8 = b'I';
+const PING_TYPE: u8 = b'P';
+const PONG_TYPE: u8 = b'O';
+const HEADER_BYTES: usize = SERVER_QUERY_PREFIX.len() + 1 + 8;
+const PING_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Configuration for answering server browser queries.
+///
+/// Queries are unauthenticated packets, so the response never is bigger than the query
+/// to avoid being used for amplification attacks, and the number of responses is limited.
+#[derive(Debug, Clone)]
+pub struct ServerQueryConfig {
+    /// Name of the server, truncated to [`SERVER_QUERY_MAX_STRING_BYTES`].
+    pub name: String,
+    /// Current map of the server, truncated to [`SERVER_QUERY_MAX_STRING_BYTES`].
+    pub map: String,
+    /// Maximum number of queries answered per second, the others are ignored.
+    pub max_responses_per_second: u32,
+}
+
+/// Information about a server, received as a response to a query.
+///
+/// The ping can be measured by the time between sending the query and receiving the response with the same token.
+///
+/// # Usage
+/// ```
+/// # use renet_netcode::{server_query_packet, ServerInfo};
+/// # let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+/// # let server_addr = socket.local_addr().unwrap();
+/// let token = 42;
+/// socket.send_to(&server_query_packet(token), server_addr).unwrap();
+///
+/// // When receiving a packet from the server
+/// # let packet = [0u8; 16];
+/// if let Some(server_info) = ServerInfo::from_response(&packet) {
+///     if server_info.token == token {
+///         println!("{}: {}/{} players", server_info.name, server_info.players, server_info.max_players);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    /// Token sent in the query.
+    pub token: u64,
+    pub name: String,
+    pub map: String,
+    pub players: u32,
+    pub max_players: u32,
+}
+
+/// Returns a query packet to be sent to a server, the token is repeated in the response.
+pub fn server_query_packet(token: u64) -> Vec<u8> {
+    let mut packet = vec![0; SERVER_QUERY_PACKET_BYTES];
+    packet[..4].copy_from_slice(&SERVER_QUERY_PREFIX);
+    packet[4] = QUERY_TYPE;
+    packet[5..HEADER_BYTES].copy_from_slice(&token.to_le_bytes());
+    packet
+}
+
+impl ServerInfo {
+    /// Reads the server information from a query response.
+    /// Returns None if the packet is not a valid response.
+    pub fn from_response(packet: &[u8]) -> Option<Self> {
+        if packet.len() < HEADER_BYTES + 8 || packet[..4] != SERVER_QUERY_PREFIX || packet[4] != RESPONSE_TYPE {
+            return None;
+        }
+
+        let token = u64::from_le_bytes(packet[5..HEADER_BYTES].try_into().unwrap());
+        let players = u32::from_le_bytes(packet[HEADER_BYTES..HEADER_BYTES + 4].try_into().unwrap());
+        let max_players = u32::from_le_bytes(packet[HEADER_BYTES + 4..HEADER_BYTES + 8].try_into().unwrap());
+
+        let rest = &packet[HEADER_BYTES + 8..];
+        let (name, rest) = read_string(rest)?;
+        let (map, _) = read_string(rest)?;
+
+        Some(Self {
+            token,
+            name,
+            map,
+            players,
+            max_players,
+        })
+    }
+
+    fn to_response(&self) -> Vec<u8> {
+        let name = truncate(&self.name);
+        let map = truncate(&self.map);
+
+        let mut packet = Vec::with_capacity(HEADER_BYTES + 8 + 2 + name.len() + map.len());
+        packet.extend_from_slice(&SERVER_QUERY_PREFIX);
+        packet.push(RESPONSE_TYPE);
+        packet.extend_from_slice(&self.token.to_le_bytes());
+        packet.extend_from_slice(&self.players.to_le_bytes());
+        packet.extend_from_slice(&self.max_players.to_le_bytes());
+        packet.push(name.len() as u8);
+        packet.extend_from_slice(name.as_bytes());
+        packet.push(map.len() as u8);
+        packet.extend_from_slice(map.as_bytes());
+        packet
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct ServerQuery {
+    config: ServerQueryConfig,
+    current_time: Duration,
+    window_start: Duration,
+    responses_in_window: u32,
+}
+
+impl ServerQuery {
+    pub fn new(config: ServerQueryConfig) -> Self {
+        Self {
+            config,
+            current_time: Duration::ZERO,
+            window_start: Duration::ZERO,
+            responses_in_window: 0,
+        }
+    }
+
+    pub fn update(&mut self, duration: Duration) {
+        self.current_time += duration;
+        if self.current_time - self.window_start >= Duration::from_secs(1) {
+            self.window_start = self.current_time;
+            self.responses_in_window = 0;
+        }
+    }
+
+    pub fn is_query(packet: &[u8]) -> bool {
+        packet.len() >= HEADER_BYTES && packet[..4] == SERVER_QUERY_PREFIX && matches!(packet[4], QUERY_TYPE | PING_TYPE)
+    }
+
+    /// Returns the response for the query or ping, or None if it should be ignored.
+    pub fn response(&mut self, query: &[u8], players: u32, max_players: u32) -> Option<Vec<u8>> {
+        if self.responses_in_window >= self.config.max_responses_per_second {
+            log::trace!("Ignored server query, above the limit of responses per second");
+            return None;
+        }
+
+        // Pings are answered with the same packet, so the response is never bigger
+        if query[4] == PING_TYPE {
+            self.responses_in_window += 1;
+            let mut pong = query[..HEADER_BYTES].to_vec();
+            pong[4] = PONG_TYPE;
+            return Some(pong);
+        }
+
+        let info = ServerInfo {
+            token: u64::from_le_bytes(query[5..HEADER_BYTES].try_into().unwrap()),
+            name: self.config.name.clone(),
+            map: self.config.map.clone(),
+            players,
+            max_players,
+        };
+        let response = info.to_response();
+        if response.len() > query.len() {
+            log::trace!("Ignored server query with {} bytes, smaller than the response", query.len());
+            return None;
+        }
+
+        self.responses_in_window += 1;
+        Some(response)
+    }
+}
+
+/// Pings the candidate servers with small echo packets, and returns the latency to the servers that answered, nearest first.
+/// Can be used to choose the nearest region before requesting a connect token.
+///
+/// Each server is pinged [`SERVER_PING_ATTEMPTS`] times, and the lowest latency is used.
+/// Blocks until all pings are answered or the timeout elapses. The socket must be able to send to all the addresses.
+///
+/// Servers only answer pings with server queries enabled, see
+/// [`NetcodeServerTransport::set_server_query`](crate::NetcodeServerTransport::set_server_query).
+///
+/// # Usage
+/// ```no_run
+/// # use renet_netcode::ping_servers;
+/// # use std::{net::{SocketAddr, UdpSocket}, time::Duration};
+/// # let regions: Vec<SocketAddr> = vec![];
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let latencies = ping_servers(&socket, &regions, Duration::from_secs(1)).unwrap();
+/// if let Some((nearest, latency)) = latencies.first() {
+///     println!("Nearest server {nearest} with {latency:?}");
+/// }
+/// ```
+pub fn ping_servers(socket: &UdpSocket, addresses: &[SocketAddr], timeout: Duration) -> io::Result<Vec<(SocketAddr, Duration)>> {
+    socket.set_nonblocking(true)?;
+
+    // Each ping has its own token, from the server index and the attempt
+    let base_token = u64::from_le_bytes(renetcode::generate_random_bytes());
+    let mut sent_at: Vec<[Option<Instant>; SERVER_PING_ATTEMPTS]> = vec![[None; SERVER_PING_ATTEMPTS]; addresses.len()];
+    let mut latencies: Vec<Option<Duration>> = vec![None; addresses.len()];
+    let mut responses = 0;
+    let mut attempts = 0;
+    let mut buffer = [0u8; SERVER_QUERY_PACKET_BYTES];
+
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if attempts < SERVER_PING_ATTEMPTS && start.elapsed() >= PING_INTERVAL * attempts as u32 {
+            for (index, addr) in addresses.iter().enumerate() {
+                let token = base_token.wrapping_add((attempts * addresses.len() + index) as u64);
+                let mut ping = [0u8; HEADER_BYTES];
+                ping[..4].copy_from_slice(&SERVER_QUERY_PREFIX);
+                ping[4] = PING_TYPE;
+                ping[5..].copy_from_slice(&token.to_le_bytes());
+                let now = Instant::now();
+                match socket.send_to(&ping, addr) {
+                    Ok(_) => sent_at[index][attempts] = Some(now),
+                    Err(e) => log::debug!("Failed to ping server {addr}: {e}"),
+                }
+            }
+            attempts += 1;
+        }
+
+        loop {
+            let (len, addr) = match socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref e) if matches!(e.kind(), io::ErrorKind::Interrupted | io::ErrorKind::ConnectionReset) => continue,
+                Err(e) => return Err(e),
+            };
+            let pong = &buffer[..len];
+            if len != HEADER_BYTES || pong[..4] != SERVER_QUERY_PREFIX || pong[4] != PONG_TYPE {
+                continue;
+            }
+
+            let offset = u64::from_le_bytes(pong[5..].try_into().unwrap()).wrapping_sub(base_token) as usize;
+            if offset >= addresses.len() * SERVER_PING_ATTEMPTS {
+                continue;
+            }
+            let (index, attempt) = (offset % addresses.len(), offset / addresses.len());
+            if addresses[index] != addr {
+                continue;
+            }
+            if let Some(sent_at) = sent_at[index][attempt].take() {
+                let latency = sent_at.elapsed();
+                latencies[index] = Some(latencies[index].map_or(latency, |current| current.min(latency)));
+                responses += 1;
+            }
+        }
+
+        if responses == addresses.len() * SERVER_PING_ATTEMPTS {
+            break;
+        }
+
+        let next_ping = match attempts < SERVER_PING_ATTEMPTS {
+            true => PING_INTERVAL * attempts as u32,
+            false => timeout,
+        };
+        let wait = next_ping.min(timeout).saturating_sub(start.elapsed());
+        crate::wait_for_packet(socket, &mut buffer, wait)?;
+    }
+
+    let mut latencies: Vec<(SocketAddr, Duration)> = addresses
+        .iter()
+        .zip(latencies)
+        .filter_map(|(addr, latency)| latency.map(|latency| (*addr, latency)))
+        .collect();
+    latencies.sort_by_key(|(_, latency)| *latency);
+
+    Ok(latencies)
+}
+
+fn read_string(packet: &[u8]) -> Option<(String, &[u8])> {
+    let (&len, rest) = packet.split_first()?;
+    let len = len as usize;
+    if rest.len() < len {
+        return None;
+    }
+
+    let value = String::from_utf8(rest[..len].to_vec()).ok()?;
+    Some((value, &rest[len..]))
+}
+
+fn truncate(value: &str) -> &str {
+    if value.len() <= SERVER_QUERY_MAX_STRING_BYTES {
+        return value;
+    }
+
+    let mut end = SERVER_QUERY_MAX_STRING_BYTES;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
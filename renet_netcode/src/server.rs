@@ -1,13 +1,22 @@
 use std::{
+    collections::HashMap,
     io,
     net::{SocketAddr, UdpSocket},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
-use renetcode::{NetcodeServer, ServerConfig, ServerResult, NETCODE_MAX_PACKET_BYTES, NETCODE_USER_DATA_BYTES};
+use renetcode::{
+    ConnectTokenStore, ConnectionDeniedReason, NetcodeServer, ServerConfig, ServerResult, NETCODE_MAX_PACKET_BYTES,
+    NETCODE_MAX_PAYLOAD_BYTES, NETCODE_USER_DATA_BYTES,
+};
 
-use renet::ClientId;
 use renet::RenetServer;
+use renet::{Bytes, ClientId};
+
+use crate::capture::PacketCapture;
+use crate::pacing::PacedPackets;
+use crate::query::{ServerQuery, ServerQueryConfig};
+use crate::unconnected::{UnconnectedMessageConfig, UnconnectedMessageError, UnconnectedMessages};
 
 use super::NetcodeTransportError;
 
@@ -17,6 +26,21 @@ pub struct NetcodeServerTransport {
     socket: UdpSocket,
     netcode_server: NetcodeServer,
     buffer: [u8; NETCODE_MAX_PACKET_BYTES],
+    server_query: Option<ServerQuery>,
+    unconnected_messages: Option<UnconnectedMessages>,
+    connected_clients: Vec<NetcodeClientConnected>,
+    packet_capture: PacketCapture,
+    pacing_rate: Option<u64>,
+    paced_packets: HashMap<ClientId, PacedPackets>,
+}
+
+/// A client that connected to the [`NetcodeServerTransport`], with the user data from its connect token.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Event))]
+pub struct NetcodeClientConnected {
+    pub client_id: ClientId,
+    pub addr: SocketAddr,
+    pub user_data: Box<[u8; NETCODE_USER_DATA_BYTES]>,
 }
 
 impl NetcodeServerTransport {
@@ -29,6 +53,12 @@ impl NetcodeServerTransport {
             socket,
             netcode_server,
             buffer: [0; NETCODE_MAX_PACKET_BYTES],
+            server_query: None,
+            unconnected_messages: None,
+            connected_clients: Vec::new(),
+            packet_capture: PacketCapture::default(),
+            pacing_rate: None,
+            paced_packets: HashMap::new(),
         })
     }
 
@@ -37,12 +67,28 @@ impl NetcodeServerTransport {
         self.netcode_server.addresses()
     }
 
+    /// Returns the interval that keep-alive packets are sent to the clients when no other packets are sent,
+    /// the transport must be updated at least this often to not time out the clients.
+    pub fn keep_alive_interval(&self) -> Duration {
+        self.netcode_server.keep_alive_interval()
+    }
+
+    /// Blocks until a packet is received or the timeout elapses, returns whether a packet was received.
+    /// The packet is processed in the next update.
+    ///
+    /// Can be used to stop updating while all connections are idle (see [`RenetServer::is_idle`]),
+    /// waking up when a packet arrives, with a timeout of at most the [`keep_alive_interval`](Self::keep_alive_interval).
+    pub fn wait_for_packet(&mut self, timeout: Duration) -> io::Result<bool> {
+        crate::wait_for_packet(&self.socket, &mut self.buffer, timeout)
+    }
+
     /// Returns the maximum number of clients that can be connected.
     pub fn max_clients(&self) -> usize {
         self.netcode_server.max_clients()
     }
 
     /// Update the maximum numbers of clients that can be connected.
+    /// New connections are denied while the server is at the limit.
     ///
     /// Changing the `max_clients` to a lower value than the current number of connect clients
     /// does not disconnect clients. So [`NetcodeServerTransport::connected_clients()`] can
@@ -51,16 +97,191 @@ impl NetcodeServerTransport {
         self.netcode_server.set_max_clients(max_clients);
     }
 
+    /// Reserves a slot for the client for the duration, so a matchmaker can guarantee a seat while the connect token is in flight.
+    /// Reserved slots are not free for other clients, the reservation ends when the client connects or it expires.
+    ///
+    /// Returns false if there are no free slots to reserve.
+    pub fn reserve_slot(&mut self, client_id: ClientId, duration: Duration) -> bool {
+        self.netcode_server.reserve_slot(client_id, duration)
+    }
+
+    /// Cancels the slot reservation of the client, freeing the slot for other clients.
+    pub fn cancel_slot_reservation(&mut self, client_id: ClientId) {
+        self.netcode_server.cancel_slot_reservation(client_id);
+    }
+
+    /// Returns the number of slots reserved for clients not yet connected.
+    pub fn reserved_slots(&self) -> usize {
+        self.netcode_server.reserved_slots()
+    }
+
+    /// Bans a client id from connecting, for the given duration or permanently if `None`.
+    /// Connection requests from banned clients are denied before a pending connection is created.
+    ///
+    /// Does not disconnect the client if it's already connected, use [`RenetServer::disconnect`] for that.
+    pub fn ban_client_id(&mut self, client_id: ClientId, duration: Option<Duration>) {
+        self.netcode_server.ban_client_id(client_id, duration);
+    }
+
+    /// Bans an address from connecting, for the given duration or permanently if `None`.
+    /// Packets from banned addresses are ignored before any decryption is done.
+    ///
+    /// Does not disconnect clients already connected from this address.
+    pub fn ban_addr(&mut self, addr: SocketAddr, duration: Option<Duration>) {
+        self.netcode_server.ban_addr(addr, duration);
+    }
+
+    /// Removes the ban from a client id.
+    pub fn unban_client_id(&mut self, client_id: ClientId) {
+        self.netcode_server.unban_client_id(client_id);
+    }
+
+    /// Removes the ban from an address.
+    pub fn unban_addr(&mut self, addr: SocketAddr) {
+        self.netcode_server.unban_addr(addr);
+    }
+
+    /// Returns whether the client id is currently banned.
+    pub fn is_client_id_banned(&self, client_id: ClientId) -> bool {
+        self.netcode_server.is_client_id_banned(client_id)
+    }
+
+    /// Returns whether the address is currently banned.
+    pub fn is_addr_banned(&self, addr: SocketAddr) -> bool {
+        self.netcode_server.is_addr_banned(addr)
+    }
+
+    /// Sets a callback to approve or deny clients before they are connected.
+    ///
+    /// The callback is called with the client id, address and user data after the connect token
+    /// is validated, before a slot is given to the client. When it returns `false`,
+    /// the client receives a connection denied packet and is never added to the [`RenetServer`].
+    pub fn set_connection_approval<F>(&mut self, approval: F)
+    where
+        F: FnMut(ClientId, SocketAddr, &[u8; NETCODE_USER_DATA_BYTES]) -> bool + Send + Sync + 'static,
+    {
+        self.netcode_server.set_connection_approval(approval);
+    }
+
+    /// Removes the connection approval callback, all valid clients are accepted.
+    pub fn clear_connection_approval(&mut self) {
+        self.netcode_server.clear_connection_approval();
+    }
+
+    /// Sets a store shared between multiple servers to check if a connect token was already used,
+    /// for servers that share the same public address behind a load balancer.
+    /// See [`ConnectTokenStore`].
+    pub fn set_connect_token_store<S: ConnectTokenStore + 'static>(&mut self, store: S) {
+        self.netcode_server.set_connect_token_store(store);
+    }
+
+    /// Removes the shared connect token store, tokens are only checked in this server.
+    pub fn clear_connect_token_store(&mut self) {
+        self.netcode_server.clear_connect_token_store();
+    }
+
+    /// Enables responses to server browser queries, with the server name, map and number of players.
+    /// Queries are sent with [`server_query_packet`](crate::server_query_packet)
+    /// and the responses are read with [`ServerInfo::from_response`](crate::ServerInfo::from_response).
+    /// Pings from [`ping_servers`](crate::ping_servers) are also answered, sharing the limit of responses per second.
+    pub fn set_server_query(&mut self, config: ServerQueryConfig) {
+        self.server_query = Some(ServerQuery::new(config));
+    }
+
+    /// Disables the responses to server browser queries.
+    pub fn clear_server_query(&mut self) {
+        self.server_query = None;
+    }
+
+    /// Enables sending and receiving unconnected messages, small unauthenticated messages from any address,
+    /// for custom ping, query or server browser protocols on the same socket. See [`UnconnectedMessageConfig`].
+    pub fn set_unconnected_messages(&mut self, config: UnconnectedMessageConfig) {
+        self.unconnected_messages = Some(UnconnectedMessages::new(config));
+    }
+
+    /// Disables the unconnected messages, the messages not yet received are dropped.
+    pub fn clear_unconnected_messages(&mut self) {
+        self.unconnected_messages = None;
+    }
+
+    /// Receives an unconnected message with the address that sent it, the messages are received in the transport update.
+    pub fn receive_unconnected_message(&mut self) -> Option<(SocketAddr, Bytes)> {
+        self.unconnected_messages.as_mut()?.receive()
+    }
+
+    /// Sends an unconnected message to the address, it's received with
+    /// [`NetcodeClientTransport::receive_unconnected_message`](crate::NetcodeClientTransport::receive_unconnected_message)
+    /// or read with [`read_unconnected_message`](crate::read_unconnected_message).
+    pub fn send_unconnected_message(&mut self, addr: SocketAddr, message: &[u8]) -> Result<(), UnconnectedMessageError> {
+        match &mut self.unconnected_messages {
+            Some(unconnected_messages) => unconnected_messages.send(message, |packet| self.socket.send_to(packet, addr)),
+            None => Err(UnconnectedMessageError::Disabled),
+        }
+    }
+
+    /// Captures the packets sent and received from all clients in a pcap file, before they are encrypted,
+    /// to debug them in Wireshark. See [`PcapWriter`](renet::PcapWriter).
+    #[cfg(feature = "pcap")]
+    pub fn set_packet_capture(&mut self, writer: renet::PcapWriter) {
+        self.packet_capture.set_writer(Some(writer));
+    }
+
+    /// Stops capturing the packets.
+    #[cfg(feature = "pcap")]
+    pub fn clear_packet_capture(&mut self) {
+        self.packet_capture.set_writer(None);
+    }
+
     /// Returns current number of clients connected.
     pub fn connected_clients(&self) -> usize {
         self.netcode_server.connected_clients()
     }
 
+    /// Returns current number of clients pending in the handshake.
+    pub fn pending_clients(&self) -> usize {
+        self.netcode_server.pending_clients()
+    }
+
+    /// Returns the approximate memory in bytes used by the clients pending in the handshake.
+    pub fn pending_clients_memory(&self) -> usize {
+        self.netcode_server.pending_clients_memory()
+    }
+
+    /// Update the maximum numbers of clients that can be pending in the handshake at a time,
+    /// the initial value is the `max_pending_clients` of the [`ServerConfig`].
+    pub fn set_max_pending_clients(&mut self, max_pending_clients: usize) {
+        self.netcode_server.set_max_pending_clients(max_pending_clients);
+    }
+
+    /// Limits the approximate memory in bytes used by the clients pending in the handshake,
+    /// when reached, the pending client inactive for the longest time is evicted for a new one.
+    pub fn set_max_pending_memory(&mut self, max_bytes: usize) {
+        self.netcode_server.set_max_pending_memory(max_bytes);
+    }
+
+    /// Removes the memory limit of the pending clients.
+    pub fn clear_max_pending_memory(&mut self) {
+        self.netcode_server.clear_max_pending_memory();
+    }
+
+    /// Returns how many pending clients were evicted to make room for new connection requests.
+    pub fn evicted_pending_clients(&self) -> u64 {
+        self.netcode_server.evicted_pending_clients()
+    }
+
     /// Returns the user data for client if connected.
     pub fn user_data(&self, client_id: ClientId) -> Option<[u8; NETCODE_USER_DATA_BYTES]> {
         self.netcode_server.user_data(client_id)
     }
 
+    /// Returns the clients that connected in the last update, with their user data.
+    ///
+    /// Unlike [`NetcodeServerTransport::user_data`], the user data is available even if the client
+    /// already disconnected, until the next update.
+    pub fn clients_connected_last_update(&self) -> &[NetcodeClientConnected] {
+        &self.connected_clients
+    }
+
     /// Returns the client address if connected.
     pub fn client_addr(&self, client_id: ClientId) -> Option<SocketAddr> {
         self.netcode_server.client_addr(client_id)
@@ -72,7 +293,13 @@ impl NetcodeServerTransport {
     pub fn disconnect_all(&mut self, server: &mut RenetServer) {
         for client_id in self.netcode_server.clients_id() {
             let server_result = self.netcode_server.disconnect(client_id);
-            handle_server_result(server_result, &self.socket, server);
+            handle_server_result(
+                server_result,
+                &self.socket,
+                server,
+                &mut self.connected_clients,
+                &mut self.packet_capture,
+            );
         }
     }
 
@@ -82,44 +309,145 @@ impl NetcodeServerTransport {
         self.netcode_server.time_since_last_received_packet(client_id)
     }
 
+    /// Update the maximum time a client in the background is kept connected without sending packets,
+    /// the default is 30 seconds. See [`RenetClient::pause`][renet::RenetClient::pause].
+    pub fn set_max_suspend_duration(&mut self, max_suspend_duration: Duration) {
+        self.netcode_server.set_max_suspend_duration(max_suspend_duration);
+    }
+
+    /// Returns whether the client is in the background, see [`RenetClient::pause`][renet::RenetClient::pause].
+    pub fn is_client_suspended(&self, client_id: ClientId) -> bool {
+        self.netcode_server.is_client_suspended(client_id)
+    }
+
     /// Advances the transport by the duration, and receive packets from the network.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn update(&mut self, duration: Duration, server: &mut RenetServer) -> Result<(), NetcodeTransportError> {
+        let start = Instant::now();
+        match server.session_resumption() {
+            Some(grace_period) => self.netcode_server.set_session_resumption(grace_period),
+            None => self.netcode_server.clear_session_resumption(),
+        }
         self.netcode_server.update(duration);
+        self.connected_clients.clear();
+        if let Some(server_query) = &mut self.server_query {
+            server_query.update(duration);
+        }
+        if let Some(unconnected_messages) = &mut self.unconnected_messages {
+            unconnected_messages.update(duration);
+        }
 
         loop {
             match self.socket.recv_from(&mut self.buffer) {
                 Ok((len, addr)) => {
+                    if let Some(server_query) = &mut self.server_query {
+                        if ServerQuery::is_query(&self.buffer[..len]) {
+                            // Reserved slots are not free capacity
+                            let players = (self.netcode_server.connected_clients() + self.netcode_server.reserved_slots()) as u32;
+                            let max_players = self.netcode_server.max_clients() as u32;
+                            if let Some(response) = server_query.response(&self.buffer[..len], players, max_players) {
+                                if let Err(e) = self.socket.send_to(&response, addr) {
+                                    log::error!("Failed to send server query response to {addr}: {e}");
+                                }
+                            }
+                            continue;
+                        }
+                    }
+
+                    if let Some(unconnected_messages) = &mut self.unconnected_messages {
+                        if unconnected_messages.process_packet(addr, &self.buffer[..len]) {
+                            continue;
+                        }
+                    }
+
                     let server_result = self.netcode_server.process_packet(addr, &mut self.buffer[..len]);
-                    handle_server_result(server_result, &self.socket, server);
+                    // Disconnections when processing packets are requested by the client
+                    if let ServerResult::ClientDisconnected { client_id, .. } = server_result {
+                        server.client_disconnected(client_id);
+                    }
+                    handle_server_result(
+                        server_result,
+                        &self.socket,
+                        server,
+                        &mut self.connected_clients,
+                        &mut self.packet_capture,
+                    );
                 }
                 Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
                 Err(ref e) if e.kind() == io::ErrorKind::Interrupted => break,
                 Err(ref e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
-                Err(e) => return Err(e.into()),
+                Err(e) => {
+                    server.record_receive_time(start.elapsed());
+                    return Err(e.into());
+                }
             };
         }
 
         for client_id in self.netcode_server.clients_id() {
             let server_result = self.netcode_server.update_client(client_id);
-            handle_server_result(server_result, &self.socket, server);
+            // Clients are only disconnected when updated if they timed out, the connection can be resumed
+            if let ServerResult::ClientDisconnected { client_id, addr, payload } = server_result {
+                server.connection_lost(client_id);
+                if let Some(payload) = payload {
+                    if let Err(e) = self.socket.send_to(payload, addr) {
+                        log::error!("Failed to send packet to {addr}: {e}");
+                    }
+                }
+                continue;
+            }
+            handle_server_result(
+                server_result,
+                &self.socket,
+                server,
+                &mut self.connected_clients,
+                &mut self.packet_capture,
+            );
         }
 
         for disconnection_id in server.disconnections_id() {
             let server_result = self.netcode_server.disconnect(disconnection_id);
-            handle_server_result(server_result, &self.socket, server);
+            handle_server_result(
+                server_result,
+                &self.socket,
+                server,
+                &mut self.connected_clients,
+                &mut self.packet_capture,
+            );
         }
 
+        server.record_receive_time(start.elapsed());
         Ok(())
     }
 
     /// Send packets to connected clients.
+    ///
+    /// With a [`pacing_rate`](renet::ConnectionConfig::pacing_rate), only the packets due are sent,
+    /// the others are sent in [`NetcodeServerTransport::send_paced_packets`], or in the next call.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn send_packets(&mut self, server: &mut RenetServer) {
+        let start = Instant::now();
+        let packing = server.timing().packing;
+
+        // Packets of the previous tick still queued are sent now, they are never delayed by more than a tick
+        for (client_id, paced_packets) in self.paced_packets.iter_mut() {
+            if let Err(e) = paced_packets.flush(|packet, addr| self.socket.send_to(packet, addr)) {
+                log::error!("Failed to send packet to client {client_id}: {e}");
+                paced_packets.clear();
+            }
+        }
+        self.paced_packets.retain(|client_id, _| server.is_connected(*client_id));
+        self.pacing_rate = server.pacing_rate();
+
+        server.set_max_packet_size(NETCODE_MAX_PAYLOAD_BYTES);
         'clients: for client_id in server.clients_id() {
             let packets = server.get_packets_to_send(client_id).unwrap();
             for packet in packets {
+                self.packet_capture.capture(false, client_id, &packet);
                 match self.netcode_server.generate_payload_packet(client_id, &packet) {
                     Ok((addr, payload)) => {
-                        if let Err(e) = self.socket.send_to(payload, addr) {
+                        if self.pacing_rate.is_some() {
+                            self.paced_packets.entry(client_id).or_default().push(start, addr, payload);
+                        } else if let Err(e) = self.socket.send_to(payload, addr) {
                             log::error!("Failed to send packet to client {client_id} ({addr}): {e}");
                             continue 'clients;
                         }
@@ -131,10 +459,42 @@ impl NetcodeServerTransport {
                 }
             }
         }
+        self.send_paced_packets();
+
+        // The time spent in get_packets_to_send is already measured as packing
+        let elapsed = start.elapsed().saturating_sub(server.timing().packing - packing);
+        server.record_send_time(elapsed);
+    }
+
+    /// Sends the packets queued by the [`pacing_rate`](renet::ConnectionConfig::pacing_rate) whose time has come.
+    /// Should be called between ticks, as often as possible, to spread the packets of the tick across the tick interval.
+    pub fn send_paced_packets(&mut self) {
+        let Some(pacing_rate) = self.pacing_rate else {
+            return;
+        };
+
+        let now = Instant::now();
+        for (client_id, paced_packets) in self.paced_packets.iter_mut() {
+            if let Err(e) = paced_packets.send_due(now, pacing_rate, |packet, addr| self.socket.send_to(packet, addr)) {
+                log::error!("Failed to send packet to client {client_id}: {e}");
+                paced_packets.clear();
+            }
+        }
+    }
+
+    /// Returns the number of packets waiting to be sent to the client because of the pacing rate.
+    pub fn paced_packets(&self, client_id: ClientId) -> usize {
+        self.paced_packets.get(&client_id).map_or(0, PacedPackets::len)
     }
 }
 
-fn handle_server_result(server_result: ServerResult, socket: &UdpSocket, reliable_server: &mut RenetServer) {
+fn handle_server_result(
+    server_result: ServerResult,
+    socket: &UdpSocket,
+    reliable_server: &mut RenetServer,
+    connected_clients: &mut Vec<NetcodeClientConnected>,
+    packet_capture: &mut PacketCapture,
+) {
     let send_packet = |packet: &[u8], addr: SocketAddr| {
         if let Err(err) = socket.send_to(packet, addr) {
             log::error!("Failed to send packet to {addr}: {err}");
@@ -147,17 +507,27 @@ fn handle_server_result(server_result: ServerResult, socket: &UdpSocket, reliabl
             send_packet(payload, addr);
         }
         ServerResult::Payload { client_id, payload } => {
+            packet_capture.capture(true, client_id, payload);
             if let Err(e) = reliable_server.process_packet_from(payload, client_id) {
                 log::error!("Error while processing payload for {}: {}", client_id, e);
             }
         }
         ServerResult::ClientConnected {
             client_id,
-            user_data: _,
+            user_data,
             addr,
             payload,
+            resumed,
         } => {
-            reliable_server.add_connection(client_id);
+            // The resumed connection may no longer be held by renet, then it's a new connection
+            if !resumed || !reliable_server.resume_connection(client_id) {
+                reliable_server.add_connection(client_id);
+            }
+            connected_clients.push(NetcodeClientConnected {
+                client_id,
+                addr,
+                user_data,
+            });
             send_packet(payload, addr);
         }
         ServerResult::ClientDisconnected { client_id, addr, payload } => {
@@ -166,5 +536,20 @@ fn handle_server_result(server_result: ServerResult, socket: &UdpSocket, reliabl
                 send_packet(payload, addr);
             }
         }
+        ServerResult::ConnectionDenied { addr, reason, payload } => {
+            let reason = match reason {
+                ConnectionDeniedReason::TokenExpired => renet::ConnectionDeniedReason::TokenExpired,
+                ConnectionDeniedReason::InvalidVersion => renet::ConnectionDeniedReason::InvalidVersion,
+                ConnectionDeniedReason::InvalidProtocolId => renet::ConnectionDeniedReason::InvalidProtocolId,
+                ConnectionDeniedReason::NotInHostList => renet::ConnectionDeniedReason::NotInHostList,
+                ConnectionDeniedReason::Banned => renet::ConnectionDeniedReason::Banned,
+                ConnectionDeniedReason::ServerFull => renet::ConnectionDeniedReason::ServerFull,
+                ConnectionDeniedReason::NotApproved => renet::ConnectionDeniedReason::NotApproved,
+            };
+            reliable_server.connection_denied(addr, reason);
+            if let Some(payload) = payload {
+                send_packet(payload, addr);
+            }
+        }
     }
 }
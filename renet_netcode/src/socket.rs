@@ -0,0 +1,70 @@
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+};
+
+/// Options to create the socket of a transport, to choose the local address and interface used,
+/// for multi-homed servers or clients that must use a specific interface like a VPN.
+///
+/// # Usage
+/// ```
+/// # use renet_netcode::SocketOptions;
+/// let options = SocketOptions {
+///     // Only receive and send from this local address
+///     bind_addr: "127.0.0.1:0".parse().unwrap(),
+///     ..Default::default()
+/// };
+/// let socket = options.bind().unwrap();
+/// assert!(socket.local_addr().unwrap().ip().is_loopback());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketOptions {
+    /// Local address to bind, its IP is the source address of the packets sent.
+    /// Unspecified addresses (`0.0.0.0` or `::`) use any interface, and port 0 uses any available port.
+    /// Default: `0.0.0.0:0`
+    pub bind_addr: SocketAddr,
+    /// Name of the network interface to bind, like `"wg0"`, the packets are only sent and received through it,
+    /// even if the routing table would choose another interface.
+    /// Requires the `bind-device` feature, and is only supported on Linux and Android, usually with `CAP_NET_RAW`.
+    /// Default: None
+    pub device: Option<String>,
+}
+
+impl Default for SocketOptions {
+    fn default() -> Self {
+        Self {
+            bind_addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            device: None,
+        }
+    }
+}
+
+impl SocketOptions {
+    /// Creates the socket with the options, to be used in
+    /// [`NetcodeServerTransport::new`](crate::NetcodeServerTransport::new) or
+    /// [`NetcodeClientTransport::new`](crate::NetcodeClientTransport::new).
+    pub fn bind(&self) -> io::Result<UdpSocket> {
+        match &self.device {
+            None => UdpSocket::bind(self.bind_addr),
+            Some(device) => bind_device(self.bind_addr, device),
+        }
+    }
+}
+
+#[cfg(all(feature = "bind-device", any(target_os = "android", target_os = "linux")))]
+fn bind_device(bind_addr: SocketAddr, device: &str) -> io::Result<UdpSocket> {
+    use socket2::{Domain, Protocol, Socket, Type};
+
+    let socket = Socket::new(Domain::for_address(bind_addr), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.bind_device(Some(device.as_bytes()))?;
+    socket.bind(&bind_addr.into())?;
+    Ok(socket.into())
+}
+
+#[cfg(not(all(feature = "bind-device", any(target_os = "android", target_os = "linux"))))]
+fn bind_device(_bind_addr: SocketAddr, device: &str) -> io::Result<UdpSocket> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("binding to the device {device} requires the bind-device feature on Linux"),
+    ))
+}
@@ -0,0 +1,208 @@
+use std::{collections::VecDeque, error::Error, fmt, io, net::SocketAddr, time::Duration};
+
+use renet::Bytes;
+
+/// Prefix of the unconnected message packets, it's not a valid netcode packet prefix
+/// and it's different from the [`SERVER_QUERY_PREFIX`](crate::SERVER_QUERY_PREFIX).
+pub const UNCONNECTED_MESSAGE_PREFIX: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFE];
+/// Maximum size of the unconnected messages, without the prefix.
+pub const MAX_UNCONNECTED_MESSAGE_BYTES: usize = 1024;
+
+/// Configuration of the unconnected messages, small unauthenticated messages sent on the same socket as the netcode packets,
+/// to implement custom ping, query or server browser protocols without a second port.
+///
+/// Unconnected messages are not encrypted nor authenticated, anyone can send them with any source address.
+/// To avoid being used for amplification attacks, the responses should never be bigger than the requests,
+/// requests can be padded for that like the server queries.
+///
+/// # Usage
+/// ```
+/// # use renet::{ConnectionConfig, RenetServer};
+/// # use renet_netcode::*;
+/// # use std::{net::UdpSocket, time::{Duration, SystemTime}};
+/// # let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+/// # let server_addr = socket.local_addr().unwrap();
+/// # let server_config = ServerConfig {
+/// #     current_time: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap(),
+/// #     max_clients: 64,
+/// #     protocol_id: 0,
+/// #     public_addresses: vec![server_addr],
+/// #     authentication: ServerAuthentication::Unsecure,
+/// #     keep_alive_interval: NETCODE_SEND_RATE,
+/// #     max_pending_clients: NETCODE_MAX_PENDING_CLIENTS,
+/// # };
+/// # let mut server = RenetServer::new(ConnectionConfig::default());
+/// let mut transport = NetcodeServerTransport::new(server_config, socket).unwrap();
+/// transport.set_unconnected_messages(UnconnectedMessageConfig::default());
+///
+/// // Any socket can send the messages, like the socket of a server browser
+/// let browser = UdpSocket::bind("127.0.0.1:0").unwrap();
+/// browser.send_to(&unconnected_message_packet(b"status"), server_addr).unwrap();
+/// # std::thread::sleep(Duration::from_millis(50));
+///
+/// transport.update(Duration::from_millis(16), &mut server).unwrap();
+/// while let Some((addr, message)) = transport.receive_unconnected_message() {
+///     if message == "status" {
+///         transport.send_unconnected_message(addr, b"ok").unwrap();
+///     }
+/// }
+///
+/// let mut buffer = [0u8; 1200];
+/// let (len, _) = browser.recv_from(&mut buffer).unwrap();
+/// assert_eq!(read_unconnected_message(&buffer[..len]), Some(&b"ok"[..]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct UnconnectedMessageConfig {
+    /// Maximum size of the messages sent and received, capped by [`MAX_UNCONNECTED_MESSAGE_BYTES`].
+    /// Bigger messages received are ignored.
+    pub max_message_bytes: usize,
+    /// Maximum number of messages received per second, the others are ignored.
+    /// Also the maximum number of messages waiting to be received by the game.
+    pub max_received_per_second: u32,
+    /// Maximum number of messages sent per second, the others return [`UnconnectedMessageError::RateLimited`].
+    pub max_sent_per_second: u32,
+}
+
+impl Default for UnconnectedMessageConfig {
+    fn default() -> Self {
+        Self {
+            max_message_bytes: 256,
+            max_received_per_second: 100,
+            max_sent_per_second: 100,
+        }
+    }
+}
+
+/// Error when sending an unconnected message.
+#[derive(Debug)]
+pub enum UnconnectedMessageError {
+    /// The unconnected messages are not enabled in the transport.
+    Disabled,
+    /// The message is bigger than the `max_message_bytes` of the config.
+    MessageTooLarge {
+        len: usize,
+        max: usize,
+    },
+    /// The limit of messages sent per second was reached.
+    RateLimited,
+    IO(io::Error),
+}
+
+impl Error for UnconnectedMessageError {}
+
+impl fmt::Display for UnconnectedMessageError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            UnconnectedMessageError::Disabled => write!(fmt, "unconnected messages are not enabled"),
+            UnconnectedMessageError::MessageTooLarge { len, max } => {
+                write!(
+                    fmt,
+                    "unconnected message with {len} bytes is bigger than the maximum of {max} bytes"
+                )
+            }
+            UnconnectedMessageError::RateLimited => write!(fmt, "reached the limit of unconnected messages sent per second"),
+            UnconnectedMessageError::IO(ref err) => err.fmt(fmt),
+        }
+    }
+}
+
+impl From<io::Error> for UnconnectedMessageError {
+    fn from(inner: io::Error) -> Self {
+        UnconnectedMessageError::IO(inner)
+    }
+}
+
+/// Returns the packet with the unconnected message, to be sent with any socket to a transport with unconnected messages enabled.
+pub fn unconnected_message_packet(message: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(UNCONNECTED_MESSAGE_PREFIX.len() + message.len());
+    packet.extend_from_slice(&UNCONNECTED_MESSAGE_PREFIX);
+    packet.extend_from_slice(message);
+    packet
+}
+
+/// Returns the message of an unconnected message packet, or None if the packet is not an unconnected message.
+pub fn read_unconnected_message(packet: &[u8]) -> Option<&[u8]> {
+    packet.strip_prefix(&UNCONNECTED_MESSAGE_PREFIX)
+}
+
+#[derive(Debug)]
+pub(crate) struct UnconnectedMessages {
+    config: UnconnectedMessageConfig,
+    current_time: Duration,
+    window_start: Duration,
+    received_in_window: u32,
+    sent_in_window: u32,
+    received: VecDeque<(SocketAddr, Bytes)>,
+}
+
+impl UnconnectedMessages {
+    pub fn new(config: UnconnectedMessageConfig) -> Self {
+        Self {
+            config,
+            current_time: Duration::ZERO,
+            window_start: Duration::ZERO,
+            received_in_window: 0,
+            sent_in_window: 0,
+            received: VecDeque::new(),
+        }
+    }
+
+    fn max_message_bytes(&self) -> usize {
+        self.config.max_message_bytes.min(MAX_UNCONNECTED_MESSAGE_BYTES)
+    }
+
+    pub fn update(&mut self, duration: Duration) {
+        self.current_time += duration;
+        if self.current_time - self.window_start >= Duration::from_secs(1) {
+            self.window_start = self.current_time;
+            self.received_in_window = 0;
+            self.sent_in_window = 0;
+        }
+    }
+
+    /// Queues the message of the packet, returns false if the packet is not an unconnected message.
+    pub fn process_packet(&mut self, addr: SocketAddr, packet: &[u8]) -> bool {
+        let Some(message) = read_unconnected_message(packet) else {
+            return false;
+        };
+
+        if message.len() > self.max_message_bytes() {
+            log::trace!(
+                "Ignored unconnected message from {addr} with {} bytes, above the maximum",
+                message.len()
+            );
+        } else if self.received_in_window >= self.config.max_received_per_second
+            || self.received.len() >= self.config.max_received_per_second as usize
+        {
+            // Messages not received by the game also count, so the queue can't grow forever
+            log::trace!("Ignored unconnected message from {addr}, above the limit of messages per second");
+        } else {
+            self.received_in_window += 1;
+            self.received.push_back((addr, Bytes::copy_from_slice(message)));
+        }
+
+        true
+    }
+
+    pub fn receive(&mut self) -> Option<(SocketAddr, Bytes)> {
+        self.received.pop_front()
+    }
+
+    /// Checks the limits and sends the packet with the message with the send function.
+    pub fn send<F>(&mut self, message: &[u8], send: F) -> Result<(), UnconnectedMessageError>
+    where
+        F: FnOnce(&[u8]) -> io::Result<usize>,
+    {
+        let max = self.max_message_bytes();
+        if message.len() > max {
+            return Err(UnconnectedMessageError::MessageTooLarge { len: message.len(), max });
+        }
+        if self.sent_in_window >= self.config.max_sent_per_second {
+            return Err(UnconnectedMessageError::RateLimited);
+        }
+
+        self.sent_in_window += 1;
+        send(&unconnected_message_packet(message))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+//! Connects a backend link to a netcode server with the pre-shared key, over UDP loopback sockets.
+use std::{
+    net::UdpSocket,
+    time::{Duration, SystemTime},
+};
+
+use renet::{ClientId, ConnectionConfig, DefaultChannel, RenetServer};
+use renet_netcode::{
+    BackendLink, BackendLinkConfig, NetcodeServerTransport, ServerAuthentication, ServerConfig, NETCODE_KEY_BYTES,
+    NETCODE_MAX_PENDING_CLIENTS, NETCODE_SEND_RATE,
+};
+
+const PROTOCOL_ID: u64 = 11;
+const LINK_ID: ClientId = 1;
+const PRIVATE_KEY: [u8; NETCODE_KEY_BYTES] = *b"an example very very secret key.";
+const DELTA: Duration = Duration::from_millis(16);
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_STEPS: usize = 500;
+
+struct Backend {
+    server: RenetServer,
+    server_transport: NetcodeServerTransport,
+    link: BackendLink,
+    link_errors: usize,
+}
+
+impl Backend {
+    fn new() -> Self {
+        let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+        let server_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = server_socket.local_addr().unwrap();
+        let server_config = ServerConfig {
+            current_time,
+            max_clients: 4,
+            protocol_id: PROTOCOL_ID,
+            public_addresses: vec![server_addr],
+            authentication: ServerAuthentication::Secure { private_key: PRIVATE_KEY },
+            keep_alive_interval: NETCODE_SEND_RATE,
+            max_pending_clients: NETCODE_MAX_PENDING_CLIENTS,
+        };
+        let server_transport = NetcodeServerTransport::new(server_config, server_socket).unwrap();
+
+        let link_config = BackendLinkConfig {
+            server_addr,
+            protocol_id: PROTOCOL_ID,
+            private_key: PRIVATE_KEY,
+            client_id: LINK_ID,
+            user_data: None,
+            timeout_seconds: 15,
+            reconnect_delay: RECONNECT_DELAY,
+            connection_config: ConnectionConfig::default(),
+        };
+        let link_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let link = BackendLink::new(current_time, link_config, link_socket).unwrap();
+
+        Self {
+            server: RenetServer::new(ConnectionConfig::default()),
+            server_transport,
+            link,
+            link_errors: 0,
+        }
+    }
+
+    /// Updates the server and the link, and exchanges their packets.
+    fn step(&mut self) {
+        self.server.update(DELTA);
+        self.server_transport.update(DELTA, &mut self.server).unwrap();
+        self.server_transport.send_packets(&mut self.server);
+
+        if self.link.update(DELTA).is_err() {
+            self.link_errors += 1;
+        }
+        self.link.send_packets().unwrap();
+
+        // Give time for the packets to arrive in the loopback sockets
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    // Steps until the condition is true
+    fn step_until(&mut self, mut condition: impl FnMut(&mut Self) -> bool) {
+        for _ in 0..MAX_STEPS {
+            self.step();
+            if condition(self) {
+                return;
+            }
+        }
+        panic!("condition not reached after {MAX_STEPS} steps");
+    }
+}
+
+#[test]
+fn test_backend_link_reconnect() {
+    let mut backend = Backend::new();
+    backend.step_until(|b| b.link.is_connected() && b.server.is_connected(LINK_ID));
+
+    backend.link.client_mut().send_message(DefaultChannel::ReliableOrdered, "ping");
+    backend.step_until(|b| {
+        b.server
+            .receive_message(LINK_ID, DefaultChannel::ReliableOrdered)
+            .is_some_and(|message| message == "ping")
+    });
+
+    backend.server.send_message(LINK_ID, DefaultChannel::ReliableOrdered, "pong");
+    backend.step_until(|b| {
+        b.link
+            .client_mut()
+            .receive_message(DefaultChannel::ReliableOrdered)
+            .is_some_and(|message| message == "pong")
+    });
+
+    // The backend server drops the link
+    backend.server.disconnect(LINK_ID);
+    backend.step_until(|b| b.link_errors > 0);
+    assert!(!backend.link.is_connected());
+
+    // The link waits for the reconnect delay before connecting again
+    let delay_steps = RECONNECT_DELAY.as_millis() / DELTA.as_millis();
+    for _ in 0..delay_steps {
+        backend.step();
+        assert!(!backend.link.is_connected());
+        assert!(!backend.server.is_connected(LINK_ID));
+    }
+
+    backend.step_until(|b| b.link.is_connected() && b.server.is_connected(LINK_ID));
+    assert_eq!(backend.link_errors, 1);
+
+    // The reconnected link starts with new channels
+    backend
+        .link
+        .client_mut()
+        .send_message(DefaultChannel::ReliableOrdered, "ping again");
+    backend.step_until(|b| {
+        b.server
+            .receive_message(LINK_ID, DefaultChannel::ReliableOrdered)
+            .is_some_and(|message| message == "ping again")
+    });
+}
@@ -0,0 +1,173 @@
+//! Runs the same connect/send/disconnect scenario over each transport,
+//! the server events must be the same regardless of the transport used.
+use std::{
+    net::UdpSocket,
+    time::{Duration, SystemTime},
+};
+
+use renet::{ClientId, ConnectionConfig, DefaultChannel, DisconnectReason, RenetClient, RenetServer, ServerEvent};
+use renet_netcode::{
+    ClientAuthentication, NetcodeClientTransport, NetcodeServerTransport, ServerAuthentication, ServerConfig, NETCODE_MAX_PENDING_CLIENTS,
+    NETCODE_SEND_RATE,
+};
+
+const PROTOCOL_ID: u64 = 7;
+const CLIENT_ID: ClientId = 42;
+const DELTA: Duration = Duration::from_millis(16);
+const MAX_STEPS: usize = 500;
+
+trait Transport {
+    fn server(&mut self) -> &mut RenetServer;
+    fn client(&mut self) -> &mut RenetClient;
+    /// Updates the server and client, and exchanges their packets.
+    fn step(&mut self);
+}
+
+/// Client and server in the same process, see `RenetServer::new_local_client`.
+struct LocalTransport {
+    server: RenetServer,
+    client: RenetClient,
+}
+
+impl LocalTransport {
+    fn new() -> Self {
+        let mut server = RenetServer::new(ConnectionConfig::default());
+        let client = server.new_local_client(CLIENT_ID);
+        Self { server, client }
+    }
+}
+
+impl Transport for LocalTransport {
+    fn server(&mut self) -> &mut RenetServer {
+        &mut self.server
+    }
+
+    fn client(&mut self) -> &mut RenetClient {
+        &mut self.client
+    }
+
+    fn step(&mut self) {
+        self.server.update(DELTA);
+        self.client.update(DELTA);
+        // The connection is removed after the client disconnects
+        let _ = self.server.process_local_client(CLIENT_ID, &mut self.client);
+    }
+}
+
+/// Netcode transports over UDP loopback sockets.
+struct NetcodeTransport {
+    server: RenetServer,
+    client: RenetClient,
+    server_transport: NetcodeServerTransport,
+    client_transport: NetcodeClientTransport,
+}
+
+impl NetcodeTransport {
+    fn new() -> Self {
+        let current_time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap();
+        let server_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server_addr = server_socket.local_addr().unwrap();
+        let server_config = ServerConfig {
+            current_time,
+            max_clients: 4,
+            protocol_id: PROTOCOL_ID,
+            public_addresses: vec![server_addr],
+            authentication: ServerAuthentication::Unsecure,
+            keep_alive_interval: NETCODE_SEND_RATE,
+            max_pending_clients: NETCODE_MAX_PENDING_CLIENTS,
+        };
+        let server_transport = NetcodeServerTransport::new(server_config, server_socket).unwrap();
+
+        let authentication = ClientAuthentication::Unsecure {
+            protocol_id: PROTOCOL_ID,
+            client_id: CLIENT_ID,
+            server_addr,
+            user_data: None,
+        };
+        let client_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client_transport = NetcodeClientTransport::new(current_time, authentication, client_socket).unwrap();
+
+        Self {
+            server: RenetServer::new(ConnectionConfig::default()),
+            client: RenetClient::new(ConnectionConfig::default()),
+            server_transport,
+            client_transport,
+        }
+    }
+}
+
+impl Transport for NetcodeTransport {
+    fn server(&mut self) -> &mut RenetServer {
+        &mut self.server
+    }
+
+    fn client(&mut self) -> &mut RenetClient {
+        &mut self.client
+    }
+
+    fn step(&mut self) {
+        self.server.update(DELTA);
+        self.server_transport.update(DELTA, &mut self.server).unwrap();
+        self.server_transport.send_packets(&mut self.server);
+
+        self.client.update(DELTA);
+        // Returns the disconnect reason as an error once the client is disconnected
+        if self.client_transport.update(DELTA, &mut self.client).is_ok() {
+            self.client_transport.send_packets(&mut self.client).unwrap();
+        }
+
+        // Give time for the packets to arrive in the loopback sockets
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+// Steps until the condition is true, collecting the server events
+fn step_until<T: Transport>(transport: &mut T, events: &mut Vec<ServerEvent>, mut condition: impl FnMut(&mut T) -> bool) {
+    for _ in 0..MAX_STEPS {
+        transport.step();
+        while let Some(event) = transport.server().get_event() {
+            events.push(event);
+        }
+        if condition(transport) {
+            return;
+        }
+    }
+    panic!("condition not reached after {MAX_STEPS} steps");
+}
+
+fn run_scenario<T: Transport>(mut transport: T) -> Vec<ServerEvent> {
+    let mut events = vec![];
+
+    step_until(&mut transport, &mut events, |t| {
+        t.client().is_connected() && t.server().is_connected(CLIENT_ID)
+    });
+
+    transport.client().send_message(DefaultChannel::ReliableOrdered, "ping");
+    step_until(&mut transport, &mut events, |t| {
+        t.server().receive_message(CLIENT_ID, DefaultChannel::ReliableOrdered).is_some()
+    });
+
+    transport.server().send_message(CLIENT_ID, DefaultChannel::ReliableOrdered, "pong");
+    step_until(&mut transport, &mut events, |t| {
+        t.client().receive_message(DefaultChannel::ReliableOrdered).is_some()
+    });
+
+    transport.client().disconnect();
+    step_until(&mut transport, &mut events, |t| t.server().connected_clients() == 0);
+
+    events
+}
+
+#[test]
+fn test_transport_matrix() {
+    let expected = vec![
+        ServerEvent::ClientConnected { client_id: CLIENT_ID },
+        ServerEvent::ClientDisconnected {
+            client_id: CLIENT_ID,
+            reason: DisconnectReason::DisconnectedByClient,
+        },
+    ];
+
+    assert_eq!(run_scenario(LocalTransport::new()), expected, "local transport");
+    assert_eq!(run_scenario(NetcodeTransport::new()), expected, "netcode transport");
+}
@@ -0,0 +1,80 @@
+use std::collections::{BTreeSet, VecDeque};
+
+use bytes::Bytes;
+use renet::RenetClient;
+
+use crate::{deserialize_events, NetworkEntity, ReplicationEvent};
+
+/// Client side of the replication, receives the changes of the replicated entities from the server.
+#[derive(Debug)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
+pub struct ReplicationClient {
+    channel_id: u8,
+    entities: BTreeSet<NetworkEntity>,
+    events: VecDeque<ReplicationEvent>,
+}
+
+impl ReplicationClient {
+    /// Creates the replication client, the messages are received from the channel, that must be reliable and ordered.
+    pub fn new<I: Into<u8>>(channel_id: I) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            entities: BTreeSet::new(),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Receives the replication messages from the server, the changes are available with [`ReplicationClient::get_event`].
+    pub fn receive(&mut self, client: &mut RenetClient) {
+        while let Some(message) = client.receive_message(self.channel_id) {
+            self.process_message(message);
+        }
+    }
+
+    /// Processes a replication message received from the server.
+    pub fn process_message(&mut self, message: Bytes) {
+        let events = match deserialize_events(&message) {
+            Ok(events) => events,
+            Err(_) => {
+                log::error!("Failed to deserialize replication message with {} bytes", message.len());
+                return;
+            }
+        };
+
+        for event in events {
+            match event {
+                ReplicationEvent::Spawn(entity) => {
+                    self.entities.insert(entity);
+                }
+                ReplicationEvent::Despawn(entity) => {
+                    self.entities.remove(&entity);
+                }
+                ReplicationEvent::Insert { .. } | ReplicationEvent::Remove { .. } => {}
+            }
+            self.events.push_back(event);
+        }
+    }
+
+    /// Returns the next change received from the server.
+    pub fn get_event(&mut self) -> Option<ReplicationEvent> {
+        self.events.pop_front()
+    }
+
+    /// Returns whether the entity is replicated to this client.
+    pub fn contains(&self, entity: NetworkEntity) -> bool {
+        self.entities.contains(&entity)
+    }
+
+    /// Returns the entities replicated to this client.
+    pub fn entities(&self) -> impl Iterator<Item = NetworkEntity> + '_ {
+        self.entities.iter().copied()
+    }
+
+    /// Despawns all the replicated entities, should be called when disconnected from the server.
+    /// The despawn events are available with [`ReplicationClient::get_event`].
+    pub fn clear(&mut self) {
+        for entity in std::mem::take(&mut self.entities) {
+            self.events.push_back(ReplicationEvent::Despawn(entity));
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Entity replication on top of renet channels.
+//!
+//! The server keeps the serialized components of the replicated entities, and sends to each client
+//! only the spawns, despawns and components that changed since the last update. Entities can be hidden
+//! from some clients with an interest filter. The client receives the changes as [`ReplicationEvent`]s,
+//! that can be applied to any ECS or game state.
+//!
+//! The replication messages are sent in a single channel, that must be reliable and ordered.
+//!
+//! # Usage
+//! ```
+//! # use renet::{ConnectionConfig, DefaultChannel, RenetClient, RenetServer};
+//! # use renet_replication::{ReplicationClient, ReplicationEvent, ReplicationServer};
+//! # let mut server = RenetServer::new(ConnectionConfig::default());
+//! # server.add_connection(0);
+//! # let mut client = RenetClient::new(ConnectionConfig::default());
+//! # client.set_connected();
+//! const POSITION: u16 = 0;
+//!
+//! let mut replication_server = ReplicationServer::new(DefaultChannel::ReliableOrdered);
+//! replication_server.insert(7, POSITION, vec![1, 2, 3]);
+//! replication_server.send(&mut server);
+//! # for packet in server.get_packets_to_send(0).unwrap() {
+//! #     client.process_packet(&packet);
+//! # }
+//!
+//! let mut replication_client = ReplicationClient::new(DefaultChannel::ReliableOrdered);
+//! replication_client.receive(&mut client);
+//! while let Some(event) = replication_client.get_event() {
+//!     match event {
+//!         ReplicationEvent::Spawn(entity) => { /* Spawn a new entity */ }
+//!         ReplicationEvent::Despawn(entity) => { /* Despawn the entity */ }
+//!         ReplicationEvent::Insert { entity, component, data } => { /* Deserialize and insert or update the component */ }
+//!         ReplicationEvent::Remove { entity, component } => { /* Remove the component */ }
+//!     }
+//! }
+//! ```
+mod client;
+mod server;
+
+pub use client::ReplicationClient;
+pub use server::{InterestFilterFn, ReplicationServer};
+
+use bytes::Bytes;
+
+/// Identifier of a replicated entity, it's the same on the server and in the clients.
+pub type NetworkEntity = u64;
+/// Identifier of a replicated component type, it's the same on the server and in the clients.
+pub type ComponentKind = u16;
+
+const SPAWN: u8 = 0;
+const DESPAWN: u8 = 1;
+const INSERT: u8 = 2;
+const REMOVE: u8 = 3;
+
+/// Change in the replicated entities, received from the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplicationEvent {
+    /// The entity is now replicated, followed by the insertion of its components.
+    Spawn(NetworkEntity),
+    /// The entity is not replicated anymore, despawned in the server or hidden from this client.
+    Despawn(NetworkEntity),
+    /// A component was added or changed, with its serialized data.
+    Insert {
+        entity: NetworkEntity,
+        component: ComponentKind,
+        data: Bytes,
+    },
+    /// A component was removed from the entity.
+    Remove { entity: NetworkEntity, component: ComponentKind },
+}
+
+impl ReplicationEvent {
+    fn serialized_size(&self) -> usize {
+        match self {
+            ReplicationEvent::Spawn(_) | ReplicationEvent::Despawn(_) => 1 + 8,
+            ReplicationEvent::Insert { data, .. } => 1 + 8 + 2 + octets::varint_len(data.len() as u64) + data.len(),
+            ReplicationEvent::Remove { .. } => 1 + 8 + 2,
+        }
+    }
+
+    fn write(&self, b: &mut octets::OctetsMut) -> Result<(), octets::BufferTooShortError> {
+        match self {
+            ReplicationEvent::Spawn(entity) => {
+                b.put_u8(SPAWN)?;
+                b.put_u64(*entity)?;
+            }
+            ReplicationEvent::Despawn(entity) => {
+                b.put_u8(DESPAWN)?;
+                b.put_u64(*entity)?;
+            }
+            ReplicationEvent::Insert { entity, component, data } => {
+                b.put_u8(INSERT)?;
+                b.put_u64(*entity)?;
+                b.put_u16(*component)?;
+                b.put_varint(data.len() as u64)?;
+                b.put_bytes(data)?;
+            }
+            ReplicationEvent::Remove { entity, component } => {
+                b.put_u8(REMOVE)?;
+                b.put_u64(*entity)?;
+                b.put_u16(*component)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read(message: &Bytes, b: &mut octets::Octets) -> Result<Self, octets::BufferTooShortError> {
+        let event_type = b.get_u8()?;
+        let entity = b.get_u64()?;
+        let event = match event_type {
+            SPAWN => ReplicationEvent::Spawn(entity),
+            DESPAWN => ReplicationEvent::Despawn(entity),
+            INSERT => {
+                let component = b.get_u16()?;
+                let len = b.get_varint()? as usize;
+                let start = b.off();
+                b.skip(len)?;
+                ReplicationEvent::Insert {
+                    entity,
+                    component,
+                    data: message.slice(start..start + len),
+                }
+            }
+            REMOVE => {
+                let component = b.get_u16()?;
+                ReplicationEvent::Remove { entity, component }
+            }
+            // Invalid events are treated as a malformed message
+            _ => return Err(octets::BufferTooShortError),
+        };
+
+        Ok(event)
+    }
+}
+
+fn serialize_events(events: &[ReplicationEvent]) -> Vec<u8> {
+    let size = events.iter().map(ReplicationEvent::serialized_size).sum();
+    let mut message = vec![0; size];
+    let mut b = octets::OctetsMut::with_slice(&mut message);
+    for event in events {
+        event.write(&mut b).expect("message has the serialized size of the events");
+    }
+
+    message
+}
+
+fn deserialize_events(message: &Bytes) -> Result<Vec<ReplicationEvent>, octets::BufferTooShortError> {
+    let mut b = octets::Octets::with_slice(message);
+    let mut events = vec![];
+    while b.cap() > 0 {
+        events.push(ReplicationEvent::read(message, &mut b)?);
+    }
+
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serialize_events_roundtrip() {
+        let events = vec![
+            ReplicationEvent::Spawn(u64::MAX),
+            ReplicationEvent::Insert {
+                entity: u64::MAX,
+                component: 3,
+                data: Bytes::from(vec![7; 300]),
+            },
+            ReplicationEvent::Remove { entity: 1, component: 2 },
+            ReplicationEvent::Despawn(1),
+        ];
+
+        let message = Bytes::from(serialize_events(&events));
+        assert_eq!(deserialize_events(&message).unwrap(), events);
+
+        let truncated = message.slice(..message.len() - 1);
+        assert!(deserialize_events(&truncated).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+
+use bytes::Bytes;
+use renet::{ClientId, RenetServer};
+
+use crate::{serialize_events, ComponentKind, NetworkEntity, ReplicationEvent};
+
+/// Callback that decides if an entity is replicated to a client, called with the client id and the entity.
+pub type InterestFilterFn = dyn Fn(ClientId, NetworkEntity) -> bool + Send + Sync;
+
+struct InterestFilter(Box<InterestFilterFn>);
+
+impl std::fmt::Debug for InterestFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("InterestFilter")
+    }
+}
+
+#[derive(Debug)]
+struct ReplicatedComponent {
+    // Tick of the last change, the component is sent to the clients that received an older version
+    changed_tick: u64,
+    data: Bytes,
+}
+
+// Entities and components already sent to a client, with the tick they were sent
+#[derive(Debug, Default)]
+struct ClientReplication {
+    entities: BTreeMap<NetworkEntity, BTreeMap<ComponentKind, u64>>,
+}
+
+/// Server side of the replication, keeps the replicated entities and sends their changes to the clients.
+///
+/// The components are stored serialized, inserting a component with the same data as before is not considered a change,
+/// so the components can be inserted every update without sending them again.
+#[derive(Debug)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
+pub struct ReplicationServer {
+    channel_id: u8,
+    tick: u64,
+    entities: BTreeMap<NetworkEntity, BTreeMap<ComponentKind, ReplicatedComponent>>,
+    clients: BTreeMap<ClientId, ClientReplication>,
+    interest_filter: Option<InterestFilter>,
+}
+
+impl ReplicationServer {
+    /// Creates the replication server, the messages are sent in the channel, that must be reliable and ordered.
+    pub fn new<I: Into<u8>>(channel_id: I) -> Self {
+        Self {
+            channel_id: channel_id.into(),
+            tick: 0,
+            entities: BTreeMap::new(),
+            clients: BTreeMap::new(),
+            interest_filter: None,
+        }
+    }
+
+    /// Starts replicating the entity, does nothing if it's already replicated.
+    pub fn spawn(&mut self, entity: NetworkEntity) {
+        self.entities.entry(entity).or_default();
+    }
+
+    /// Stops replicating the entity, the clients receive a despawn.
+    pub fn despawn(&mut self, entity: NetworkEntity) {
+        self.entities.remove(&entity);
+    }
+
+    /// Returns whether the entity is replicated.
+    pub fn contains(&self, entity: NetworkEntity) -> bool {
+        self.entities.contains_key(&entity)
+    }
+
+    /// Returns the number of replicated entities.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Inserts or updates the serialized component of the entity, spawning the entity if it's not replicated.
+    pub fn insert<B: Into<Bytes>>(&mut self, entity: NetworkEntity, component: ComponentKind, data: B) {
+        let data = data.into();
+        let components = self.entities.entry(entity).or_default();
+        if components.get(&component).is_some_and(|current| current.data == data) {
+            return;
+        }
+
+        components.insert(
+            component,
+            ReplicatedComponent {
+                changed_tick: self.tick,
+                data,
+            },
+        );
+    }
+
+    /// Removes the component of the entity.
+    pub fn remove(&mut self, entity: NetworkEntity, component: ComponentKind) {
+        if let Some(components) = self.entities.get_mut(&entity) {
+            components.remove(&component);
+        }
+    }
+
+    /// Returns the serialized component of the entity.
+    pub fn get(&self, entity: NetworkEntity, component: ComponentKind) -> Option<&Bytes> {
+        Some(&self.entities.get(&entity)?.get(&component)?.data)
+    }
+
+    /// Set the interest filter, only the entities accepted by the filter are replicated to each client.
+    /// When an entity stops being accepted, the client receives a despawn, and it's spawned again when accepted.
+    pub fn set_interest_filter<F>(&mut self, filter: F)
+    where
+        F: Fn(ClientId, NetworkEntity) -> bool + Send + Sync + 'static,
+    {
+        self.interest_filter = Some(InterestFilter(Box::new(filter)));
+    }
+
+    /// Remove the interest filter, all entities are replicated to all clients.
+    pub fn clear_interest_filter(&mut self) {
+        self.interest_filter = None;
+    }
+
+    /// Forgets what was replicated to the client, should be called when the client disconnects.
+    /// If the same client id connects again, all entities are sent again.
+    pub fn remove_client(&mut self, client_id: ClientId) {
+        self.clients.remove(&client_id);
+    }
+
+    /// Sends the changes since the last call to each connected client, in a single message per client.
+    pub fn send(&mut self, server: &mut RenetServer) {
+        let clients_id = server.clients_id();
+        self.clients.retain(|client_id, _| clients_id.contains(client_id));
+
+        for client_id in clients_id {
+            let client = self.clients.entry(client_id).or_default();
+            let events = client_events(self.tick, &self.entities, client, client_id, self.interest_filter.as_ref());
+            if !events.is_empty() {
+                server.send_message(client_id, self.channel_id, serialize_events(&events));
+            }
+        }
+
+        self.tick += 1;
+    }
+}
+
+fn client_events(
+    tick: u64,
+    entities: &BTreeMap<NetworkEntity, BTreeMap<ComponentKind, ReplicatedComponent>>,
+    client: &mut ClientReplication,
+    client_id: ClientId,
+    interest_filter: Option<&InterestFilter>,
+) -> Vec<ReplicationEvent> {
+    let is_visible = |entity: NetworkEntity| interest_filter.is_none_or(|filter| (filter.0)(client_id, entity));
+    let mut events = vec![];
+
+    client.entities.retain(|&entity, _| {
+        let keep = entities.contains_key(&entity) && is_visible(entity);
+        if !keep {
+            events.push(ReplicationEvent::Despawn(entity));
+        }
+        keep
+    });
+
+    for (&entity, components) in entities {
+        if !client.entities.contains_key(&entity) {
+            if !is_visible(entity) {
+                continue;
+            }
+            events.push(ReplicationEvent::Spawn(entity));
+        }
+
+        let client_components = client.entities.entry(entity).or_default();
+        client_components.retain(|&component, _| {
+            let keep = components.contains_key(&component);
+            if !keep {
+                events.push(ReplicationEvent::Remove { entity, component });
+            }
+            keep
+        });
+
+        for (&component, replicated) in components {
+            let sent_tick = client_components.get(&component);
+            if sent_tick.is_some_and(|&sent_tick| sent_tick >= replicated.changed_tick) {
+                continue;
+            }
+
+            client_components.insert(component, tick);
+            events.push(ReplicationEvent::Insert {
+                entity,
+                component,
+                data: replicated.data.clone(),
+            });
+        }
+    }
+
+    events
+}
@@ -0,0 +1,167 @@
+use bytes::Bytes;
+use renet::{ClientId, ConnectionConfig, DefaultChannel, RenetClient, RenetServer};
+use renet_replication::{ReplicationClient, ReplicationEvent, ReplicationServer};
+
+const POSITION: u16 = 0;
+const HEALTH: u16 = 1;
+
+struct TestClient {
+    client: RenetClient,
+    replication: ReplicationClient,
+}
+
+impl TestClient {
+    fn new(server: &mut RenetServer, client_id: ClientId) -> Self {
+        server.add_connection(client_id);
+        let mut client = RenetClient::new(ConnectionConfig::default());
+        client.set_connected();
+        Self {
+            client,
+            replication: ReplicationClient::new(DefaultChannel::ReliableOrdered),
+        }
+    }
+
+    fn receive(&mut self, server: &mut RenetServer, client_id: ClientId) -> Vec<ReplicationEvent> {
+        for packet in server.get_packets_to_send(client_id).unwrap() {
+            self.client.process_packet(&packet);
+        }
+        self.replication.receive(&mut self.client);
+
+        let mut events = vec![];
+        while let Some(event) = self.replication.get_event() {
+            events.push(event);
+        }
+        events
+    }
+}
+
+fn insert(entity: u64, component: u16, data: &'static [u8]) -> ReplicationEvent {
+    ReplicationEvent::Insert {
+        entity,
+        component,
+        data: Bytes::from_static(data),
+    }
+}
+
+#[test]
+fn replicate_changes() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = TestClient::new(&mut server, 0);
+    let mut replication = ReplicationServer::new(DefaultChannel::ReliableOrdered);
+
+    replication.insert(1, POSITION, &b"a"[..]);
+    replication.insert(1, HEALTH, &b"b"[..]);
+    replication.send(&mut server);
+    assert_eq!(
+        client.receive(&mut server, 0),
+        vec![ReplicationEvent::Spawn(1), insert(1, POSITION, b"a"), insert(1, HEALTH, b"b")]
+    );
+    assert!(client.replication.contains(1));
+
+    // Inserting the same data is not a change
+    replication.insert(1, POSITION, &b"a"[..]);
+    replication.insert(1, HEALTH, &b"c"[..]);
+    replication.send(&mut server);
+    assert_eq!(client.receive(&mut server, 0), vec![insert(1, HEALTH, b"c")]);
+
+    replication.send(&mut server);
+    assert!(client.receive(&mut server, 0).is_empty());
+
+    replication.remove(1, HEALTH);
+    replication.send(&mut server);
+    assert_eq!(
+        client.receive(&mut server, 0),
+        vec![ReplicationEvent::Remove {
+            entity: 1,
+            component: HEALTH
+        }]
+    );
+
+    replication.despawn(1);
+    replication.send(&mut server);
+    assert_eq!(client.receive(&mut server, 0), vec![ReplicationEvent::Despawn(1)]);
+    assert!(!client.replication.contains(1));
+}
+
+#[test]
+fn late_client_receives_all_entities() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut replication = ReplicationServer::new(DefaultChannel::ReliableOrdered);
+
+    replication.insert(1, POSITION, &b"a"[..]);
+    replication.send(&mut server);
+    replication.insert(1, POSITION, &b"b"[..]);
+    replication.spawn(2);
+    replication.send(&mut server);
+
+    let mut client = TestClient::new(&mut server, 0);
+    replication.send(&mut server);
+    assert_eq!(
+        client.receive(&mut server, 0),
+        vec![ReplicationEvent::Spawn(1), insert(1, POSITION, b"b"), ReplicationEvent::Spawn(2)]
+    );
+
+    // Reconnecting with the same client id sends everything again
+    server.remove_connection(0);
+    replication.remove_client(0);
+    let mut client = TestClient::new(&mut server, 0);
+    replication.send(&mut server);
+    assert_eq!(
+        client.receive(&mut server, 0),
+        vec![ReplicationEvent::Spawn(1), insert(1, POSITION, b"b"), ReplicationEvent::Spawn(2)]
+    );
+}
+
+#[test]
+fn interest_filter() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client_0 = TestClient::new(&mut server, 0);
+    let mut client_1 = TestClient::new(&mut server, 1);
+    let mut replication = ReplicationServer::new(DefaultChannel::ReliableOrdered);
+
+    // Each client only sees the entity with the same id
+    replication.set_interest_filter(|client_id, entity| client_id == entity);
+    replication.insert(0, POSITION, &b"a"[..]);
+    replication.insert(1, POSITION, &b"b"[..]);
+    replication.send(&mut server);
+    assert_eq!(
+        client_0.receive(&mut server, 0),
+        vec![ReplicationEvent::Spawn(0), insert(0, POSITION, b"a")]
+    );
+    assert_eq!(
+        client_1.receive(&mut server, 1),
+        vec![ReplicationEvent::Spawn(1), insert(1, POSITION, b"b")]
+    );
+
+    // Hidden entities are despawned, and spawned again when visible
+    replication.set_interest_filter(|client_id, entity| client_id == 1 - entity);
+    replication.send(&mut server);
+    assert_eq!(
+        client_0.receive(&mut server, 0),
+        vec![ReplicationEvent::Despawn(0), ReplicationEvent::Spawn(1), insert(1, POSITION, b"b")]
+    );
+
+    replication.clear_interest_filter();
+    replication.send(&mut server);
+    assert_eq!(
+        client_0.receive(&mut server, 0),
+        vec![ReplicationEvent::Spawn(0), insert(0, POSITION, b"a")]
+    );
+}
+
+#[test]
+fn clear_client_entities() {
+    let mut server = RenetServer::new(ConnectionConfig::default());
+    let mut client = TestClient::new(&mut server, 0);
+    let mut replication = ReplicationServer::new(DefaultChannel::ReliableOrdered);
+
+    replication.spawn(1);
+    replication.spawn(2);
+    replication.send(&mut server);
+    client.receive(&mut server, 0);
+
+    client.replication.clear();
+    assert_eq!(client.replication.entities().count(), 0);
+    assert_eq!(client.replication.get_event(), Some(ReplicationEvent::Despawn(1)));
+    assert_eq!(client.replication.get_event(), Some(ReplicationEvent::Despawn(2)));
+}
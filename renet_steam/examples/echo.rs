@@ -98,6 +98,10 @@ fn run_server(steam_client: Client<ClientManager>, single: SingleClient, with_lo
                 ServerEvent::ClientDisconnected { client_id, reason } => {
                     println!("Client {} disconnected: {}", client_id, reason);
                 }
+                ServerEvent::ConnectionDenied { addr, reason } => {
+                    println!("Connection from {} denied: {}", addr, reason);
+                }
+                _ => {}
             }
         }
 
@@ -0,0 +1,92 @@
+use std::collections::VecDeque;
+
+/// Limits of the packets processed for a connection in each update.
+///
+/// Packets received above the budget are carried over to the next update, up to `max_carry_over_packets`,
+/// the remaining ones are dropped, so a peer sending too many packets can't delay the processing of the other peers.
+/// At least one packet is processed per update, even if it's bigger than `max_bytes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SteamReceiveBudget {
+    /// Maximum number of packets processed per update.
+    pub max_packets: usize,
+    /// Maximum number of bytes processed per update.
+    pub max_bytes: usize,
+    /// Maximum number of packets kept for the next update when the budget is exhausted.
+    pub max_carry_over_packets: usize,
+}
+
+impl Default for SteamReceiveBudget {
+    fn default() -> Self {
+        Self {
+            max_packets: usize::MAX,
+            max_bytes: usize::MAX,
+            max_carry_over_packets: 0,
+        }
+    }
+}
+
+/// Statistics of the packets received from a connection.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SteamReceiveStats {
+    /// Number of packets processed.
+    pub packets_received: u64,
+    /// Number of packets dropped because the receive budget was exhausted and the carry-over was full.
+    pub packets_dropped: u64,
+    /// Number of updates where the receive budget was exhausted.
+    pub budget_exhausted: u64,
+}
+
+// Packets carried over to the next update
+#[derive(Debug, Default)]
+pub(crate) struct ReceiveQueue {
+    carry_over: VecDeque<Vec<u8>>,
+}
+
+impl ReceiveQueue {
+    /// Processes the carried over packets and then the new ones, until the budget is exhausted.
+    pub fn process<'a>(
+        &mut self,
+        received: impl IntoIterator<Item = &'a [u8]>,
+        budget: &SteamReceiveBudget,
+        stats: &mut SteamReceiveStats,
+        mut process_packet: impl FnMut(&[u8]),
+    ) {
+        let mut packets: usize = 0;
+        let mut bytes: usize = 0;
+        let within_budget = |packets: usize, bytes: usize, len: usize| {
+            packets == 0 || (packets < budget.max_packets && bytes.saturating_add(len) <= budget.max_bytes)
+        };
+
+        while let Some(packet) = self.carry_over.front() {
+            if !within_budget(packets, bytes, packet.len()) {
+                break;
+            }
+            let packet = self.carry_over.pop_front().unwrap();
+            packets += 1;
+            bytes += packet.len();
+            process_packet(&packet);
+        }
+
+        let mut exhausted = !self.carry_over.is_empty();
+        for packet in received {
+            if !exhausted && within_budget(packets, bytes, packet.len()) {
+                packets += 1;
+                bytes += packet.len();
+                process_packet(packet);
+                continue;
+            }
+
+            exhausted = true;
+            if self.carry_over.len() < budget.max_carry_over_packets {
+                self.carry_over.push_back(packet.to_vec());
+            } else {
+                stats.packets_dropped += 1;
+            }
+        }
+
+        stats.packets_received += packets as u64;
+        if exhausted {
+            stats.budget_exhausted += 1;
+        }
+    }
+}
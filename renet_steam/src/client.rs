@@ -1,4 +1,8 @@
+use std::net::SocketAddr;
+
 use super::MAX_MESSAGE_BATCH_SIZE;
+use crate::budget::{ReceiveQueue, SteamReceiveBudget, SteamReceiveStats};
+use crate::runner::SteamCallbackHandle;
 use renet::RenetClient;
 use steamworks::{
     networking_sockets::{InvalidHandle, NetConnection, NetworkingSockets},
@@ -15,20 +19,65 @@ enum ConnectionState {
 pub struct SteamClientTransport {
     networking_sockets: NetworkingSockets<ClientManager>,
     state: ConnectionState,
+    receive_queue: ReceiveQueue,
+    receive_stats: SteamReceiveStats,
+    receive_budget: SteamReceiveBudget,
+    callback_handle: Option<SteamCallbackHandle>,
 }
 
 impl SteamClientTransport {
     pub fn new(client: &steamworks::Client<ClientManager>, steam_id: &SteamId) -> Result<Self, InvalidHandle> {
-        let networking_sockets = client.networking_sockets();
+        Self::new_with_identity(client, NetworkingIdentity::new_steam_id(*steam_id))
+    }
+
+    /// Connects to a server with any networking identity, like a generic string identity.
+    /// The server sees this client with the id from [`client_id_from_identity`](crate::client_id_from_identity).
+    pub fn new_with_identity(client: &steamworks::Client<ClientManager>, identity: NetworkingIdentity) -> Result<Self, InvalidHandle> {
+        let options = Vec::new();
+        let connection = client.networking_sockets().connect_p2p(identity, 0, options)?;
+        Ok(Self::from_connection(client, connection))
+    }
 
+    /// Connects to a server by its IP address, like a dedicated server registered
+    /// in the Steam Datagram Relay with a fake IP.
+    pub fn new_with_ip(client: &steamworks::Client<ClientManager>, addr: SocketAddr) -> Result<Self, InvalidHandle> {
         let options = Vec::new();
-        let connection = client
-            .networking_sockets()
-            .connect_p2p(NetworkingIdentity::new_steam_id(*steam_id), 0, options)?;
-        Ok(Self {
+        let connection = client.networking_sockets().connect_by_ip_address(addr, options)?;
+        Ok(Self::from_connection(client, connection))
+    }
+
+    fn from_connection(client: &steamworks::Client<ClientManager>, connection: NetConnection<ClientManager>) -> Self {
+        let networking_sockets = client.networking_sockets();
+        Self {
             networking_sockets,
             state: ConnectionState::Connected { connection },
-        })
+            receive_queue: ReceiveQueue::default(),
+            receive_stats: SteamReceiveStats::default(),
+            receive_budget: SteamReceiveBudget::default(),
+            callback_handle: None,
+        }
+    }
+
+    /// Returns the limits of the packets processed per update.
+    pub fn receive_budget(&self) -> SteamReceiveBudget {
+        self.receive_budget
+    }
+
+    /// Update the limits of the packets processed per update.
+    /// Default: no limits.
+    pub fn set_receive_budget(&mut self, receive_budget: SteamReceiveBudget) {
+        self.receive_budget = receive_budget;
+    }
+
+    /// Sets the handle of the [`SteamTransportRunner`](crate::SteamTransportRunner), so the transport
+    /// warns when the steam callbacks are not being run and connection events are missed.
+    pub fn set_callback_handle(&mut self, callback_handle: SteamCallbackHandle) {
+        self.callback_handle = Some(callback_handle);
+    }
+
+    /// Returns the statistics of the packets received from the server.
+    pub fn receive_stats(&self) -> SteamReceiveStats {
+        self.receive_stats
     }
 
     fn is_connected(&self) -> bool {
@@ -100,7 +149,12 @@ impl SteamClientTransport {
         }
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn update(&mut self, client: &mut RenetClient) {
+        if let Some(callback_handle) = self.callback_handle.as_mut() {
+            callback_handle.check();
+        }
+
         if self.is_disconnected() {
             // Mark the client as disconnected if an error occured in the transport layer
             client.disconnect_due_to_transport();
@@ -129,13 +183,16 @@ impl SteamClientTransport {
             unreachable!()
         };
 
-        if let Ok(messages) = connection.receive_messages(MAX_MESSAGE_BATCH_SIZE) {
-            messages.iter().for_each(|message| {
-                client.process_packet(message.data());
-            });
-        }
+        let messages = connection.receive_messages(MAX_MESSAGE_BATCH_SIZE).unwrap_or_default();
+        self.receive_queue.process(
+            messages.iter().map(|message| message.data()),
+            &self.receive_budget,
+            &mut self.receive_stats,
+            |packet| client.process_packet(packet),
+        );
     }
 
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn send_packets(&mut self, client: &mut RenetClient) -> Result<(), SteamError> {
         if self.is_disconnected() {
             return Err(SteamError::NoConnection);
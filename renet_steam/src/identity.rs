@@ -0,0 +1,26 @@
+use renet::ClientId;
+use steamworks::networking_types::NetworkingIdentity;
+
+/// Returns the [`ClientId`] used by the server for a peer with the given identity.
+///
+/// Peers with a steam id use the raw steam id, like before. Other identities, like IP addresses
+/// of dedicated servers using fake IPs in the Steam Datagram Relay, or generic strings,
+/// use a stable hash of their string representation (`ip:1.2.3.4:27015`, `str:my-server`),
+/// so the same identity always has the same client id.
+/// The mapping back to the identity is available with [`SteamServerTransport::client_identity`](crate::SteamServerTransport::client_identity).
+pub fn client_id_from_identity(identity: &NetworkingIdentity) -> ClientId {
+    match identity.steam_id() {
+        Some(steam_id) => steam_id.raw(),
+        None => fnv1a(identity.debug_string().as_bytes()),
+    }
+}
+
+// FNV-1a, stable between runs and platforms unlike the std hasher
+fn fnv1a(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+
+    bytes
+        .iter()
+        .fold(OFFSET_BASIS, |hash, &byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+}
@@ -1,10 +1,24 @@
 const MAX_MESSAGE_BATCH_SIZE: usize = 512;
 
+// The `tracing` feature emits the log events with tracing
+#[cfg(feature = "tracing")]
+extern crate tracing as log;
+
+mod budget;
 mod client;
+mod identity;
+mod mesh;
+mod ping;
+mod runner;
 mod server;
 
+pub use budget::{SteamReceiveBudget, SteamReceiveStats};
 pub use client::SteamClientTransport;
-pub use server::{AccessPermission, SteamServerConfig, SteamServerTransport};
+pub use identity::client_id_from_identity;
+pub use mesh::{SteamMeshTransport, MESH_MESSAGES_CHANNEL};
+pub use ping::{PingLocation, SteamPingEstimator, PING_LOCATION_LOBBY_KEY};
+pub use runner::{SteamCallbackHandle, SteamTransportRunner, WrongCallbackThread};
+pub use server::{AccessPermission, SteamSendStats, SteamServerConfig, SteamServerTransport};
 
 #[doc(hidden)]
 pub use steamworks;
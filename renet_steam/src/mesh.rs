@@ -0,0 +1,201 @@
+use std::{
+    collections::HashSet,
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+};
+
+use renet::{ClientId, RenetServer};
+use steamworks::{
+    networking_messages::NetworkingMessages,
+    networking_types::{NetworkingIdentity, SendFlags},
+    CallbackHandle, ChatMemberStateChange, Client, ClientManager, LobbyChatUpdate, LobbyId, Manager, SteamId,
+};
+
+use super::MAX_MESSAGE_BATCH_SIZE;
+use crate::runner::SteamCallbackHandle;
+
+/// Steam messages channel used by the mesh packets.
+pub const MESH_MESSAGES_CHANNEL: u32 = 0;
+
+/// Maintains a full mesh of renet connections between the members of a steam lobby, for small co-op games without a dedicated host.
+///
+/// Each peer hosts a [`RenetServer`] with a connection for every other lobby member, using their steam id as client id.
+/// Connections are added and removed when members join or leave the lobby, generating the usual [`renet::ServerEvent`],
+/// so messages are sent and received with the [`RenetServer`] methods.
+///
+/// The packets are sent with the steam networking messages, the relay network access must be initialized
+/// with `networking_utils().init_relay_network_access()`.
+/// Since every peer sends its packets to every other peer, this is meant for games with 2 to 4 players.
+pub struct SteamMeshTransport<Manager = ClientManager> {
+    networking_messages: NetworkingMessages<Manager>,
+    lobby: LobbyId,
+    local_steam_id: SteamId,
+    peers: HashSet<SteamId>,
+    // Members of the lobby, shared with the session request callback
+    members: Arc<Mutex<HashSet<SteamId>>>,
+    lobby_events: Receiver<(SteamId, bool)>,
+    _lobby_callback: CallbackHandle<Manager>,
+    message_batch_size: usize,
+    callback_handle: Option<SteamCallbackHandle>,
+}
+
+impl<T: Manager + 'static> SteamMeshTransport<T> {
+    /// Creates the mesh for a lobby the local user has already joined,
+    /// connections are added to the server for the current lobby members in the next update.
+    pub fn new(client: &Client<T>, lobby: LobbyId) -> Self {
+        let networking_messages = client.networking_messages();
+        let local_steam_id = client.user().steam_id();
+
+        let members: HashSet<SteamId> = client.matchmaking().lobby_members(lobby).into_iter().collect();
+        let members = Arc::new(Mutex::new(members));
+
+        // Only accept sessions from lobby members
+        let session_members = members.clone();
+        networking_messages.session_request_callback(move |request| {
+            let is_member = match request.remote().steam_id() {
+                Some(steam_id) => session_members.lock().unwrap().contains(&steam_id),
+                None => false,
+            };
+            if is_member {
+                request.accept();
+            } else {
+                request.reject();
+            }
+        });
+
+        let (sender, lobby_events) = mpsc::channel();
+        let lobby_callback = client.register_callback(move |update: LobbyChatUpdate| {
+            if update.lobby != lobby {
+                return;
+            }
+
+            let joined = matches!(update.member_state_change, ChatMemberStateChange::Entered);
+            let _ = sender.send((update.user_changed, joined));
+        });
+
+        Self {
+            networking_messages,
+            lobby,
+            local_steam_id,
+            peers: HashSet::new(),
+            members,
+            lobby_events,
+            _lobby_callback: lobby_callback,
+            message_batch_size: MAX_MESSAGE_BATCH_SIZE,
+            callback_handle: None,
+        }
+    }
+
+    /// Returns the lobby of the mesh.
+    pub fn lobby(&self) -> LobbyId {
+        self.lobby
+    }
+
+    /// Returns the steam ids of the connected peers.
+    pub fn peers(&self) -> impl Iterator<Item = SteamId> + '_ {
+        self.peers.iter().copied()
+    }
+
+    /// Update the maximum number of messages received from a peer in each update.
+    pub fn set_message_batch_size(&mut self, message_batch_size: usize) {
+        self.message_batch_size = message_batch_size.max(1);
+    }
+
+    /// Sets the handle of the [`SteamTransportRunner`](crate::SteamTransportRunner), so the transport
+    /// warns when the steam callbacks are not being run and connection events are missed.
+    pub fn set_callback_handle(&mut self, callback_handle: SteamCallbackHandle) {
+        self.callback_handle = Some(callback_handle);
+    }
+
+    /// Removes the connections of all peers, should be called before leaving the lobby.
+    pub fn disconnect_all(&mut self, server: &mut RenetServer) {
+        for peer in self.peers.drain() {
+            server.remove_connection(peer.raw());
+        }
+        self.members.lock().unwrap().clear();
+    }
+
+    /// Update the mesh membership from the lobby events, and receive packets from the peers.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn update(&mut self, server: &mut RenetServer) {
+        if let Some(callback_handle) = self.callback_handle.as_mut() {
+            callback_handle.check();
+        }
+
+        {
+            let mut members = self.members.lock().unwrap();
+
+            // Connections disconnected by renet are only added again when the peer rejoins the lobby
+            let server_clients: HashSet<ClientId> = server.clients_id_iter().collect();
+            self.peers.retain(|steam_id| {
+                let connected = server_clients.contains(&steam_id.raw());
+                if !connected {
+                    members.remove(steam_id);
+                }
+                connected
+            });
+
+            while let Ok((steam_id, joined)) = self.lobby_events.try_recv() {
+                if joined {
+                    members.insert(steam_id);
+                } else {
+                    members.remove(&steam_id);
+                }
+            }
+
+            let left: Vec<SteamId> = self.peers.difference(&members).copied().collect();
+            for steam_id in left {
+                log::debug!("Peer {} left the lobby", steam_id.raw());
+                self.peers.remove(&steam_id);
+                server.remove_connection(steam_id.raw());
+            }
+
+            for steam_id in members.iter() {
+                if *steam_id != self.local_steam_id && self.peers.insert(*steam_id) {
+                    log::debug!("Peer {} joined the lobby", steam_id.raw());
+                    server.add_connection(steam_id.raw());
+                }
+            }
+        }
+
+        let max_messages = self.message_batch_size.saturating_mul(self.peers.len().max(1));
+        let messages = self
+            .networking_messages
+            .receive_messages_on_channel(MESH_MESSAGES_CHANNEL, max_messages);
+        for message in messages.iter() {
+            let Some(steam_id) = message.identity_peer().steam_id() else {
+                continue;
+            };
+            if !self.peers.contains(&steam_id) {
+                continue;
+            }
+
+            if let Err(e) = server.process_packet_from(message.data(), steam_id.raw()) {
+                log::error!("Error while processing payload for {}: {}", steam_id.raw(), e);
+            }
+        }
+    }
+
+    /// Send packets to the peers.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
+    pub fn send_packets(&mut self, server: &mut RenetServer) {
+        for steam_id in self.peers.iter() {
+            let Ok(packets) = server.get_packets_to_send(steam_id.raw()) else {
+                continue;
+            };
+
+            for packet in packets {
+                let identity = NetworkingIdentity::new_steam_id(*steam_id);
+                if let Err(e) =
+                    self.networking_messages
+                        .send_message_to_user(identity, SendFlags::UNRELIABLE, &packet, MESH_MESSAGES_CHANNEL)
+                {
+                    log::error!("Failed to send packet to peer {}: {e}", steam_id.raw());
+                    break;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    ffi::{c_char, CStr, CString},
+    time::Duration,
+};
+
+use steamworks::{Client, ClientManager, LobbyId, Manager, SteamId};
+use steamworks_sys as sys;
+
+/// Lobby member data key used to share the ping location with the other lobby members.
+pub const PING_LOCATION_LOBBY_KEY: &str = "renet_ping_location";
+
+// k_cchMaxSteamNetworkingPingLocationString
+const MAX_PING_LOCATION_STRING_LEN: usize = 1024;
+
+/// Location of a host in the Steam Datagram Relay network.
+/// Can be used to estimate the ping between two hosts without sending any packet.
+pub struct PingLocation(sys::SteamNetworkPingLocation_t);
+
+/// Estimates pings using the Steam Datagram Relay network, so matchmaking code can pick
+/// the best host between lobby members before anyone connects.
+///
+/// The relay network access must be initialized with `networking_utils().init_relay_network_access()`,
+/// and it can take a few seconds until the ping data is available.
+pub struct SteamPingEstimator<Manager = ClientManager> {
+    // Keeps the steam client initialized while the estimator is used
+    _client: Client<Manager>,
+}
+
+impl<T: Manager + 'static> SteamPingEstimator<T> {
+    pub fn new(client: &Client<T>) -> Self {
+        Self { _client: client.clone() }
+    }
+
+    /// Returns the ping location of the local host, or `None` if it is not available yet.
+    pub fn local_ping_location(&self) -> Option<PingLocation> {
+        // SAFETY: the steam client is initialized, since the estimator holds it.
+        unsafe {
+            let mut location: sys::SteamNetworkPingLocation_t = std::mem::zeroed();
+            let age = sys::SteamAPI_ISteamNetworkingUtils_GetLocalPingLocation(networking_utils(), &mut location);
+            if age < 0.0 {
+                return None;
+            }
+
+            Some(PingLocation(location))
+        }
+    }
+
+    /// Estimates the round-trip time from the local host to the given location.
+    pub fn estimate_ping_to_location(&self, location: &PingLocation) -> Option<Duration> {
+        // SAFETY: the steam client is initialized, since the estimator holds it.
+        let ping = unsafe { sys::SteamAPI_ISteamNetworkingUtils_EstimatePingTimeFromLocalHost(networking_utils(), &location.0) };
+        ping_to_duration(ping)
+    }
+
+    /// Estimates the round-trip time between two locations.
+    pub fn estimate_ping_between(&self, location1: &PingLocation, location2: &PingLocation) -> Option<Duration> {
+        // SAFETY: the steam client is initialized, since the estimator holds it.
+        let ping = unsafe {
+            sys::SteamAPI_ISteamNetworkingUtils_EstimatePingTimeBetweenTwoLocations(networking_utils(), &location1.0, &location2.0)
+        };
+        ping_to_duration(ping)
+    }
+
+    /// Converts a ping location to a string, so it can be shared with other users.
+    pub fn location_to_string(&self, location: &PingLocation) -> String {
+        let mut buffer = [0 as c_char; MAX_PING_LOCATION_STRING_LEN];
+        // SAFETY: the buffer is valid for its whole length, and the steam client is initialized.
+        unsafe {
+            sys::SteamAPI_ISteamNetworkingUtils_ConvertPingLocationToString(
+                networking_utils(),
+                &location.0,
+                buffer.as_mut_ptr(),
+                buffer.len() as i32,
+            );
+            CStr::from_ptr(buffer.as_ptr()).to_string_lossy().into_owned()
+        }
+    }
+
+    /// Parses a ping location generated by [`SteamPingEstimator::location_to_string`].
+    pub fn parse_location(&self, location: &str) -> Option<PingLocation> {
+        let location = CString::new(location).ok()?;
+        // SAFETY: the string is null terminated, and the steam client is initialized.
+        unsafe {
+            let mut result: sys::SteamNetworkPingLocation_t = std::mem::zeroed();
+            if !sys::SteamAPI_ISteamNetworkingUtils_ParsePingLocationString(networking_utils(), location.as_ptr(), &mut result) {
+                return None;
+            }
+
+            Some(PingLocation(result))
+        }
+    }
+
+    /// Shares the local ping location with the other lobby members, using the lobby member data.
+    /// Returns false if the local ping location is not available yet.
+    pub fn share_ping_location(&self, lobby: LobbyId) -> bool {
+        let Some(location) = self.local_ping_location() else {
+            return false;
+        };
+
+        let location = CString::new(self.location_to_string(&location)).expect("ping location has no nul bytes");
+        let key = CString::new(PING_LOCATION_LOBBY_KEY).unwrap();
+        // SAFETY: the strings are null terminated, and the steam client is initialized.
+        unsafe {
+            sys::SteamAPI_ISteamMatchmaking_SetLobbyMemberData(matchmaking(), lobby.raw(), key.as_ptr(), location.as_ptr());
+        }
+        true
+    }
+
+    /// Estimates the round-trip time from the local host to a lobby member,
+    /// the member must have shared its location with [`SteamPingEstimator::share_ping_location`].
+    pub fn estimate_ping_to(&self, lobby: LobbyId, steam_id: SteamId) -> Option<Duration> {
+        let key = CString::new(PING_LOCATION_LOBBY_KEY).unwrap();
+        // SAFETY: the key is null terminated, and steam returns an empty string when the member has no data.
+        // The returned string is only valid until the next call, so it's copied before parsing.
+        let location = unsafe {
+            let data = sys::SteamAPI_ISteamMatchmaking_GetLobbyMemberData(matchmaking(), lobby.raw(), steam_id.raw(), key.as_ptr());
+            if data.is_null() {
+                return None;
+            }
+            CStr::from_ptr(data).to_string_lossy().into_owned()
+        };
+        if location.is_empty() {
+            return None;
+        }
+
+        let location = self.parse_location(&location)?;
+        self.estimate_ping_to_location(&location)
+    }
+
+    /// Returns the estimated round-trip time to each data center, the ping goes through the best relay route.
+    pub fn data_center_pings(&self) -> Vec<(u32, Duration)> {
+        // SAFETY: the list has the size passed to steam, and the steam client is initialized.
+        unsafe {
+            let utils = networking_utils();
+            let count = sys::SteamAPI_ISteamNetworkingUtils_GetPOPCount(utils);
+            if count <= 0 {
+                return vec![];
+            }
+
+            let mut pop_ids: Vec<sys::SteamNetworkingPOPID> = vec![0; count as usize];
+            let count = sys::SteamAPI_ISteamNetworkingUtils_GetPOPList(utils, pop_ids.as_mut_ptr(), count);
+            pop_ids.truncate(count.max(0) as usize);
+
+            pop_ids
+                .into_iter()
+                .filter_map(|pop_id| {
+                    let mut via_relay_pop: sys::SteamNetworkingPOPID = 0;
+                    let ping = sys::SteamAPI_ISteamNetworkingUtils_GetPingToDataCenter(utils, pop_id, &mut via_relay_pop);
+                    ping_to_duration(ping).map(|ping| (pop_id, ping))
+                })
+                .collect()
+        }
+    }
+}
+
+fn networking_utils() -> *mut sys::ISteamNetworkingUtils {
+    // SAFETY: only called while the steam client is initialized.
+    unsafe { sys::SteamAPI_SteamNetworkingUtils_SteamAPI_v004() }
+}
+
+fn matchmaking() -> *mut sys::ISteamMatchmaking {
+    // SAFETY: only called while the steam client is initialized.
+    unsafe { sys::SteamAPI_SteamMatchmaking_v009() }
+}
+
+// Steam returns a negative ping when the estimation is not available
+fn ping_to_duration(ping: i32) -> Option<Duration> {
+    if ping < 0 {
+        return None;
+    }
+
+    Some(Duration::from_millis(ping as u64))
+}
@@ -0,0 +1,117 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread::{self, ThreadId},
+};
+
+use steamworks::SingleClient;
+
+// Updates without callbacks being run before warning that events are being missed
+const STALLED_UPDATES_WARNING: u32 = 120;
+
+/// Owns the steam [`SingleClient`] and dispatches its callbacks.
+///
+/// The transports receive their connection events from the steam callbacks, if the callbacks are not run,
+/// or are run in another thread than the one that initialized steam, connection events are missed.
+/// The runner must be created and run in the thread that initialized steam, usually the main thread,
+/// calling [`SteamTransportRunner::run_callbacks`] before updating the transports.
+/// With [`SteamTransportRunner::handle`], transports warn when the callbacks stop being run.
+///
+/// ```ignore
+/// let (steam_client, single) = Client::init_app(480).unwrap();
+/// let runner = SteamTransportRunner::new(single);
+/// steam_transport.set_callback_handle(runner.handle());
+///
+/// loop {
+///     runner.run_callbacks().unwrap();
+///     server.update(delta_time);
+///     steam_transport.update(&mut server);
+///     // ...
+/// }
+/// ```
+pub struct SteamTransportRunner {
+    single: SingleClient,
+    thread: ThreadId,
+    runs: Arc<AtomicU64>,
+}
+
+/// The steam callbacks were run in another thread than the one that created the [`SteamTransportRunner`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WrongCallbackThread;
+
+impl std::error::Error for WrongCallbackThread {}
+
+impl fmt::Display for WrongCallbackThread {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "steam callbacks must be run in the thread that created the runner")
+    }
+}
+
+impl SteamTransportRunner {
+    /// Creates the runner, must be called in the thread that initialized steam.
+    pub fn new(single: SingleClient) -> Self {
+        Self {
+            single,
+            thread: thread::current().id(),
+            runs: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Dispatches the steam callbacks, should be called before updating the transports.
+    /// Returns an error, without running the callbacks, when called in another thread than the one that created the runner.
+    pub fn run_callbacks(&self) -> Result<(), WrongCallbackThread> {
+        if thread::current().id() != self.thread {
+            return Err(WrongCallbackThread);
+        }
+
+        self.single.run_callbacks();
+        self.runs.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns a handle for the transports to detect when the callbacks stop being run.
+    pub fn handle(&self) -> SteamCallbackHandle {
+        SteamCallbackHandle {
+            runs: self.runs.clone(),
+            last_runs: 0,
+            stalled_updates: 0,
+        }
+    }
+}
+
+/// Handle of a [`SteamTransportRunner`], can be sent to other threads.
+/// Set in the transports with `set_callback_handle`.
+#[derive(Debug, Clone)]
+pub struct SteamCallbackHandle {
+    runs: Arc<AtomicU64>,
+    last_runs: u64,
+    stalled_updates: u32,
+}
+
+impl SteamCallbackHandle {
+    /// Returns how many times the callbacks were run.
+    pub fn runs(&self) -> u64 {
+        self.runs.load(Ordering::Relaxed)
+    }
+
+    // Called on each transport update, warns once when the callbacks were not run for many updates
+    pub(crate) fn check(&mut self) {
+        let runs = self.runs();
+        if runs != self.last_runs {
+            self.last_runs = runs;
+            self.stalled_updates = 0;
+            return;
+        }
+
+        self.stalled_updates = self.stalled_updates.saturating_add(1);
+        if self.stalled_updates == STALLED_UPDATES_WARNING {
+            log::warn!(
+                "Steam callbacks were not run in the last {STALLED_UPDATES_WARNING} transport updates, connection events are being missed. \
+                 Call SteamTransportRunner::run_callbacks before updating the transport"
+            );
+        }
+    }
+}
@@ -1,14 +1,23 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Instant,
+};
 
 use renet::{ClientId, RenetServer};
 use steamworks::{
     networking_sockets::{InvalidHandle, ListenSocket, NetConnection},
-    networking_types::{ListenSocketEvent, NetConnectionEnd, NetworkingConfigEntry, SendFlags},
-    Client, ClientManager, FriendFlags, Friends, LobbyId, Manager, Matchmaking, SteamId,
+    networking_types::{ListenSocketEvent, NetConnectionEnd, NetworkingConfigEntry, NetworkingIdentity, SendFlags},
+    Client, ClientManager, FriendFlags, Friends, LobbyId, Manager, Matchmaking, SteamError, SteamId,
 };
 
 use super::MAX_MESSAGE_BATCH_SIZE;
+use crate::budget::{ReceiveQueue, SteamReceiveBudget, SteamReceiveStats};
+use crate::identity::client_id_from_identity;
+use crate::runner::SteamCallbackHandle;
 
+/// Who can connect to the server.
+/// Peers without a steam id, like dedicated servers with fake IPs or generic string identities,
+/// can only connect when the access is [`AccessPermission::Public`].
 pub enum AccessPermission {
     /// Everyone can connect
     Public,
@@ -22,6 +31,17 @@ pub enum AccessPermission {
     InLobby(LobbyId),
 }
 
+/// Statistics of the packets sent to a client.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SteamSendStats {
+    /// Number of packets handed to steam.
+    pub packets_sent: u64,
+    /// Number of packets dropped because the steam send queue for the client was full.
+    pub packets_dropped: u64,
+    /// Number of times the steam send queue for the client was full.
+    pub send_queue_full: u64,
+}
+
 pub struct SteamServerConfig {
     pub max_clients: usize,
     pub access_permission: AccessPermission,
@@ -35,6 +55,13 @@ pub struct SteamServerTransport<Manager = ClientManager> {
     max_clients: usize,
     access_permission: AccessPermission,
     connections: HashMap<ClientId, NetConnection<Manager>>,
+    identities: HashMap<ClientId, NetworkingIdentity>,
+    send_stats: HashMap<ClientId, SteamSendStats>,
+    receive_queues: HashMap<ClientId, ReceiveQueue>,
+    receive_stats: HashMap<ClientId, SteamReceiveStats>,
+    receive_budget: SteamReceiveBudget,
+    message_batch_size: usize,
+    callback_handle: Option<SteamCallbackHandle>,
 }
 
 impl<T: Manager + 'static> SteamServerTransport<T> {
@@ -51,6 +78,13 @@ impl<T: Manager + 'static> SteamServerTransport<T> {
             max_clients: config.max_clients,
             access_permission: config.access_permission,
             connections: HashMap::new(),
+            identities: HashMap::new(),
+            send_stats: HashMap::new(),
+            receive_queues: HashMap::new(),
+            receive_stats: HashMap::new(),
+            receive_budget: SteamReceiveBudget::default(),
+            message_batch_size: MAX_MESSAGE_BATCH_SIZE,
+            callback_handle: None,
         })
     }
 
@@ -58,6 +92,49 @@ impl<T: Manager + 'static> SteamServerTransport<T> {
         self.max_clients
     }
 
+    /// Returns the maximum number of messages received or sent to a client before flushing.
+    pub fn message_batch_size(&self) -> usize {
+        self.message_batch_size
+    }
+
+    /// Update the maximum number of messages received or sent to a client before flushing.
+    pub fn set_message_batch_size(&mut self, message_batch_size: usize) {
+        self.message_batch_size = message_batch_size.max(1);
+    }
+
+    /// Sets the handle of the [`SteamTransportRunner`](crate::SteamTransportRunner), so the transport
+    /// warns when the steam callbacks are not being run and connection events are missed.
+    pub fn set_callback_handle(&mut self, callback_handle: SteamCallbackHandle) {
+        self.callback_handle = Some(callback_handle);
+    }
+
+    /// Returns the steam networking identity of the client, see [`client_id_from_identity`].
+    pub fn client_identity(&self, client_id: ClientId) -> Option<&NetworkingIdentity> {
+        self.identities.get(&client_id)
+    }
+
+    /// Returns the statistics of the packets sent to the client.
+    pub fn send_stats(&self, client_id: ClientId) -> Option<SteamSendStats> {
+        self.send_stats.get(&client_id).copied()
+    }
+
+    /// Returns the limits of the packets processed for each client per update.
+    pub fn receive_budget(&self) -> SteamReceiveBudget {
+        self.receive_budget
+    }
+
+    /// Update the limits of the packets processed for each client per update,
+    /// so one client sending too many packets can't delay the processing of the other clients.
+    /// Default: no limits.
+    pub fn set_receive_budget(&mut self, receive_budget: SteamReceiveBudget) {
+        self.receive_budget = receive_budget;
+    }
+
+    /// Returns the statistics of the packets received from the client.
+    pub fn receive_stats(&self, client_id: ClientId) -> Option<SteamReceiveStats> {
+        self.receive_stats.get(&client_id).copied()
+    }
+
     /// Update the access permission to the server,
     /// this change only applies to new connections.
     pub fn set_access_permissions(&mut self, access_permission: AccessPermission) {
@@ -69,6 +146,10 @@ impl<T: Manager + 'static> SteamServerTransport<T> {
         if let Some((_key, value)) = self.connections.remove_entry(&client_id) {
             let _ = value.close(NetConnectionEnd::AppGeneric, Some("Client was kicked"), flush_last_packets);
         }
+        self.identities.remove(&client_id);
+        self.send_stats.remove(&client_id);
+        self.receive_queues.remove(&client_id);
+        self.receive_stats.remove(&client_id);
         server.remove_connection(client_id);
     }
 
@@ -83,23 +164,37 @@ impl<T: Manager + 'static> SteamServerTransport<T> {
             );
             server.remove_connection(client_id);
         }
+        self.identities.clear();
+        self.send_stats.clear();
+        self.receive_queues.clear();
+        self.receive_stats.clear();
     }
 
     /// Update server connections, and receive packets from the network.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn update(&mut self, server: &mut RenetServer) {
+        let start = Instant::now();
+        if let Some(callback_handle) = self.callback_handle.as_mut() {
+            callback_handle.check();
+        }
+
         while let Some(event) = self.listen_socket.try_receive_event() {
             match event {
                 ListenSocketEvent::Connected(event) => {
-                    if let Some(steam_id) = event.remote().steam_id() {
-                        server.add_connection(steam_id.raw());
-                        self.connections.insert(steam_id.raw(), event.take_connection());
-                    }
+                    let identity = event.remote();
+                    let client_id = client_id_from_identity(&identity);
+                    server.add_connection(client_id);
+                    self.connections.insert(client_id, event.take_connection());
+                    self.identities.insert(client_id, identity);
                 }
                 ListenSocketEvent::Disconnected(event) => {
-                    if let Some(steam_id) = event.remote().steam_id() {
-                        server.remove_connection(steam_id.raw());
-                        self.connections.remove(&steam_id.raw());
-                    }
+                    let client_id = client_id_from_identity(&event.remote());
+                    server.remove_connection(client_id);
+                    self.connections.remove(&client_id);
+                    self.identities.remove(&client_id);
+                    self.send_stats.remove(&client_id);
+                    self.receive_queues.remove(&client_id);
+                    self.receive_stats.remove(&client_id);
                 }
                 ListenSocketEvent::Connecting(event) => {
                     if server.connected_clients() >= self.max_clients {
@@ -107,20 +202,18 @@ impl<T: Manager + 'static> SteamServerTransport<T> {
                         continue;
                     }
 
-                    let Some(steam_id) = event.remote().steam_id() else {
-                        event.reject(NetConnectionEnd::AppGeneric, Some("Invalid steam id"));
-                        continue;
-                    };
-
-                    let permitted = match &self.access_permission {
-                        AccessPermission::Public => true,
-                        AccessPermission::Private => false,
-                        AccessPermission::FriendsOnly => {
+                    let identity = event.remote();
+                    let permitted = match (&self.access_permission, identity.steam_id()) {
+                        (AccessPermission::Public, _) => true,
+                        (AccessPermission::Private, _) => false,
+                        // The other permissions need a steam id to check
+                        (_, None) => false,
+                        (AccessPermission::FriendsOnly, Some(steam_id)) => {
                             let friend = self.friends.get_friend(steam_id);
                             friend.has_friend(FriendFlags::IMMEDIATE)
                         }
-                        AccessPermission::InList(list) => list.contains(&steam_id),
-                        AccessPermission::InLobby(lobby) => {
+                        (AccessPermission::InList(list), Some(steam_id)) => list.contains(&steam_id),
+                        (AccessPermission::InLobby(lobby), Some(steam_id)) => {
                             let users_in_lobby = self.matchmaking.lobby_members(*lobby);
                             users_in_lobby.contains(&steam_id)
                         }
@@ -128,7 +221,7 @@ impl<T: Manager + 'static> SteamServerTransport<T> {
 
                     if permitted {
                         if let Err(e) = event.accept() {
-                            log::error!("Failed to accept connection from {steam_id:?}: {e}");
+                            log::error!("Failed to accept connection from {}: {e}", identity.debug_string());
                         }
                     } else {
                         event.reject(NetConnectionEnd::AppGeneric, Some("Not allowed"));
@@ -139,35 +232,79 @@ impl<T: Manager + 'static> SteamServerTransport<T> {
 
         for (client_id, connection) in self.connections.iter_mut() {
             // TODO this allocates on the side of steamworks.rs and should be avoided, PR needed
-            if let Ok(messages) = connection.receive_messages(MAX_MESSAGE_BATCH_SIZE) {
-                messages.iter().for_each(|message| {
-                    if let Err(e) = server.process_packet_from(message.data(), *client_id) {
+            let messages = connection.receive_messages(self.message_batch_size).unwrap_or_default();
+            let queue = self.receive_queues.entry(*client_id).or_default();
+            let stats = self.receive_stats.entry(*client_id).or_default();
+            let packets_dropped = stats.packets_dropped;
+            queue.process(
+                messages.iter().map(|message| message.data()),
+                &self.receive_budget,
+                stats,
+                |packet| {
+                    if let Err(e) = server.process_packet_from(packet, *client_id) {
                         log::error!("Error while processing payload for {}: {}", client_id, e);
                     };
-                });
+                },
+            );
+            if stats.packets_dropped > packets_dropped {
+                log::warn!(
+                    "Receive budget exhausted for client {client_id}, dropped {} packets",
+                    stats.packets_dropped - packets_dropped
+                );
             }
         }
+
+        server.record_receive_time(start.elapsed());
     }
 
     /// Send packets to connected clients.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all))]
     pub fn send_packets(&mut self, server: &mut RenetServer) {
+        let start = Instant::now();
+        let packing = server.timing().packing;
         'clients: for client_id in server.clients_id() {
             let Some(connection) = self.connections.get(&client_id) else {
                 log::error!("Error while sending packet: connection not found");
                 continue;
             };
             let packets = server.get_packets_to_send(client_id).unwrap();
+            let stats = self.send_stats.entry(client_id).or_default();
+            let mut packets_sent: usize = 0;
             // TODO: while this works fine we should probaly use the send_messages function from the listen_socket
-            for packet in packets {
-                if let Err(e) = connection.send_message(&packet, SendFlags::UNRELIABLE) {
-                    log::error!("Failed to send packet to client {client_id}: {e}");
-                    continue 'clients;
+            'chunks: for chunk in packets.chunks(self.message_batch_size) {
+                for packet in chunk {
+                    match connection.send_message(packet, SendFlags::UNRELIABLE) {
+                        Ok(_) => packets_sent += 1,
+                        Err(SteamError::LimitExceeded) => {
+                            // The send queue is full, drop the remaining packets for this tick.
+                            // Reliable messages will be resent by renet.
+                            let packets_dropped = packets.len() - packets_sent;
+                            log::warn!("Steam send queue is full for client {client_id}, dropped {packets_dropped} packets");
+                            stats.packets_dropped += packets_dropped as u64;
+                            stats.send_queue_full += 1;
+                            if let Err(e) = connection.flush_messages() {
+                                log::error!("Failed flush messages for {client_id}: {e}");
+                            }
+                            break 'chunks;
+                        }
+                        Err(e) => {
+                            log::error!("Failed to send packet to client {client_id}: {e}");
+                            stats.packets_sent += packets_sent as u64;
+                            continue 'clients;
+                        }
+                    }
                 }
-            }
 
-            if let Err(e) = connection.flush_messages() {
-                log::error!("Failed flush messages for {client_id}: {e}");
+                if let Err(e) = connection.flush_messages() {
+                    log::error!("Failed flush messages for {client_id}: {e}");
+                }
             }
+
+            stats.packets_sent += packets_sent as u64;
         }
+
+        // The time spent in get_packets_to_send is already measured as packing
+        let elapsed = start.elapsed().saturating_sub(server.timing().packing - packing);
+        server.record_send_time(elapsed);
     }
 }
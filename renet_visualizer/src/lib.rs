@@ -1,32 +1,45 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+};
 
 use egui::{
     epaint::{PathShape, RectShape},
     pos2, remap, vec2, Color32, Rect, Rgba, RichText, Rounding, Sense, Shape, Stroke, TextStyle, Vec2, WidgetText,
 };
 
-use renet::{ClientId, NetworkInfo, RenetServer};
+use renet::{ChannelError, ClientId, DisconnectReason, NetworkInfo, RenetServer};
 
 use circular_buffer::CircularBuffer;
 
 mod circular_buffer;
 
+/// Packet loss above which a [`TimelineEvent::HighPacketLoss`] is added to the timeline.
+pub const HIGH_PACKET_LOSS: f64 = 0.1;
+
 /// Egui visualizer for the renet client. Draws graphs with metrics:
-/// RTT, Packet Loss, Kbitps Sent/Received.
+/// RTT, Jitter, Packet Loss, Retransmission, Kbitps Sent/Received.
+/// Under each graph a timeline shows the events added with [`RenetClientVisualizer::add_event`].
 ///
 /// N: determines how many values are shown in the graph.
 /// 200 is a good value, if updated at 60 fps the graphs would hold 3 seconds of data.
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::system::Resource))]
 pub struct RenetClientVisualizer<const N: usize> {
     rtt: CircularBuffer<N, f32>,
+    jitter: CircularBuffer<N, f32>,
     sent_bandwidth_kbps: CircularBuffer<N, f32>,
     received_bandwidth_kbps: CircularBuffer<N, f32>,
     packet_loss: CircularBuffer<N, f32>,
+    retransmission: CircularBuffer<N, f32>,
+    // Number of network infos added, the events are aligned with it
+    samples: u64,
+    events: VecDeque<(u64, TimelineEvent)>,
+    high_packet_loss: bool,
     style: RenetVisualizerStyle,
 }
 
 /// Egui visualizer for the renet server. Draws graphs for each connected client with metrics:
-/// RTT, Packet Loss, Kbitps Sent/Received.
+/// RTT, Jitter, Packet Loss, Retransmission, Kbitps Sent/Received.
 ///
 /// N: determines how many values are shown in the graph.
 /// 200 is a good value, if updated at 60 fps the graphs would hold 3 seconds of data.
@@ -48,14 +61,108 @@ pub struct RenetVisualizerStyle {
     pub line_stroke: Stroke,
 }
 
+/// Values of a metric with the range to draw them, the graphs drawn by the visualizers.
+/// Can be used to draw the metrics with other renderers or plotting libraries.
+#[derive(Debug, Clone)]
+pub struct MetricGraph {
+    pub label: &'static str,
+    /// Values from oldest to newest.
+    pub values: Vec<f32>,
+    /// Top of the graph, the bottom is always zero.
+    pub max: f32,
+    pub text_format: TextFormat,
+}
+
+/// Discrete event shown in the timeline under the graphs, aligned with the metrics added after it.
+/// Useful to correlate spikes in the graphs with their causes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimelineEvent {
+    Connected,
+    Disconnected(DisconnectReason),
+    /// The packet loss went above [`HIGH_PACKET_LOSS`], added automatically at the start of each episode.
+    HighPacketLoss(f64),
+    ChannelError {
+        channel_id: u8,
+        error: ChannelError,
+    },
+    /// Any other event of the game, like a map change or a lag spike in the server.
+    Custom(String),
+}
+
+impl TimelineEvent {
+    fn color(&self) -> Color32 {
+        match self {
+            TimelineEvent::Connected => Color32::GREEN,
+            TimelineEvent::Disconnected(_) => Color32::RED,
+            TimelineEvent::HighPacketLoss(_) => Color32::YELLOW,
+            TimelineEvent::ChannelError { .. } => Color32::LIGHT_RED,
+            TimelineEvent::Custom(_) => Color32::LIGHT_BLUE,
+        }
+    }
+}
+
+impl fmt::Display for TimelineEvent {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimelineEvent::Connected => write!(fmt, "Connected"),
+            TimelineEvent::Disconnected(reason) => write!(fmt, "Disconnected: {reason}"),
+            TimelineEvent::HighPacketLoss(packet_loss) => write!(fmt, "High packet loss: {:.1}%", packet_loss * 100.),
+            TimelineEvent::ChannelError { channel_id, error } => write!(fmt, "Error in channel {channel_id}: {error}"),
+            TimelineEvent::Custom(text) => write!(fmt, "{text}"),
+        }
+    }
+}
+
+/// How the values of a [`MetricGraph`] are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextFormat {
+    /// Values are ratios between 0 and 1, shown as percentages.
+    Percentage,
+    Normal,
+}
+
 enum TopValue {
     SuggestedValues([f32; 5]),
     MaxValue { multiplicated: f32 },
 }
 
-enum TextFormat {
-    Percentage,
-    Normal,
+impl MetricGraph {
+    fn new(label: &'static str, text_format: TextFormat, top_value: TopValue, values: Vec<f32>) -> Self {
+        let mut max = values.iter().copied().fold(0.0, f32::max);
+        match top_value {
+            TopValue::MaxValue { multiplicated } => {
+                max *= multiplicated;
+            }
+            TopValue::SuggestedValues(suggested_values) => {
+                for value in suggested_values.into_iter() {
+                    if max < value {
+                        max = value;
+                        break;
+                    }
+                }
+            }
+        }
+
+        Self {
+            label,
+            values,
+            max,
+            text_format,
+        }
+    }
+
+    /// Returns the last value added to the metric.
+    pub fn last_value(&self) -> Option<f32> {
+        self.values.last().copied()
+    }
+
+    /// Formats the value with the precision given, as a percentage for percentage metrics.
+    pub fn format_value(&self, value: f32, precision: usize) -> String {
+        match self.text_format {
+            TextFormat::Normal => format!("{:.*}", precision, value),
+            TextFormat::Percentage => format!("{:.*}%", precision, value * 100.),
+        }
+    }
 }
 
 impl Default for RenetVisualizerStyle {
@@ -86,9 +193,14 @@ impl<const N: usize> RenetClientVisualizer<N> {
     pub fn new(style: RenetVisualizerStyle) -> Self {
         Self {
             rtt: CircularBuffer::default(),
+            jitter: CircularBuffer::default(),
             sent_bandwidth_kbps: CircularBuffer::default(),
             received_bandwidth_kbps: CircularBuffer::default(),
             packet_loss: CircularBuffer::default(),
+            retransmission: CircularBuffer::default(),
+            samples: 0,
+            events: VecDeque::new(),
+            high_packet_loss: false,
             style,
         }
     }
@@ -108,11 +220,54 @@ impl<const N: usize> RenetClientVisualizer<N> {
     /// ```
     pub fn add_network_info(&mut self, network_info: NetworkInfo) {
         self.rtt.push((network_info.rtt * 1000.) as f32);
+        self.jitter.push((network_info.jitter * 1000.) as f32);
         self.sent_bandwidth_kbps
             .push((network_info.bytes_sent_per_second * 8. / 1000.) as f32);
         self.received_bandwidth_kbps
             .push((network_info.bytes_received_per_second * 8. / 1000.) as f32);
         self.packet_loss.push(network_info.packet_loss as f32);
+        self.retransmission.push(network_info.retransmission_ratio as f32);
+        self.samples += 1;
+
+        let high_packet_loss = network_info.packet_loss > HIGH_PACKET_LOSS;
+        if high_packet_loss && !self.high_packet_loss {
+            // Aligned with the values just added
+            self.events
+                .push_back((self.samples - 1, TimelineEvent::HighPacketLoss(network_info.packet_loss)));
+        }
+        self.high_packet_loss = high_packet_loss;
+
+        // Remove the events older than the values shown
+        while let Some((sample, _)) = self.events.front() {
+            if sample + (N as u64) >= self.samples {
+                break;
+            }
+            self.events.pop_front();
+        }
+    }
+
+    /// Add an event to the timeline, aligned with the next network information added.
+    ///
+    /// # Usage
+    /// ```
+    /// # use renet::{RenetClient, ConnectionConfig};
+    /// # use renet_visualizer::{RenetClientVisualizer, TimelineEvent};
+    /// # let mut client = RenetClient::new(ConnectionConfig::default());
+    /// # let mut visualizer = RenetClientVisualizer::<5>::new(Default::default());
+    /// if let Some(reason) = client.disconnect_reason() {
+    ///     visualizer.add_event(TimelineEvent::Disconnected(reason));
+    /// }
+    /// ```
+    pub fn add_event(&mut self, event: TimelineEvent) {
+        self.events.push_back((self.samples, event));
+    }
+
+    /// Returns the events in the timeline, with the index of the value in the graphs they are aligned with.
+    /// The index is the length of the graphs for events added after the last network information.
+    pub fn events(&self) -> impl Iterator<Item = (usize, &TimelineEvent)> {
+        self.events
+            .iter()
+            .map(|(sample, event)| ((sample + N as u64 - self.samples) as usize, event))
     }
 
     /// Renders a new window with all the graphs metrics drawn.
@@ -129,58 +284,120 @@ impl<const N: usize> RenetClientVisualizer<N> {
 
     /// Draws only the Received Kilobits Per Second metric.
     pub fn draw_received_kbps(&self, ui: &mut egui::Ui) {
-        show_graph(
-            ui,
-            &self.style,
+        show_graph(ui, &self.style, &self.received_kbps_graph());
+    }
+
+    /// Draws only the Sent Kilobits Per Second metric.
+    pub fn draw_sent_kbps(&self, ui: &mut egui::Ui) {
+        show_graph(ui, &self.style, &self.sent_kbps_graph());
+    }
+
+    /// Draws only the Packet Loss metric.
+    pub fn draw_packet_loss(&self, ui: &mut egui::Ui) {
+        show_graph(ui, &self.style, &self.packet_loss_graph());
+    }
+
+    /// Draws only the Retransmission metric, the percentage of reliable messages that were resent.
+    pub fn draw_retransmission(&self, ui: &mut egui::Ui) {
+        show_graph(ui, &self.style, &self.retransmission_graph());
+    }
+
+    /// Draws only the Round Time Trip metric.
+    pub fn draw_rtt(&self, ui: &mut egui::Ui) {
+        show_graph(ui, &self.style, &self.rtt_graph());
+    }
+
+    /// Draws only the Jitter metric, the variation of the Round Time Trip.
+    pub fn draw_jitter(&self, ui: &mut egui::Ui) {
+        show_graph(ui, &self.style, &self.jitter_graph());
+    }
+
+    /// Draws only the timeline with the events, with the same width as the graphs.
+    /// Hovering the events shows their description.
+    pub fn draw_timeline(&self, ui: &mut egui::Ui) {
+        show_timeline(ui, &self.style, N, self.events());
+    }
+
+    /// Returns the graph of the Received Kilobits Per Second metric.
+    pub fn received_kbps_graph(&self) -> MetricGraph {
+        MetricGraph::new(
             "Received Kbitps",
             TextFormat::Normal,
             TopValue::MaxValue { multiplicated: 1.5 },
             self.received_bandwidth_kbps.as_vec(),
-        );
+        )
     }
 
-    /// Draws only the Sent Kilobits Per Second metric.
-    pub fn draw_sent_kbps(&self, ui: &mut egui::Ui) {
-        show_graph(
-            ui,
-            &self.style,
+    /// Returns the graph of the Sent Kilobits Per Second metric.
+    pub fn sent_kbps_graph(&self) -> MetricGraph {
+        MetricGraph::new(
             "Sent Kbitps",
             TextFormat::Normal,
             TopValue::MaxValue { multiplicated: 1.5 },
             self.sent_bandwidth_kbps.as_vec(),
-        );
+        )
     }
 
-    /// Draws only the Packet Loss metric.
-    pub fn draw_packet_loss(&self, ui: &mut egui::Ui) {
-        show_graph(
-            ui,
-            &self.style,
+    /// Returns the graph of the Packet Loss metric.
+    pub fn packet_loss_graph(&self) -> MetricGraph {
+        MetricGraph::new(
             "Packet Loss",
             TextFormat::Percentage,
             TopValue::SuggestedValues([0.05, 0.1, 0.25, 0.5, 1.]),
             self.packet_loss.as_vec(),
-        );
+        )
     }
 
-    /// Draws only the Round Time Trip metric.
-    pub fn draw_rtt(&self, ui: &mut egui::Ui) {
-        show_graph(
-            ui,
-            &self.style,
+    /// Returns the graph of the Retransmission metric.
+    pub fn retransmission_graph(&self) -> MetricGraph {
+        MetricGraph::new(
+            "Retransmission",
+            TextFormat::Percentage,
+            TopValue::SuggestedValues([0.05, 0.1, 0.25, 0.5, 1.]),
+            self.retransmission.as_vec(),
+        )
+    }
+
+    /// Returns the graph of the Round Time Trip metric.
+    pub fn rtt_graph(&self) -> MetricGraph {
+        MetricGraph::new(
             "Round Time Trip (ms)",
             TextFormat::Normal,
             TopValue::SuggestedValues([32., 64., 128., 256., 512.]),
             self.rtt.as_vec(),
-        );
+        )
+    }
+
+    /// Returns the graph of the Jitter metric.
+    pub fn jitter_graph(&self) -> MetricGraph {
+        MetricGraph::new(
+            "Jitter (ms)",
+            TextFormat::Normal,
+            TopValue::SuggestedValues([8., 16., 32., 64., 128.]),
+            self.jitter.as_vec(),
+        )
+    }
+
+    /// Returns the graphs of all metrics, in the order they are drawn.
+    pub fn graphs(&self) -> [MetricGraph; 6] {
+        [
+            self.received_kbps_graph(),
+            self.sent_kbps_graph(),
+            self.rtt_graph(),
+            self.jitter_graph(),
+            self.packet_loss_graph(),
+            self.retransmission_graph(),
+        ]
     }
 
-    /// Draw all metrics without a window or layout.
+    /// Draw all metrics without a window or layout, with the timeline under each graph.
     pub fn draw_all(&self, ui: &mut egui::Ui) {
-        self.draw_received_kbps(ui);
-        self.draw_sent_kbps(ui);
-        self.draw_rtt(ui);
-        self.draw_packet_loss(ui);
+        for graph in self.graphs() {
+            ui.vertical(|ui| {
+                show_graph(ui, &self.style, &graph);
+                self.draw_timeline(ui);
+            });
+        }
     }
 }
 
@@ -240,9 +457,42 @@ impl<const N: usize> RenetServerVisualizer<N> {
         self.clients.remove(&client_id);
     }
 
-    fn add_network_info(&mut self, client_id: ClientId, network_info: NetworkInfo) {
+    /// Add an event to the timeline of the client, see [`RenetClientVisualizer::add_event`].
+    /// Events of clients not added yet are ignored.
+    ///
+    /// # Usage
+    /// ```
+    /// # use renet_visualizer::{RenetServerVisualizer, TimelineEvent};
+    /// # let mut visualizer = RenetServerVisualizer::<5>::new(Default::default());
+    /// # let client_id = 0;
+    /// visualizer.add_client(client_id);
+    /// visualizer.add_event(client_id, TimelineEvent::Connected);
+    /// visualizer.add_event(client_id, TimelineEvent::Custom("Joined match".to_string()));
+    /// ```
+    pub fn add_event(&mut self, client_id: ClientId, event: TimelineEvent) {
         if let Some(client) = self.clients.get_mut(&client_id) {
-            client.add_network_info(network_info);
+            client.add_event(event);
+        }
+    }
+
+    /// Add the network information of the clients, the clients not added yet are added.
+    /// The information doesn't need to come from a local server, it can be received from a remote server.
+    ///
+    /// # Usage
+    /// ```
+    /// # use renet::{NetworkInfo, ClientId};
+    /// # use renet_visualizer::RenetServerVisualizer;
+    /// # let mut visualizer = RenetServerVisualizer::<5>::new(Default::default());
+    /// # let received_infos: Vec<(ClientId, NetworkInfo)> = vec![];
+    /// // Network info received from a remote server in an admin channel
+    /// visualizer.update(received_infos);
+    /// ```
+    pub fn update<I: IntoIterator<Item = (ClientId, NetworkInfo)>>(&mut self, network_infos: I) {
+        for (client_id, network_info) in network_infos {
+            self.clients
+                .entry(client_id)
+                .or_insert_with(|| RenetClientVisualizer::new(self.style.clone()))
+                .add_network_info(network_info);
         }
     }
 
@@ -257,14 +507,19 @@ impl<const N: usize> RenetServerVisualizer<N> {
     /// # let mut visualizer = RenetServerVisualizer::<5>::new(Default::default());
     /// # let delta = std::time::Duration::ZERO;
     /// renet_server.update(delta);
-    /// visualizer.update(&renet_server);
+    /// visualizer.update_from_server(&renet_server);
     /// ```
-    pub fn update(&mut self, server: &RenetServer) {
-        for client_id in server.clients_id_iter() {
-            if let Ok(network_info) = server.network_info(client_id) {
-                self.add_network_info(client_id, network_info);
-            }
-        }
+    pub fn update_from_server(&mut self, server: &RenetServer) {
+        self.update(
+            server
+                .clients_id_iter()
+                .filter_map(|client_id| Some((client_id, server.network_info(client_id).ok()?))),
+        );
+    }
+
+    /// Returns the graphs of all metrics of the client.
+    pub fn client_graphs(&self, client_id: ClientId) -> Option<[MetricGraph; 6]> {
+        Some(self.clients.get(&client_id)?.graphs())
     }
 
     /// Draw all metrics without a window or layout for the specified client.
@@ -319,45 +574,19 @@ impl<const N: usize> RenetServerVisualizer<N> {
     }
 }
 
-fn show_graph(
-    ui: &mut egui::Ui,
-    style: &RenetVisualizerStyle,
-    label: &str,
-    text_format: TextFormat,
-    top_value: TopValue,
-    values: Vec<f32>,
-) {
-    if values.is_empty() {
+fn show_graph(ui: &mut egui::Ui, style: &RenetVisualizerStyle, graph: &MetricGraph) {
+    let Some(last_value) = graph.last_value() else {
         return;
-    }
+    };
 
     ui.vertical(|ui| {
-        ui.label(RichText::new(label).heading().color(style.text_color));
-
-        let last_value = values.last().unwrap();
+        ui.label(RichText::new(graph.label).heading().color(style.text_color));
 
         let min = 0.0;
-        let mut max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
-        match top_value {
-            TopValue::MaxValue { multiplicated } => {
-                max *= multiplicated;
-            }
-            TopValue::SuggestedValues(suggested_values) => {
-                for value in suggested_values.into_iter() {
-                    if max < value {
-                        max = value;
-                        break;
-                    }
-                }
-            }
-        }
-
+        let max = graph.max;
         let spacing_x = ui.spacing().item_spacing.x;
 
-        let last_text: WidgetText = match text_format {
-            TextFormat::Normal => format!("{:.2}", last_value).into(),
-            TextFormat::Percentage => format!("{:.1}%", last_value * 100.).into(),
-        };
+        let last_text: WidgetText = graph.format_value(last_value, 2).into();
         let galley = last_text.into_galley(ui, Some(egui::TextWrapMode::Wrap), f32::INFINITY, TextStyle::Button);
         let (outer_rect, _) = ui.allocate_exact_size(Vec2::new(style.width + galley.size().x + spacing_x, style.height), Sense::hover());
         let rect = Rect::from_min_size(outer_rect.left_top(), vec2(style.width, style.height));
@@ -376,8 +605,9 @@ fn show_graph(
         ui.painter().add(body);
         let init_point = rect.left_bottom();
 
-        let size = values.len();
-        let points = values
+        let size = graph.values.len();
+        let points = graph
+            .values
             .iter()
             .enumerate()
             .map(|(i, value)| {
@@ -392,22 +622,78 @@ fn show_graph(
         ui.painter().add(path);
 
         {
-            let text: WidgetText = match text_format {
-                TextFormat::Normal => format!("{:.0}", max).into(),
-                TextFormat::Percentage => format!("{:.0}%", max * 100.).into(),
-            };
+            let text: WidgetText = graph.format_value(max, 0).into();
             let galley = text.into_galley(ui, Some(egui::TextWrapMode::Wrap), f32::INFINITY, TextStyle::Button);
             let text_pos = rect.left_top() + Vec2::new(0.0, galley.size().y / 2.) + vec2(spacing_x, 0.0);
             ui.painter().with_clip_rect(outer_rect).galley(text_pos, galley, style.text_color);
         }
         {
-            let text: WidgetText = match text_format {
-                TextFormat::Normal => format!("{:.0}", min).into(),
-                TextFormat::Percentage => format!("{:.0}%", min * 100.).into(),
-            };
+            let text: WidgetText = graph.format_value(min, 0).into();
             let galley = text.into_galley(ui, Some(egui::TextWrapMode::Wrap), f32::INFINITY, TextStyle::Button);
             let text_pos = rect.left_bottom() - Vec2::new(0.0, galley.size().y * 1.5) + vec2(spacing_x, 0.0);
             ui.painter().with_clip_rect(outer_rect).galley(text_pos, galley, style.text_color);
         }
     });
 }
+
+fn show_timeline<'a>(
+    ui: &mut egui::Ui,
+    style: &RenetVisualizerStyle,
+    size: usize,
+    events: impl Iterator<Item = (usize, &'a TimelineEvent)>,
+) {
+    const HOVER_DISTANCE: f32 = 3.0;
+
+    let (rect, response) = ui.allocate_exact_size(vec2(style.width, style.height / 5.), Sense::hover());
+    ui.painter().rect_stroke(rect, Rounding::ZERO, style.rectangle_stroke);
+
+    let hover_x = response.hover_pos().map(|pos| pos.x);
+    let mut hovered = vec![];
+    for (index, event) in events {
+        let x = rect.left() + remap(index as f32, 0.0..=size as f32, 0.0..=style.width);
+        ui.painter()
+            .vline(x, rect.y_range(), Stroke::new(style.line_stroke.width.max(2.), event.color()));
+        if hover_x.is_some_and(|hover_x| (hover_x - x).abs() <= HOVER_DISTANCE) {
+            hovered.push(event.to_string());
+        }
+    }
+
+    if !hovered.is_empty() {
+        response.on_hover_text(hovered.join("\n"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeline_events() {
+        let network_info = renet::RenetClient::new(renet::ConnectionConfig::default()).network_info();
+        let mut visualizer = RenetClientVisualizer::<3>::default();
+        visualizer.add_event(TimelineEvent::Connected);
+        visualizer.add_network_info(network_info);
+        assert_eq!(visualizer.events().collect::<Vec<_>>(), vec![(2, &TimelineEvent::Connected)]);
+
+        let high_loss = NetworkInfo {
+            packet_loss: 0.5,
+            ..network_info
+        };
+        visualizer.add_network_info(high_loss);
+        visualizer.add_network_info(high_loss);
+        assert_eq!(
+            visualizer.events().collect::<Vec<_>>(),
+            vec![(0, &TimelineEvent::Connected), (1, &TimelineEvent::HighPacketLoss(0.5))]
+        );
+
+        visualizer.add_event(TimelineEvent::Custom("Map changed".to_string()));
+        assert_eq!(
+            visualizer.events().last(),
+            Some((3, &TimelineEvent::Custom("Map changed".to_string())))
+        );
+
+        // Events older than the values shown are removed
+        visualizer.add_network_info(network_info);
+        assert_eq!(visualizer.events().next(), Some((0, &TimelineEvent::HighPacketLoss(0.5))));
+    }
+}
@@ -1,6 +1,6 @@
 use renetcode::{
     ClientAuthentication, ConnectToken, NetcodeClient, NetcodeServer, ServerAuthentication, ServerConfig, ServerResult, NETCODE_KEY_BYTES,
-    NETCODE_MAX_PACKET_BYTES, NETCODE_USER_DATA_BYTES,
+    NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PENDING_CLIENTS, NETCODE_SEND_RATE, NETCODE_USER_DATA_BYTES,
 };
 use std::time::Duration;
 use std::{collections::HashMap, thread};
@@ -101,6 +101,7 @@ fn handle_server_result(
             user_data,
             payload,
             addr,
+            ..
         } => {
             let username = Username::from_user_data(&user_data);
             println!("Client {} with id {} connected.", username.0, client_id);
@@ -114,6 +115,12 @@ fn handle_server_result(
                 socket.send_to(payload, addr).unwrap();
             }
         }
+        ServerResult::ConnectionDenied { addr, reason, payload } => {
+            println!("Connection from {} denied: {:?}.", addr, reason);
+            if let Some(payload) = payload {
+                socket.send_to(payload, addr).unwrap();
+            }
+        }
         ServerResult::None => {}
     }
 }
@@ -126,6 +133,8 @@ fn server(addr: SocketAddr, private_key: [u8; NETCODE_KEY_BYTES]) {
         protocol_id: PROTOCOL_ID,
         public_addresses: vec![addr],
         authentication: ServerAuthentication::Secure { private_key },
+        keep_alive_interval: NETCODE_SEND_RATE,
+        max_pending_clients: NETCODE_MAX_PENDING_CLIENTS,
     };
     let mut server: NetcodeServer = NetcodeServer::new(config);
     let udp_socket = UdpSocket::bind(addr).unwrap();
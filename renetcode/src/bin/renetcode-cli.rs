@@ -0,0 +1,229 @@
+use std::{
+    net::SocketAddr,
+    process,
+    time::{Duration, SystemTime},
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use renetcode::{generate_random_bytes, ConnectToken, NETCODE_KEY_BYTES, NETCODE_USER_DATA_BYTES};
+
+const USAGE: &str = "Usage:
+    renetcode-cli keygen [--format <hex|base64>]
+        Generates a random private key.
+
+    renetcode-cli token --private-key <KEY> --protocol-id <ID> --client-id <ID> --server <ADDR>... [OPTIONS]
+        Generates a connect token, more servers can be added with more --server options.
+        --expire <SECONDS>       Seconds until the token expires [default: 300]
+        --timeout <SECONDS>      Seconds without packets before the client times out, negative disables it [default: 15]
+        --user-data <TEXT>       User data as text, padded with zeros
+        --user-data-hex <HEX>    User data as hex, padded with zeros
+        --format <hex|base64>    Output format [default: base64]
+
+    renetcode-cli inspect <TOKEN> [--private-key <KEY>]
+        Shows the content of a connect token in hex or base64. With the private key,
+        also checks that the token was generated with the key and shows the user data.
+
+Keys are read in hex or base64.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Hex,
+    Base64,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("keygen") => keygen(&args[1..]),
+        Some("token") => token(&args[1..]),
+        Some("inspect") => inspect(&args[1..]),
+        _ => Err("expected a command: keygen, token or inspect".to_string()),
+    };
+
+    if let Err(e) = result {
+        eprintln!("error: {e}\n\n{USAGE}");
+        process::exit(1);
+    }
+}
+
+fn keygen(args: &[String]) -> Result<(), String> {
+    let mut format = Format::Hex;
+    for (option, value) in options(args)? {
+        match option {
+            "--format" => format = parse_format(value)?,
+            _ => return Err(format!("unknown option {option}")),
+        }
+    }
+
+    let private_key: [u8; NETCODE_KEY_BYTES] = generate_random_bytes();
+    println!("{}", encode(&private_key, format));
+
+    Ok(())
+}
+
+fn token(args: &[String]) -> Result<(), String> {
+    let mut private_key = None;
+    let mut protocol_id = None;
+    let mut client_id = None;
+    let mut server_addresses: Vec<SocketAddr> = vec![];
+    let mut expire_seconds = 300;
+    let mut timeout_seconds = 15;
+    let mut user_data = None;
+    let mut format = Format::Base64;
+    for (option, value) in options(args)? {
+        let invalid = |e: &dyn std::fmt::Display| format!("invalid value for {option}: {e}");
+        match option {
+            "--private-key" => private_key = Some(parse_key(value)?),
+            "--protocol-id" => protocol_id = Some(value.parse().map_err(|e| invalid(&e))?),
+            "--client-id" => client_id = Some(value.parse().map_err(|e| invalid(&e))?),
+            "--server" => server_addresses.push(value.parse().map_err(|e| invalid(&e))?),
+            "--expire" => expire_seconds = value.parse().map_err(|e| invalid(&e))?,
+            "--timeout" => timeout_seconds = value.parse().map_err(|e| invalid(&e))?,
+            "--user-data" => user_data = Some(parse_user_data(value.as_bytes())?),
+            "--user-data-hex" => user_data = Some(parse_user_data(&decode_hex(value)?)?),
+            "--format" => format = parse_format(value)?,
+            _ => return Err(format!("unknown option {option}")),
+        }
+    }
+
+    let private_key = private_key.ok_or("missing --private-key")?;
+    let protocol_id = protocol_id.ok_or("missing --protocol-id")?;
+    let client_id = client_id.ok_or("missing --client-id")?;
+    if server_addresses.is_empty() {
+        return Err("missing --server".to_string());
+    }
+
+    let connect_token = ConnectToken::generate(
+        current_time(),
+        protocol_id,
+        expire_seconds,
+        client_id,
+        timeout_seconds,
+        server_addresses,
+        user_data.as_ref(),
+        &private_key,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let mut bytes: Vec<u8> = vec![];
+    connect_token.write(&mut bytes).map_err(|e| e.to_string())?;
+    println!("{}", encode(&bytes, format));
+
+    Ok(())
+}
+
+fn inspect(args: &[String]) -> Result<(), String> {
+    let (token, args) = args.split_first().ok_or("missing token")?;
+    let mut private_key = None;
+    for (option, value) in options(args)? {
+        match option {
+            "--private-key" => private_key = Some(parse_key(value)?),
+            _ => return Err(format!("unknown option {option}")),
+        }
+    }
+
+    let bytes = decode(token)?;
+    let connect_token = ConnectToken::read(&mut bytes.as_slice()).map_err(|e| format!("invalid token: {e}"))?;
+
+    let now = current_time().as_secs();
+    let expiration = if connect_token.expire_timestamp > now {
+        format!("expires in {}s", connect_token.expire_timestamp - now)
+    } else {
+        format!("expired {}s ago", now - connect_token.expire_timestamp)
+    };
+    println!("Client id:         {}", connect_token.client_id);
+    println!("Protocol id:       {}", connect_token.protocol_id);
+    println!("Created at:        {}", connect_token.create_timestamp);
+    println!("Expires at:        {} ({expiration})", connect_token.expire_timestamp);
+    println!("Timeout seconds:   {}", connect_token.timeout_seconds);
+    for server_addr in connect_token.server_addresses.iter().flatten() {
+        println!("Server address:    {server_addr}");
+    }
+    println!("Client to server:  {}", encode(&connect_token.client_to_server_key, Format::Hex));
+    println!("Server to client:  {}", encode(&connect_token.server_to_client_key, Format::Hex));
+
+    if let Some(private_key) = private_key {
+        let user_data = connect_token
+            .decrypt_user_data(&private_key)
+            .map_err(|e| format!("the token was not generated with the private key: {e}"))?;
+        let user_data_len = user_data.iter().rposition(|&byte| byte != 0).map_or(0, |i| i + 1);
+        println!("Private key:       valid");
+        println!("User data:         {}", encode(&user_data[..user_data_len], Format::Hex));
+        if let Ok(text) = std::str::from_utf8(&user_data[..user_data_len]) {
+            println!("User data (text):  {text}");
+        }
+    }
+
+    Ok(())
+}
+
+// Pairs of options and values
+fn options(args: &[String]) -> Result<Vec<(&str, &String)>, String> {
+    args.chunks(2)
+        .map(|chunk| match chunk {
+            [option, value] => Ok((option.as_str(), value)),
+            [option] => Err(format!("missing value for {option}")),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+fn current_time() -> Duration {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap()
+}
+
+fn parse_format(value: &str) -> Result<Format, String> {
+    match value {
+        "hex" => Ok(Format::Hex),
+        "base64" => Ok(Format::Base64),
+        _ => Err(format!("invalid format {value}, expected hex or base64")),
+    }
+}
+
+fn parse_key(value: &str) -> Result<[u8; NETCODE_KEY_BYTES], String> {
+    decode(value)?
+        .try_into()
+        .map_err(|bytes: Vec<u8>| format!("the private key must have {NETCODE_KEY_BYTES} bytes, got {}", bytes.len()))
+}
+
+fn parse_user_data(bytes: &[u8]) -> Result<[u8; NETCODE_USER_DATA_BYTES], String> {
+    if bytes.len() > NETCODE_USER_DATA_BYTES {
+        return Err(format!(
+            "the user data can have at most {NETCODE_USER_DATA_BYTES} bytes, got {}",
+            bytes.len()
+        ));
+    }
+
+    let mut user_data = [0u8; NETCODE_USER_DATA_BYTES];
+    user_data[..bytes.len()].copy_from_slice(bytes);
+
+    Ok(user_data)
+}
+
+fn encode(bytes: &[u8], format: Format) -> String {
+    match format {
+        Format::Hex => bytes.iter().map(|byte| format!("{byte:02x}")).collect(),
+        Format::Base64 => BASE64.encode(bytes),
+    }
+}
+
+// Reads hex if possible, otherwise base64
+fn decode(value: &str) -> Result<Vec<u8>, String> {
+    let value = value.trim();
+    if value.len().is_multiple_of(2) && value.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return decode_hex(value);
+    }
+
+    BASE64.decode(value).map_err(|e| format!("expected hex or base64: {e}"))
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, String> {
+    if !value.len().is_multiple_of(2) {
+        return Err("hex value must have an even number of characters".to_string());
+    }
+
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).map_err(|e| format!("invalid hex: {e}")))
+        .collect()
+}
@@ -2,9 +2,13 @@ use std::{error::Error, fmt, net::SocketAddr, time::Duration};
 
 use crate::{
     packet::Packet, replay_protection::ReplayProtection, token::ConnectToken, NetcodeError, NETCODE_CHALLENGE_TOKEN_BYTES,
-    NETCODE_KEY_BYTES, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_SEND_RATE, NETCODE_USER_DATA_BYTES,
+    NETCODE_KEY_BYTES, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_RESUME_TICKET_BYTES, NETCODE_SEND_RATE,
+    NETCODE_USER_DATA_BYTES,
 };
 
+// Delay before sending connection requests to the fallback address when racing two addresses
+const HAPPY_EYEBALLS_DELAY: Duration = Duration::from_millis(250);
+
 /// The reason why a client is in error state
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DisconnectReason {
@@ -59,12 +63,21 @@ pub struct NetcodeClient {
     sequence: u64,
     server_addr: SocketAddr,
     server_addr_index: usize,
+    // Fallback address that is also sent connection requests, until one of the addresses answers
+    racing_addr: Option<SocketAddr>,
+    send_to_racing_addr: bool,
     connect_token: ConnectToken,
     challenge_token_sequence: u64,
     challenge_token_data: [u8; NETCODE_CHALLENGE_TOKEN_BYTES],
     max_clients: u32,
     client_index: u32,
     send_rate: Duration,
+    // In the background, the connection doesn't time out and no keep-alives are sent
+    suspended: bool,
+    // Resumed from the background, the timeout restarts in the next update
+    resuming: bool,
+    // Presented when connecting, and replaced by the ticket of the new connection
+    resume_ticket: Option<[u8; NETCODE_RESUME_TICKET_BYTES]>,
     replay_protection: ReplayProtection,
     out: [u8; NETCODE_MAX_PACKET_BYTES],
 }
@@ -115,6 +128,8 @@ impl NetcodeClient {
             client_id: connect_token.client_id,
             server_addr,
             server_addr_index: 0,
+            racing_addr: None,
+            send_to_racing_addr: false,
             challenge_token_sequence: 0,
             state: ClientState::SendingConnectionRequest,
             connect_start_time: current_time,
@@ -124,6 +139,9 @@ impl NetcodeClient {
             max_clients: 0,
             client_index: 0,
             send_rate: NETCODE_SEND_RATE,
+            suspended: false,
+            resuming: false,
+            resume_ticket: None,
             challenge_token_data: [0u8; NETCODE_CHALLENGE_TOKEN_BYTES],
             connect_token,
             replay_protection: ReplayProtection::new(),
@@ -160,6 +178,21 @@ impl NetcodeClient {
         self.current_time - self.last_packet_received_time
     }
 
+    /// Returns the interval between keep-alive packets sent to the server, when no other packets are sent.
+    /// It's the interval used by the server, received when connected.
+    pub fn keep_alive_interval(&self) -> Duration {
+        self.send_rate
+    }
+
+    /// Returns the duration without receiving packets after which the client times out, from the connect token.
+    /// Returns None if the timeout is disabled.
+    pub fn timeout(&self) -> Option<Duration> {
+        if self.connect_token.timeout_seconds <= 0 {
+            return None;
+        }
+        Some(Duration::from_secs(self.connect_token.timeout_seconds as u64))
+    }
+
     /// Returns the reason that the client was disconnected for.
     pub fn disconnect_reason(&self) -> Option<DisconnectReason> {
         if let ClientState::Disconnected(reason) = &self.state {
@@ -173,6 +206,42 @@ impl NetcodeClient {
         self.server_addr
     }
 
+    /// Returns whether packets from the address should be processed by the client.
+    /// When connecting with happy eyeballs, both addresses being tried are accepted.
+    pub fn is_server_addr(&self, addr: SocketAddr) -> bool {
+        addr == self.server_addr || self.racing_addr == Some(addr)
+    }
+
+    /// Enables or disables connecting to the first two addresses of the connect token concurrently (happy eyeballs).
+    ///
+    /// The IPv6 address is preferred, connection requests to the other address start after 250ms, and the client
+    /// continues with the first server that answers. Should be called before the first update, and the socket
+    /// must be able to send to both addresses (a dual-stack socket when the addresses are IPv6 and IPv4).
+    /// The packets received should be processed with [`NetcodeClient::process_packet_from`].
+    pub fn set_happy_eyeballs(&mut self, enabled: bool) {
+        if self.state != ClientState::SendingConnectionRequest || self.server_addr_index != 0 {
+            return;
+        }
+
+        let first_addr =
+            self.connect_token.server_addresses[0].expect("cannot create or deserialize a ConnectToken without a server address");
+        self.server_addr = first_addr;
+        self.racing_addr = None;
+        self.send_to_racing_addr = false;
+        if !enabled {
+            return;
+        }
+
+        if let Some(second_addr) = self.connect_token.server_addresses[1] {
+            if first_addr.is_ipv4() && second_addr.is_ipv6() {
+                self.server_addr = second_addr;
+                self.racing_addr = Some(first_addr);
+            } else {
+                self.racing_addr = Some(second_addr);
+            }
+        }
+    }
+
     /// Disconnect the client from the server.
     /// Returns a disconnect packet that should be sent to the server.
     pub fn disconnect(&mut self) -> Result<(SocketAddr, &mut [u8]), NetcodeError> {
@@ -187,10 +256,73 @@ impl NetcodeClient {
         Ok((self.server_addr, &mut self.out[..len]))
     }
 
+    /// Suspend the client when the app goes to the background, for example on mobile platforms.
+    /// Returns a suspend packet that should be sent to the server, so it doesn't time out the client
+    /// for [`NetcodeServer::max_suspend_duration`][crate::NetcodeServer::max_suspend_duration].
+    ///
+    /// While suspended, the client doesn't time out and no keep-alive packets are generated.
+    pub fn suspend(&mut self) -> Result<(SocketAddr, &mut [u8]), NetcodeError> {
+        if self.state != ClientState::Connected {
+            return Err(NetcodeError::ClientNotConnected);
+        }
+
+        let packet = Packet::Suspend;
+        let len = packet.encode(
+            &mut self.out,
+            self.connect_token.protocol_id,
+            Some((self.sequence, &self.connect_token.client_to_server_key)),
+        )?;
+        self.sequence += 1;
+        self.suspended = true;
+        self.last_packet_send_time = Some(self.current_time);
+
+        Ok((self.server_addr, &mut self.out[..len]))
+    }
+
+    /// Resume the client when the app returns to the foreground.
+    /// The time spent in the background is not counted for the timeout, and a keep-alive packet is generated in the next update.
+    pub fn resume(&mut self) {
+        if self.suspended {
+            self.suspended = false;
+            self.resuming = true;
+            self.last_packet_send_time = None;
+        }
+    }
+
+    /// Returns whether the client is suspended.
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    /// Returns the ticket to resume this connection, received when connected to a server with session resumption enabled.
+    /// If the connection is lost, a new client can present it with [`NetcodeClient::set_resume_ticket`]
+    /// to continue with the same client id.
+    pub fn resume_ticket(&self) -> Option<[u8; NETCODE_RESUME_TICKET_BYTES]> {
+        self.resume_ticket
+    }
+
+    /// Sets the ticket of a lost connection, presented to the server when connecting to resume it.
+    /// Should be set before connecting, the server ignores invalid or expired tickets, connecting as a new client.
+    pub fn set_resume_ticket(&mut self, resume_ticket: [u8; NETCODE_RESUME_TICKET_BYTES]) {
+        self.resume_ticket = Some(resume_ticket);
+    }
+
     /// Process any packet received from the server. This function might return a payload sent from the
     /// server. If nothing is returned, it was a packet used for the internal protocol or an
     /// invalid packet.
     pub fn process_packet<'a>(&mut self, buffer: &'a mut [u8]) -> Option<&'a [u8]> {
+        self.process_packet_from(buffer, self.server_addr)
+    }
+
+    /// Process any packet received from the address, packets from addresses that are not
+    /// the server are ignored. See [`NetcodeClient::process_packet`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip_all, fields(client_id = self.client_id)))]
+    pub fn process_packet_from<'a>(&mut self, buffer: &'a mut [u8], addr: SocketAddr) -> Option<&'a [u8]> {
+        if !self.is_server_addr(addr) {
+            log::debug!("Discarded packet from unknown server {:?}", addr);
+            return None;
+        }
+
         let packet = match Packet::decode(
             buffer,
             self.connect_token.protocol_id,
@@ -205,6 +337,34 @@ impl NetcodeClient {
         };
         log::trace!("Received packet from server: {:?}", packet.packet_type());
 
+        if let Some(racing_addr) = self.racing_addr {
+            match packet {
+                Packet::ConnectionDenied => {
+                    // Continue with the other address
+                    log::debug!("Connection denied by {addr} while connecting with happy eyeballs");
+                    if addr == self.server_addr {
+                        self.server_addr = racing_addr;
+                    }
+                    self.server_addr_index = 1;
+                    self.racing_addr = None;
+                    self.last_packet_send_time = None;
+                    // The other server uses its own sequences
+                    self.replay_protection = ReplayProtection::new();
+                    return None;
+                }
+                Packet::Challenge { .. } => {
+                    // Commit to the first server that answered
+                    if addr == racing_addr {
+                        self.server_addr = racing_addr;
+                    }
+                    // Both addresses were tried, if the connection fails the next address is used
+                    self.server_addr_index = 1;
+                    self.racing_addr = None;
+                }
+                _ => {}
+            }
+        }
+
         match (packet, &self.state) {
             (Packet::ConnectionDenied, ClientState::SendingConnectionRequest | ClientState::SendingConnectionResponse) => {
                 self.state = ClientState::Disconnected(DisconnectReason::ConnectionDenied);
@@ -223,20 +383,46 @@ impl NetcodeClient {
                 self.challenge_token_data = token_data;
                 self.state = ClientState::SendingConnectionResponse;
             }
-            (Packet::KeepAlive { .. }, ClientState::Connected) => {
+            (
+                Packet::KeepAlive {
+                    keep_alive_interval_ms,
+                    resume_ticket,
+                    ..
+                },
+                ClientState::Connected,
+            ) => {
                 self.last_packet_received_time = self.current_time;
+                self.set_keep_alive_interval(keep_alive_interval_ms);
+                if resume_ticket.is_some() {
+                    self.resume_ticket = resume_ticket;
+                }
             }
-            (Packet::KeepAlive { client_index, max_clients }, ClientState::SendingConnectionResponse) => {
+            (
+                Packet::KeepAlive {
+                    client_index,
+                    max_clients,
+                    keep_alive_interval_ms,
+                    resume_ticket,
+                },
+                ClientState::SendingConnectionResponse,
+            ) => {
                 self.last_packet_received_time = self.current_time;
+                self.set_keep_alive_interval(keep_alive_interval_ms);
+                // Tickets are only issued by servers with session resumption enabled
+                self.resume_ticket = resume_ticket;
                 self.max_clients = max_clients;
                 self.client_index = client_index;
                 self.state = ClientState::Connected;
+
+                log::debug!("Connected to server with client index {client_index} of {max_clients}");
             }
             (Packet::Payload(p), ClientState::Connected) => {
                 self.last_packet_received_time = self.current_time;
                 return Some(p);
             }
             (Packet::Disconnect, ClientState::Connected) => {
+                log::debug!("Disconnected by server");
+
                 self.state = ClientState::Disconnected(DisconnectReason::DisconnectedByServer);
                 self.last_packet_received_time = self.current_time;
             }
@@ -246,6 +432,13 @@ impl NetcodeClient {
         None
     }
 
+    fn set_keep_alive_interval(&mut self, keep_alive_interval_ms: Option<u32>) {
+        self.send_rate = match keep_alive_interval_ms {
+            Some(keep_alive_interval_ms) => Duration::from_millis(keep_alive_interval_ms as u64),
+            None => NETCODE_SEND_RATE,
+        };
+    }
+
     /// Returns the server address and an encrypted payload packet that can be sent to the server.
     pub fn generate_payload_packet(&mut self, payload: &[u8]) -> Result<(SocketAddr, &mut [u8]), NetcodeError> {
         if payload.len() > NETCODE_MAX_PAYLOAD_BYTES {
@@ -282,6 +475,10 @@ impl NetcodeClient {
 
     fn update_internal_state(&mut self, duration: Duration) -> Result<(), NetcodeError> {
         self.current_time += duration;
+        if self.suspended || self.resuming {
+            self.last_packet_received_time = self.current_time;
+            self.resuming = false;
+        }
         let connection_timed_out = self.connect_token.timeout_seconds > 0
             && (self.last_packet_received_time + Duration::from_secs(self.connect_token.timeout_seconds as u64) < self.current_time);
 
@@ -300,7 +497,10 @@ impl NetcodeClient {
                         DisconnectReason::ConnectionRequestTimedOut
                     };
                     self.state = ClientState::Disconnected(reason);
-                    // Try to connect to the next server address
+                    // Try to connect to the next server address, after the ones being raced
+                    if self.racing_addr.take().is_some() {
+                        self.server_addr_index = 1;
+                    }
                     self.server_addr_index += 1;
                     if self.server_addr_index >= 32 {
                         return Err(NetcodeError::NoMoreServers);
@@ -334,6 +534,10 @@ impl NetcodeClient {
     }
 
     fn generate_packet(&mut self) -> Option<(&mut [u8], SocketAddr)> {
+        if self.suspended {
+            return None;
+        }
+
         if let Some(last_packet_send_time) = self.last_packet_send_time {
             if self.current_time - last_packet_send_time < self.send_rate {
                 return None;
@@ -351,14 +555,28 @@ impl NetcodeClient {
             ClientState::SendingConnectionResponse => Packet::Response {
                 token_sequence: self.challenge_token_sequence,
                 token_data: self.challenge_token_data,
+                resume_ticket: self.resume_ticket,
             },
             ClientState::Connected => Packet::KeepAlive {
                 client_index: 0,
                 max_clients: 0,
+                keep_alive_interval_ms: None,
+                resume_ticket: None,
             },
             _ => return None,
         };
 
+        // Alternate the connection requests between the raced addresses, after the delay
+        let mut addr = self.server_addr;
+        if let Some(racing_addr) = self.racing_addr {
+            if self.current_time - self.connect_start_time >= HAPPY_EYEBALLS_DELAY {
+                self.send_to_racing_addr = !self.send_to_racing_addr;
+                if self.send_to_racing_addr {
+                    addr = racing_addr;
+                }
+            }
+        }
+
         let result = packet.encode(
             &mut self.out,
             self.connect_token.protocol_id,
@@ -368,7 +586,7 @@ impl NetcodeClient {
             Err(_) => None,
             Ok(encoded) => {
                 self.sequence += 1;
-                Some((&mut self.out[..encoded], self.server_addr))
+                Some((&mut self.out[..encoded], addr))
             }
         }
     }
@@ -425,11 +643,18 @@ mod tests {
 
         let max_clients = 4;
         let client_index = 2;
-        let keep_alive_packet = Packet::KeepAlive { max_clients, client_index };
+        let keep_alive_packet = Packet::KeepAlive {
+            max_clients,
+            client_index,
+            keep_alive_interval_ms: Some(1000),
+            resume_ticket: None,
+        };
         let len = keep_alive_packet.encode(&mut buffer, protocol_id, Some((1, &server_key))).unwrap();
         client.process_packet(&mut buffer[..len]);
 
         assert_eq!(client.state, ClientState::Connected);
+        assert_eq!(client.keep_alive_interval(), Duration::from_millis(1000));
+        assert_eq!(client.timeout(), Some(Duration::from_secs(timeout_seconds as u64)));
 
         let payload = vec![7u8; 500];
         let payload_packet = Packet::Payload(&payload[..]);
@@ -446,4 +671,80 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    #[test]
+    fn happy_eyeballs() {
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let ipv4_addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let ipv6_addr: SocketAddr = "[::1]:8080".parse().unwrap();
+        let protocol_id = 2;
+        let client_id = 4;
+        let connect_token = ConnectToken::generate(
+            Duration::ZERO,
+            protocol_id,
+            30,
+            client_id,
+            5,
+            vec![ipv4_addr, ipv6_addr],
+            None,
+            b"an example very very secret key.",
+        )
+        .unwrap();
+        let server_key = connect_token.server_to_client_key;
+        let authentication = ClientAuthentication::Secure { connect_token };
+        let mut client = NetcodeClient::new(Duration::ZERO, authentication).unwrap();
+        client.set_happy_eyeballs(true);
+
+        // IPv6 is tried first, and after the delay both addresses are tried
+        let (_, addr) = client.update(Duration::ZERO).unwrap();
+        assert_eq!(addr, ipv6_addr);
+        let (_, addr) = client.update(NETCODE_SEND_RATE).unwrap();
+        assert_eq!(addr, ipv4_addr);
+        let (_, addr) = client.update(NETCODE_SEND_RATE).unwrap();
+        assert_eq!(addr, ipv6_addr);
+
+        // Commits to the first server that answers
+        let challenge_packet = Packet::generate_challenge(client_id, &[0; NETCODE_USER_DATA_BYTES], 0, &generate_random_bytes()).unwrap();
+        let len = challenge_packet.encode(&mut buffer, protocol_id, Some((0, &server_key))).unwrap();
+        client.process_packet_from(&mut buffer[..len], ipv4_addr);
+        assert_eq!(ClientState::SendingConnectionResponse, client.state);
+        assert_eq!(client.server_addr(), ipv4_addr);
+        assert!(!client.is_server_addr(ipv6_addr));
+
+        let (_, addr) = client.update(NETCODE_SEND_RATE).unwrap();
+        assert_eq!(addr, ipv4_addr);
+    }
+
+    #[test]
+    fn happy_eyeballs_denied() {
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let first_addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let second_addr: SocketAddr = "127.0.0.2:8080".parse().unwrap();
+        let protocol_id = 2;
+        let connect_token = ConnectToken::generate(
+            Duration::ZERO,
+            protocol_id,
+            30,
+            4,
+            5,
+            vec![first_addr, second_addr],
+            None,
+            b"an example very very secret key.",
+        )
+        .unwrap();
+        let server_key = connect_token.server_to_client_key;
+        let authentication = ClientAuthentication::Secure { connect_token };
+        let mut client = NetcodeClient::new(Duration::ZERO, authentication).unwrap();
+        client.set_happy_eyeballs(true);
+        assert_eq!(client.update(Duration::ZERO).unwrap().1, first_addr);
+
+        // Denied by one of the servers, continues with the other one
+        let len = Packet::ConnectionDenied
+            .encode(&mut buffer, protocol_id, Some((1, &server_key)))
+            .unwrap();
+        client.process_packet_from(&mut buffer[..len], first_addr);
+        assert!(client.is_connecting());
+        assert_eq!(client.server_addr(), second_addr);
+        assert_eq!(client.update(Duration::ZERO).unwrap().1, second_addr);
+    }
 }
@@ -1,65 +1,211 @@
+use std::sync::OnceLock;
+use std::{error, fmt};
+
 use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
-use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, Error as CryptoError, Key, KeyInit, Nonce, Tag, XChaCha20Poly1305, XNonce};
+use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305, Key, KeyInit, Nonce, Tag, XChaCha20Poly1305, XNonce};
+
+pub use chacha20poly1305::aead::Error as CryptoError;
 
 use crate::NETCODE_MAC_BYTES;
 
-pub fn dencrypted_in_place(buffer: &mut [u8], sequence: u64, private_key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
-    let mut nonce = [0; 12];
-    nonce[4..12].copy_from_slice(&sequence.to_le_bytes());
-    let nonce = Nonce::from(nonce);
-    let (buffer, tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
-    let tag = Tag::from_slice(tag);
+static CRYPTO_BACKEND: OnceLock<Box<dyn CryptoBackend>> = OnceLock::new();
+
+/// Authenticated encryption and randomness used by the netcode protocol.
+///
+/// The default backend is [`ChaChaCrypto`], that follows the netcode standard. Another backend can be set
+/// with [`set_crypto_backend`] when the platform requires a certified implementation, all the clients,
+/// servers and the connect token generator must use compatible backends.
+///
+/// In all methods the last 16 bytes of the buffer are the authentication tag.
+///
+/// The connect token methods are always called with the private key shared between the servers and the
+/// token generator, so a backend can use it as an identifier for a key stored in the OS keystore
+/// instead of the key itself.
+pub trait CryptoBackend: Send + Sync + 'static {
+    /// Encrypts a packet in place, the nonce is derived from the packet sequence.
+    fn encrypt_packet(&self, buffer: &mut [u8], sequence: u64, key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError>;
+
+    /// Decrypts a packet in place, the nonce is derived from the packet sequence.
+    fn decrypt_packet(&self, buffer: &mut [u8], sequence: u64, key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError>;
+
+    /// Encrypts the private part of a connect token in place.
+    fn encrypt_token(&self, buffer: &mut [u8], xnonce: &[u8; 24], private_key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError>;
 
-    let key = Key::from_slice(private_key);
-    let cipher = ChaCha20Poly1305::new(key);
+    /// Decrypts the private part of a connect token in place.
+    fn decrypt_token(&self, buffer: &mut [u8], xnonce: &[u8; 24], private_key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError>;
 
-    cipher.decrypt_in_place_detached(&nonce, aad, buffer, tag)
+    /// Fills the buffer with cryptographically secure random bytes, used for keys, nonces and tokens.
+    /// Defaults to the randomness from the operating system.
+    fn fill_random_bytes(&self, buffer: &mut [u8]) {
+        OsRng.fill_bytes(buffer);
+    }
 }
 
-pub fn dencrypted_in_place_xnonce(buffer: &mut [u8], xnonce: &[u8; 24], private_key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
-    let xnonce = XNonce::from_slice(xnonce);
-    let (buffer, tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
-    let tag = Tag::from_slice(tag);
+/// Crypto backend from the netcode standard: ChaCha20-Poly1305 for packets
+/// and XChaCha20-Poly1305 for connect tokens.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ChaChaCrypto;
+
+impl CryptoBackend for ChaChaCrypto {
+    fn encrypt_packet(&self, buffer: &mut [u8], sequence: u64, key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
+        let nonce = Nonce::from(sequence_nonce(sequence));
+        let (buffer, buffer_tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
+
+        let key = Key::from_slice(key);
+        let cipher = ChaCha20Poly1305::new(key);
+        let tag = cipher.encrypt_in_place_detached(&nonce, aad, buffer)?;
+        buffer_tag.copy_from_slice(&tag);
+
+        Ok(())
+    }
+
+    fn decrypt_packet(&self, buffer: &mut [u8], sequence: u64, key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
+        let nonce = Nonce::from(sequence_nonce(sequence));
+        let (buffer, tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
+        let tag = Tag::from_slice(tag);
+
+        let key = Key::from_slice(key);
+        let cipher = ChaCha20Poly1305::new(key);
+
+        cipher.decrypt_in_place_detached(&nonce, aad, buffer, tag)
+    }
 
-    let key = Key::from_slice(private_key);
-    let cipher = XChaCha20Poly1305::new(key);
+    fn encrypt_token(&self, buffer: &mut [u8], xnonce: &[u8; 24], private_key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
+        let (buffer, buffer_tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
 
-    cipher.decrypt_in_place_detached(xnonce, aad, buffer, tag)
+        let xnonce = XNonce::from_slice(xnonce);
+        let key = Key::from_slice(private_key);
+        let cipher = XChaCha20Poly1305::new(key);
+        let tag = cipher.encrypt_in_place_detached(xnonce, aad, buffer)?;
+        buffer_tag.copy_from_slice(&tag);
+
+        Ok(())
+    }
+
+    fn decrypt_token(&self, buffer: &mut [u8], xnonce: &[u8; 24], private_key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
+        let xnonce = XNonce::from_slice(xnonce);
+        let (buffer, tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
+        let tag = Tag::from_slice(tag);
+
+        let key = Key::from_slice(private_key);
+        let cipher = XChaCha20Poly1305::new(key);
+
+        cipher.decrypt_in_place_detached(xnonce, aad, buffer, tag)
+    }
 }
 
-pub fn encrypt_in_place(buffer: &mut [u8], sequence: u64, key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
+/// Crypto backend using AES-256-GCM for packets and connect tokens, faster on platforms with hardware AES.
+/// Not compatible with the netcode standard, all peers must use this backend.
+#[cfg(feature = "aes-gcm")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AesGcmCrypto;
+
+#[cfg(feature = "aes-gcm")]
+type Aes256GcmXNonce = aes_gcm::AesGcm<aes_gcm::aes::Aes256, aes_gcm::aead::consts::U24>;
+
+#[cfg(feature = "aes-gcm")]
+impl CryptoBackend for AesGcmCrypto {
+    fn encrypt_packet(&self, buffer: &mut [u8], sequence: u64, key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
+        let nonce = aes_gcm::Nonce::from(sequence_nonce(sequence));
+        let (buffer, buffer_tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
+
+        let cipher = aes_gcm::Aes256Gcm::new(key.into());
+        let tag = cipher.encrypt_in_place_detached(&nonce, aad, buffer)?;
+        buffer_tag.copy_from_slice(&tag);
+
+        Ok(())
+    }
+
+    fn decrypt_packet(&self, buffer: &mut [u8], sequence: u64, key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
+        let nonce = aes_gcm::Nonce::from(sequence_nonce(sequence));
+        let (buffer, tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
+        let tag = aes_gcm::Tag::from_slice(tag);
+
+        let cipher = aes_gcm::Aes256Gcm::new(key.into());
+
+        cipher.decrypt_in_place_detached(&nonce, aad, buffer, tag)
+    }
+
+    fn encrypt_token(&self, buffer: &mut [u8], xnonce: &[u8; 24], private_key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
+        let (buffer, buffer_tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
+
+        let cipher = Aes256GcmXNonce::new(private_key.into());
+        let tag = cipher.encrypt_in_place_detached(xnonce.into(), aad, buffer)?;
+        buffer_tag.copy_from_slice(&tag);
+
+        Ok(())
+    }
+
+    fn decrypt_token(&self, buffer: &mut [u8], xnonce: &[u8; 24], private_key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
+        let (buffer, tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
+        let tag = aes_gcm::Tag::from_slice(tag);
+
+        let cipher = Aes256GcmXNonce::new(private_key.into());
+
+        cipher.decrypt_in_place_detached(xnonce.into(), aad, buffer, tag)
+    }
+}
+
+/// Error returned by [`set_crypto_backend`] when a backend is already in use.
+#[derive(Debug)]
+pub struct SetCryptoBackendError;
+
+impl fmt::Display for SetCryptoBackendError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "crypto backend was already set or used")
+    }
+}
+
+impl error::Error for SetCryptoBackendError {}
+
+/// Sets the crypto backend used by renetcode in this process.
+///
+/// Must be called before creating any client, server or connect token, after that
+/// the backend in use can no longer be changed and an error is returned.
+///
+/// # Usage
+/// ```
+/// # use renetcode::{set_crypto_backend, ChaChaCrypto};
+/// set_crypto_backend(ChaChaCrypto).unwrap();
+/// ```
+pub fn set_crypto_backend(backend: impl CryptoBackend) -> Result<(), SetCryptoBackendError> {
+    CRYPTO_BACKEND.set(Box::new(backend)).map_err(|_| SetCryptoBackendError)
+}
+
+fn crypto_backend() -> &'static dyn CryptoBackend {
+    CRYPTO_BACKEND.get_or_init(|| Box::new(ChaChaCrypto)).as_ref()
+}
+
+fn sequence_nonce(sequence: u64) -> [u8; 12] {
     let mut nonce = [0; 12];
     nonce[4..12].copy_from_slice(&sequence.to_le_bytes());
-    let nonce = Nonce::from(nonce);
-    let (buffer, buffer_tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
-
-    let key = Key::from_slice(key);
-    let cipher = ChaCha20Poly1305::new(key);
-    let tag = cipher.encrypt_in_place_detached(&nonce, aad, buffer)?;
-    buffer_tag.copy_from_slice(&tag);
+    nonce
+}
 
-    Ok(())
+pub fn dencrypted_in_place(buffer: &mut [u8], sequence: u64, private_key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
+    crypto_backend().decrypt_packet(buffer, sequence, private_key, aad)
 }
 
-pub fn encrypt_in_place_xnonce(buffer: &mut [u8], xnonce: &[u8; 24], key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
-    let (buffer, buffer_tag) = buffer.split_at_mut(buffer.len() - NETCODE_MAC_BYTES);
+pub fn dencrypted_in_place_xnonce(buffer: &mut [u8], xnonce: &[u8; 24], private_key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
+    crypto_backend().decrypt_token(buffer, xnonce, private_key, aad)
+}
 
-    let xnonce = XNonce::from_slice(xnonce);
-    let key = Key::from_slice(key);
-    let cipher = XChaCha20Poly1305::new(key);
-    let tag = cipher.encrypt_in_place_detached(xnonce, aad, buffer)?;
-    buffer_tag.copy_from_slice(&tag);
+pub fn encrypt_in_place(buffer: &mut [u8], sequence: u64, key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
+    crypto_backend().encrypt_packet(buffer, sequence, key, aad)
+}
 
-    Ok(())
+pub fn encrypt_in_place_xnonce(buffer: &mut [u8], xnonce: &[u8; 24], key: &[u8; 32], aad: &[u8]) -> Result<(), CryptoError> {
+    crypto_backend().encrypt_token(buffer, xnonce, key, aad)
 }
 
-/// Generate a buffer with random bytes using randomness from the operating system.
+/// Generate a buffer with random bytes using the randomness from the crypto backend,
+/// by default from the operating system.
 ///
-/// The implementation is provided by the `getrandom` crate. Refer to
+/// The default implementation is provided by the `getrandom` crate. Refer to
 /// `getrandom` documentation for details.
 pub fn generate_random_bytes<const N: usize>() -> [u8; N] {
     let mut bytes = [0; N];
-    OsRng.fill_bytes(&mut bytes);
+    crypto_backend().fill_random_bytes(&mut bytes);
     bytes
 }
 
@@ -81,4 +227,31 @@ mod tests {
         dencrypted_in_place(&mut data, sequence, key, aad).unwrap();
         assert_eq!(&data[..data_len], b"some packet data");
     }
+
+    #[test]
+    fn test_set_backend_after_use() {
+        generate_random_bytes::<8>();
+        assert!(set_crypto_backend(ChaChaCrypto).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "aes-gcm")]
+    fn test_aes_gcm_backend() {
+        let key = b"an example very very secret key."; // 32-bytes
+        let aad = b"test";
+
+        let mut data = b"some packet data".to_vec();
+        let data_len = data.len();
+        data.extend_from_slice(&[0u8; NETCODE_MAC_BYTES]);
+
+        AesGcmCrypto.encrypt_packet(&mut data, 2, key, aad).unwrap();
+        assert!(ChaChaCrypto.decrypt_packet(&mut data.clone(), 2, key, aad).is_err());
+        AesGcmCrypto.decrypt_packet(&mut data, 2, key, aad).unwrap();
+        assert_eq!(&data[..data_len], b"some packet data");
+
+        let xnonce = [7; 24];
+        AesGcmCrypto.encrypt_token(&mut data, &xnonce, key, aad).unwrap();
+        AesGcmCrypto.decrypt_token(&mut data, &xnonce, key, aad).unwrap();
+        assert_eq!(&data[..data_len], b"some packet data");
+    }
 }
@@ -14,11 +14,25 @@
 //! - DDoS amplification
 //! - Packet replay attacks
 //!
+//! With the `low-level` feature, the `packet` module is public, so other transports
+//! can reuse the packet encryption and framing without the client and server state machines.
+//!
+//! The encryption and the random number generator can be replaced with [`set_crypto_backend`],
+//! the `aes-gcm` feature adds the `AesGcmCrypto` backend.
+//!
 //! [standard]: https://github.com/networkprotocol/netcode/blob/master/STANDARD.md
 //! [netcode]: https://github.com/networkprotocol/netcode
+
+// The `tracing` feature emits the log events with tracing
+#[cfg(feature = "tracing")]
+extern crate tracing as log;
+
 mod client;
 mod crypto;
 mod error;
+#[cfg(feature = "low-level")]
+pub mod packet;
+#[cfg(not(feature = "low-level"))]
 mod packet;
 mod replay_protection;
 mod serialize;
@@ -26,16 +40,24 @@ mod server;
 mod token;
 
 pub use client::{ClientAuthentication, DisconnectReason, NetcodeClient};
-pub use crypto::generate_random_bytes;
+#[cfg(feature = "aes-gcm")]
+pub use crypto::AesGcmCrypto;
+pub use crypto::{generate_random_bytes, set_crypto_backend, ChaChaCrypto, CryptoBackend, CryptoError, SetCryptoBackendError};
 pub use error::NetcodeError;
-pub use server::{NetcodeServer, ServerAuthentication, ServerConfig, ServerResult};
+pub use server::{
+    ConnectTokenStore, ConnectionApprovalFn, ConnectionDeniedReason, NetcodeServer, ServerAuthentication, ServerConfig, ServerResult,
+};
 pub use token::{ConnectToken, TokenGenerationError};
 
+#[cfg(feature = "low-level")]
+pub use replay_protection::ReplayProtection;
+
 use std::time::Duration;
 
 const NETCODE_VERSION_INFO: &[u8; 13] = b"NETCODE 1.02\0";
 const NETCODE_MAX_CLIENTS: usize = 1024;
-const NETCODE_MAX_PENDING_CLIENTS: usize = NETCODE_MAX_CLIENTS * 4;
+/// The default maximum number of clients that can be pending in the handshake at a time.
+pub const NETCODE_MAX_PENDING_CLIENTS: usize = NETCODE_MAX_CLIENTS * 4;
 
 const NETCODE_ADDRESS_NONE: u8 = 0;
 const NETCODE_ADDRESS_IPV4: u8 = 1;
@@ -53,7 +75,12 @@ const NETCODE_MAC_BYTES: usize = 16;
 /// The number of bytes that an user data can contain in the ConnectToken.
 pub const NETCODE_USER_DATA_BYTES: usize = 256;
 const NETCODE_CHALLENGE_TOKEN_BYTES: usize = 300;
+/// The number of bytes of a resume ticket, the ticket sequence and the encrypted client id.
+pub const NETCODE_RESUME_TICKET_BYTES: usize = 8 + 8 + NETCODE_MAC_BYTES;
 const NETCODE_CONNECT_TOKEN_XNONCE_BYTES: usize = 24;
 
 const NETCODE_ADDITIONAL_DATA_SIZE: usize = 13 + 8 + 8;
-const NETCODE_SEND_RATE: Duration = Duration::from_millis(250);
+/// The default interval between keep-alive packets, when no other packets are sent.
+pub const NETCODE_SEND_RATE: Duration = Duration::from_millis(250);
+/// The default maximum time a suspended client is kept connected without sending packets.
+pub const NETCODE_MAX_SUSPEND_DURATION: Duration = Duration::from_secs(30);
@@ -1,3 +1,28 @@
+//! Encoding and decoding of netcode packets, available with the `low-level` feature.
+//!
+//! Packets, except connection requests, are encrypted with the connection key and the packet sequence,
+//! and decoded packets can be checked against replays with a [`ReplayProtection`].
+//!
+// The module is only public with the `low-level` feature, so is the usage example
+#![cfg_attr(
+    feature = "low-level",
+    doc = r#"
+# Usage
+```
+# use renetcode::{packet::Packet, ReplayProtection, NETCODE_MAX_PACKET_BYTES};
+let protocol_id = 7;
+let key = [1u8; 32];
+let mut replay_protection = ReplayProtection::new();
+
+let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+let sequence = 0;
+let len = Packet::Payload(b"hello").encode(&mut buffer, protocol_id, Some((sequence, &key))).unwrap();
+
+let (_, packet) = Packet::decode(&mut buffer[..len], protocol_id, Some(&key), Some(&mut replay_protection)).unwrap();
+assert_eq!(packet, Packet::Payload(b"hello"));
+```
+"#
+)]
 use std::io::{self, Cursor, Write};
 
 use crate::crypto::{dencrypted_in_place, encrypt_in_place};
@@ -5,12 +30,13 @@ use crate::replay_protection::ReplayProtection;
 use crate::token::ConnectToken;
 use crate::{
     serialize::*, NetcodeError, NETCODE_CHALLENGE_TOKEN_BYTES, NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES,
-    NETCODE_KEY_BYTES, NETCODE_MAC_BYTES,
+    NETCODE_KEY_BYTES, NETCODE_MAC_BYTES, NETCODE_RESUME_TICKET_BYTES, NETCODE_SEND_RATE,
 };
 use crate::{NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO};
 
 #[derive(Debug)]
 #[repr(u8)]
+/// Type of a netcode packet, written in the packet prefix byte.
 pub enum PacketType {
     ConnectionRequest = 0,
     ConnectionDenied = 1,
@@ -19,10 +45,12 @@ pub enum PacketType {
     KeepAlive = 4,
     Payload = 5,
     Disconnect = 6,
+    Suspend = 7,
 }
 
 #[derive(Debug, PartialEq, Eq)]
 #[allow(clippy::large_enum_variant)] // TODO: Consider boxing types
+/// A netcode packet, payload packets borrow the buffer they were decoded from.
 pub enum Packet<'a> {
     ConnectionRequest {
         version_info: [u8; 13], // "NETCODE 1.02" ASCII with null terminator.
@@ -39,15 +67,27 @@ pub enum Packet<'a> {
     Response {
         token_sequence: u64,
         token_data: [u8; NETCODE_CHALLENGE_TOKEN_BYTES], // encrypted ChallengeToken
+        /// Ticket of a previous connection to resume, not part of the netcode standard.
+        resume_ticket: Option<[u8; NETCODE_RESUME_TICKET_BYTES]>,
     },
     KeepAlive {
         client_index: u32,
         max_clients: u32,
+        /// Interval between the server keep-alive packets, only written when it's not the default.
+        /// Not part of the netcode standard, so it's omitted to stay compatible with other implementations.
+        keep_alive_interval_ms: Option<u32>,
+        /// Ticket to resume the connection, sent until the client confirms the connection.
+        /// Not part of the netcode standard, only written when the server has session resumption enabled.
+        resume_ticket: Option<[u8; NETCODE_RESUME_TICKET_BYTES]>,
     },
     Payload(&'a [u8]),
     Disconnect,
+    /// Sent by the client when the app goes to the background, the server doesn't time out the client for a while.
+    /// Not part of the netcode standard.
+    Suspend,
 }
 
+/// Token sent encrypted in the challenge packet, only the server can decrypt it.
 #[derive(Debug, PartialEq, Eq)]
 pub struct ChallengeToken {
     pub client_id: u64,
@@ -66,6 +106,7 @@ impl PacketType {
             4 => KeepAlive,
             5 => Payload,
             6 => Disconnect,
+            7 => Suspend,
             _ => return Err(NetcodeError::InvalidPacketType),
         };
         Ok(packet_type)
@@ -74,7 +115,7 @@ impl PacketType {
     fn apply_replay_protection(&self) -> bool {
         use PacketType::*;
 
-        matches!(self, KeepAlive | Payload | Disconnect)
+        matches!(self, KeepAlive | Payload | Disconnect | Suspend)
     }
 }
 
@@ -88,6 +129,7 @@ impl<'a> Packet<'a> {
             Packet::KeepAlive { .. } => PacketType::KeepAlive,
             Packet::Payload { .. } => PacketType::Payload,
             Packet::Disconnect => PacketType::Disconnect,
+            Packet::Suspend => PacketType::Suspend,
         }
     }
 
@@ -140,22 +182,45 @@ impl<'a> Packet<'a> {
             Packet::Challenge {
                 token_data,
                 token_sequence,
+            } => {
+                writer.write_all(&token_sequence.to_le_bytes())?;
+                writer.write_all(token_data)?;
             }
-            | Packet::Response {
+            Packet::Response {
                 token_data,
                 token_sequence,
+                resume_ticket,
             } => {
                 writer.write_all(&token_sequence.to_le_bytes())?;
                 writer.write_all(token_data)?;
+                if let Some(resume_ticket) = resume_ticket {
+                    writer.write_all(resume_ticket)?;
+                }
             }
-            Packet::KeepAlive { max_clients, client_index } => {
+            Packet::KeepAlive {
+                max_clients,
+                client_index,
+                keep_alive_interval_ms,
+                resume_ticket,
+            } => {
                 writer.write_all(&client_index.to_le_bytes())?;
                 writer.write_all(&max_clients.to_le_bytes())?;
+                // The ticket is written after the interval, so the interval is written even when it's the default
+                let keep_alive_interval_ms = match resume_ticket {
+                    Some(_) => Some(keep_alive_interval_ms.unwrap_or(NETCODE_SEND_RATE.as_millis() as u32)),
+                    None => *keep_alive_interval_ms,
+                };
+                if let Some(keep_alive_interval_ms) = keep_alive_interval_ms {
+                    writer.write_all(&keep_alive_interval_ms.to_le_bytes())?;
+                }
+                if let Some(resume_ticket) = resume_ticket {
+                    writer.write_all(resume_ticket)?;
+                }
             }
             Packet::Payload(p) => {
                 writer.write_all(p)?;
             }
-            Packet::ConnectionDenied | Packet::Disconnect => {}
+            Packet::ConnectionDenied | Packet::Disconnect | Packet::Suspend => {}
         }
 
         Ok(())
@@ -196,24 +261,37 @@ impl<'a> Packet<'a> {
             PacketType::Response => {
                 let token_sequence = read_u64(src)?;
                 let token_data = read_bytes(src)?;
+                let resume_ticket = read_bytes(src).ok();
 
                 Ok(Packet::Response {
                     token_data,
                     token_sequence,
+                    resume_ticket,
                 })
             }
             PacketType::KeepAlive => {
                 let client_index = read_u32(src)?;
                 let max_clients = read_u32(src)?;
-
-                Ok(Packet::KeepAlive { client_index, max_clients })
+                let keep_alive_interval_ms = read_u32(src).ok();
+                let resume_ticket = read_bytes(src).ok();
+
+                Ok(Packet::KeepAlive {
+                    client_index,
+                    max_clients,
+                    keep_alive_interval_ms,
+                    resume_ticket,
+                })
             }
             PacketType::ConnectionDenied => Ok(Packet::ConnectionDenied),
             PacketType::Disconnect => Ok(Packet::Disconnect),
+            PacketType::Suspend => Ok(Packet::Suspend),
             PacketType::Payload => unreachable!(),
         }
     }
 
+    /// Writes the packet in the buffer, returns the number of bytes written.
+    /// Connection requests are written unencrypted,
+    /// the other packets are encrypted with the sequence and key from `crypto_info`.
     pub fn encode(&self, buffer: &mut [u8], protocol_id: u64, crypto_info: Option<(u64, &[u8; 32])>) -> Result<usize, NetcodeError> {
         if matches!(self, Packet::ConnectionRequest { .. }) {
             let mut writer = io::Cursor::new(buffer);
@@ -252,6 +330,8 @@ impl<'a> Packet<'a> {
         }
     }
 
+    /// Reads a packet from the buffer, decrypting it in place, returns the packet sequence and the packet.
+    /// If `replay_protection` is set, keep alive, payload and disconnect packets already received are rejected.
     pub fn decode(
         mut buffer: &'a mut [u8],
         protocol_id: u64,
@@ -417,15 +497,72 @@ mod tests {
         let connection_keep_alive = Packet::KeepAlive {
             max_clients: 2,
             client_index: 1,
+            keep_alive_interval_ms: None,
+            resume_ticket: None,
         };
 
         let mut buffer = Vec::new();
         connection_keep_alive.write(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), 8);
         let deserialized = Packet::read(PacketType::KeepAlive, buffer.as_slice()).unwrap();
 
         assert_eq!(deserialized, connection_keep_alive);
     }
 
+    #[test]
+    fn connection_keep_alive_interval_serialization() {
+        let connection_keep_alive = Packet::KeepAlive {
+            max_clients: 2,
+            client_index: 1,
+            keep_alive_interval_ms: Some(1000),
+            resume_ticket: None,
+        };
+
+        let mut buffer = Vec::new();
+        connection_keep_alive.write(&mut buffer).unwrap();
+        let deserialized = Packet::read(PacketType::KeepAlive, buffer.as_slice()).unwrap();
+
+        assert_eq!(deserialized, connection_keep_alive);
+    }
+
+    #[test]
+    fn resume_ticket_serialization() {
+        let connection_keep_alive = Packet::KeepAlive {
+            max_clients: 2,
+            client_index: 1,
+            keep_alive_interval_ms: None,
+            resume_ticket: Some([3u8; NETCODE_RESUME_TICKET_BYTES]),
+        };
+
+        let mut buffer = Vec::new();
+        connection_keep_alive.write(&mut buffer).unwrap();
+        let deserialized = Packet::read(PacketType::KeepAlive, buffer.as_slice()).unwrap();
+
+        // The default interval is written before the ticket
+        let Packet::KeepAlive {
+            keep_alive_interval_ms,
+            resume_ticket,
+            ..
+        } = deserialized
+        else {
+            unreachable!()
+        };
+        assert_eq!(keep_alive_interval_ms, Some(NETCODE_SEND_RATE.as_millis() as u32));
+        assert_eq!(resume_ticket, Some([3u8; NETCODE_RESUME_TICKET_BYTES]));
+
+        let connection_response = Packet::Response {
+            token_sequence: 0,
+            token_data: [1u8; 300],
+            resume_ticket: Some([3u8; NETCODE_RESUME_TICKET_BYTES]),
+        };
+
+        let mut buffer = Vec::new();
+        connection_response.write(&mut buffer).unwrap();
+        let deserialized = Packet::read(PacketType::Response, buffer.as_slice()).unwrap();
+
+        assert_eq!(deserialized, connection_response);
+    }
+
     #[test]
     fn prefix_sequence() {
         let packet_type = Packet::Disconnect.id();
@@ -457,6 +594,19 @@ mod tests {
         assert_eq!(packet, d_packet);
     }
 
+    #[test]
+    fn encrypt_decrypt_suspend_packet() {
+        let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+        let key = b"an example very very secret key."; // 32-bytes
+        let packet = Packet::Suspend;
+        let protocol_id = 12;
+        let sequence = 3;
+        let len = packet.encode(&mut buffer, protocol_id, Some((sequence, key))).unwrap();
+        let (d_sequence, d_packet) = Packet::decode(&mut buffer[..len], protocol_id, Some(key), None).unwrap();
+        assert_eq!(sequence, d_sequence);
+        assert_eq!(packet, d_packet);
+    }
+
     #[test]
     fn encrypt_decrypt_denied_packet() {
         let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
@@ -508,4 +658,89 @@ mod tests {
             _ => unreachable!(),
         }
     }
+
+    const TEST_VECTOR_PROTOCOL_ID: u64 = 0x1122334455667788;
+
+    // Key of the test vectors, the bytes from 0 to 31
+    fn test_vector_key() -> [u8; NETCODE_KEY_BYTES] {
+        std::array::from_fn(|i| i as u8)
+    }
+
+    // Packets and sequences of the test vectors, in the same order as the test_vectors/packets.txt file
+    fn test_vector_packets() -> Vec<(&'static str, u64, Packet<'static>)> {
+        vec![
+            (
+                "connection_request",
+                0,
+                Packet::ConnectionRequest {
+                    version_info: *NETCODE_VERSION_INFO,
+                    protocol_id: TEST_VECTOR_PROTOCOL_ID,
+                    expire_timestamp: 1_700_000_000,
+                    xnonce: [0x24; NETCODE_CONNECT_TOKEN_XNONCE_BYTES],
+                    data: [0x55; NETCODE_CONNECT_TOKEN_PRIVATE_BYTES],
+                },
+            ),
+            ("connection_denied", 1, Packet::ConnectionDenied),
+            (
+                "challenge",
+                2,
+                Packet::Challenge {
+                    token_sequence: 7,
+                    token_data: [0x11; NETCODE_CHALLENGE_TOKEN_BYTES],
+                },
+            ),
+            (
+                "response",
+                3,
+                Packet::Response {
+                    token_sequence: 7,
+                    token_data: [0x22; NETCODE_CHALLENGE_TOKEN_BYTES],
+                    resume_ticket: None,
+                },
+            ),
+            (
+                "keep_alive",
+                300,
+                Packet::KeepAlive {
+                    client_index: 2,
+                    max_clients: 16,
+                    keep_alive_interval_ms: None,
+                    resume_ticket: None,
+                },
+            ),
+            ("payload", 70000, Packet::Payload(b"hello")),
+            ("disconnect", u64::MAX, Packet::Disconnect),
+        ]
+    }
+
+    fn decode_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_vectors() {
+        let vectors = include_str!("../test_vectors/packets.txt");
+        let mut lines = vectors.lines().filter(|line| !line.is_empty() && !line.starts_with('#'));
+        let key = test_vector_key();
+
+        for (name, sequence, packet) in test_vector_packets() {
+            let line = lines.next().unwrap_or_else(|| panic!("missing test vector {name}"));
+            let (vector_name, hex) = line.split_once(' ').unwrap();
+            assert_eq!(vector_name, name);
+            let mut bytes = decode_hex(hex);
+
+            let mut buffer = [0u8; NETCODE_MAX_PACKET_BYTES];
+            let len = packet.encode(&mut buffer, TEST_VECTOR_PROTOCOL_ID, Some((sequence, &key))).unwrap();
+            assert_eq!(buffer[..len], bytes[..], "test vector {name}");
+
+            let (d_sequence, d_packet) = Packet::decode(&mut bytes, TEST_VECTOR_PROTOCOL_ID, Some(&key), None).unwrap();
+            assert_eq!(d_sequence, sequence, "test vector {name}");
+            assert_eq!(d_packet, packet, "test vector {name}");
+        }
+
+        assert_eq!(lines.next(), None, "test vector without packet");
+    }
 }
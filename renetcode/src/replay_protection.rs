@@ -1,6 +1,7 @@
 const NETCODE_REPLAY_BUFFER_SIZE: usize = 256;
 const EMPTY: u64 = u64::MAX;
 
+/// Tracks the sequences of the packets received, to reject packets that were already received.
 #[derive(Debug, Clone)]
 pub struct ReplayProtection {
     most_recent_sequence: u64,
@@ -1,13 +1,13 @@
 use std::{collections::HashMap, net::SocketAddr, time::Duration};
 
 use crate::{
-    crypto::generate_random_bytes,
+    crypto::{dencrypted_in_place, encrypt_in_place, generate_random_bytes},
     packet::{ChallengeToken, Packet},
     replay_protection::ReplayProtection,
     token::PrivateConnectToken,
     NetcodeError, NETCODE_CONNECT_TOKEN_PRIVATE_BYTES, NETCODE_CONNECT_TOKEN_XNONCE_BYTES, NETCODE_KEY_BYTES, NETCODE_MAC_BYTES,
-    NETCODE_MAX_CLIENTS, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_MAX_PENDING_CLIENTS, NETCODE_SEND_RATE,
-    NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO,
+    NETCODE_MAX_CLIENTS, NETCODE_MAX_PACKET_BYTES, NETCODE_MAX_PAYLOAD_BYTES, NETCODE_MAX_PENDING_CLIENTS, NETCODE_MAX_SUSPEND_DURATION,
+    NETCODE_RESUME_TICKET_BYTES, NETCODE_SEND_RATE, NETCODE_USER_DATA_BYTES, NETCODE_VERSION_INFO,
 };
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -29,16 +29,67 @@ struct Connection {
     last_packet_received_time: Duration,
     last_packet_send_time: Duration,
     timeout_seconds: i32,
+    // The client is in the background, it doesn't time out until then
+    suspended_until: Option<Duration>,
+    // Issued when connected with session resumption enabled
+    resume_ticket: Option<[u8; NETCODE_RESUME_TICKET_BYTES]>,
     sequence: u64,
     expire_timestamp: u64,
     replay_protection: ReplayProtection,
 }
 
+// Approximate memory used by each pending client
+const PENDING_CLIENT_BYTES: usize = std::mem::size_of::<SocketAddr>() + std::mem::size_of::<Connection>();
+
+// Connection that timed out, it can be resumed with its ticket during the grace period
+#[derive(Debug)]
+struct LostSession {
+    resume_ticket: [u8; NETCODE_RESUME_TICKET_BYTES],
+    lost_at: Duration,
+}
+
 #[derive(Debug, Copy, Clone)]
 struct ConnectTokenEntry {
     time: Duration,
     address: SocketAddr,
     mac: [u8; NETCODE_MAC_BYTES],
+    expire_timestamp: u64,
+}
+
+/// Callback that decides if a client is accepted, called with the client id, address and user data.
+pub type ConnectionApprovalFn = dyn FnMut(u64, SocketAddr, &[u8; NETCODE_USER_DATA_BYTES]) -> bool + Send + Sync;
+
+struct ConnectionApproval(Box<ConnectionApprovalFn>);
+
+impl std::fmt::Debug for ConnectionApproval {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ConnectionApproval")
+    }
+}
+
+/// Store of the connect tokens already used, shared by multiple servers.
+///
+/// Each server only remembers the connect tokens used in it, so when multiple servers share the same
+/// public address (behind a load balancer), a connect token could be used to connect to each of them.
+/// With a shared store (a Redis instance, for example), a connect token can only be used from one address in the fleet.
+///
+/// The store is only consulted for connect tokens not yet used in this server, retries of the same
+/// connection request are answered locally.
+pub trait ConnectTokenStore: Send + Sync {
+    /// Registers the connect token, identified by its mac, as used by the address.
+    /// Returns false if the token was already used by another address, and the connection request should be ignored.
+    ///
+    /// The entry can be removed from the store after the `expire_timestamp` (in seconds since the unix epoch),
+    /// since the token is no longer accepted after that.
+    fn use_connect_token(&mut self, mac: &[u8; NETCODE_MAC_BYTES], addr: SocketAddr, expire_timestamp: u64) -> bool;
+}
+
+struct SharedConnectTokenStore(Box<dyn ConnectTokenStore>);
+
+impl std::fmt::Debug for SharedConnectTokenStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SharedConnectTokenStore")
+    }
 }
 
 /// A server that can generate packets from connect clients, that are encrypted, or process
@@ -48,10 +99,26 @@ struct ConnectTokenEntry {
 pub struct NetcodeServer {
     clients: Box<[Option<Connection>]>,
     pending_clients: HashMap<SocketAddr, Connection>,
+    max_pending_clients: usize,
+    max_pending_memory: Option<usize>,
+    evicted_pending_clients: u64,
+    // Bans with the time they expire, if any
+    banned_client_ids: HashMap<u64, Option<Duration>>,
+    banned_addrs: HashMap<SocketAddr, Option<Duration>>,
+    // Slots reserved for clients, with the time the reservation expires
+    slot_reservations: HashMap<u64, Duration>,
+    connection_approval: Option<ConnectionApproval>,
     connect_token_entries: Box<[Option<ConnectTokenEntry>; NETCODE_MAX_CLIENTS * 2]>,
+    connect_token_store: Option<SharedConnectTokenStore>,
     protocol_id: u64,
     connect_key: [u8; NETCODE_KEY_BYTES],
     max_clients: usize,
+    keep_alive_interval: Duration,
+    max_suspend_duration: Duration,
+    session_resumption: Option<Duration>,
+    lost_sessions: HashMap<u64, LostSession>,
+    resume_key: [u8; NETCODE_KEY_BYTES],
+    resume_ticket_sequence: u64,
     challenge_sequence: u64,
     challenge_key: [u8; NETCODE_KEY_BYTES],
     public_addresses: Vec<SocketAddr>,
@@ -76,6 +143,8 @@ pub enum ServerResult<'a, 's> {
         addr: SocketAddr,
         user_data: Box<[u8; NETCODE_USER_DATA_BYTES]>,
         payload: &'s mut [u8],
+        /// The client presented the resume ticket of a lost connection, and continues with its client id.
+        resumed: bool,
     },
     /// The client connection has been terminated.
     ClientDisconnected {
@@ -83,6 +152,31 @@ pub enum ServerResult<'a, 's> {
         addr: SocketAddr,
         payload: Option<&'s mut [u8]>,
     },
+    /// A connection request was denied, the payload is the connection denied packet to be sent back when available.
+    ConnectionDenied {
+        addr: SocketAddr,
+        reason: ConnectionDeniedReason,
+        payload: Option<&'s mut [u8]>,
+    },
+}
+
+/// Reason for denying a connection request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionDeniedReason {
+    /// The connect token has expired.
+    TokenExpired,
+    /// The client uses a different netcode version.
+    InvalidVersion,
+    /// The client uses a different protocol id.
+    InvalidProtocolId,
+    /// The server address is not in the connect token.
+    NotInHostList,
+    /// The client id is banned.
+    Banned,
+    /// The server has no free slots.
+    ServerFull,
+    /// The connection approval callback rejected the client.
+    NotApproved,
 }
 
 /// Configuration to establish a secure or unsecure connection with the server.
@@ -110,6 +204,14 @@ pub struct ServerConfig {
     pub public_addresses: Vec<SocketAddr>,
     /// Authentication configuration for the server
     pub authentication: ServerAuthentication,
+    /// Interval between keep-alive packets sent to the clients, when no other packets are sent.
+    /// The clients use the same interval for their keep-alive packets.
+    /// Should be a fraction of the timeout in the connect tokens, [`NETCODE_SEND_RATE`] is the default.
+    pub keep_alive_interval: Duration,
+    /// Maximum numbers of clients that can be pending in the handshake at a time,
+    /// when reached, the pending client inactive for the longest time is evicted for a new one.
+    /// [`NETCODE_MAX_PENDING_CLIENTS`] is the default, lower it to bound the memory used by handshakes in small instances.
+    pub max_pending_clients: usize,
 }
 
 impl NetcodeServer {
@@ -119,6 +221,12 @@ impl NetcodeServer {
             //       only using for token entries
             panic!("The max clients allowed is {}", NETCODE_MAX_CLIENTS);
         }
+        if config.keep_alive_interval.is_zero() || config.keep_alive_interval.as_millis() > u32::MAX as u128 {
+            panic!("The keep alive interval must be between 1ms and {}ms", u32::MAX);
+        }
+        if config.max_pending_clients == 0 {
+            panic!("The max pending clients must be at least 1");
+        }
         let challenge_key = generate_random_bytes();
         let clients = vec![None; config.max_clients].into_boxed_slice();
 
@@ -136,9 +244,23 @@ impl NetcodeServer {
             clients,
             connect_token_entries: Box::new([None; NETCODE_MAX_CLIENTS * 2]),
             pending_clients: HashMap::new(),
+            max_pending_clients: config.max_pending_clients,
+            max_pending_memory: None,
+            evicted_pending_clients: 0,
+            banned_client_ids: HashMap::new(),
+            banned_addrs: HashMap::new(),
+            slot_reservations: HashMap::new(),
+            connection_approval: None,
+            connect_token_store: None,
             protocol_id: config.protocol_id,
             connect_key,
             max_clients: config.max_clients,
+            keep_alive_interval: config.keep_alive_interval,
+            max_suspend_duration: NETCODE_MAX_SUSPEND_DURATION,
+            session_resumption: None,
+            lost_sessions: HashMap::new(),
+            resume_key: generate_random_bytes(),
+            resume_ticket_sequence: 0,
             challenge_sequence: 0,
             global_sequence: 0,
             challenge_key,
@@ -157,6 +279,8 @@ impl NetcodeServer {
             protocol_id: 0,
             public_addresses: vec!["127.0.0.1:0".parse().unwrap()],
             authentication: ServerAuthentication::Unsecure,
+            keep_alive_interval: NETCODE_SEND_RATE,
+            max_pending_clients: NETCODE_MAX_PENDING_CLIENTS,
         };
         Self::new(config)
     }
@@ -169,6 +293,18 @@ impl NetcodeServer {
         self.current_time
     }
 
+    pub fn keep_alive_interval(&self) -> Duration {
+        self.keep_alive_interval
+    }
+
+    // The interval is only sent when it's not the default, so standard clients can still connect
+    fn keep_alive_interval_ms(&self) -> Option<u32> {
+        match self.keep_alive_interval == NETCODE_SEND_RATE {
+            true => None,
+            false => Some(self.keep_alive_interval.as_millis() as u32),
+        }
+    }
+
     fn find_or_add_connect_token_entry(&mut self, new_entry: ConnectTokenEntry) -> bool {
         let mut min = Duration::MAX;
         let mut oldest_entry = 0;
@@ -198,6 +334,15 @@ impl NetcodeServer {
             return entry.address == new_entry.address;
         }
 
+        if let Some(store) = &mut self.connect_token_store {
+            if !store
+                .0
+                .use_connect_token(&new_entry.mac, new_entry.address, new_entry.expire_timestamp)
+            {
+                return false;
+            }
+        }
+
         self.connect_token_entries[oldest_entry] = Some(new_entry);
 
         true
@@ -241,16 +386,25 @@ impl NetcodeServer {
         xnonce: [u8; NETCODE_CONNECT_TOKEN_XNONCE_BYTES],
         data: [u8; NETCODE_CONNECT_TOKEN_PRIVATE_BYTES],
     ) -> Result<ServerResult<'a, '_>, NetcodeError> {
+        let denied = |reason: ConnectionDeniedReason| {
+            log::debug!("Connection request from {} denied: {:?}.", addr, reason);
+            Ok(ServerResult::ConnectionDenied {
+                addr,
+                reason,
+                payload: None,
+            })
+        };
+
         if version_info != *NETCODE_VERSION_INFO {
-            return Err(NetcodeError::InvalidVersion);
+            return denied(ConnectionDeniedReason::InvalidVersion);
         }
 
         if protocol_id != self.protocol_id {
-            return Err(NetcodeError::InvalidProtocolID);
+            return denied(ConnectionDeniedReason::InvalidProtocolId);
         }
 
         if self.current_time.as_secs() >= expire_timestamp {
-            return Err(NetcodeError::Expired);
+            return denied(ConnectionDeniedReason::TokenExpired);
         }
 
         let connect_token = PrivateConnectToken::decode(&data, self.protocol_id, expire_timestamp, &xnonce, &self.connect_key)?;
@@ -264,10 +418,27 @@ impl NetcodeServer {
                 .any(|addr| self.public_addresses.contains(&addr));
 
             if !in_host_list {
-                return Err(NetcodeError::NotInHostList);
+                return denied(ConnectionDeniedReason::NotInHostList);
             }
         }
 
+        if self.is_client_id_banned(connect_token.client_id) {
+            log::debug!("Connection request denied: client {} is banned.", connect_token.client_id);
+            self.pending_clients.remove(&addr);
+            let packet = Packet::ConnectionDenied;
+            let len = packet.encode(
+                &mut self.out,
+                self.protocol_id,
+                Some((self.global_sequence, &connect_token.server_to_client_key)),
+            )?;
+            self.global_sequence += 1;
+            return Ok(ServerResult::ConnectionDenied {
+                addr,
+                reason: ConnectionDeniedReason::Banned,
+                payload: Some(&mut self.out[..len]),
+            });
+        }
+
         let addr_already_connected = find_client_mut_by_addr(&mut self.clients, addr).is_some();
         let id_already_connected = find_client_mut_by_id(&mut self.clients, connect_token.client_id).is_some();
         if id_already_connected || addr_already_connected {
@@ -279,12 +450,22 @@ impl NetcodeServer {
             return Ok(ServerResult::None);
         }
 
-        if !self.pending_clients.contains_key(&addr) && self.pending_clients.len() >= NETCODE_MAX_PENDING_CLIENTS {
+        if !self.pending_clients.contains_key(&addr) {
+            let pending_capacity = self.pending_capacity();
+            if pending_capacity == 0 {
+                log::warn!("Connection request denied: the pending clients memory limit is lower than a single pending client.");
+                return Ok(ServerResult::None);
+            }
+            self.evict_pending_clients(pending_capacity - 1);
+        }
+
+        if connect_token.timeout_seconds > 0 && Duration::from_secs(connect_token.timeout_seconds as u64) <= self.keep_alive_interval {
             log::warn!(
-                "Connection request denied: reached max amount allowed of pending clients ({}).",
-                NETCODE_MAX_PENDING_CLIENTS
+                "Client {} connect token timeout ({}s) is not above the keep alive interval ({:?}), the connection will time out.",
+                connect_token.client_id,
+                connect_token.timeout_seconds,
+                self.keep_alive_interval
             );
-            return Ok(ServerResult::None);
         }
 
         let mut mac = [0u8; NETCODE_MAC_BYTES];
@@ -293,6 +474,7 @@ impl NetcodeServer {
             address: addr,
             time: self.current_time,
             mac,
+            expire_timestamp,
         };
 
         if !self.find_or_add_connect_token_entry(connect_token_entry) {
@@ -301,6 +483,7 @@ impl NetcodeServer {
         }
 
         if self.clients.iter().flatten().count() >= self.max_clients {
+            log::debug!("Connection request denied: server is full.");
             self.pending_clients.remove(&addr);
             let packet = Packet::ConnectionDenied;
             let len = packet.encode(
@@ -309,9 +492,10 @@ impl NetcodeServer {
                 Some((self.global_sequence, &connect_token.server_to_client_key)),
             )?;
             self.global_sequence += 1;
-            return Ok(ServerResult::PacketToSend {
+            return Ok(ServerResult::ConnectionDenied {
                 addr,
-                payload: &mut self.out[..len],
+                reason: ConnectionDeniedReason::ServerFull,
+                payload: Some(&mut self.out[..len]),
             });
         }
 
@@ -343,6 +527,8 @@ impl NetcodeServer {
             send_key: connect_token.server_to_client_key,
             receive_key: connect_token.client_to_server_key,
             timeout_seconds: connect_token.timeout_seconds,
+            suspended_until: None,
+            resume_ticket: None,
             expire_timestamp,
             user_data: connect_token.user_data,
             replay_protection: ReplayProtection::new(),
@@ -376,6 +562,7 @@ impl NetcodeServer {
 
     /// Process an packet from the especifed address. Returns a server result, check out
     /// [ServerResult].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self, buffer), fields(len = buffer.len())))]
     pub fn process_packet<'a, 's>(&'s mut self, addr: SocketAddr, buffer: &'a mut [u8]) -> ServerResult<'a, 's> {
         match self.process_packet_internal(addr, buffer) {
             Err(e) => {
@@ -406,6 +593,12 @@ impl NetcodeServer {
             );
 
             client.last_packet_received_time = self.current_time;
+            if client.state == ConnectionState::Connected {
+                client.suspended_until = match packet {
+                    Packet::Suspend => Some(self.current_time + self.max_suspend_duration),
+                    _ => None,
+                };
+            }
             match client.state {
                 ConnectionState::Connected => match packet {
                     Packet::Disconnect => {
@@ -436,12 +629,21 @@ impl NetcodeServer {
                         }
                         return Ok(ServerResult::None);
                     }
+                    Packet::Suspend => {
+                        log::debug!("Client {} suspended", client.client_id);
+                        return Ok(ServerResult::None);
+                    }
                     _ => return Ok(ServerResult::None),
                 },
                 _ => return Ok(ServerResult::None),
             }
         }
 
+        if self.is_addr_banned(addr) {
+            log::trace!("Ignored packet from banned address {}.", addr);
+            return Ok(ServerResult::None);
+        }
+
         // Handle pending client
         if let Some(pending) = self.pending_clients.get_mut(&addr) {
             let (_, packet) = Packet::decode(
@@ -465,6 +667,7 @@ impl NetcodeServer {
                 Packet::Response {
                     token_data,
                     token_sequence,
+                    resume_ticket,
                 } => {
                     let challenge_token = ChallengeToken::decode(token_data, token_sequence, &self.challenge_key)?;
                     let mut pending = self.pending_clients.remove(&addr).unwrap();
@@ -475,26 +678,68 @@ impl NetcodeServer {
                         );
                         return Ok(ServerResult::None);
                     }
-                    match self.clients.iter().position(|c| c.is_none()) {
+
+                    // The client continues with the client id of the resumed connection
+                    let resumed_client_id = resume_ticket.and_then(|resume_ticket| self.resume_session(&resume_ticket));
+                    if let Some(resumed_client_id) = resumed_client_id {
+                        log::debug!(
+                            "Client {} resumed the connection of Client {}",
+                            pending.client_id,
+                            resumed_client_id
+                        );
+                        pending.client_id = resumed_client_id;
+                    }
+                    let approved = match &mut self.connection_approval {
+                        Some(approval) => (approval.0)(pending.client_id, addr, &challenge_token.user_data),
+                        None => true,
+                    };
+                    if !approved {
+                        log::debug!("Connection denied for Client {}, not approved.", pending.client_id);
+                    }
+
+                    // The slots can be above max_clients if it was lowered while clients were connected,
+                    // the slots reserved for other clients are not free
+                    let reserved_slots = self.reserved_slots() - self.slot_reservations.contains_key(&pending.client_id) as usize;
+                    let free_slot = if !approved {
+                        None
+                    } else if self.connected_clients() + reserved_slots < self.max_clients {
+                        self.clients[..self.max_clients].iter().position(|c| c.is_none())
+                    } else {
+                        None
+                    };
+                    match free_slot {
                         None => {
                             let packet = Packet::ConnectionDenied;
                             let len = packet.encode(&mut self.out, self.protocol_id, Some((self.global_sequence, &pending.send_key)))?;
                             pending.state = ConnectionState::Disconnected;
                             self.global_sequence += 1;
                             pending.last_packet_send_time = self.current_time;
-                            return Ok(ServerResult::PacketToSend {
+                            let reason = match approved {
+                                true => ConnectionDeniedReason::ServerFull,
+                                false => ConnectionDeniedReason::NotApproved,
+                            };
+                            return Ok(ServerResult::ConnectionDenied {
                                 addr,
-                                payload: &mut self.out[..len],
+                                reason,
+                                payload: Some(&mut self.out[..len]),
                             });
                         }
                         Some(client_index) => {
                             pending.state = ConnectionState::Connected;
                             pending.user_data = challenge_token.user_data;
                             pending.last_packet_send_time = self.current_time;
+                            if self.session_resumption.is_some() {
+                                let resume_ticket =
+                                    generate_resume_ticket(pending.client_id, self.resume_ticket_sequence, &self.resume_key)?;
+                                self.resume_ticket_sequence += 1;
+                                pending.resume_ticket = Some(resume_ticket);
+                            }
 
                             let packet = Packet::KeepAlive {
                                 max_clients: self.max_clients as u32,
                                 client_index: client_index as u32,
+                                keep_alive_interval_ms: self.keep_alive_interval_ms(),
+                                resume_ticket: pending.resume_ticket,
                             };
                             let len = packet.encode(&mut self.out, self.protocol_id, Some((pending.sequence, &pending.send_key)))?;
                             pending.sequence += 1;
@@ -502,12 +747,16 @@ impl NetcodeServer {
                             let client_id: u64 = pending.client_id;
                             let user_data: [u8; NETCODE_USER_DATA_BYTES] = pending.user_data;
                             self.clients[client_index] = Some(pending);
+                            self.slot_reservations.remove(&client_id);
+
+                            log::debug!("Client {client_id} connected in slot {client_index}");
 
                             return Ok(ServerResult::ClientConnected {
                                 client_id,
                                 addr,
                                 user_data: Box::new(user_data),
                                 payload: &mut self.out[..len],
+                                resumed: resumed_client_id.is_some(),
                             });
                         }
                     }
@@ -553,7 +802,8 @@ impl NetcodeServer {
         self.max_clients
     }
 
-    /// Update the maximum numbers of clients that can be connected
+    /// Update the maximum numbers of clients that can be connected, resizing the client slots.
+    /// New connections are denied while the server is at the limit.
     ///
     /// Changing the `max_clients` to a lower value than the current number of connect clients
     /// does not disconnect clients. So [`NetcodeServer::connected_clients()`] can return a
@@ -563,6 +813,115 @@ impl NetcodeServer {
         log::debug!("Netcode max_clients set to {}", max_clients);
 
         self.max_clients = max_clients;
+
+        // Connected clients keep their slot index, so we can only shrink down to the last slot in use
+        let slots_in_use = self.clients.iter().rposition(|slot| slot.is_some()).map_or(0, |index| index + 1);
+        let mut clients = std::mem::take(&mut self.clients).into_vec();
+        clients.resize(max_clients.max(slots_in_use), None);
+        self.clients = clients.into_boxed_slice();
+    }
+
+    /// Reserves a slot for the client for the duration, for example while a matchmaker sends it the connect token.
+    /// Reserved slots are not free for other clients, the reservation ends when the client connects or it expires.
+    /// Reserving again the slot of a client updates the expiration.
+    ///
+    /// Returns false if there are no free slots to reserve.
+    pub fn reserve_slot(&mut self, client_id: u64, duration: Duration) -> bool {
+        let expire_time = self.current_time + duration;
+        if let Some(reservation) = self.slot_reservations.get_mut(&client_id) {
+            *reservation = expire_time;
+            return true;
+        }
+
+        if self.is_client_connected(client_id) || self.connected_clients() + self.reserved_slots() >= self.max_clients {
+            return false;
+        }
+
+        self.slot_reservations.insert(client_id, expire_time);
+        true
+    }
+
+    /// Cancels the slot reservation of the client, freeing the slot for other clients.
+    pub fn cancel_slot_reservation(&mut self, client_id: u64) {
+        self.slot_reservations.remove(&client_id);
+    }
+
+    /// Returns the number of slots reserved for clients not yet connected.
+    pub fn reserved_slots(&self) -> usize {
+        self.slot_reservations.len()
+    }
+
+    /// Bans a client id from connecting, for the given duration or permanently if `None`.
+    /// Connection requests from banned clients are denied before a pending connection is created.
+    ///
+    /// Does not disconnect the client if it's already connected, use [`NetcodeServer::disconnect`] for that.
+    pub fn ban_client_id(&mut self, client_id: u64, duration: Option<Duration>) {
+        log::debug!("Banned client {} for {:?}", client_id, duration);
+        self.banned_client_ids.insert(client_id, duration.map(|d| self.current_time + d));
+    }
+
+    /// Bans an address from connecting, for the given duration or permanently if `None`.
+    /// Packets from banned addresses are ignored before any decryption is done.
+    ///
+    /// Does not disconnect clients already connected from this address, use [`NetcodeServer::disconnect`] for that.
+    pub fn ban_addr(&mut self, addr: SocketAddr, duration: Option<Duration>) {
+        log::debug!("Banned address {} for {:?}", addr, duration);
+        self.banned_addrs.insert(addr, duration.map(|d| self.current_time + d));
+        self.pending_clients.remove(&addr);
+    }
+
+    /// Removes the ban from a client id.
+    pub fn unban_client_id(&mut self, client_id: u64) {
+        self.banned_client_ids.remove(&client_id);
+    }
+
+    /// Removes the ban from an address.
+    pub fn unban_addr(&mut self, addr: SocketAddr) {
+        self.banned_addrs.remove(&addr);
+    }
+
+    /// Returns whether the client id is currently banned.
+    pub fn is_client_id_banned(&self, client_id: u64) -> bool {
+        match self.banned_client_ids.get(&client_id) {
+            Some(&expire_time) => is_ban_active(expire_time, self.current_time),
+            None => false,
+        }
+    }
+
+    /// Returns whether the address is currently banned.
+    pub fn is_addr_banned(&self, addr: SocketAddr) -> bool {
+        match self.banned_addrs.get(&addr) {
+            Some(&expire_time) => is_ban_active(expire_time, self.current_time),
+            None => false,
+        }
+    }
+
+    /// Sets a callback to approve or deny clients before they are connected.
+    ///
+    /// The callback is called with the client id, address and user data after the connect token
+    /// is validated, before a slot is given to the client. When it returns `false`,
+    /// the client receives a connection denied packet.
+    pub fn set_connection_approval<F>(&mut self, approval: F)
+    where
+        F: FnMut(u64, SocketAddr, &[u8; NETCODE_USER_DATA_BYTES]) -> bool + Send + Sync + 'static,
+    {
+        self.connection_approval = Some(ConnectionApproval(Box::new(approval)));
+    }
+
+    /// Removes the connection approval callback, all valid clients are accepted.
+    pub fn clear_connection_approval(&mut self) {
+        self.connection_approval = None;
+    }
+
+    /// Sets a store shared between multiple servers to check if a connect token was already used.
+    /// See [`ConnectTokenStore`].
+    pub fn set_connect_token_store<S: ConnectTokenStore + 'static>(&mut self, store: S) {
+        self.connect_token_store = Some(SharedConnectTokenStore(Box::new(store)));
+    }
+
+    /// Removes the shared connect token store, tokens are only checked in this server.
+    pub fn clear_connect_token_store(&mut self) {
+        self.connect_token_store = None;
     }
 
     /// Returns current number of clients connected.
@@ -570,6 +929,75 @@ impl NetcodeServer {
         self.clients.iter().filter(|slot| slot.is_some()).count()
     }
 
+    /// Returns current number of clients pending in the handshake.
+    pub fn pending_clients(&self) -> usize {
+        self.pending_clients.len()
+    }
+
+    /// Returns the approximate memory in bytes used by the clients pending in the handshake.
+    pub fn pending_clients_memory(&self) -> usize {
+        self.pending_clients.len() * PENDING_CLIENT_BYTES
+    }
+
+    /// Returns the maximum numbers of clients that can be pending in the handshake at a time.
+    pub fn max_pending_clients(&self) -> usize {
+        self.max_pending_clients
+    }
+
+    /// Update the maximum numbers of clients that can be pending in the handshake at a time,
+    /// evicting the pending clients inactive for the longest time when lowered.
+    pub fn set_max_pending_clients(&mut self, max_pending_clients: usize) {
+        self.max_pending_clients = max_pending_clients.max(1);
+        self.evict_pending_clients(self.pending_capacity());
+    }
+
+    /// Limits the approximate memory in bytes used by the clients pending in the handshake,
+    /// when reached, the pending client inactive for the longest time is evicted for a new one.
+    /// The pending clients above the limit are evicted.
+    pub fn set_max_pending_memory(&mut self, max_bytes: usize) {
+        self.max_pending_memory = Some(max_bytes);
+        self.evict_pending_clients(self.pending_capacity());
+    }
+
+    /// Removes the memory limit of the pending clients, only [`NetcodeServer::max_pending_clients`] is used.
+    pub fn clear_max_pending_memory(&mut self) {
+        self.max_pending_memory = None;
+    }
+
+    /// Returns how many pending clients were evicted to make room for new connection requests.
+    pub fn evicted_pending_clients(&self) -> u64 {
+        self.evicted_pending_clients
+    }
+
+    fn pending_capacity(&self) -> usize {
+        match self.max_pending_memory {
+            Some(max_bytes) => self.max_pending_clients.min(max_bytes / PENDING_CLIENT_BYTES),
+            None => self.max_pending_clients,
+        }
+    }
+
+    // Evicts the pending clients inactive for the longest time until there are at most `capacity` left
+    fn evict_pending_clients(&mut self, capacity: usize) {
+        while self.pending_clients.len() > capacity {
+            let Some(addr) = self
+                .pending_clients
+                .values()
+                .min_by_key(|pending| pending.last_packet_received_time)
+                .map(|pending| pending.addr)
+            else {
+                return;
+            };
+
+            let pending = self.pending_clients.remove(&addr).unwrap();
+            self.evicted_pending_clients += 1;
+            log::debug!(
+                "Pending Client {} evicted, reached max amount allowed of pending clients ({} evicted in total).",
+                pending.client_id,
+                self.evicted_pending_clients
+            );
+        }
+    }
+
     /// Advance the server current time, and remove any pending connections that have expired.
     pub fn update(&mut self, duration: Duration) {
         self.current_time += duration;
@@ -582,6 +1010,19 @@ impl NetcodeServer {
         }
 
         self.pending_clients.retain(|_, c| c.state != ConnectionState::Disconnected);
+
+        let current_time = self.current_time;
+        match self.session_resumption {
+            Some(grace_period) => self
+                .lost_sessions
+                .retain(|_, session| current_time < session.lost_at + grace_period),
+            None => self.lost_sessions.clear(),
+        }
+
+        self.slot_reservations.retain(|_, expire_time| current_time < *expire_time);
+        self.banned_client_ids
+            .retain(|_, expire_time| is_ban_active(*expire_time, current_time));
+        self.banned_addrs.retain(|_, expire_time| is_ban_active(*expire_time, current_time));
     }
 
     /// Updates the client, returns a ServerResult.
@@ -598,16 +1039,29 @@ impl NetcodeServer {
     /// }
     /// # fn send_to(p: &[u8], addr: std::net::SocketAddr) {}
     /// ```
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(self)))]
     pub fn update_client(&mut self, client_id: u64) -> ServerResult<'_, '_> {
         let slot = match find_client_slot_by_id(&self.clients, client_id) {
             None => return ServerResult::None,
             Some(slot) => slot,
         };
 
+        let keep_alive_interval_ms = self.keep_alive_interval_ms();
         if let Some(client) = &mut self.clients[slot] {
+            let suspended = client.suspended_until.is_some_and(|until| self.current_time < until);
+            let timeout_start = match client.suspended_until {
+                // The timeout starts again when the suspension ends
+                Some(until) => until,
+                None => client.last_packet_received_time,
+            };
             let connection_timed_out = client.timeout_seconds > 0
-                && (client.last_packet_received_time + Duration::from_secs(client.timeout_seconds as u64) < self.current_time);
+                && !suspended
+                && (timeout_start + Duration::from_secs(client.timeout_seconds as u64) < self.current_time);
             if connection_timed_out {
+                if let (Some(resume_ticket), Some(_)) = (client.resume_ticket, self.session_resumption) {
+                    let lost_at = self.current_time;
+                    self.lost_sessions.insert(client_id, LostSession { resume_ticket, lost_at });
+                }
                 log::debug!("Client {} disconnected, connection timed out", client.client_id);
                 client.state = ConnectionState::Disconnected;
             }
@@ -638,10 +1092,14 @@ impl NetcodeServer {
                 };
             }
 
-            if client.last_packet_send_time + NETCODE_SEND_RATE <= self.current_time {
+            // Suspended clients are in the background, keep-alives would not be received
+            if !suspended && client.last_packet_send_time + self.keep_alive_interval <= self.current_time {
                 let packet = Packet::KeepAlive {
                     client_index: slot as u32,
                     max_clients: self.max_clients as u32,
+                    keep_alive_interval_ms,
+                    // Sent until the client confirms it received the first keep-alive
+                    resume_ticket: if client.confirmed { None } else { client.resume_ticket },
                 };
 
                 let len = match packet.encode(&mut self.out, self.protocol_id, Some((client.sequence, &client.send_key))) {
@@ -667,6 +1125,65 @@ impl NetcodeServer {
         find_client_slot_by_id(&self.clients, client_id).is_some()
     }
 
+    /// Returns the maximum time a suspended client is kept connected without sending packets.
+    pub fn max_suspend_duration(&self) -> Duration {
+        self.max_suspend_duration
+    }
+
+    /// Update the maximum time a suspended client is kept connected without sending packets,
+    /// the default is [`NETCODE_MAX_SUSPEND_DURATION`].
+    /// After it, the client has the usual timeout of its connect token to send a packet.
+    ///
+    /// Clients are suspended when they go to the background, see [`NetcodeClient::suspend`][crate::NetcodeClient::suspend].
+    pub fn set_max_suspend_duration(&mut self, max_suspend_duration: Duration) {
+        self.max_suspend_duration = max_suspend_duration;
+    }
+
+    /// Enables session resumption: connected clients receive a resume ticket, and if their connection times out,
+    /// they can present it when connecting again during the grace period to continue with the same client id.
+    /// The connection is reported as resumed in [`ServerResult::ClientConnected`].
+    pub fn set_session_resumption(&mut self, grace_period: Duration) {
+        self.session_resumption = Some(grace_period);
+    }
+
+    /// Disables session resumption, the connections that timed out can no longer be resumed.
+    pub fn clear_session_resumption(&mut self) {
+        self.session_resumption = None;
+        self.lost_sessions.clear();
+    }
+
+    // Returns the client id of the lost session of the ticket, if it can be resumed
+    fn resume_session(&mut self, resume_ticket: &[u8; NETCODE_RESUME_TICKET_BYTES]) -> Option<u64> {
+        let grace_period = self.session_resumption?;
+        let client_id = match decode_resume_ticket(resume_ticket, &self.resume_key) {
+            Ok(client_id) => client_id,
+            Err(e) => {
+                log::debug!("Ignored invalid resume ticket: {e}");
+                return None;
+            }
+        };
+
+        // Only the last connection of the client can be resumed, once
+        let session = self.lost_sessions.get(&client_id)?;
+        if session.resume_ticket != *resume_ticket || self.current_time >= session.lost_at + grace_period {
+            return None;
+        }
+        if self.is_client_connected(client_id) || self.is_client_id_banned(client_id) {
+            return None;
+        }
+
+        self.lost_sessions.remove(&client_id);
+        Some(client_id)
+    }
+
+    /// Returns whether the client is suspended, it has gone to the background and hasn't sent any packet since then.
+    pub fn is_client_suspended(&self, client_id: u64) -> bool {
+        match find_client_by_id(&self.clients, client_id) {
+            Some(client) => client.suspended_until.is_some_and(|until| self.current_time < until),
+            None => false,
+        }
+    }
+
     /// Disconnect an client and returns its address and a disconnect packet to be sent to them.
     // TODO: we can return Result<PacketToSend, NetcodeError>
     //       but the library user would need to be aware that he has to run
@@ -676,6 +1193,8 @@ impl NetcodeServer {
             let client = self.clients[slot].take().unwrap();
             let packet = Packet::Disconnect;
 
+            log::debug!("Disconnected client {client_id}");
+
             let len = match packet.encode(&mut self.out, self.protocol_id, Some((client.sequence, &client.send_key))) {
                 Err(e) => {
                     log::error!("Failed to encode disconnect packet: {}", e);
@@ -698,6 +1217,39 @@ impl NetcodeServer {
     }
 }
 
+// Resume tickets are the ticket sequence followed by the client id encrypted with the resume key
+fn generate_resume_ticket(
+    client_id: u64,
+    sequence: u64,
+    resume_key: &[u8; NETCODE_KEY_BYTES],
+) -> Result<[u8; NETCODE_RESUME_TICKET_BYTES], NetcodeError> {
+    let mut resume_ticket = [0u8; NETCODE_RESUME_TICKET_BYTES];
+    resume_ticket[..8].copy_from_slice(&sequence.to_le_bytes());
+    resume_ticket[8..16].copy_from_slice(&client_id.to_le_bytes());
+    encrypt_in_place(&mut resume_ticket[8..], sequence, resume_key, b"")?;
+
+    Ok(resume_ticket)
+}
+
+fn decode_resume_ticket(
+    resume_ticket: &[u8; NETCODE_RESUME_TICKET_BYTES],
+    resume_key: &[u8; NETCODE_KEY_BYTES],
+) -> Result<u64, NetcodeError> {
+    let sequence = u64::from_le_bytes(resume_ticket[..8].try_into().unwrap());
+    let mut decoded = [0u8; NETCODE_RESUME_TICKET_BYTES - 8];
+    decoded.copy_from_slice(&resume_ticket[8..]);
+    dencrypted_in_place(&mut decoded, sequence, resume_key, b"")?;
+
+    Ok(u64::from_le_bytes(decoded[..8].try_into().unwrap()))
+}
+
+fn is_ban_active(expire_time: Option<Duration>, current_time: Duration) -> bool {
+    match expire_time {
+        Some(expire_time) => current_time < expire_time,
+        None => true,
+    }
+}
+
 fn find_client_mut_by_id(clients: &mut [Option<Connection>], client_id: u64) -> Option<&mut Connection> {
     clients.iter_mut().flatten().find(|c| c.client_id == client_id)
 }
@@ -736,6 +1288,8 @@ mod tests {
             protocol_id: TEST_PROTOCOL_ID,
             public_addresses: vec!["127.0.0.1:5000".parse().unwrap()],
             authentication: ServerAuthentication::Secure { private_key: *TEST_KEY },
+            keep_alive_interval: NETCODE_SEND_RATE,
+            max_pending_clients: NETCODE_MAX_PENDING_CLIENTS,
         };
         NetcodeServer::new(config)
     }
@@ -837,6 +1391,311 @@ mod tests {
         assert!(!server.is_client_connected(client_id));
     }
 
+    fn connect_client(server: &mut NetcodeServer, client_id: u64, client_addr: SocketAddr) -> bool {
+        new_client(server, client_id, client_addr);
+        server.is_client_connected(client_id)
+    }
+
+    fn new_client(server: &mut NetcodeServer, client_id: u64, client_addr: SocketAddr) -> NetcodeClient {
+        let connect_token = ConnectToken::generate(
+            Duration::ZERO,
+            TEST_PROTOCOL_ID,
+            3,
+            client_id,
+            5,
+            server.addresses(),
+            None,
+            TEST_KEY,
+        )
+        .unwrap();
+        let client_auth = ClientAuthentication::Secure { connect_token };
+        let mut client = NetcodeClient::new(Duration::ZERO, client_auth).unwrap();
+
+        // Connection request and challenge response
+        for _ in 0..2 {
+            let Some((client_packet, _)) = client.update(Duration::ZERO) else {
+                break;
+            };
+            if let ServerResult::PacketToSend { payload, .. } | ServerResult::ClientConnected { payload, .. } =
+                server.process_packet(client_addr, client_packet)
+            {
+                client.process_packet(payload);
+            }
+        }
+
+        client
+    }
+
+    #[test]
+    fn reserve_slot() {
+        let mut server = new_server();
+        server.set_max_clients(2);
+        assert!(server.reserve_slot(5, Duration::from_secs(10)));
+        assert!(connect_client(&mut server, 0, "127.0.0.1:3000".parse().unwrap()));
+
+        // The reserved slot is not free for other clients
+        assert!(!connect_client(&mut server, 1, "127.0.0.1:3001".parse().unwrap()));
+        assert!(!server.reserve_slot(6, Duration::from_secs(10)));
+
+        assert!(connect_client(&mut server, 5, "127.0.0.1:3005".parse().unwrap()));
+        assert_eq!(server.reserved_slots(), 0);
+
+        // Reservations expire
+        server.set_max_clients(4);
+        assert!(server.reserve_slot(7, Duration::from_secs(1)));
+        assert_eq!(server.reserved_slots(), 1);
+        server.update(Duration::from_secs(1));
+        assert_eq!(server.reserved_slots(), 0);
+    }
+
+    #[test]
+    fn resume_session() {
+        let mut server = new_server();
+        server.set_session_resumption(Duration::from_secs(10));
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let client = new_client(&mut server, 0, client_addr);
+        let resume_ticket = client.resume_ticket().unwrap();
+
+        // Times out
+        server.update(Duration::from_secs(6));
+        assert!(matches!(server.update_client(0), ServerResult::ClientDisconnected { .. }));
+
+        // Connects with another client id, presenting the ticket
+        let connect_token = ConnectToken::generate(Duration::ZERO, TEST_PROTOCOL_ID, 30, 1, 5, server.addresses(), None, TEST_KEY).unwrap();
+        let client_auth = ClientAuthentication::Secure { connect_token };
+        let mut client = NetcodeClient::new(Duration::ZERO, client_auth).unwrap();
+        client.set_resume_ticket(resume_ticket);
+
+        let new_client_addr: SocketAddr = "127.0.0.1:3001".parse().unwrap();
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        let ServerResult::PacketToSend { payload, .. } = server.process_packet(new_client_addr, client_packet) else {
+            unreachable!()
+        };
+        client.process_packet(payload);
+        let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+        match server.process_packet(new_client_addr, client_packet) {
+            ServerResult::ClientConnected {
+                client_id,
+                resumed,
+                payload,
+                ..
+            } => {
+                assert_eq!(client_id, 0);
+                assert!(resumed);
+                client.process_packet(payload);
+            }
+            _ => unreachable!(),
+        }
+        assert!(client.is_connected());
+        assert!(server.is_client_connected(0));
+        assert!(!server.is_client_connected(1));
+
+        // The new connection has its own ticket, the previous one can't be used again
+        assert!(client.resume_ticket().is_some());
+        assert_ne!(client.resume_ticket(), Some(resume_ticket));
+        assert!(server.resume_session(&resume_ticket).is_none());
+    }
+
+    #[test]
+    fn suspended_client() {
+        let mut server = new_server();
+        let client_id = 0;
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let mut client = new_client(&mut server, client_id, client_addr);
+        assert!(client.is_connected());
+
+        let (_, packet) = client.suspend().unwrap();
+        assert_eq!(server.process_packet(client_addr, packet), ServerResult::None);
+        assert!(server.is_client_suspended(client_id));
+
+        // No timeout or keep-alives while suspended
+        let duration = Duration::from_secs(10);
+        server.update(duration);
+        assert_eq!(server.update_client(client_id), ServerResult::None);
+        assert!(client.update(duration).is_none());
+        assert!(client.is_connected());
+
+        // Any packet resumes the client
+        client.resume();
+        let (packet, _) = client.update(duration).unwrap();
+        assert_eq!(server.process_packet(client_addr, packet), ServerResult::None);
+        assert!(!server.is_client_suspended(client_id));
+        assert!(client.is_connected());
+
+        // Times out after the max suspend duration
+        let (_, packet) = client.suspend().unwrap();
+        server.process_packet(client_addr, packet);
+        server.update(server.max_suspend_duration());
+        assert!(!server.is_client_suspended(client_id));
+        assert!(!matches!(server.update_client(client_id), ServerResult::ClientDisconnected { .. }));
+        assert!(server.is_client_connected(client_id));
+        server.update(Duration::from_secs(6));
+        assert!(matches!(server.update_client(client_id), ServerResult::ClientDisconnected { .. }));
+    }
+
+    #[test]
+    fn max_pending_clients() {
+        let mut server = new_server();
+        server.set_max_pending_clients(2);
+        let request_connection = |server: &mut NetcodeServer, client_id: u64, client_addr: SocketAddr| {
+            let connect_token = ConnectToken::generate(
+                Duration::ZERO,
+                TEST_PROTOCOL_ID,
+                3,
+                client_id,
+                5,
+                server.addresses(),
+                None,
+                TEST_KEY,
+            )
+            .unwrap();
+            let mut client = NetcodeClient::new(Duration::ZERO, ClientAuthentication::Secure { connect_token }).unwrap();
+            let (packet, _) = client.update(Duration::ZERO).unwrap();
+            server.process_packet(client_addr, packet);
+        };
+
+        let addrs: Vec<SocketAddr> = (0..4).map(|i| format!("127.0.0.1:{}", 3000 + i).parse().unwrap()).collect();
+        request_connection(&mut server, 0, addrs[0]);
+        server.update(Duration::from_millis(100));
+        request_connection(&mut server, 1, addrs[1]);
+        server.update(Duration::from_millis(100));
+        assert_eq!(server.pending_clients(), 2);
+        assert_eq!(server.evicted_pending_clients(), 0);
+
+        // The pending client inactive for the longest time is evicted
+        request_connection(&mut server, 2, addrs[2]);
+        assert_eq!(server.pending_clients(), 2);
+        assert_eq!(server.evicted_pending_clients(), 1);
+        assert!(!server.pending_clients.contains_key(&addrs[0]));
+        assert!(server.pending_clients.contains_key(&addrs[1]));
+
+        // The memory limit also evicts pending clients
+        assert_eq!(server.pending_clients_memory(), 2 * PENDING_CLIENT_BYTES);
+        server.set_max_pending_memory(PENDING_CLIENT_BYTES);
+        assert_eq!(server.pending_clients(), 1);
+        assert_eq!(server.evicted_pending_clients(), 2);
+        assert!(server.pending_clients.contains_key(&addrs[2]));
+
+        server.clear_max_pending_memory();
+        server.update(Duration::from_millis(100));
+        request_connection(&mut server, 3, addrs[3]);
+        assert_eq!(server.pending_clients(), 2);
+        assert_eq!(server.evicted_pending_clients(), 2);
+    }
+
+    #[test]
+    fn set_max_clients() {
+        let mut server = new_server();
+        assert!(connect_client(&mut server, 0, "127.0.0.1:3000".parse().unwrap()));
+        assert!(connect_client(&mut server, 1, "127.0.0.1:3001".parse().unwrap()));
+
+        // Lowering the max clients does not disconnect clients
+        server.set_max_clients(1);
+        assert_eq!(server.max_clients(), 1);
+        assert_eq!(server.connected_clients(), 2);
+        assert_eq!(server.clients.len(), 2);
+        assert!(!connect_client(&mut server, 2, "127.0.0.1:3002".parse().unwrap()));
+
+        // Slots above the max clients are not reused
+        server.disconnect(0);
+        assert!(!connect_client(&mut server, 3, "127.0.0.1:3003".parse().unwrap()));
+
+        server.set_max_clients(2);
+        assert!(connect_client(&mut server, 4, "127.0.0.1:3004".parse().unwrap()));
+        assert_eq!(server.clients_slot(), vec![0, 1]);
+
+        server.set_max_clients(NETCODE_MAX_CLIENTS + 1);
+        assert_eq!(server.max_clients(), NETCODE_MAX_CLIENTS);
+        assert_eq!(server.clients.len(), NETCODE_MAX_CLIENTS);
+    }
+
+    #[test]
+    fn ban_list() {
+        let mut server = new_server();
+        let addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+
+        server.ban_client_id(0, None);
+        assert!(!connect_client(&mut server, 0, addr));
+        assert!(server.pending_clients.is_empty());
+
+        server.unban_client_id(0);
+        assert!(connect_client(&mut server, 0, addr));
+
+        let banned_addr: SocketAddr = "127.0.0.1:3001".parse().unwrap();
+        server.ban_addr(banned_addr, Some(Duration::from_secs(1)));
+        assert!(!connect_client(&mut server, 1, banned_addr));
+        assert!(server.pending_clients.is_empty());
+
+        // Ban expired
+        server.update(Duration::from_secs(1));
+        assert!(!server.is_addr_banned(banned_addr));
+        assert!(server.banned_addrs.is_empty());
+        assert!(connect_client(&mut server, 1, banned_addr));
+    }
+
+    #[test]
+    fn connection_approval() {
+        let mut server = new_server();
+        server.set_connection_approval(|client_id, _, _| client_id != 1);
+
+        assert!(connect_client(&mut server, 0, "127.0.0.1:3000".parse().unwrap()));
+        assert!(!connect_client(&mut server, 1, "127.0.0.1:3001".parse().unwrap()));
+        assert_eq!(server.connected_clients(), 1);
+        assert!(server.pending_clients.is_empty());
+
+        server.clear_connection_approval();
+        assert!(connect_client(&mut server, 1, "127.0.0.1:3002".parse().unwrap()));
+    }
+
+    fn connection_denied_reason(server: &mut NetcodeServer, client_id: u64, protocol_id: u64) -> Option<ConnectionDeniedReason> {
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let connect_token =
+            ConnectToken::generate(Duration::ZERO, protocol_id, 3, client_id, 5, server.addresses(), None, TEST_KEY).unwrap();
+        let client_auth = ClientAuthentication::Secure { connect_token };
+        let mut client = NetcodeClient::new(Duration::ZERO, client_auth).unwrap();
+
+        for _ in 0..2 {
+            let (client_packet, _) = client.update(Duration::ZERO)?;
+            match server.process_packet(client_addr, client_packet) {
+                ServerResult::ConnectionDenied { reason, .. } => return Some(reason),
+                ServerResult::PacketToSend { payload, .. } | ServerResult::ClientConnected { payload, .. } => {
+                    client.process_packet(payload);
+                }
+                _ => {}
+            }
+        }
+
+        None
+    }
+
+    #[test]
+    fn connection_denied() {
+        let mut server = new_server();
+        assert_eq!(
+            connection_denied_reason(&mut server, 0, TEST_PROTOCOL_ID + 1),
+            Some(ConnectionDeniedReason::InvalidProtocolId)
+        );
+
+        server.ban_client_id(0, None);
+        assert_eq!(
+            connection_denied_reason(&mut server, 0, TEST_PROTOCOL_ID),
+            Some(ConnectionDeniedReason::Banned)
+        );
+
+        server.set_connection_approval(|_, _, _| false);
+        assert_eq!(
+            connection_denied_reason(&mut server, 1, TEST_PROTOCOL_ID),
+            Some(ConnectionDeniedReason::NotApproved)
+        );
+
+        server.clear_connection_approval();
+        server.set_max_clients(0);
+        assert_eq!(
+            connection_denied_reason(&mut server, 2, TEST_PROTOCOL_ID),
+            Some(ConnectionDeniedReason::ServerFull)
+        );
+    }
+
     #[test]
     fn connect_token_already_used() {
         let mut server = new_server();
@@ -846,6 +1705,7 @@ mod tests {
             time: Duration::ZERO,
             address: client_addr,
             mac: generate_random_bytes(),
+            expire_timestamp: 0,
         };
         // Allow first entry
         assert!(server.find_or_add_connect_token_entry(connect_token));
@@ -856,4 +1716,89 @@ mod tests {
         // Don't allow same token with different address
         assert!(!server.find_or_add_connect_token_entry(connect_token));
     }
+
+    #[test]
+    fn connect_token_shared_store() {
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Clone, Default)]
+        struct Store(Arc<Mutex<HashMap<[u8; NETCODE_MAC_BYTES], SocketAddr>>>);
+
+        impl ConnectTokenStore for Store {
+            fn use_connect_token(&mut self, mac: &[u8; NETCODE_MAC_BYTES], addr: SocketAddr, _: u64) -> bool {
+                *self.0.lock().unwrap().entry(*mac).or_insert(addr) == addr
+            }
+        }
+
+        let store = Store::default();
+        let mut server = new_server();
+        let mut other_server = new_server();
+        server.set_connect_token_store(store.clone());
+        other_server.set_connect_token_store(store.clone());
+
+        let mut connect_token = ConnectTokenEntry {
+            time: Duration::ZERO,
+            address: "127.0.0.1:3000".parse().unwrap(),
+            mac: generate_random_bytes(),
+            expire_timestamp: 0,
+        };
+        assert!(server.find_or_add_connect_token_entry(connect_token));
+
+        // Token already used in another server by a different address
+        connect_token.address = "127.0.0.1:3001".parse().unwrap();
+        assert!(!other_server.find_or_add_connect_token_entry(connect_token));
+
+        // Same address can use the token in any server
+        connect_token.address = "127.0.0.1:3000".parse().unwrap();
+        assert!(other_server.find_or_add_connect_token_entry(connect_token));
+    }
+
+    #[test]
+    fn keep_alive_interval() {
+        let keep_alive_interval = Duration::from_secs(1);
+        let mut server = NetcodeServer::new(ServerConfig {
+            current_time: Duration::ZERO,
+            max_clients: 16,
+            protocol_id: TEST_PROTOCOL_ID,
+            public_addresses: vec!["127.0.0.1:5000".parse().unwrap()],
+            authentication: ServerAuthentication::Secure { private_key: *TEST_KEY },
+            keep_alive_interval,
+            max_pending_clients: NETCODE_MAX_PENDING_CLIENTS,
+        });
+        let client_id = 4;
+        let client_addr: SocketAddr = "127.0.0.1:3000".parse().unwrap();
+        let connect_token = ConnectToken::generate(
+            Duration::ZERO,
+            TEST_PROTOCOL_ID,
+            3,
+            client_id,
+            5,
+            server.addresses(),
+            None,
+            TEST_KEY,
+        )
+        .unwrap();
+        let client_auth = ClientAuthentication::Secure { connect_token };
+        let mut client = NetcodeClient::new(Duration::ZERO, client_auth).unwrap();
+
+        for _ in 0..2 {
+            let (client_packet, _) = client.update(Duration::ZERO).unwrap();
+            if let ServerResult::PacketToSend { payload, .. } | ServerResult::ClientConnected { payload, .. } =
+                server.process_packet(client_addr, client_packet)
+            {
+                client.process_packet(payload);
+            }
+        }
+        assert!(client.is_connected());
+        assert_eq!(client.keep_alive_interval(), keep_alive_interval);
+
+        // No keep alive with the default interval
+        server.update(NETCODE_SEND_RATE);
+        assert_eq!(server.update_client(client_id), ServerResult::None);
+        assert!(client.update(NETCODE_SEND_RATE).is_none());
+
+        server.update(keep_alive_interval - NETCODE_SEND_RATE);
+        assert!(matches!(server.update_client(client_id), ServerResult::PacketToSend { .. }));
+        assert!(client.update(keep_alive_interval - NETCODE_SEND_RATE).is_some());
+    }
 }
@@ -118,6 +118,20 @@ impl ConnectToken {
         })
     }
 
+    /// Decrypts the private part of the token with the server private key, and returns the user data.
+    /// Fails if the token was not generated with the private key. Useful to inspect tokens when debugging.
+    pub fn decrypt_user_data(&self, private_key: &[u8; NETCODE_KEY_BYTES]) -> Result<[u8; NETCODE_USER_DATA_BYTES], TokenGenerationError> {
+        let private_connect_token = PrivateConnectToken::decode(
+            &self.private_data,
+            self.protocol_id,
+            self.expire_timestamp,
+            &self.xnonce,
+            private_key,
+        )?;
+
+        Ok(private_connect_token.user_data)
+    }
+
     pub fn write(&self, writer: &mut impl io::Write) -> Result<(), io::Error> {
         writer.write_all(&self.client_id.to_le_bytes())?;
         writer.write_all(&self.version_info)?;
@@ -359,6 +373,26 @@ mod tests {
         assert_eq!(token, result);
     }
 
+    #[test]
+    fn connect_token_decrypt_user_data() {
+        let key = b"an example very very secret key."; // 32-bytes
+        let user_data = generate_random_bytes();
+        let token = ConnectToken::generate(
+            Duration::ZERO,
+            2,
+            300,
+            1,
+            15,
+            vec!["127.0.0.1:8080".parse().unwrap()],
+            Some(&user_data),
+            key,
+        )
+        .unwrap();
+
+        assert_eq!(token.decrypt_user_data(key).unwrap(), user_data);
+        assert!(token.decrypt_user_data(&[0; NETCODE_KEY_BYTES]).is_err());
+    }
+
     #[test]
     fn private_connect_token_encode_decode() {
         let hosts: Vec<SocketAddr> = vec!["127.0.0.1:8080".parse().unwrap(), "127.0.0.2:3000".parse().unwrap()];